@@ -1,4 +1,5 @@
 pub mod publickey;
+pub mod salt;
 
 use std::str::FromStr;
 
@@ -19,25 +20,127 @@ const BYTES_MIDEN_WIDTH: u32 = 2;
 const ARRAY_MIDEN_WIDTH: u32 = 3;
 const MAP_MIDEN_WIDTH: u32 = ARRAY_MIDEN_WIDTH * 2;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// The Miden VM version a program is compiled for.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum StdVersion {
     #[serde(rename = "0.5.0")]
     V0_5_0,
     #[serde(rename = "0.6.1")]
     V0_6_1,
+    #[default]
     #[serde(rename = "0.7.0")]
     V0_7_0,
 }
 
+impl std::fmt::Display for StdVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StdVersion::V0_5_0 => write!(f, "0.5.0"),
+            StdVersion::V0_6_1 => write!(f, "0.6.1"),
+            StdVersion::V0_7_0 => write!(f, "0.7.0"),
+        }
+    }
+}
+
+impl FromStr for StdVersion {
+    type Err = Whatever;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "0.5.0" => Ok(StdVersion::V0_5_0),
+            "0.6.1" => Ok(StdVersion::V0_6_1),
+            "0.7.0" => Ok(StdVersion::V0_7_0),
+            _ => whatever!("invalid std version: {s}"),
+        }
+    }
+}
+
 /// An array of record hashes.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct RecordHashes {
     pub contract: String,
 }
 
+/// The records of another contract that were updated by calling their functions,
+/// an array of [`record_update_struct`] at `addr`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RecordUpdates {
+    pub contract: String,
+    pub addr: u32,
+    /// The `@private` fields of the records.
+    #[serde(default)]
+    pub private_fields: Vec<String>,
+    /// The address of an array of booleans, whether `ctx.publicKey` can read
+    /// the private fields of each of the updated records, if they have private fields.
+    #[serde(default)]
+    pub private_read_auth_addr: Option<u32>,
+}
+
+/// The records of another contract that were created with `new`,
+/// an array of [`created_record_struct`] at `addr`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CreatedRecords {
+    pub contract: String,
+    pub addr: u32,
+    /// The `@private` fields of the records.
+    #[serde(default)]
+    pub private_fields: Vec<String>,
+    /// The address of an array of booleans, whether `ctx.publicKey` can read
+    /// the private fields of each of the created records, if they have private fields.
+    #[serde(default)]
+    pub private_read_auth_addr: Option<u32>,
+}
+
+/// An event declared on a contract, which its functions can emit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventType {
+    pub contract: String,
+    pub event: Struct,
+}
+
+/// The events emitted with `emit`, an array of [`event_struct`] of `types` at `addr`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Events {
+    pub addr: u32,
+    pub types: Vec<EventType>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Abi {
     pub std_version: Option<StdVersion>,
+    /// Calls must be signed by `ctx.publicKey`. The program outputs the signed message
+    /// and the hash of the public key, below the hash of the result.
+    #[serde(default)]
+    pub signed: bool,
+    /// The function is the constructor of the contract, which creates the record,
+    /// so it is the only call that new salts are generated for.
+    #[serde(default)]
+    pub constructor: bool,
+    /// The address of the [`signature_claims_struct`] of the signatures checked
+    /// with `verifySignature`. The program outputs the hash of the claims below everything else.
+    #[serde(default)]
+    pub signature_claims_addr: Option<u32>,
+    /// The records of other contracts updated by the call, for every contract
+    /// with a function that is called. The program outputs the hash of each array
+    /// of updates, in this order, below the hash of the signature claims.
+    #[serde(default)]
+    pub record_updates: Vec<RecordUpdates>,
+    /// The records of other contracts created by the call, for every contract
+    /// that `new` is used with. The program outputs the hash of each array
+    /// of created records, in this order, below the hashes of the record updates.
+    #[serde(default)]
+    pub created_records: Vec<CreatedRecords>,
+    /// The events emitted by the call, of every event declared in the program.
+    /// The program outputs the hash of the events below the hashes of the created records.
+    #[serde(default)]
+    pub events: Option<Events>,
+    /// The `@private` fields of `this`, which are only revealed as salted hashes
+    /// to readers that don't pass the `@read` fields.
+    #[serde(default)]
+    pub private_fields: Vec<String>,
+    /// The address of the boolean of whether `ctx.publicKey` can read the private fields.
+    #[serde(default)]
+    pub private_read_auth_addr: Option<u32>,
     pub this_addr: Option<u32>,
     pub this_type: Option<Type>,
     pub result_addr: Option<u32>,
@@ -46,6 +149,146 @@ pub struct Abi {
     pub other_records: Vec<RecordHashes>,
     pub other_contract_types: Vec<Type>,
     pub dependent_fields: Vec<(String, Type)>,
+    /// The number of memory addresses used by statically allocated symbols,
+    /// dynamic allocations start after them.
+    #[serde(default)]
+    pub peak_static_memory: u32,
+    /// The spans of the compiled statements, empty unless the program was compiled for debugging.
+    /// While running, the program stores the start of the statement it is executing,
+    /// so that failures can be traced back to it.
+    #[serde(default)]
+    pub source_map: Vec<SourceSpan>,
+    /// The estimated number of cycles the program takes.
+    #[serde(default)]
+    pub cycles: Option<CycleEstimate>,
+}
+
+/// A static estimate of the number of cycles a program takes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct CycleEstimate {
+    /// The cycles when every loop is skipped and the cheapest branches are taken.
+    pub best: u64,
+    /// The cycles when the most expensive branches are taken, as a polynomial
+    /// of the number of iterations of every loop, `worst[k]` is the coefficient of `iterations^k`.
+    pub worst: Vec<u64>,
+}
+
+impl CycleEstimate {
+    pub fn worst_case(&self, loop_iterations: u64) -> u64 {
+        self.worst.iter().rev().fold(0u64, |acc, coefficient| {
+            acc.saturating_mul(loop_iterations)
+                .saturating_add(*coefficient)
+        })
+    }
+}
+
+/// The maximum number of cycles a program may take, assuming that every loop
+/// runs at most `loop_iterations` times.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CycleBudget {
+    pub max_cycles: u64,
+    pub loop_iterations: u64,
+}
+
+impl CycleBudget {
+    pub fn check(&self, estimate: &CycleEstimate) -> Result<()> {
+        let worst_case = estimate.worst_case(self.loop_iterations);
+        if worst_case > self.max_cycles {
+            return Err(Error::simple(format!(
+                "the program can take up to {worst_case} cycles, over the budget of {} cycles",
+                self.max_cycles
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Checks the estimate in the ABI, programs without one can't be bounded and are rejected.
+    pub fn check_abi(&self, abi: &Abi) -> Result<()> {
+        match &abi.cycles {
+            Some(cycles) => self.check(cycles),
+            None => Err(Error::simple(
+                "the program has no cycle estimate, so it can't be checked against the budget",
+            )),
+        }
+    }
+}
+
+/// A span of Polylang source code, `end` is the offset of its last byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Default)]
+pub struct SourceSpan {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl From<SourceSpan> for error::span::Span {
+    fn from(val: SourceSpan) -> Self {
+        error::span::Span::new(val.start, val.end)
+    }
+}
+
+/// The ABI of a contract compiled into a single library of procedures.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct LibraryAbi {
+    pub std_version: Option<StdVersion>,
+    pub contract: String,
+    /// The procedure that pops a selector from the stack and executes the selected entry point.
+    pub dispatcher: String,
+    pub entry_points: Vec<EntryPoint>,
+}
+
+/// A procedure of a library, it takes the same inputs as the program compiled for its function.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct EntryPoint {
+    pub selector: u32,
+    /// The name of the contract function, or `.readAuth` for the read authorization.
+    pub function: String,
+    pub procedure: String,
+    /// The hex encoded MAST root of the procedure.
+    pub mast_root: String,
+    pub abi: Abi,
+}
+
+/// Everything needed to check proofs of a program without recompiling its source.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Manifest {
+    /// The hex encoded BLAKE3 hash of the Polylang source.
+    pub source_hash: String,
+    pub compiler_version: String,
+    pub std_version: StdVersion,
+    pub contract: Option<String>,
+    /// The compiled functions, sorted by name.
+    pub functions: Vec<ManifestFunction>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ManifestFunction {
+    /// The name of the function, or `.readAuth` for the read authorization of a contract.
+    pub function: String,
+    /// The hex encoded MAST root of the program compiled for the function.
+    pub program_hash: String,
+    pub abi: Abi,
+}
+
+impl Manifest {
+    pub fn function(&self, name: &str) -> Result<&ManifestFunction> {
+        self.functions
+            .iter()
+            .find(|f| f.function == name)
+            .ok_or_else(|| Error::simple(format!("function `{name}` is not in the manifest")))
+    }
+
+    /// Returns the function whose program has the given hash.
+    pub fn find_program(&self, program_hash: &str) -> Result<&ManifestFunction> {
+        self.functions
+            .iter()
+            .find(|f| f.program_hash == program_hash)
+            .ok_or_else(|| {
+                Error::simple(format!(
+                    "program with hash {program_hash} is not in the manifest"
+                ))
+            })
+    }
 }
 
 impl Abi {
@@ -56,6 +299,101 @@ impl Abi {
 
         Ok(this_type.default_value())
     }
+
+    /// Returns the span of the statement that starts at `start`.
+    pub fn statement_span(&self, start: u64) -> Option<SourceSpan> {
+        self.source_map
+            .iter()
+            .find(|span| span.start as u64 == start)
+            .copied()
+    }
+}
+
+/// A signature checked with `verifySignature`, and whether it is valid.
+pub fn signature_claim_struct() -> Struct {
+    Struct {
+        name: "SignatureClaim".to_owned(),
+        fields: vec![
+            ("publicKey".to_owned(), Type::PublicKey),
+            ("message".to_owned(), Type::Bytes),
+            ("signature".to_owned(), Type::Bytes),
+            (
+                "valid".to_owned(),
+                Type::PrimitiveType(PrimitiveType::Boolean),
+            ),
+        ],
+    }
+}
+
+/// The memory layout of the signatures checked by a program.
+///
+/// Miden VM can't verify the signatures, so the claim that is being checked is `pending`
+/// and `waiting` is set until the result is read from the advice tape.
+/// The checked claims are kept in `claims`.
+pub fn signature_claims_struct() -> Struct {
+    let claim = Type::Struct(signature_claim_struct());
+
+    Struct {
+        name: "SignatureClaims".to_owned(),
+        fields: vec![
+            ("claims".to_owned(), Type::Array(Box::new(claim.clone()))),
+            ("pending".to_owned(), claim),
+            (
+                "waiting".to_owned(),
+                Type::PrimitiveType(PrimitiveType::Boolean),
+            ),
+        ],
+    }
+}
+
+/// A record of another contract after calling one of its functions,
+/// and its new hash, salted with the same salts as before the call.
+pub fn record_update_struct(record: Struct) -> Struct {
+    Struct {
+        name: format!("{}Update", record.name),
+        fields: vec![
+            ("record".to_owned(), Type::Struct(record)),
+            ("hash".to_owned(), Type::Hash),
+        ],
+    }
+}
+
+/// A record created with `new`, the salts of its fields and its hash salted with them.
+pub fn created_record_struct(record: Struct) -> Struct {
+    let salts = Struct {
+        name: format!("{}Salts", record.name),
+        fields: record
+            .fields
+            .iter()
+            .map(|(name, _)| (name.clone(), Type::Hash))
+            .collect(),
+    };
+
+    Struct {
+        name: format!("{}Created", record.name),
+        fields: vec![
+            ("record".to_owned(), Type::Struct(record)),
+            ("salts".to_owned(), Type::Struct(salts)),
+            ("hash".to_owned(), Type::Hash),
+        ],
+    }
+}
+
+/// An emitted event, with a nullable field named `Contract.Event` for every type of event,
+/// of which only the emitted one is not null.
+pub fn event_struct(types: &[EventType]) -> Struct {
+    Struct {
+        name: "Event".to_owned(),
+        fields: types
+            .iter()
+            .map(|t| {
+                (
+                    format!("{}.{}", t.contract, t.event.name),
+                    Type::Nullable(Box::new(Type::Struct(t.event.clone()))),
+                )
+            })
+            .collect(),
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -791,29 +1129,20 @@ impl Parser<str> for Type {
                     values
                         .next()
                         .parse_err("missing field", "x of public key", value)?;
-                let y_base64 =
-                    values
-                        .next()
-                        .parse_err("missing field", "y of public key", value)?;
+                let y_base64 = values.next();
 
                 let x = base64::engine::general_purpose::URL_SAFE
                     .decode(x_base64)
                     .wrap_err()?;
-                let y = base64::engine::general_purpose::URL_SAFE
-                    .decode(y_base64)
-                    .wrap_err()?;
-
-                let mut extra_bytes = vec![];
-                extra_bytes.extend_from_slice(&x);
-                extra_bytes.extend_from_slice(&y);
+                let crv: publickey::Crv = crv.parse().parse_err("crv", crv)?;
 
                 let key = publickey::Key {
                     kty: kty.parse().parse_err("kty", kty)?,
-                    crv: crv.parse().parse_err("crv", crv)?,
+                    crv,
                     alg: alg.parse().parse_err("alg", alg)?,
                     use_: use_.parse().parse_err("use", use_)?,
                     x: x.try_into().ok().parse_err("invalid size", "x", x_base64)?,
-                    y: y.try_into().ok().parse_err("invalid size", "y", y_base64)?,
+                    y: parse_public_key_y(crv, y_base64, value)?,
                 };
 
                 Ok(Value::PublicKey(key))
@@ -969,28 +1298,21 @@ impl Parser<serde_json::Value> for Type {
                     .parse_err("invalidi", "x", "json as str")?;
                 let y_base64 = value
                     .get("y")
-                    .parse_err("missing field", "y of public key", "json")?
-                    .as_str()
-                    .parse_err("invalidi", "y", "json as str")?;
+                    .map(|y| y.as_str().parse_err("invalidi", "y", "json as str"))
+                    .transpose()?;
 
                 let x = base64::engine::general_purpose::URL_SAFE
                     .decode(x_base64)
                     .wrap_err()?;
-                let y = base64::engine::general_purpose::URL_SAFE
-                    .decode(y_base64)
-                    .wrap_err()?;
-
-                let mut extra_bytes = vec![];
-                extra_bytes.extend_from_slice(&x);
-                extra_bytes.extend_from_slice(&y);
+                let crv: publickey::Crv = crv.parse().parse_err("crv", crv)?;
 
                 let key = publickey::Key {
                     kty: kty.parse().parse_err("kty", kty)?,
-                    crv: crv.parse().parse_err("crv", crv)?,
+                    crv,
                     alg: alg.parse().parse_err("alg", alg)?,
                     use_: use_.parse().parse_err("use", use_)?,
                     x: x.try_into().ok().parse_err("invalid size", "x", x_base64)?,
-                    y: y.try_into().ok().parse_err("invalid size", "y", y_base64)?,
+                    y: parse_public_key_y(crv, y_base64, "json")?,
                 };
 
                 Ok(Value::PublicKey(key))
@@ -999,6 +1321,26 @@ impl Parser<serde_json::Value> for Type {
     }
 }
 
+/// Decodes the y coordinate of a public key, Ed25519 keys don't have one.
+fn parse_public_key_y(
+    crv: publickey::Crv,
+    y_base64: Option<&str>,
+    input: &str,
+) -> Result<[u8; 32]> {
+    let Some(y_base64) = y_base64 else {
+        return (!crv.has_y()).then_some([0; 32]).parse_err(
+            "missing field",
+            "y of public key",
+            input,
+        );
+    };
+
+    let y = base64::engine::general_purpose::URL_SAFE
+        .decode(y_base64)
+        .wrap_err()?;
+    y.try_into().ok().parse_err("invalid size", "y", y_base64)
+}
+
 impl Value {
     pub fn serialize(&self) -> Vec<u64> {
         match self {
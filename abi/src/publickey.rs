@@ -1,14 +1,16 @@
 use std::str::FromStr;
 
 use base64::Engine;
+use p256::{ecdsa::signature::hazmat::PrehashVerifier, elliptic_curve::sec1::ToEncodedPoint};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 use error::prelude::{whatever, Whatever};
 use snafu::ResultExt;
 
 /// Layout: [key, crv, alg, use, extra_ptr]
-/// `extra_ptr` in secp256k1 is pointer to 64 bytes of data,
-/// the x and y coordinates of the public key.
+/// `extra_ptr` is a pointer to 64 bytes of data, the x and y coordinates of the public key.
+/// Ed25519 keys only have an x coordinate, their y bytes are zero.
 pub const WIDTH: u32 = 5;
 
 // {"alg":"ES256K","crv":"secp256k1","kty":"EC","use":"sig","x":"TOz1M-Y1MVF6i7duA-aWbNSzwgiRngrMFViHOjR3O0w=","y":"XqGeNTl4BoJMANDK160xXhGjpRqy0bHqK_Rn-jsco1o="}d
@@ -16,12 +18,15 @@ pub const WIDTH: u32 = 5;
 pub enum Kty {
     #[default]
     EC,
+    /// Octet key pairs, such as Ed25519 keys.
+    OKP,
 }
 
 impl From<Kty> for u8 {
     fn from(value: Kty) -> Self {
         match value {
             Kty::EC => 1,
+            Kty::OKP => 2,
         }
     }
 }
@@ -30,6 +35,7 @@ impl From<u8> for Kty {
     fn from(value: u8) -> Self {
         match value {
             1 => Kty::EC,
+            2 => Kty::OKP,
             _ => panic!("invalid kty: {}", value),
         }
     }
@@ -41,6 +47,7 @@ impl FromStr for Kty {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "EC" => Ok(Kty::EC),
+            "OKP" => Ok(Kty::OKP),
             _ => whatever!("invalid kty: {s}"),
         }
     }
@@ -51,12 +58,42 @@ impl FromStr for Kty {
 pub enum Crv {
     #[default]
     Secp256k1,
+    #[serde(rename = "P-256")]
+    P256,
+    #[serde(rename = "Ed25519")]
+    Ed25519,
+}
+
+impl Crv {
+    /// The key type of keys on the curve.
+    pub fn kty(self) -> Kty {
+        match self {
+            Crv::Secp256k1 | Crv::P256 => Kty::EC,
+            Crv::Ed25519 => Kty::OKP,
+        }
+    }
+
+    /// The signature algorithm of keys on the curve.
+    pub fn alg(self) -> Alg {
+        match self {
+            Crv::Secp256k1 => Alg::ES256K,
+            Crv::P256 => Alg::ES256,
+            Crv::Ed25519 => Alg::EdDSA,
+        }
+    }
+
+    /// Whether keys on the curve have a y coordinate.
+    pub fn has_y(self) -> bool {
+        self.kty() == Kty::EC
+    }
 }
 
 impl From<Crv> for u8 {
     fn from(value: Crv) -> Self {
         match value {
             Crv::Secp256k1 => 1,
+            Crv::Ed25519 => 2,
+            Crv::P256 => 3,
         }
     }
 }
@@ -65,6 +102,8 @@ impl From<u8> for Crv {
     fn from(value: u8) -> Self {
         match value {
             1 => Crv::Secp256k1,
+            2 => Crv::Ed25519,
+            3 => Crv::P256,
             _ => panic!("invalid crv: {}", value),
         }
     }
@@ -76,6 +115,8 @@ impl FromStr for Crv {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "secp256k1" => Ok(Crv::Secp256k1),
+            "P-256" => Ok(Crv::P256),
+            "Ed25519" => Ok(Crv::Ed25519),
             _ => whatever!("invalid crv: {s}"),
         }
     }
@@ -85,12 +126,16 @@ impl FromStr for Crv {
 pub enum Alg {
     #[default]
     ES256K,
+    ES256,
+    EdDSA,
 }
 
 impl From<Alg> for u8 {
     fn from(value: Alg) -> Self {
         match value {
             Alg::ES256K => 1,
+            Alg::EdDSA => 2,
+            Alg::ES256 => 3,
         }
     }
 }
@@ -99,6 +144,8 @@ impl From<u8> for Alg {
     fn from(value: u8) -> Self {
         match value {
             1 => Alg::ES256K,
+            2 => Alg::EdDSA,
+            3 => Alg::ES256,
             _ => panic!("invalid alg: {}", value),
         }
     }
@@ -110,6 +157,8 @@ impl FromStr for Alg {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "ES256K" => Ok(Alg::ES256K),
+            "ES256" => Ok(Alg::ES256),
+            "EdDSA" => Ok(Alg::EdDSA),
             _ => whatever!("invalid alg: {s}"),
         }
     }
@@ -162,13 +211,20 @@ pub struct Key {
         deserialize_with = "from_url_safe_base64"
     )]
     pub x: [u8; 32],
+    /// Zero for Ed25519 keys, which only have an x coordinate.
     #[serde(
+        default,
+        skip_serializing_if = "is_zero",
         serialize_with = "to_url_safe_base64",
         deserialize_with = "from_url_safe_base64"
     )]
     pub y: [u8; 32],
 }
 
+fn is_zero(bytes: &[u8; 32]) -> bool {
+    bytes.iter().all(|b| *b == 0)
+}
+
 fn to_url_safe_base64<S>(bytes: &[u8; 32], serializer: S) -> std::result::Result<S::Ok, S::Error>
 where
     S: serde::Serializer,
@@ -228,6 +284,142 @@ impl Key {
         })
     }
 
+    /// Parses a 32 byte Ed25519 public key, such as a Solana address.
+    pub fn from_ed25519_bytes(bytes: &[u8]) -> Result<Self, Whatever> {
+        let Ok(x) = <[u8; 32]>::try_from(bytes) else {
+            whatever!(
+                "invalid ed25519 public key length: {}. A key should be 32 bytes long.",
+                bytes.len()
+            );
+        };
+        ed25519_dalek::VerifyingKey::from_bytes(&x)
+            .map_err(|e| e.to_string())
+            .with_whatever_context(|e| format!("invalid ed25519 public key bytes: {e}"))?;
+
+        Ok(Key {
+            kty: Kty::OKP,
+            crv: Crv::Ed25519,
+            alg: Alg::EdDSA,
+            use_: Use::Sig,
+            x,
+            y: [0; 32],
+        })
+    }
+
+    /// Parses a P-256 public key, such as the key of a passkey,
+    /// from its 65 or 33 byte SEC1 encoding or its 64 byte x and y coordinates.
+    pub fn from_p256_bytes(bytes: &[u8]) -> Result<Self, Whatever> {
+        let public_key = match bytes.len() {
+            65 | 33 => p256::PublicKey::from_sec1_bytes(bytes),
+            64 => p256::PublicKey::from_sec1_bytes(&[&[4], bytes].concat()),
+            _ => whatever!(
+                "invalid P-256 public key length: {}. A key should be 65, 64 or 33 bytes long.",
+                bytes.len()
+            ),
+        }
+        .map_err(|e| e.to_string())
+        .with_whatever_context(|e| format!("invalid P-256 public key bytes: {e}"))?;
+
+        let uncompressed = public_key.to_encoded_point(false);
+        let mut x = [0; 32];
+        let mut y = [0; 32];
+        x.copy_from_slice(&uncompressed.as_bytes()[1..33]);
+        y.copy_from_slice(&uncompressed.as_bytes()[33..]);
+
+        Ok(Key {
+            kty: Kty::EC,
+            crv: Crv::P256,
+            alg: Alg::ES256,
+            use_: Use::Sig,
+            x,
+            y,
+        })
+    }
+
+    /// The 65 byte uncompressed SEC1 encoding of an EC key.
+    fn sec1_bytes(&self) -> [u8; 65] {
+        let mut bytes = [4; 65];
+        bytes[1..33].copy_from_slice(&self.x);
+        bytes[33..].copy_from_slice(&self.y);
+        bytes
+    }
+
+    /// Verifies a signature of a 32 byte message, such as a hash.
+    /// ECDSA signatures are the 64 byte `r || s`, optionally followed by a recovery id,
+    /// Ed25519 signatures are of the message itself.
+    pub fn verify(&self, message: &[u8; 32], signature: &[u8]) -> Result<bool, Whatever> {
+        match self.crv {
+            Crv::Secp256k1 => {
+                let public_key = libsecp256k1::PublicKey::parse(&self.sec1_bytes())
+                    .map_err(|e| e.to_string())
+                    .with_whatever_context(|e| format!("invalid secp256k1 public key: {e}"))?;
+
+                let signature = match signature.len() {
+                    64 | 65 => libsecp256k1::Signature::parse_standard_slice(&signature[..64])
+                        .map_err(|e| e.to_string())
+                        .with_whatever_context(|e| format!("invalid secp256k1 signature: {e}"))?,
+                    len => whatever!(
+                        "invalid secp256k1 signature length: {len}. A signature should be 64 or 65 bytes long."
+                    ),
+                };
+
+                Ok(libsecp256k1::verify(
+                    &libsecp256k1::Message::parse(message),
+                    &signature,
+                    &public_key,
+                ))
+            }
+            Crv::P256 => {
+                let public_key = p256::ecdsa::VerifyingKey::from_sec1_bytes(&self.sec1_bytes())
+                    .map_err(|e| e.to_string())
+                    .with_whatever_context(|e| format!("invalid P-256 public key: {e}"))?;
+
+                let signature = match signature.len() {
+                    64 | 65 => p256::ecdsa::Signature::from_slice(&signature[..64])
+                        .map_err(|e| e.to_string())
+                        .with_whatever_context(|e| format!("invalid P-256 signature: {e}"))?,
+                    len => whatever!(
+                        "invalid P-256 signature length: {len}. A signature should be 64 or 65 bytes long."
+                    ),
+                };
+
+                Ok(public_key.verify_prehash(message, &signature).is_ok())
+            }
+            Crv::Ed25519 => self.verify_ed25519(message, signature),
+        }
+    }
+
+    /// Verifies a signature of an arbitrary message, as checked by `verifySignature`.
+    /// ECDSA (ES256K and ES256) signatures are of the SHA-256 hash of the message.
+    pub fn verify_message(&self, message: &[u8], signature: &[u8]) -> Result<bool, Whatever> {
+        match self.crv {
+            Crv::Secp256k1 | Crv::P256 => self.verify(&Sha256::digest(message).into(), signature),
+            Crv::Ed25519 => self.verify_ed25519(message, signature),
+        }
+    }
+
+    fn verify_ed25519(&self, message: &[u8], signature: &[u8]) -> Result<bool, Whatever> {
+        let public_key = ed25519_dalek::VerifyingKey::from_bytes(&self.x)
+            .map_err(|e| e.to_string())
+            .with_whatever_context(|e| format!("invalid ed25519 public key: {e}"))?;
+
+        let signature = ed25519_dalek::Signature::from_slice(signature)
+            .map_err(|e| e.to_string())
+            .with_whatever_context(|e| format!("invalid ed25519 signature: {e}"))?;
+
+        Ok(public_key.verify_strict(message, &signature).is_ok())
+    }
+
+    /// The hex encoded key, as returned by `toHex`: the x and y coordinates
+    /// of EC keys and the 32 bytes of Ed25519 keys.
+    pub fn to_hex(&self) -> String {
+        if self.crv.has_y() {
+            self.to_64_byte_hex()
+        } else {
+            format!("0x{}", hex::encode(self.x))
+        }
+    }
+
     pub fn to_64_byte_hex(&self) -> String {
         format!("0x{}{}", hex::encode(self.x), hex::encode(self.y))
     }
@@ -0,0 +1,77 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use error::prelude::*;
+
+/// The modulus of the Miden field, the elements of a salt are less than it.
+const FIELD_MODULUS: u64 = 0xFFFF_FFFF_0000_0001;
+
+/// The salt of a field of a record, a word that is merged with the hash of the field.
+///
+/// Salts hide the values behind field hashes, such as the hashes of `@private` fields,
+/// so they must be random, and stored with the record so that its hashes stay the same
+/// across calls. Serialized as 64 hex characters, 16 per element.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Salt(pub [u64; 4]);
+
+impl Salt {
+    /// The salt of records that were hashed without salts.
+    pub const ZERO: Salt = Salt([0; 4]);
+
+    /// Creates a salt from random bytes, each element is reduced into the field.
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        let mut elements = [0; 4];
+        for (element, chunk) in elements.iter_mut().zip(bytes.chunks_exact(8)) {
+            *element = u64::from_le_bytes(chunk.try_into().unwrap()) % FIELD_MODULUS;
+        }
+
+        Salt(elements)
+    }
+}
+
+impl fmt::Display for Salt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for element in self.0 {
+            write!(f, "{element:016x}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Salt {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim_start_matches("0x");
+        if s.len() != 64 || !s.is_ascii() {
+            return Err(Error::simple("a salt must be 64 hex characters"));
+        }
+
+        let mut elements = [0; 4];
+        for (element, chunk) in elements.iter_mut().zip(s.as_bytes().chunks_exact(16)) {
+            *element = u64::from_str_radix(std::str::from_utf8(chunk).unwrap(), 16)
+                .map_err(|e| Error::simple(format!("invalid salt: {e}")))?;
+        }
+
+        if elements.iter().any(|element| *element >= FIELD_MODULUS) {
+            return Err(Error::simple("invalid salt: element is not in the field"));
+        }
+
+        Ok(Salt(elements))
+    }
+}
+
+impl Serialize for Salt {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Salt {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
@@ -96,13 +96,18 @@ export interface Directive {
   arguments: DirectiveArgument[]
 }
 
-export type DirectiveArgument = FieldReference
+export type DirectiveArgument = FieldReference | Condition
 
 export interface FieldReference {
   kind: 'fieldreference',
   path: string[]
 }
 
+export interface Condition {
+  kind: 'condition',
+  code: string
+}
+
 export interface ReturnValue {
   kind: 'returnvalue'
   name: string
@@ -26,6 +26,6 @@ export interface JSContract {
   code: string
 }
 
-export async function generateJSContract(contract: any): Promise<JSContract> {
-  return unwrap(JSON.parse((await parser).generate_js_contract(JSON.stringify(contract))))
+export async function generateJSContract(contract: any, root: any[] = []): Promise<JSContract> {
+  return unwrap(JSON.parse((await parser).generate_js_contract(JSON.stringify(contract), JSON.stringify(root))))
 }
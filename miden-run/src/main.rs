@@ -1,4 +1,4 @@
-use abi::Abi;
+use abi::{salt::Salt, Abi};
 use error::prelude::*;
 use std::{
     collections::HashMap,
@@ -9,8 +9,10 @@ struct Args {
     advice_tape_json: Option<String>,
     this_values: HashMap<String, String>,
     this_json: Option<serde_json::Value>,
+    /// The salts of the fields of `this`, zero if not given
+    this_salts: Option<Vec<Salt>>,
     /// Map of contract name to a list of records and field salts
-    other_records: HashMap<String, Vec<(serde_json::Value, Vec<u32>)>>,
+    other_records: HashMap<String, Vec<(serde_json::Value, Vec<Salt>)>>,
     abi: Abi,
     ctx: Ctx,
     proof_output: Option<String>,
@@ -29,6 +31,7 @@ impl Args {
         let mut abi = None;
         let mut this_values = HashMap::new();
         let mut this_json = None;
+        let mut this_salts = None;
         let mut other_records = HashMap::new();
         let mut ctx = None;
         let mut proof_output = None;
@@ -62,6 +65,16 @@ impl Args {
 
                     this_json = Some(this_value);
                 }
+                "--this-salts-json" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| format!("missing value for argument {}", arg))?;
+
+                    let salts = serde_json::from_str::<Vec<Salt>>(&value)
+                        .map_err(|e| format!("invalid value for argument {}: {}", value, e))?;
+
+                    this_salts = Some(salts);
+                }
                 "--other-record" => {
                     let contract_name = args
                         .next()
@@ -137,7 +150,7 @@ impl Args {
             });
 
             for (_, salts) in records {
-                *salts = vec![0; col_struct.map(|s| s.fields.len()).unwrap_or_default()];
+                *salts = vec![Salt::ZERO; col_struct.map(|s| s.fields.len()).unwrap_or_default()];
             }
         }
 
@@ -146,6 +159,7 @@ impl Args {
             abi,
             this_values,
             this_json,
+            this_salts,
             other_records,
             ctx: ctx.unwrap_or_default(),
             proof_output,
@@ -228,7 +242,7 @@ impl Args {
 
     fn inputs(
         &self,
-        hasher: impl Fn(abi::Type, &abi::Value, Option<&[u32]>) -> Result<[u64; 4]>,
+        hasher: impl Fn(abi::Type, &abi::Value, Option<&[Salt]>) -> Result<[u64; 4]>,
     ) -> Result<polylang_prover::Inputs> {
         let this = self.this_value()?;
         let abi::Value::StructValue(sv) = &this else {
@@ -238,21 +252,37 @@ impl Args {
             abi::Type::Struct(s) => &s.fields,
             _ => unreachable!(),
         };
+        let this_salts = self
+            .this_salts
+            .clone()
+            .unwrap_or_else(|| vec![Salt::ZERO; sv.len()]);
+        if this_salts.len() != sv.len() {
+            return Err(Error::simple(format!(
+                "expected {} salts, found {}",
+                sv.len(),
+                this_salts.len()
+            )));
+        }
         let this_field_hashes = sv
             .iter()
             .enumerate()
-            .map(|(i, (_, v))| hasher(this_fields[i].1.clone(), v, Some(&[0])))
+            .map(|(i, (_, v))| hasher(this_fields[i].1.clone(), v, Some(&[this_salts[i]])))
             .collect::<Result<Vec<_>, _>>()?;
 
         Ok(polylang_prover::Inputs {
             abi: self.abi.clone(),
             ctx_public_key: self.ctx.public_key.clone(),
-            this_salts: sv.iter().map(|_| 0).collect(),
+            this_salts,
             this: this.try_into()?,
             this_field_hashes,
             args: serde_json::from_str(self.advice_tape_json.as_deref().unwrap_or("[]"))
                 .wrap_err()?,
             other_records: self.other_records.clone(),
+            nonce: 0,
+            signature: None,
+            created_records_seed: Salt::ZERO,
+            stdlib_advice: Default::default(),
+            max_cycles: None,
         })
     }
 }
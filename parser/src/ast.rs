@@ -9,6 +9,7 @@ pub struct Program {
 pub enum RootNode {
     Contract(Contract),
     Function(Function),
+    Const(Const),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -23,6 +24,8 @@ pub enum ContractItem {
     Field(Field),
     Function(Function),
     Index(Index),
+    Const(Const),
+    Event(Event),
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -33,6 +36,21 @@ pub struct Field {
     pub decorators: Vec<Decorator>,
 }
 
+/// `const NAME: type = value;`, the value is evaluated at compile time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Const {
+    pub name: String,
+    pub type_: Option<Type>,
+    pub value: Expression,
+}
+
+/// `event Name { field: type; ... }`, the data that functions can publish with `emit`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub name: String,
+    pub fields: Vec<Field>,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DecoratorNode {
     pub name: String,
@@ -43,8 +61,15 @@ pub type Decorator = MaybeSpanned<DecoratorNode>;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum DecoratorArgument {
+    /// A field, or a path to a nested field such as `owner.admin`.
     Identifier(String),
     Literal(Literal),
+    /// `when: <condition>`, a boolean expression of `this`, `ctx` and the parameters.
+    When {
+        condition: Expression,
+        /// The source code of the condition.
+        code: String,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -200,6 +225,7 @@ pub enum StatementKind {
     Expression(Expression),
     Throw(Expression),
     Let(Let),
+    Emit(Emit),
 }
 
 pub type Expression = MaybeSpanned<ExpressionKind>;
@@ -227,6 +253,7 @@ pub enum ExpressionKind {
     AssignAdd(Box<Expression>, Box<Expression>),
     Increment(Box<Expression>),
     Or(Box<Expression>, Box<Expression>),
+    NullishCoalesce(Box<Expression>, Box<Expression>),
     And(Box<Expression>, Box<Expression>),
     Equal(Box<Expression>, Box<Expression>),
     NotEqual(Box<Expression>, Box<Expression>),
@@ -249,8 +276,22 @@ pub enum ExpressionKind {
     BitNot(Box<Expression>),
     Negate(Box<Expression>),
     Dot(Box<Expression>, String),
+    OptionalDot(Box<Expression>, String),
     Index(Box<Expression>, Box<Expression>),
     Call(Box<Expression>, Vec<Expression>),
+    Asm(Asm),
+    /// `new Contract(args)`, creates a record of a contract by calling its constructor.
+    New(String, Vec<Expression>),
+}
+
+/// Inline Miden assembly, `asm(a, b): u32 { u32checked_add }`.
+///
+/// The inputs are pushed onto the stack in order, the code must replace them with the output.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Asm {
+    pub inputs: Vec<Expression>,
+    pub output: Option<Type>,
+    pub code: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -260,6 +301,13 @@ pub struct Let {
     pub expression: Expression,
 }
 
+/// `emit Event { field: value, ... }`, publishes an event declared on the contract.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Emit {
+    pub event: String,
+    pub fields: Vec<(String, Expression)>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct If {
     pub condition: Expression,
@@ -313,6 +361,8 @@ pub enum ForEachType {
 pub enum Primitive {
     // (value, has_decimal_point)
     Number(f64, bool),
+    // An integer literal above 2^53, which a f64 can't represent exactly
+    Integer(u64),
     String(String),
 }
 
@@ -323,5 +373,10 @@ pub struct Object {
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Literal {
+    /// A secp256k1 public key.
     Eth(Vec<u8>),
+    /// An Ed25519 public key.
+    Ed25519(Vec<u8>),
+    /// A P-256 public key.
+    P256(Vec<u8>),
 }
@@ -7,6 +7,8 @@ pub enum Tok<'input> {
     StringLiteral(&'input str),
     Identifier(&'input str),
     EthLiteralStart,
+    Ed25519LiteralStart,
+    P256LiteralStart,
     FalconLiteralStart,
     Desc,
     Asc,
@@ -26,6 +28,7 @@ pub enum Tok<'input> {
     PublicKey,
     Bytes,
     Let,
+    Const,
     Break,
     Return,
     Throw,
@@ -39,6 +42,12 @@ pub enum Tok<'input> {
     Index,
     Collection,
     Contract,
+    Asm,
+    New,
+    Event,
+    Emit,
+    /// The Miden assembly between the braces of an `asm` block.
+    AsmCode(&'input str),
     LBrace,
     RBrace,
     LBracket,
@@ -64,6 +73,8 @@ pub enum Tok<'input> {
     Percent,
     Bang,
     Question,
+    QuestionDot,
+    QuestionQuestion,
     Tilde,
     Ampersand,
     AmpersandAmpersand,
@@ -83,6 +94,8 @@ impl std::fmt::Display for Tok<'_> {
             Tok::StringLiteral(s) => write!(f, "{}", s),
             Tok::Identifier(s) => write!(f, "{}", s),
             Tok::EthLiteralStart => write!(f, "eth#"),
+            Tok::Ed25519LiteralStart => write!(f, "ed#"),
+            Tok::P256LiteralStart => write!(f, "p256#"),
             Tok::FalconLiteralStart => write!(f, "falcon#"),
             Tok::Desc => write!(f, "desc"),
             Tok::Asc => write!(f, "asc"),
@@ -102,6 +115,7 @@ impl std::fmt::Display for Tok<'_> {
             Tok::PublicKey => write!(f, "PublicKey"),
             Tok::Bytes => write!(f, "bytes"),
             Tok::Let => write!(f, "let"),
+            Tok::Const => write!(f, "const"),
             Tok::Break => write!(f, "break"),
             Tok::Return => write!(f, "return"),
             Tok::Throw => write!(f, "throw"),
@@ -115,6 +129,11 @@ impl std::fmt::Display for Tok<'_> {
             Tok::Index => write!(f, "index"),
             Tok::Collection => write!(f, "contract"),
             Tok::Contract => write!(f, "contract"),
+            Tok::Asm => write!(f, "asm"),
+            Tok::New => write!(f, "new"),
+            Tok::Event => write!(f, "event"),
+            Tok::Emit => write!(f, "emit"),
+            Tok::AsmCode(s) => write!(f, "{{{}}}", s),
             Tok::LBrace => write!(f, "{{"),
             Tok::RBrace => write!(f, "}}"),
             Tok::LBracket => write!(f, "["),
@@ -140,6 +159,8 @@ impl std::fmt::Display for Tok<'_> {
             Tok::Percent => write!(f, "%"),
             Tok::Bang => write!(f, "!"),
             Tok::Question => write!(f, "?"),
+            Tok::QuestionDot => write!(f, "?."),
+            Tok::QuestionQuestion => write!(f, "??"),
             Tok::Tilde => write!(f, "~"),
             Tok::Ampersand => write!(f, "&"),
             Tok::AmpersandAmpersand => write!(f, "&&"),
@@ -223,6 +244,7 @@ const KEYWORDS: &[(Tok, &str)] = &[
     (Tok::Map, "map"),
     (Tok::Record, "record"),
     (Tok::Let, "let"),
+    (Tok::Const, "const"),
     (Tok::Break, "break"),
     (Tok::Return, "return"),
     (Tok::Throw, "throw"),
@@ -238,12 +260,19 @@ const KEYWORDS: &[(Tok, &str)] = &[
     (Tok::Contract, "contract"),
     (Tok::PublicKey, "PublicKey"),
     (Tok::Bytes, "bytes"),
+    (Tok::Asm, "asm"),
+    (Tok::New, "new"),
+    (Tok::Event, "event"),
+    (Tok::Emit, "emit"),
 ];
 
 pub struct Lexer<'input> {
     input: &'input str,
     position: usize,
     errored: bool,
+    /// The depth of parentheses after an `asm` keyword,
+    /// the next `{` outside of them starts the Miden assembly.
+    asm_depth: Option<usize>,
 }
 
 type LexerItem<'input> = Spanned<Tok<'input>, usize, LexicalError>;
@@ -254,6 +283,7 @@ impl<'input> Lexer<'input> {
             input,
             position: 0,
             errored: false,
+            asm_depth: None,
         }
     }
 
@@ -410,6 +440,8 @@ impl<'input> Lexer<'input> {
 
         [
             (Tok::EthLiteralStart, "eth#"),
+            (Tok::Ed25519LiteralStart, "ed#"),
+            (Tok::P256LiteralStart, "p256#"),
             (Tok::FalconLiteralStart, "falcon#"),
         ]
         .iter()
@@ -550,6 +582,26 @@ impl<'input> Lexer<'input> {
         )))
     }
 
+    /// Lexes `{ ... }` after an `asm` keyword as the raw Miden assembly between the braces.
+    fn lex_asm_code(&mut self) -> Option<LexerItem<'input>> {
+        let (start, '{') = self.peek_char()? else {
+            return None;
+        };
+        self.next_char();
+
+        let Some(len) = self.input[self.position..].find('}') else {
+            return Some(Err(LexicalError::UserError {
+                start,
+                end: self.input.len(),
+                message: "unterminated asm block".to_owned(),
+            }));
+        };
+
+        let code = &self.input[self.position..self.position + len];
+        self.position += len + 1;
+        Some(Ok((start, Tok::AsmCode(code), self.position)))
+    }
+
     fn lex_identifier(&mut self) -> Option<LexerItem<'input>> {
         let (start, c) = self.peek_char()?;
         if !(c.is_ascii_alphabetic() || c == '_' || c == '$') {
@@ -599,6 +651,14 @@ impl<'input> Iterator for Lexer<'input> {
             }
         }
 
+        if self.asm_depth == Some(0) {
+            if let Some(result) = self.reset_if_none(Self::lex_asm_code) {
+                self.asm_depth = None;
+                self.errored = result.is_err();
+                return Some(result);
+            }
+        }
+
         let result = self
             .reset_if_none(Self::lex_keyword)
             .or_else(|| self.reset_if_none(Self::lex_literal_start))
@@ -720,7 +780,17 @@ impl<'input> Iterator for Lexer<'input> {
                 }
                 (i, '?') => {
                     self.next_char();
-                    Some(Ok((i, Tok::Question, i + 1)))
+                    match self.peek_char() {
+                        Some((_, '?')) => {
+                            self.next_char();
+                            Some(Ok((i, Tok::QuestionQuestion, i + 2)))
+                        }
+                        Some((_, '.')) => {
+                            self.next_char();
+                            Some(Ok((i, Tok::QuestionDot, i + 2)))
+                        }
+                        _ => Some(Ok((i, Tok::Question, i + 1))),
+                    }
                 }
                 (i, '~') => {
                     self.next_char();
@@ -775,6 +845,15 @@ impl<'input> Iterator for Lexer<'input> {
                 }
             });
 
+        match (&result, self.asm_depth) {
+            (Some(Ok((_, Tok::Asm, _))), _) => self.asm_depth = Some(0),
+            (Some(Ok((_, Tok::LParen, _))), Some(depth)) => self.asm_depth = Some(depth + 1),
+            (Some(Ok((_, Tok::RParen, _))), Some(depth)) => {
+                self.asm_depth = Some(depth.saturating_sub(1))
+            }
+            _ => {}
+        }
+
         if let Some(Err(_)) = result {
             self.errored = true;
         }
@@ -941,6 +1020,8 @@ mod tests {
             ("%", Tok::Percent),
             ("!", Tok::Bang),
             ("?", Tok::Question),
+            ("?.", Tok::QuestionDot),
+            ("??", Tok::QuestionQuestion),
             ("~", Tok::Tilde),
             ("&", Tok::Ampersand),
             ("&&", Tok::AmpersandAmpersand),
@@ -970,6 +1051,51 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_lex_asm() {
+        let input = "asm(f(a), b): u32 { u32checked_add }";
+        let tokens = Lexer::new(input)
+            .map(|t| t.map(|(_, tok, _)| tok))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(
+            tokens,
+            vec![
+                Tok::Asm,
+                Tok::LParen,
+                Tok::Identifier("f"),
+                Tok::LParen,
+                Tok::Identifier("a"),
+                Tok::RParen,
+                Tok::Comma,
+                Tok::Identifier("b"),
+                Tok::RParen,
+                Tok::Colon,
+                Tok::U32,
+                Tok::AsmCode(" u32checked_add "),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lex_asm_unterminated() {
+        let mut lexer = Lexer::new("asm() { drop");
+
+        assert_eq!(lexer.next(), Some(Ok((0, Tok::Asm, 3))));
+        assert_eq!(lexer.next(), Some(Ok((3, Tok::LParen, 4))));
+        assert_eq!(lexer.next(), Some(Ok((4, Tok::RParen, 5))));
+        assert_eq!(
+            lexer.next(),
+            Some(Err(LexicalError::UserError {
+                start: 6,
+                end: 12,
+                message: "unterminated asm block".to_owned(),
+            }))
+        );
+        assert_eq!(lexer.next(), None);
+    }
+
     #[test]
     fn test_comments() {
         let input = "/* comment */";
@@ -0,0 +1,156 @@
+//! A content-addressed cache of assembled programs.
+//!
+//! Programs are keyed on the hash of their Miden assembly and the std version they
+//! were compiled for, so repeated calls of the same function skip assembly.
+//! An optional [`DiskStore`] keeps the parsed programs across restarts.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use miden::assembly::{
+    ast::{AstSerdeOptions, ProgramAst},
+    ParsingError,
+};
+use miden_processor::Program;
+
+/// The number of programs kept by [`struct@PROGRAM_CACHE`] unless configured otherwise.
+pub const DEFAULT_CAPACITY: usize = 128;
+
+lazy_static::lazy_static! {
+    /// The cache used by [`crate::compile_program`].
+    pub static ref PROGRAM_CACHE: Mutex<ProgramCache> =
+        Mutex::new(ProgramCache::new(DEFAULT_CAPACITY));
+}
+
+/// The key of a program in the cache.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ProgramKey(String);
+
+impl ProgramKey {
+    pub fn new(std_version: abi::StdVersion, miden_code: &str) -> Self {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(std_version.to_string().as_bytes());
+        hasher.update(b"\n");
+        hasher.update(miden_code.as_bytes());
+        ProgramKey(hasher.finalize().to_hex().to_string())
+    }
+}
+
+/// A least recently used cache of assembled programs.
+pub struct ProgramCache {
+    capacity: usize,
+    programs: HashMap<ProgramKey, Program>,
+    /// The keys from the least to the most recently used.
+    recent: VecDeque<ProgramKey>,
+    disk_store: Option<DiskStore>,
+}
+
+impl ProgramCache {
+    pub fn new(capacity: usize) -> Self {
+        ProgramCache {
+            capacity,
+            programs: HashMap::new(),
+            recent: VecDeque::new(),
+            disk_store: None,
+        }
+    }
+
+    /// Sets the store that programs missing from memory are looked up in, and saved to.
+    pub fn set_disk_store(&mut self, disk_store: Option<DiskStore>) {
+        self.disk_store = disk_store;
+    }
+
+    pub fn disk_store(&self) -> Option<&DiskStore> {
+        self.disk_store.as_ref()
+    }
+
+    /// Changes the capacity, evicting the least recently used programs if needed.
+    /// A capacity of 0 disables the cache.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict();
+    }
+
+    pub fn len(&self) -> usize {
+        self.programs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.programs.is_empty()
+    }
+
+    pub fn get(&mut self, key: &ProgramKey) -> Option<Program> {
+        let program = self.programs.get(key)?.clone();
+        self.touch(key);
+        Some(program)
+    }
+
+    pub fn insert(&mut self, key: ProgramKey, program: Program) {
+        if self.programs.insert(key.clone(), program).is_some() {
+            self.touch(&key);
+        } else {
+            self.recent.push_back(key);
+        }
+
+        self.evict();
+    }
+
+    fn touch(&mut self, key: &ProgramKey) {
+        if let Some(i) = self.recent.iter().position(|k| k == key) {
+            let key = self.recent.remove(i).unwrap();
+            self.recent.push_back(key);
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.programs.len() > self.capacity {
+            let Some(key) = self.recent.pop_front() else {
+                break;
+            };
+            self.programs.remove(&key);
+        }
+    }
+}
+
+/// Parses a program, to be saved in a [`DiskStore`] and assembled.
+pub fn parse_program(miden_code: &str) -> Result<ProgramAst, ParsingError> {
+    ProgramAst::parse(miden_code)
+}
+
+/// A store of parsed programs in a directory, one file per key.
+///
+/// Miden VM 0.7 can't serialize assembled programs, so loading a program
+/// from the store skips parsing it, but it still has to be assembled.
+#[derive(Debug, Clone)]
+pub struct DiskStore {
+    dir: PathBuf,
+}
+
+impl DiskStore {
+    /// Opens the store in `dir`, creating the directory if it doesn't exist.
+    pub fn new(dir: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(DiskStore { dir })
+    }
+
+    fn path(&self, key: &ProgramKey) -> PathBuf {
+        self.dir.join(format!("{}.masb", key.0))
+    }
+
+    /// Returns the stored program, files that can't be read or deserialized are treated as missing.
+    pub fn load(&self, key: &ProgramKey) -> Option<ProgramAst> {
+        let bytes = std::fs::read(self.path(key)).ok()?;
+        ProgramAst::from_bytes(&bytes).ok()
+    }
+
+    pub fn save(&self, key: &ProgramKey, program: &ProgramAst) -> std::io::Result<()> {
+        // Write to a temporary file first, so that concurrent loads never see a partial program
+        let path = self.path(key);
+        let tmp_path = path.with_extension(format!("masb.{:x}", rand::random::<u64>()));
+        // The imports are needed to assemble the program again
+        std::fs::write(&tmp_path, program.to_bytes(AstSerdeOptions::new(true)))?;
+        std::fs::rename(tmp_path, path)
+    }
+}
@@ -1,10 +1,16 @@
-use std::collections::HashMap;
+pub mod cache;
+pub mod stdlib;
 
-use abi::{publickey, Abi, Parser, Type, TypeReader, Value};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use abi::{publickey, salt::Salt, Abi, Parser, Type, TypeReader, Value};
 use error::prelude::*;
 use miden::{ExecutionProof, ProvingOptions};
 use miden_processor::{
-    math::Felt, utils::Serializable, Program, ProgramInfo, StackInputs, StackOutputs,
+    math::Felt,
+    utils::{Deserializable, Serializable},
+    AdviceExtractor, AdviceInjector, Digest, ExecutionError, Host, HostResponse, Kernel,
+    ProcessState, Program, ProgramInfo, StackInputs, StackOutputs,
 };
 use polylang::compiler;
 
@@ -43,6 +49,7 @@ pub struct Output {
     pub run_output: RunOutput,
     pub stack: Vec<u64>,
     pub input_stack: Vec<u64>,
+    /// The new `this`, with the private fields redacted, see [`RunOutput::redacted_this`].
     pub new_this: Value,
     pub new_hashes: Vec<[u64; 4]>,
     pub proof: Vec<u8>,
@@ -76,22 +83,20 @@ fn json_to_this_value(this_json: &serde_json::Value, this_type: &Type) -> Result
     Ok(Value::StructValue(struct_values))
 }
 
-pub fn hash_this(type_: Type, this: &Value, salts: Option<&[u32]>) -> Result<[u64; 4]> {
-    let hasher_program = compiler::compile_hasher(type_, salts)?;
+pub fn hash_this(type_: Type, this: &Value, salts: Option<&[Salt]>) -> Result<[u64; 4]> {
+    let hasher_program = compiler::compile_hasher(type_, salts.is_some())?;
 
-    let assembler = miden::Assembler::default()
-        .with_library(&miden_stdlib::StdLibrary::default())
-        .map_err(MidenError::Assembly)
-        .wrap_err()?;
-
-    let program = assembler
-        .compile(hasher_program)
-        .map_err(MidenError::Assembly)
-        .wrap_err()?;
+    let program = assemble_cached(abi::StdVersion::default(), &hasher_program)?;
 
     let mem_advice_provider = miden::MemAdviceProvider::from(
         miden::AdviceInputs::default()
-            .with_stack_values(this.serialize().into_iter())
+            .with_stack_values(
+                salts
+                    .unwrap_or_default()
+                    .iter()
+                    .flat_map(|salt| Value::Hash(salt.0).serialize())
+                    .chain(this.serialize()),
+            )
             .map_err(MidenError::Input)
             .wrap_err()?,
     );
@@ -110,27 +115,127 @@ pub fn hash_this(type_: Type, this: &Value, salts: Option<&[u32]>) -> Result<[u6
         .wrap_err()
 }
 
+/// Generates a random salt from the OS random number generator.
+#[cfg(feature = "rand")]
+pub fn generate_salt() -> Salt {
+    use rand::RngCore;
+
+    let mut bytes = [0; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    Salt::from_bytes(bytes)
+}
+
+/// Generates a salt for every field of `this`, for the constructor, which creates the record.
+///
+/// The salts must be stored with the record and passed to every call of it,
+/// so that its field hashes stay the same.
+#[cfg(feature = "rand")]
+pub fn generate_salts(abi: &Abi) -> Result<Vec<Salt>> {
+    Ok((0..this_fields(abi)?).map(|_| generate_salt()).collect())
+}
+
+/// Returns zero salts for every field of `this`, the salts of records that were hashed without salts.
+///
+/// Zero salts let anyone check guesses of the values of `@private` fields against their hashes,
+/// so records with private fields should be called with the salts stored with them.
+pub fn zero_salts(abi: &Abi) -> Result<Vec<Salt>> {
+    Ok(vec![Salt::ZERO; this_fields(abi)?])
+}
+
+fn this_fields(abi: &Abi) -> Result<usize> {
+    match &abi.this_type {
+        Some(Type::Struct(this_struct)) => Ok(this_struct.fields.len()),
+        Some(_) => Err(Error::simple("this type must be a struct")),
+        None => Ok(0),
+    }
+}
+
+/// The Miden VM versions that programs can be run and proven with.
+pub const SUPPORTED_STD_VERSIONS: &[abi::StdVersion] = &[abi::StdVersion::V0_7_0];
+
 pub fn compile_program(abi: &Abi, miden_code: &str) -> Result<Program> {
-    let std_library = match &abi.std_version {
-        None => miden_stdlib::StdLibrary::default(),
-        Some(version) => match version {
-            abi::StdVersion::V0_5_0 => unimplemented!("Unsupported std version: 0.5.0"),
-            abi::StdVersion::V0_6_1 => unimplemented!("Unsupported std version: 0.6.1"),
-            abi::StdVersion::V0_7_0 => miden_stdlib::StdLibrary::default(),
-        },
+    let std_version = abi.std_version.unwrap_or_default();
+    if !SUPPORTED_STD_VERSIONS.contains(&std_version) {
+        return Err(Error::simple(format!(
+            "unsupported std version {std_version}, the prover supports {}",
+            SUPPORTED_STD_VERSIONS
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )));
+    }
+
+    assemble_cached(std_version, miden_code)
+}
+
+fn assemble_cached(std_version: abi::StdVersion, miden_code: &str) -> Result<Program> {
+    let key = cache::ProgramKey::new(std_version, miden_code);
+    let disk_store = {
+        let mut cache = cache::PROGRAM_CACHE.lock().unwrap();
+        if let Some(program) = cache.get(&key) {
+            return Ok(program);
+        }
+
+        cache.disk_store().cloned()
+    };
+
+    // Assemble without holding the lock, so other programs can be looked up meanwhile
+    let ast = match disk_store.as_ref().and_then(|store| store.load(&key)) {
+        Some(ast) => ast,
+        None => {
+            let ast = cache::parse_program(miden_code)
+                .map_err(|e| MidenError::Assembly(e.into()))
+                .wrap_err()?;
+            if let Some(store) = &disk_store {
+                // The store only saves time, the program is assembled anyway if saving fails
+                let _ = store.save(&key, &ast);
+            }
+            ast
+        }
     };
+    let program = assemble(&ast)?;
+    cache::PROGRAM_CACHE
+        .lock()
+        .unwrap()
+        .insert(key, program.clone());
+
+    Ok(program)
+}
+
+fn assemble(ast: &miden::assembly::ast::ProgramAst) -> Result<Program> {
+    let std_library = miden_stdlib::StdLibrary::default();
     let assembler = miden::Assembler::default()
         .with_library(&std_library)
         .map_err(MidenError::Assembly)
         .wrap_err()?;
 
     assembler
-        .compile(miden_code)
+        .compile_ast(ast)
         .map_err(MidenError::Assembly)
         .wrap_err()
 }
 
-type OtherRecordsType = HashMap<String, Vec<(Type, Value, Value, Vec<u32>)>>;
+/// Checks that the program is one of the programs in the manifest, and returns its function.
+pub fn check_manifest<'m>(
+    program: &Program,
+    manifest: &'m abi::Manifest,
+) -> Result<&'m abi::ManifestFunction> {
+    manifest.find_program(&program.program_hash())
+}
+
+/// Returns the program info to verify proofs of a function in the manifest with.
+pub fn manifest_program_info(manifest: &abi::Manifest, function: &str) -> Result<ProgramInfo> {
+    let function = manifest.function(function)?;
+    let hash = hex::decode(&function.program_hash)
+        .map_err(|e| Error::simple(format!("invalid program hash: {e}")))?;
+    let hash = Digest::read_from_bytes(&hash)
+        .map_err(|e| Error::simple(format!("invalid program hash: {e}")))?;
+
+    Ok(ProgramInfo::new(hash, Kernel::default()))
+}
+
+type OtherRecordsType = HashMap<String, Vec<(Type, Value, Value, Vec<Salt>)>>;
 
 #[derive(Clone)]
 pub struct Inputs {
@@ -138,22 +243,41 @@ pub struct Inputs {
     pub ctx_public_key: Option<publickey::Key>,
     pub this: serde_json::Value,
     pub this_field_hashes: Vec<[u64; 4]>,
-    pub this_salts: Vec<u32>,
+    pub this_salts: Vec<Salt>,
     pub args: Vec<serde_json::Value>,
     /// Map from contract name to a vector of record value and field salts
-    pub other_records: HashMap<String, Vec<(serde_json::Value, Vec<u32>)>>,
+    pub other_records: HashMap<String, Vec<(serde_json::Value, Vec<Salt>)>>,
+    /// The nonce of the call, `ctx.nonce` in the program, and part of the signed message of signed calls.
+    pub nonce: u64,
+    /// The signature of the call message by `ctx_public_key`, required to prove signed calls.
+    pub signature: Option<Vec<u8>>,
+    /// The seed that the salts of the records created with `new` are derived from,
+    /// see [`Inputs::with_created_records_seed`].
+    pub created_records_seed: Salt,
+    /// The advice of the standard library builtins, see [`stdlib`].
+    pub stdlib_advice: stdlib::StdlibAdvice,
+    /// The number of cycles the program may take, see [`Inputs::with_max_cycles`].
+    pub max_cycles: Option<u64>,
 }
 
 impl Inputs {
     pub fn new(
         abi: Abi,
         ctx_public_key: Option<publickey::Key>,
-        this_salts: Vec<u32>,
+        this_salts: Vec<Salt>,
         this: serde_json::Value,
         args: Vec<serde_json::Value>,
-        other_records: HashMap<String, Vec<(serde_json::Value, Vec<u32>)>>,
+        other_records: HashMap<String, Vec<(serde_json::Value, Vec<Salt>)>>,
     ) -> Result<Self> {
         let this_field_hashes = if let Some(Type::Struct(this_struct)) = &abi.this_type {
+            if this_salts.len() != this_struct.fields.len() {
+                return Err(Error::simple(format!(
+                    "expected {} salts, one for each field of this, found {}",
+                    this_struct.fields.len(),
+                    this_salts.len()
+                )));
+            }
+
             this_struct
                 .fields
                 .iter()
@@ -192,9 +316,43 @@ impl Inputs {
             this_salts,
             args,
             other_records,
+            nonce: 0,
+            signature: None,
+            created_records_seed: Salt::ZERO,
+            stdlib_advice: stdlib::StdlibAdvice::default(),
+            max_cycles: None,
         })
     }
 
+    /// Sets the nonce and signature of a signed call. The message to sign
+    /// is the [`RunOutput::call_message`] of running the call with the same nonce.
+    pub fn with_call_signature(mut self, nonce: u64, signature: Option<Vec<u8>>) -> Self {
+        self.nonce = nonce;
+        self.signature = signature;
+        self
+    }
+
+    /// Sets the seed that the salts of the records created with `new` are derived from.
+    /// The seed is zero by default, so that proofs can be reproduced, but then the salts
+    /// are the same for every call, so records with `@private` fields need a random seed.
+    pub fn with_created_records_seed(mut self, seed: Salt) -> Self {
+        self.created_records_seed = seed;
+        self
+    }
+
+    /// Sets the advice that the standard library builtins of the program request.
+    pub fn with_stdlib_advice(mut self, stdlib_advice: stdlib::StdlibAdvice) -> Self {
+        self.stdlib_advice = stdlib_advice;
+        self
+    }
+
+    /// Stops running the program once it took more than `max_cycles` cycles.
+    /// The cycle estimate in the ABI comes from whoever compiled the program, so it can't bound it.
+    pub fn with_max_cycles(mut self, max_cycles: u64) -> Self {
+        self.max_cycles = Some(max_cycles);
+        self
+    }
+
     pub fn stack_values(&self, other_records: &OtherRecordsType) -> Vec<u64> {
         let mut other_record_hashes = vec![];
         for or in &self.abi.other_records {
@@ -345,10 +503,11 @@ impl Inputs {
             )
             .serialize(),
         );
+        advice_tape.extend(Value::UInt64(self.nonce).serialize());
 
         if let Some(Type::Struct(this_struct)) = &self.abi.this_type {
             for (i, _) in this_struct.fields.iter().enumerate() {
-                advice_tape.push(self.this_salts[i] as u64);
+                advice_tape.extend(Value::Hash(self.this_salts[i].0).serialize());
             }
         }
 
@@ -356,7 +515,10 @@ impl Inputs {
             advice_tape.extend_from_slice(&t.parse(&self.args[i])?.serialize());
         }
 
-        let mut advice_map = Vec::<([u8; 32], _)>::new();
+        // The words read with `readAdviceHash`
+        advice_tape.extend(self.stdlib_advice.words.iter().flatten());
+
+        let mut advice_map = self.stdlib_advice.map.clone();
 
         let Value::StructValue(this_value) = self.this_value()? else {
             return Err(Error::simple("This value is not a struct"));
@@ -384,6 +546,24 @@ impl Inputs {
             ));
         }
 
+        if !self.abi.created_records.is_empty() {
+            // The key that `new` reads the seed with
+            let key = [Felt::new(0), Felt::new(0), Felt::new(0), Felt::new(2)];
+
+            advice_map.push((
+                key.iter()
+                    .flat_map(|f| f.to_bytes())
+                    .collect::<Vec<u8>>()
+                    .try_into()
+                    .unwrap(),
+                Value::Hash(self.created_records_seed.0)
+                    .serialize()
+                    .into_iter()
+                    .map(Felt::from)
+                    .collect(),
+            ));
+        }
+
         for records in other_records.values() {
             for (position, (id_type, id, record, salts)) in records.iter().enumerate() {
                 let id_hash = hash_this(id_type.clone(), id, None)?;
@@ -407,7 +587,7 @@ impl Inputs {
                         .chain(
                             salts
                                 .iter()
-                                .flat_map(|s| Value::UInt32(*s).serialize().into_iter()),
+                                .flat_map(|s| Value::Hash(s.0).serialize().into_iter()),
                         )
                         .chain(record.serialize().into_iter())
                         .map(Felt::from)
@@ -448,6 +628,7 @@ impl Inputs {
                 .with_stack_values(advice_tape)
                 .map_err(MidenError::Input)
                 .wrap_err()?
+                .with_merkle_store(self.stdlib_advice.merkle_store.clone())
                 .with_map(advice_map),
         ))
     }
@@ -455,10 +636,26 @@ impl Inputs {
 
 pub fn prove(program: &Program, inputs: &Inputs) -> Result<Output> {
     let (output, prove) = run(program, inputs)?;
+    if inputs.abi.signed {
+        // The program fails without a public key
+        let (Some(public_key), Some(message)) = (&inputs.ctx_public_key, output.call_message())
+        else {
+            return Err(Error::simple("signed calls require a public key"));
+        };
+        let Some(signature) = &inputs.signature else {
+            return Err(Error::simple(format!(
+                "signed calls require a signature of the call message {}",
+                hex::encode(call_message_bytes(&message))
+            )));
+        };
+
+        verify_call_signature(&inputs.abi, &output.stack, public_key, signature)?;
+    }
+
     let proof = prove()?;
 
     Ok(Output {
-        new_this: output.this(&inputs.abi)?,
+        new_this: output.redacted_this(&inputs.abi, &inputs.this_salts)?,
         new_hashes: output.hashes(),
         proof: proof.0.to_bytes(),
         overflow_addrs: proof.1.overflow_addrs().to_vec(),
@@ -476,6 +673,8 @@ pub struct RunOutput {
     pub stack: Vec<u64>,
     pub input_stack: Vec<u64>,
     pub stack_inputs: StackInputs,
+    /// The signatures checked with `verifySignature`, see [`verify_signature_claims`].
+    pub signature_claims: Vec<SignatureClaim>,
 }
 
 impl RunOutput {
@@ -551,6 +750,87 @@ impl RunOutput {
         )
     }
 
+    /// Whether `ctx.publicKey` can read the `@private` fields of `this`.
+    pub fn private_read_auth(&self, abi: &Abi) -> bool {
+        if abi.private_fields.is_empty() {
+            return true;
+        }
+
+        abi.private_read_auth_addr.is_some_and(|addr| {
+            self.memory
+                .get(&(addr as u64))
+                .is_some_and(|word| mont_red_cst(word[0] as u128) == 1)
+        })
+    }
+
+    /// Returns `this` with the `@private` fields replaced by their salted hashes,
+    /// unless `ctx.publicKey` can read them.
+    pub fn redacted_this(&self, abi: &Abi, salts: &[Salt]) -> Result<Value> {
+        let this = self.this(abi)?;
+        if self.private_read_auth(abi) {
+            return Ok(this);
+        }
+
+        let Some(Type::Struct(this_struct)) = &abi.this_type else {
+            return Err(Error::simple("this type must be a struct"));
+        };
+
+        redact_fields(this_struct, &abi.private_fields, this, salts)
+    }
+
+    /// Returns the salts of `this` with the salts of the `@private` fields removed,
+    /// unless `ctx.publicKey` can read them. The hashes of the fields can't be checked
+    /// against guesses of their values without the salts.
+    pub fn redacted_salts(&self, abi: &Abi, salts: &[Salt]) -> Vec<Option<Salt>> {
+        if self.private_read_auth(abi) {
+            return salts.iter().copied().map(Some).collect();
+        }
+
+        let fields = match &abi.this_type {
+            Some(Type::Struct(this_struct)) => this_struct.fields.as_slice(),
+            _ => &[],
+        };
+
+        redact_salts(fields, &abi.private_fields, salts)
+    }
+
+    /// Whether `ctx.publicKey` can read the `@private` fields of each of `len` records,
+    /// from the array of booleans at `addr`, all of them if the records have no private fields.
+    fn records_read_auth(&self, addr: Option<u32>, len: usize) -> Result<Vec<bool>> {
+        let Some(addr) = addr else {
+            return Ok(vec![true; len]);
+        };
+
+        let read_auth_type =
+            Type::Array(Box::new(Type::PrimitiveType(abi::PrimitiveType::Boolean)));
+        let Value::Array(values) = read_auth_type.read(
+            &|addr| {
+                Some(
+                    self.memory
+                        .get(&addr)
+                        .map(|x| x.map(|v| mont_red_cst(v as u128)))
+                        .unwrap_or_default(),
+                )
+            },
+            addr as u64,
+        )?
+        else {
+            return Err(Error::simple("read checks are not an array"));
+        };
+
+        if values.len() != len {
+            return Err(Error::simple("missing read checks of records"));
+        }
+
+        values
+            .into_iter()
+            .map(|value| match value {
+                Value::Boolean(read_auth) => Ok(read_auth),
+                _ => Err(Error::simple("read check is not a boolean")),
+            })
+            .collect()
+    }
+
     pub fn result(&self, abi: &Abi) -> Result<Value> {
         let Some(result_type) = &abi.result_type else {
             return Ok(abi::Value::Nullable(None));
@@ -601,10 +881,819 @@ impl RunOutput {
         }
     }
 
+    /// The number of words allocated on the heap, including the sizes of the blocks.
+    pub fn heap_size(&self) -> u64 {
+        let get_mem_value = |addr: u64| {
+            self.memory
+                .get(&addr)
+                .map(|word| mont_red_cst(word[0] as u128))
+                .unwrap_or_default()
+        };
+
+        // 3 is the top of the heap, 8 is the start of the heap
+        get_mem_value(3) - get_mem_value(8)
+    }
+
+    /// The message that signed calls must be signed with, see [`call_message_bytes`].
+    pub fn call_message(&self) -> Option<[u64; 4]> {
+        call_signature_outputs(&self.abi, &self.stack).map(|(message, _)| message)
+    }
+
     pub fn read_auth(&self) -> bool {
         let offset = self.abi.dependent_fields.len() * 4 + 1; // + 1 for self_destructed
         self.stack[offset] == 1
     }
+
+    /// The records of other contracts that the call updated by calling their functions,
+    /// see [`verify_record_updates`].
+    pub fn record_updates(&self, abi: &Abi) -> Result<Vec<RecordUpdate>> {
+        let mut updates = vec![];
+        for record_updates in &abi.record_updates {
+            let update_type = Type::Struct(abi::record_update_struct(
+                record_struct(abi, &record_updates.contract)?.clone(),
+            ));
+            let Value::Array(values) = Type::Array(Box::new(update_type)).read(
+                &|addr| {
+                    Some(
+                        self.memory
+                            .get(&addr)
+                            .map(|x| x.map(|v| mont_red_cst(v as u128)))
+                            .unwrap_or_default(),
+                    )
+                },
+                record_updates.addr as u64,
+            )?
+            else {
+                return Err(Error::simple("record updates are not an array"));
+            };
+
+            let read_auth =
+                self.records_read_auth(record_updates.private_read_auth_addr, values.len())?;
+            for (value, private_read_auth) in values.into_iter().zip(read_auth) {
+                updates.push(RecordUpdate::from_value(
+                    &record_updates.contract,
+                    value,
+                    private_read_auth,
+                )?);
+            }
+        }
+
+        Ok(updates)
+    }
+
+    /// The records of other contracts that the call created with `new`,
+    /// see [`verify_created_records`].
+    pub fn created_records(&self, abi: &Abi) -> Result<Vec<CreatedRecord>> {
+        let mut created = vec![];
+        for created_records in &abi.created_records {
+            let created_type = Type::Struct(abi::created_record_struct(
+                record_struct(abi, &created_records.contract)?.clone(),
+            ));
+            let Value::Array(values) = Type::Array(Box::new(created_type)).read(
+                &|addr| {
+                    Some(
+                        self.memory
+                            .get(&addr)
+                            .map(|x| x.map(|v| mont_red_cst(v as u128)))
+                            .unwrap_or_default(),
+                    )
+                },
+                created_records.addr as u64,
+            )?
+            else {
+                return Err(Error::simple("created records are not an array"));
+            };
+
+            let read_auth =
+                self.records_read_auth(created_records.private_read_auth_addr, values.len())?;
+            for (value, private_read_auth) in values.into_iter().zip(read_auth) {
+                created.push(CreatedRecord::from_value(
+                    &created_records.contract,
+                    value,
+                    private_read_auth,
+                )?);
+            }
+        }
+
+        Ok(created)
+    }
+
+    /// Returns the events emitted by the program, in the order they were emitted.
+    pub fn events(&self, abi: &Abi) -> Result<Vec<Event>> {
+        let Some(events) = &abi.events else {
+            return Ok(vec![]);
+        };
+
+        let event_type = Type::Struct(abi::event_struct(&events.types));
+        let Value::Array(values) = Type::Array(Box::new(event_type)).read(
+            &|addr| {
+                Some(
+                    self.memory
+                        .get(&addr)
+                        .map(|x| x.map(|v| mont_red_cst(v as u128)))
+                        .unwrap_or_default(),
+                )
+            },
+            events.addr as u64,
+        )?
+        else {
+            return Err(Error::simple("events are not an array"));
+        };
+
+        values
+            .into_iter()
+            .map(|value| Event::from_value(&events.types, value))
+            .collect()
+    }
+}
+
+/// Returns `record` with the `private_fields` replaced by their hashes, salted with `salts`.
+fn redact_fields(
+    record_struct: &abi::Struct,
+    private_fields: &[String],
+    record: Value,
+    salts: &[Salt],
+) -> Result<Value> {
+    let Value::StructValue(fields) = record else {
+        return Err(Error::simple("record is not a struct"));
+    };
+
+    fields
+        .into_iter()
+        .zip(&record_struct.fields)
+        .zip(salts)
+        .map(|(((name, value), (_, type_)), salt)| {
+            if !private_fields.contains(&name) {
+                return Ok((name, value));
+            }
+
+            let hash = hash_this(type_.clone(), &value, Some(&[*salt]))?;
+            Ok((name, Value::Hash(hash)))
+        })
+        .collect::<Result<Vec<_>>>()
+        .map(Value::StructValue)
+}
+
+/// Returns `salts` without the salts of the `private_fields`.
+fn redact_salts(
+    fields: &[(String, Type)],
+    private_fields: &[String],
+    salts: &[Salt],
+) -> Vec<Option<Salt>> {
+    salts
+        .iter()
+        .zip(fields)
+        .map(|(salt, (name, _))| (!private_fields.contains(name)).then_some(*salt))
+        .collect()
+}
+
+fn record_struct<'a>(abi: &'a Abi, contract: &str) -> Result<&'a abi::Struct> {
+    abi.other_contract_types
+        .iter()
+        .find_map(|t| match t {
+            Type::Struct(s) if s.name == contract => Some(s),
+            _ => None,
+        })
+        .ok_or_else(|| Error::simple(format!("unknown contract {contract}")))
+}
+
+/// Returns the call message and the hash of the public key that signs it,
+/// which signed programs output below the result hash.
+fn call_signature_outputs(abi: &Abi, stack: &[u64]) -> Option<([u64; 4], [u64; 4])> {
+    if !abi.signed {
+        return None;
+    }
+
+    // + 1 for self_destructed
+    let offset = 1 + abi.dependent_fields.len() * 4 + abi.result_type.as_ref().map_or(0, |_| 4);
+    let message = stack.get(offset..offset + 4)?.try_into().ok()?;
+    let public_key_hash = stack.get(offset + 4..offset + 8)?.try_into().ok()?;
+
+    Some((message, public_key_hash))
+}
+
+/// The bytes of a call message that are signed, the little endian bytes of every element.
+pub fn call_message_bytes(message: &[u64; 4]) -> [u8; 32] {
+    let mut bytes = [0; 32];
+    for (chunk, element) in bytes.chunks_exact_mut(8).zip(message) {
+        chunk.copy_from_slice(&element.to_le_bytes());
+    }
+
+    bytes
+}
+
+/// Verifies that the output stack of a signed program is signed by `public_key`.
+///
+/// The program only outputs the hash of `ctx.publicKey`, so the public key
+/// must hash to it, and the signature must be of the call message.
+/// The VM doesn't verify the signature, so a proof of a signed program
+/// doesn't show that the call was signed unless this also succeeds.
+pub fn verify_call_signature(
+    abi: &Abi,
+    output_stack: &[u64],
+    public_key: &publickey::Key,
+    signature: &[u8],
+) -> Result<()> {
+    let Some((message, public_key_hash)) = call_signature_outputs(abi, output_stack) else {
+        return Err(Error::simple("the program does not output a call message"));
+    };
+
+    if hash_this(Type::PublicKey, &Value::PublicKey(public_key.clone()), None)? != public_key_hash {
+        return Err(Error::simple(
+            "the call was made with a different public key",
+        ));
+    }
+
+    if !public_key
+        .verify(&call_message_bytes(&message), signature)
+        .wrap_err()?
+    {
+        return Err(Error::simple("invalid call signature"));
+    }
+
+    Ok(())
+}
+
+/// A signature checked with `verifySignature`, and whether it is valid,
+/// as claimed by the prover, see [`verify_signature_claims`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignatureClaim {
+    pub public_key: publickey::Key,
+    pub message: Vec<u8>,
+    pub signature: Vec<u8>,
+    pub valid: bool,
+}
+
+impl SignatureClaim {
+    fn from_value(value: Value) -> Result<Self> {
+        let Value::StructValue(fields) = value else {
+            return Err(Error::simple("signature claim is not a struct"));
+        };
+
+        let mut fields = fields.into_iter().map(|(_, value)| value);
+        let (
+            Some(Value::PublicKey(public_key)),
+            Some(Value::Bytes(message)),
+            Some(Value::Bytes(signature)),
+            Some(Value::Boolean(valid)),
+        ) = (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            return Err(Error::simple("invalid signature claim"));
+        };
+
+        Ok(Self {
+            public_key,
+            message,
+            signature,
+            valid,
+        })
+    }
+
+    fn to_value(&self) -> Value {
+        Value::StructValue(vec![
+            (
+                "publicKey".to_owned(),
+                Value::PublicKey(self.public_key.clone()),
+            ),
+            ("message".to_owned(), Value::Bytes(self.message.clone())),
+            ("signature".to_owned(), Value::Bytes(self.signature.clone())),
+            ("valid".to_owned(), Value::Boolean(self.valid)),
+        ])
+    }
+
+    /// Whether the signature is valid, malformed keys and signatures are invalid.
+    fn verify(&self) -> bool {
+        self.public_key
+            .verify_message(&self.message, &self.signature)
+            .unwrap_or(false)
+    }
+}
+
+/// Returns the claim the program is waiting for, if it is reading the result of a new `verifySignature`.
+fn pending_signature_claim(
+    abi: &Abi,
+    read_memory: &dyn Fn(u64) -> Option<[u64; 4]>,
+    known_claims: usize,
+) -> Result<Option<SignatureClaim>> {
+    let Some(addr) = abi.signature_claims_addr else {
+        return Ok(None);
+    };
+
+    let Value::StructValue(fields) =
+        Type::Struct(abi::signature_claims_struct()).read(read_memory, addr as u64)?
+    else {
+        return Ok(None);
+    };
+    let mut fields = fields.into_iter().map(|(_, value)| value);
+    let (Some(Value::Array(claims)), Some(pending), Some(Value::Boolean(true))) =
+        (fields.next(), fields.next(), fields.next())
+    else {
+        return Ok(None);
+    };
+    if claims.len() != known_claims {
+        return Ok(None);
+    }
+
+    let mut claim = SignatureClaim::from_value(pending)?;
+    claim.valid = claim.verify();
+
+    Ok(Some(claim))
+}
+
+/// The host that runs programs, it answers the advice requests from the advice provider,
+/// except for the results of `verifySignature`, which it verifies when the program reads them.
+/// The claims are kept in the order they are checked, so the program runs only once.
+struct SignatureHost {
+    abi: Abi,
+    host: miden::DefaultHost<miden::MemAdviceProvider>,
+    claims: Rc<RefCell<Vec<SignatureClaim>>>,
+}
+
+impl SignatureHost {
+    fn new(
+        abi: &Abi,
+        advice_provider: miden::MemAdviceProvider,
+        claims: Rc<RefCell<Vec<SignatureClaim>>>,
+    ) -> Self {
+        Self {
+            abi: abi.clone(),
+            host: miden::DefaultHost::new(advice_provider),
+            claims,
+        }
+    }
+}
+
+impl Host for SignatureHost {
+    fn get_advice<S: ProcessState>(
+        &mut self,
+        process: &S,
+        extractor: AdviceExtractor,
+    ) -> Result<HostResponse, ExecutionError> {
+        if let AdviceExtractor::PopStack = extractor {
+            let read_memory = |addr: u64| {
+                process
+                    .get_mem_value(0, addr)
+                    .map(|word| word.map(|x| mont_red_cst(x.inner() as u128)))
+            };
+            let known_claims = self.claims.borrow().len();
+            if let Ok(Some(claim)) = pending_signature_claim(&self.abi, &read_memory, known_claims)
+            {
+                let valid = claim.valid;
+                self.claims.borrow_mut().push(claim);
+                return Ok(HostResponse::Element(Felt::new(valid as u64)));
+            }
+        }
+
+        self.host.get_advice(process, extractor)
+    }
+
+    fn set_advice<S: ProcessState>(
+        &mut self,
+        process: &S,
+        injector: AdviceInjector,
+    ) -> Result<HostResponse, ExecutionError> {
+        self.host.set_advice(process, injector)
+    }
+}
+
+/// Verifies the signatures checked with `verifySignature` by a program with the given output stack.
+///
+/// The program outputs the hash of the claims, so they must hash to it,
+/// and every claim must have the result of verifying the signature.
+/// The VM takes the results from the advice provider without verifying them,
+/// so the proof doesn't show that they are correct unless this also succeeds.
+pub fn verify_signature_claims(
+    abi: &Abi,
+    output_stack: &[u64],
+    claims: &[SignatureClaim],
+) -> Result<()> {
+    let Some(offset) = abi.signature_claims_addr.map(|_| {
+        // + 1 for self_destructed
+        1 + abi.dependent_fields.len() * 4
+            + abi.result_type.as_ref().map_or(0, |_| 4)
+            + if abi.signed { 8 } else { 0 }
+    }) else {
+        return Err(Error::simple("the program does not check signatures"));
+    };
+    let claims_hash: [u64; 4] = output_stack
+        .get(offset..offset + 4)
+        .and_then(|hash| hash.try_into().ok())
+        .ok_or_else(|| Error::simple("the program does not output signature claims"))?;
+
+    let claims_value = Value::Array(claims.iter().map(SignatureClaim::to_value).collect());
+    let claim_type = Type::Struct(abi::signature_claim_struct());
+    if hash_this(Type::Array(Box::new(claim_type)), &claims_value, None)? != claims_hash {
+        return Err(Error::simple(
+            "the signature claims don't match the program outputs",
+        ));
+    }
+
+    if claims.iter().any(|claim| claim.verify() != claim.valid) {
+        return Err(Error::simple("invalid signature claim"));
+    }
+
+    Ok(())
+}
+
+/// A record of another contract after calling one of its functions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordUpdate {
+    pub contract: String,
+    pub record: Value,
+    /// The new hash of the record, salted with the salts it was called with.
+    pub hash: [u64; 4],
+    /// Whether `ctx.publicKey` can read the `@private` fields of the record.
+    pub private_read_auth: bool,
+}
+
+impl RecordUpdate {
+    fn from_value(contract: &str, value: Value, private_read_auth: bool) -> Result<Self> {
+        let Value::StructValue(fields) = value else {
+            return Err(Error::simple("record update is not a struct"));
+        };
+
+        let mut fields = fields.into_iter().map(|(_, value)| value);
+        let (Some(record), Some(Value::Hash(hash))) = (fields.next(), fields.next()) else {
+            return Err(Error::simple("invalid record update"));
+        };
+
+        Ok(Self {
+            contract: contract.to_owned(),
+            record,
+            hash,
+            private_read_auth,
+        })
+    }
+
+    /// Returns the record with the `@private` fields replaced by their salted hashes,
+    /// unless `ctx.publicKey` can read them. The salts are the ones that the record
+    /// was passed with in the other records of `inputs`.
+    pub fn redacted_record(&self, abi: &Abi, inputs: &Inputs) -> Result<Value> {
+        let private_fields = abi
+            .record_updates
+            .iter()
+            .find(|r| r.contract == self.contract)
+            .map(|r| r.private_fields.as_slice())
+            .unwrap_or_default();
+        if self.private_read_auth || private_fields.is_empty() {
+            return Ok(self.record.clone());
+        }
+
+        let Value::StructValue(fields) = &self.record else {
+            return Err(Error::simple("record is not a struct"));
+        };
+        let id = fields
+            .iter()
+            .find(|(name, _)| name == "id")
+            .map(|(_, id)| id);
+        let other_records = inputs.other_records()?;
+        let salts = other_records
+            .get(&self.contract)
+            .and_then(|records| {
+                records
+                    .iter()
+                    .find(|(_, record_id, _, _)| Some(record_id) == id)
+            })
+            .map(|(_, _, _, salts)| salts)
+            .ok_or_else(|| {
+                Error::simple(format!(
+                    "the salts of the {} record are missing",
+                    self.contract
+                ))
+            })?;
+
+        redact_fields(
+            record_struct(abi, &self.contract)?,
+            private_fields,
+            self.record.clone(),
+            salts,
+        )
+    }
+
+    fn to_value(&self) -> Value {
+        Value::StructValue(vec![
+            ("record".to_owned(), self.record.clone()),
+            ("hash".to_owned(), Value::Hash(self.hash)),
+        ])
+    }
+}
+
+/// Verifies the records that a program with the given output stack updated in other contracts.
+///
+/// The program outputs the hash of the updates of every contract in `abi.record_updates`,
+/// so the updates of each contract, in the order they were made, must hash to it.
+pub fn verify_record_updates(
+    abi: &Abi,
+    output_stack: &[u64],
+    updates: &[RecordUpdate],
+) -> Result<()> {
+    let offset = record_updates_offset(abi);
+
+    if let Some(update) = updates
+        .iter()
+        .find(|u| !abi.record_updates.iter().any(|r| r.contract == u.contract))
+    {
+        return Err(Error::simple(format!(
+            "the program does not update records of {}",
+            update.contract
+        )));
+    }
+
+    for (i, record_updates) in abi.record_updates.iter().enumerate() {
+        let offset = offset + i * 4;
+        let updates_hash: [u64; 4] = output_stack
+            .get(offset..offset + 4)
+            .and_then(|hash| hash.try_into().ok())
+            .ok_or_else(|| Error::simple("the program does not output record updates"))?;
+
+        let update_type = Type::Struct(abi::record_update_struct(
+            record_struct(abi, &record_updates.contract)?.clone(),
+        ));
+        let updates_value = Value::Array(
+            updates
+                .iter()
+                .filter(|u| u.contract == record_updates.contract)
+                .map(RecordUpdate::to_value)
+                .collect(),
+        );
+        if hash_this(Type::Array(Box::new(update_type)), &updates_value, None)? != updates_hash {
+            return Err(Error::simple(format!(
+                "the record updates of {} don't match the program outputs",
+                record_updates.contract
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// The offset of the hashes of the record updates in the output stack.
+fn record_updates_offset(abi: &Abi) -> usize {
+    // + 1 for self_destructed
+    1 + abi.dependent_fields.len() * 4
+        + abi.result_type.as_ref().map_or(0, |_| 4)
+        + if abi.signed { 8 } else { 0 }
+        + abi.signature_claims_addr.map_or(0, |_| 4)
+}
+
+/// A record of another contract created with `new`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreatedRecord {
+    pub contract: String,
+    pub record: Value,
+    /// The salts of the fields of the record, which must be stored with it.
+    pub salts: Vec<Salt>,
+    /// The hash of the record, salted with `salts`.
+    pub hash: [u64; 4],
+    /// Whether `ctx.publicKey` can read the `@private` fields of the record.
+    pub private_read_auth: bool,
+}
+
+impl CreatedRecord {
+    fn from_value(contract: &str, value: Value, private_read_auth: bool) -> Result<Self> {
+        let Value::StructValue(fields) = value else {
+            return Err(Error::simple("created record is not a struct"));
+        };
+
+        let mut fields = fields.into_iter().map(|(_, value)| value);
+        let (Some(record), Some(Value::StructValue(salts)), Some(Value::Hash(hash))) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(Error::simple("invalid created record"));
+        };
+        let salts = salts
+            .into_iter()
+            .map(|(_, salt)| match salt {
+                Value::Hash(salt) => Ok(Salt(salt)),
+                _ => Err(Error::simple("invalid created record salt")),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            contract: contract.to_owned(),
+            record,
+            salts,
+            hash,
+            private_read_auth,
+        })
+    }
+
+    fn private_fields<'a>(&self, abi: &'a Abi) -> &'a [String] {
+        abi.created_records
+            .iter()
+            .find(|r| r.contract == self.contract)
+            .map(|r| r.private_fields.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Returns the record with the `@private` fields replaced by their salted hashes,
+    /// unless `ctx.publicKey` can read them.
+    pub fn redacted_record(&self, abi: &Abi) -> Result<Value> {
+        if self.private_read_auth {
+            return Ok(self.record.clone());
+        }
+
+        redact_fields(
+            record_struct(abi, &self.contract)?,
+            self.private_fields(abi),
+            self.record.clone(),
+            &self.salts,
+        )
+    }
+
+    /// Returns the salts of the record without the salts of the `@private` fields,
+    /// unless `ctx.publicKey` can read them.
+    pub fn redacted_salts(&self, abi: &Abi) -> Result<Vec<Option<Salt>>> {
+        if self.private_read_auth {
+            return Ok(self.salts.iter().copied().map(Some).collect());
+        }
+
+        Ok(redact_salts(
+            &record_struct(abi, &self.contract)?.fields,
+            self.private_fields(abi),
+            &self.salts,
+        ))
+    }
+
+    fn to_value(&self, record_struct: &abi::Struct) -> Value {
+        Value::StructValue(vec![
+            ("record".to_owned(), self.record.clone()),
+            (
+                "salts".to_owned(),
+                Value::StructValue(
+                    record_struct
+                        .fields
+                        .iter()
+                        .zip(&self.salts)
+                        .map(|((name, _), salt)| (name.clone(), Value::Hash(salt.0)))
+                        .collect(),
+                ),
+            ),
+            ("hash".to_owned(), Value::Hash(self.hash)),
+        ])
+    }
+}
+
+/// Verifies the records that a program with the given output stack created in other contracts.
+///
+/// The program outputs the hash of the created records of every contract in `abi.created_records`,
+/// so the records of each contract, in the order they were created, must hash to it.
+/// The hash of every record must also match the record and its salts.
+pub fn verify_created_records(
+    abi: &Abi,
+    output_stack: &[u64],
+    created: &[CreatedRecord],
+) -> Result<()> {
+    let offset = record_updates_offset(abi) + abi.record_updates.len() * 4;
+
+    if let Some(record) = created
+        .iter()
+        .find(|c| !abi.created_records.iter().any(|r| r.contract == c.contract))
+    {
+        return Err(Error::simple(format!(
+            "the program does not create records of {}",
+            record.contract
+        )));
+    }
+
+    for (i, created_records) in abi.created_records.iter().enumerate() {
+        let offset = offset + i * 4;
+        let created_hash: [u64; 4] = output_stack
+            .get(offset..offset + 4)
+            .and_then(|hash| hash.try_into().ok())
+            .ok_or_else(|| Error::simple("the program does not output created records"))?;
+
+        let record_struct = record_struct(abi, &created_records.contract)?;
+        let created_type = Type::Struct(abi::created_record_struct(record_struct.clone()));
+        let mut created_values = vec![];
+        for record in created
+            .iter()
+            .filter(|c| c.contract == created_records.contract)
+        {
+            if record.salts.len() != record_struct.fields.len()
+                || hash_this(
+                    Type::Struct(record_struct.clone()),
+                    &record.record,
+                    Some(&record.salts),
+                )? != record.hash
+            {
+                return Err(Error::simple(format!(
+                    "the hash of a created {} record doesn't match the record",
+                    created_records.contract
+                )));
+            }
+
+            created_values.push(record.to_value(record_struct));
+        }
+
+        if hash_this(
+            Type::Array(Box::new(created_type)),
+            &Value::Array(created_values),
+            None,
+        )? != created_hash
+        {
+            return Err(Error::simple(format!(
+                "the created records of {} don't match the program outputs",
+                created_records.contract
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// An event emitted with `emit`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub contract: String,
+    pub name: String,
+    pub value: Value,
+}
+
+impl Event {
+    fn from_value(types: &[abi::EventType], value: Value) -> Result<Self> {
+        let Value::StructValue(fields) = value else {
+            return Err(Error::simple("event is not a struct"));
+        };
+
+        types
+            .iter()
+            .zip(fields)
+            .find_map(|(t, (_, value))| match value {
+                Value::Nullable(Some(value)) => Some(Self {
+                    contract: t.contract.clone(),
+                    name: t.event.name.clone(),
+                    value: *value,
+                }),
+                _ => None,
+            })
+            .ok_or_else(|| Error::simple("invalid event"))
+    }
+
+    fn to_value(&self, types: &[abi::EventType]) -> Value {
+        Value::StructValue(
+            types
+                .iter()
+                .map(|t| {
+                    (
+                        format!("{}.{}", t.contract, t.event.name),
+                        Value::Nullable(
+                            (t.contract == self.contract && t.event.name == self.name)
+                                .then(|| Box::new(self.value.clone())),
+                        ),
+                    )
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Verifies the events that a program with the given output stack emitted.
+///
+/// The program outputs the hash of all of its events, below the hashes of the created records,
+/// so the events, in the order they were emitted, must hash to it.
+pub fn verify_events(abi: &Abi, output_stack: &[u64], events: &[Event]) -> Result<()> {
+    let Some(abi_events) = &abi.events else {
+        return match events.first() {
+            Some(event) => Err(Error::simple(format!(
+                "the program does not emit {}.{}",
+                event.contract, event.name
+            ))),
+            None => Ok(()),
+        };
+    };
+
+    if let Some(event) = events.iter().find(|e| {
+        !abi_events
+            .types
+            .iter()
+            .any(|t| t.contract == e.contract && t.event.name == e.name)
+    }) {
+        return Err(Error::simple(format!(
+            "the program does not emit {}.{}",
+            event.contract, event.name
+        )));
+    }
+
+    let offset =
+        record_updates_offset(abi) + abi.record_updates.len() * 4 + abi.created_records.len() * 4;
+    let events_hash: [u64; 4] = output_stack
+        .get(offset..offset + 4)
+        .and_then(|hash| hash.try_into().ok())
+        .ok_or_else(|| Error::simple("the program does not output events"))?;
+
+    let event_type = Type::Struct(abi::event_struct(&abi_events.types));
+    let events_value = Value::Array(
+        events
+            .iter()
+            .map(|event| event.to_value(&abi_events.types))
+            .collect(),
+    );
+    if hash_this(Type::Array(Box::new(event_type)), &events_value, None)? != events_hash {
+        return Err(Error::simple("the events don't match the program outputs"));
+    }
+
+    Ok(())
 }
 
 pub fn run<'a>(
@@ -616,22 +1705,36 @@ pub fn run<'a>(
 )> {
     let other_records = inputs.other_records()?;
     let input_stack = inputs.stack(&other_records)?;
+
     let advice_tape = inputs.advice_provider(&other_records)?;
+    let signature_claims = Rc::new(RefCell::new(vec![]));
 
     let mut last_ok_state = None;
     let mut err = None;
 
-    let host = miden::DefaultHost::new(advice_tape.clone());
+    let host = SignatureHost::new(
+        &inputs.abi,
+        advice_tape.clone(),
+        Rc::clone(&signature_claims),
+    );
 
     for state in miden_processor::execute_iter(program, input_stack.clone(), host) {
         match state {
             Ok(state) => {
+                if let Some(max_cycles) = inputs.max_cycles {
+                    if state.clk as u64 > max_cycles {
+                        return Err(Error::simple(format!(
+                            "the program took more than {max_cycles} cycles, over the budget"
+                        )));
+                    }
+                }
+
                 last_ok_state = Some(state);
             }
             Err(e) => {
                 // TODO: store vector of errors instead.
                 if err.is_none() {
-                    err = Some(Error::wrapped(Box::new(MidenError::Execution(e))));
+                    err = Some(e);
                 }
             }
         }
@@ -639,24 +1742,30 @@ pub fn run<'a>(
 
     let last_ok_state = match (last_ok_state, err) {
         (None, Some(e)) => {
-            return Err(e);
+            return Err(Error::wrapped(Box::new(MidenError::Execution(e))));
         }
+        (Some(state), None) => state,
         (Some(state), Some(e)) => {
+            let read_memory = |addr: u64| {
+                state
+                    .memory
+                    .iter()
+                    .find(|(a, _)| *a == addr)
+                    .map(|(_, x)| x.map(|x| mont_red_cst(x.inner() as u128)))
+            };
+
+            // 9 is where the program stores the start of the statement it is executing,
+            // errors created in this scope point at that statement.
+            let _span = read_memory(9)
+                .and_then(|word| inputs.abi.statement_span(word[0]))
+                .map(error::span::start);
+            let e = Error::wrapped(Box::new(MidenError::Execution(e)));
+
             if !state.memory.iter().any(|(a, _)| *a == 1) {
                 return Err(e);
             }
 
-            let Value::String(s) = Type::String.read(
-                &|addr| {
-                    state
-                        .memory
-                        .iter()
-                        .find(|(a, _)| *a == addr)
-                        .map(|(_, x)| x.map(|x| mont_red_cst(x.inner() as u128)))
-                },
-                1,
-            )?
-            else {
+            let Value::String(s) = Type::String.read(&read_memory, 1)? else {
                 return Err(e);
             };
 
@@ -666,7 +1775,6 @@ pub fn run<'a>(
                 Err(Error::simple(format!("{}: {}", s, e)))
             };
         }
-        (Some(state), _) => state,
         (None, None) => unreachable!(),
     };
 
@@ -698,6 +1806,7 @@ pub fn run<'a>(
         })
         .collect::<HashMap<_, _>>();
 
+    let abi = inputs.abi.clone();
     Ok((
         RunOutput {
             abi: inputs.abi.clone(),
@@ -706,9 +1815,11 @@ pub fn run<'a>(
             input_stack: input_stack_values,
             stack_inputs: input_stack.clone(),
             memory,
+            signature_claims: signature_claims.take(),
         },
         move || {
-            let host = miden::DefaultHost::new(advice_tape);
+            // The signatures are verified again, in the same order
+            let host = SignatureHost::new(&abi, advice_tape, Rc::default());
 
             let (stack_outputs, proof) =
                 miden_prover::prove(program, input_stack, host, ProvingOptions::default())
@@ -722,10 +1833,16 @@ pub fn run<'a>(
 
 pub trait ProgramExt {
     fn to_program_info_bytes(self) -> Vec<u8>;
+    /// The hex encoded hash of the program, as recorded in manifests.
+    fn program_hash(&self) -> String;
 }
 
 impl ProgramExt for Program {
     fn to_program_info_bytes(self) -> Vec<u8> {
         ProgramInfo::from(self).to_bytes()
     }
+
+    fn program_hash(&self) -> String {
+        compiler::program_hash(self)
+    }
 }
@@ -0,0 +1,99 @@
+//! Advice for the builtins that execute procedures of the Miden standard library.
+//!
+//! `smtGet`, `smtInsert`, `mmrGet` and `mmrAdd` request Merkle nodes and MMR peaks
+//! from the advice provider, and `falconVerify` requests a signature, which
+//! Miden VM 0.7 creates in the advice provider from the key pair.
+//! Words are `[u64; 4]`s, in the order they are read with `readAdviceHash`.
+
+use miden::crypto::merkle::{MerkleStore, Mmr, TieredSmt};
+
+use super::*;
+
+pub use miden::crypto::dsa::rpo_falcon512::KeyPair;
+
+/// The advice that the standard library builtins of a program request, see [`Inputs::with_stdlib_advice`].
+#[derive(Debug, Clone, Default)]
+pub struct StdlibAdvice {
+    pub(crate) map: Vec<([u8; 32], Vec<Felt>)>,
+    pub(crate) merkle_store: MerkleStore,
+    pub(crate) words: Vec<[u64; 4]>,
+}
+
+impl StdlibAdvice {
+    /// Adds a word for the program to read with `readAdviceHash`, after the arguments.
+    pub fn push_word(&mut self, word: [u64; 4]) {
+        self.words.push(word);
+    }
+
+    /// Adds the key pair that `falconVerify` signs messages for its public key with,
+    /// returns the public key.
+    pub fn add_falcon_key_pair(&mut self, key_pair: &KeyPair) -> [u64; 4] {
+        let public_key = Digest::from(miden_processor::Word::from(key_pair.public_key()));
+        self.map.push((
+            public_key.as_bytes(),
+            key_pair
+                .to_bytes()
+                .into_iter()
+                .map(|byte| Felt::new(byte as u64))
+                .collect(),
+        ));
+
+        to_word(&public_key)
+    }
+
+    /// Adds the nodes and leaves of a tiered SMT with the entries, returns its root.
+    pub fn add_smt(&mut self, entries: &[([u64; 4], [u64; 4])]) -> Result<[u64; 4]> {
+        let smt = TieredSmt::with_entries(
+            entries
+                .iter()
+                .map(|(key, value)| (Digest::new(key.map(Felt::new)), value.map(Felt::new))),
+        )
+        .map_err(|e| Error::simple(format!("invalid SMT: {e}")))?;
+
+        self.merkle_store.extend(smt.inner_nodes());
+        // The leaves are hashed with their keys, `smt::get` and `smt::insert` need both
+        for (node, key, value) in smt.upper_leaves() {
+            let mut elements = key.as_elements().to_vec();
+            elements.extend(value);
+            self.map.push((node.as_bytes(), elements));
+        }
+        for (node, leaves) in smt.bottom_leaves() {
+            let mut elements = vec![];
+            for (key, value) in leaves {
+                elements.extend(key.as_elements());
+                elements.extend(value);
+            }
+            self.map.push((node.as_bytes(), elements));
+        }
+
+        Ok(to_word(&smt.root()))
+    }
+
+    /// Adds the peaks and nodes of an MMR with the leaves, returns the hash of its peaks.
+    pub fn add_mmr(&mut self, leaves: &[[u64; 4]]) -> [u64; 4] {
+        let mut mmr = Mmr::new();
+        for leaf in leaves {
+            mmr.add(Digest::new(leaf.map(Felt::new)));
+        }
+
+        self.merkle_store.extend(mmr.inner_nodes());
+        // `mmr::unpack` reads the number of leaves, and the peaks padded like they are hashed
+        let peaks = mmr.accumulator();
+        let hash = Digest::from(peaks.hash_peaks());
+        let mut elements = vec![
+            Felt::new(mmr.forest() as u64),
+            Felt::new(0),
+            Felt::new(0),
+            Felt::new(0),
+        ];
+        elements.extend(peaks.flatten_and_pad_peaks());
+        self.map.push((hash.as_bytes(), elements));
+
+        to_word(&hash)
+    }
+}
+
+fn to_word(digest: &Digest) -> [u64; 4] {
+    let elements = digest.as_elements();
+    [0, 1, 2, 3].map(|i| mont_red_cst(elements[i].inner() as u128))
+}
@@ -2,10 +2,10 @@ use std::collections::HashMap;
 
 use base64::Engine;
 use error::prelude::*;
-use polylang_prover::{compile_program, Inputs, ProgramExt};
+use polylang_prover::{check_manifest, compile_program, Inputs, ProgramExt};
 use serde::Deserialize;
 
-type OtherRecordsType = HashMap<String, Vec<(serde_json::Value, Vec<u32>)>>;
+type OtherRecordsType = HashMap<String, Vec<(serde_json::Value, Vec<abi::salt::Salt>)>>;
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -14,13 +14,34 @@ pub struct ProveRequest {
     pub abi: abi::Abi,
     pub ctx_public_key: Option<abi::publickey::Key>,
     pub this: Option<serde_json::Value>, // this_json
-    pub this_salts: Option<Vec<u32>>,
+    /// The salts of the fields of `this`, stored with the record.
+    /// New salts are generated for constructors, and zero salts are used for
+    /// other calls of records without `@private` fields if missing.
+    pub this_salts: Option<Vec<abi::salt::Salt>>,
     pub args: Vec<serde_json::Value>,
     pub other_records: Option<OtherRecordsType>,
+    /// If set, the program must be one of the programs in the manifest.
+    pub manifest: Option<abi::Manifest>,
+    /// The nonce of the call, `ctx.nonce` in the program.
+    pub nonce: Option<u64>,
+    /// The hex encoded signature of the call message, required by signed programs.
+    pub signature: Option<String>,
 }
 
-pub async fn prove(mut req: ProveRequest) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+pub async fn prove(
+    mut req: ProveRequest,
+    cycle_budget: Option<abi::CycleBudget>,
+) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    // Reject programs that are too expensive before spending time on running them.
+    // The estimate comes with the request, so the budget is also enforced while running them
+    if let Some(cycle_budget) = cycle_budget {
+        cycle_budget.check_abi(&req.abi)?;
+    }
+
     let program = compile_program(&req.abi, &req.miden_code)?;
+    if let Some(manifest) = &req.manifest {
+        check_manifest(&program, manifest)?;
+    }
 
     let has_this = req.abi.this_type.is_some();
     let this = req.this.clone().unwrap_or(if has_this {
@@ -37,17 +58,16 @@ pub async fn prove(mut req: ProveRequest) -> Result<serde_json::Value, Box<dyn s
         req.abi.this_addr = Some(0);
     }
 
-    let this_salts = req.this_salts.unwrap_or(
-        req.abi
-            .this_type
-            .as_ref()
-            .map(|ty| match ty {
-                abi::Type::Struct(st) => Ok(st.fields.iter().map(|_| 0).collect()),
-                _ => Err(Error::simple("this type must be a struct")),
-            })
-            .transpose()?
-            .unwrap_or(vec![]),
-    );
+    // The salts generated for the constructor are only known to the caller that creates the record
+    let generated_salts = req.this_salts.is_none() && req.abi.constructor;
+    let this_salts = match req.this_salts.take() {
+        Some(salts) => salts,
+        None if generated_salts => polylang_prover::generate_salts(&req.abi)?,
+        None if !req.abi.private_fields.is_empty() => {
+            return Err("thisSalts is required for records with private fields".into())
+        }
+        None => polylang_prover::zero_salts(&req.abi)?,
+    };
 
     let inputs = Inputs::new(
         req.abi.clone(),
@@ -56,9 +76,22 @@ pub async fn prove(mut req: ProveRequest) -> Result<serde_json::Value, Box<dyn s
         this.clone(),
         req.args.clone(),
         req.other_records.clone().unwrap_or_default(),
-    )?;
+    )?
+    .with_call_signature(
+        req.nonce.unwrap_or_default(),
+        req.signature
+            .as_deref()
+            .map(|signature| hex::decode(signature.trim_start_matches("0x")))
+            .transpose()?,
+    )
+    .with_created_records_seed(polylang_prover::generate_salt());
+    let inputs = match cycle_budget {
+        Some(cycle_budget) => inputs.with_max_cycles(cycle_budget.max_cycles),
+        None => inputs,
+    };
 
     let program_info = program.clone().to_program_info_bytes();
+    let program_hash = program.program_hash();
     let output = tokio::task::spawn_blocking({
         let inputs = inputs.clone();
         move || polylang_prover::prove(&program, &inputs).map_err(|e| e.to_string())
@@ -80,6 +113,14 @@ pub async fn prove(mut req: ProveRequest) -> Result<serde_json::Value, Box<dyn s
         "new": {
             "selfDestructed": output.run_output.self_destructed()?,
             "this": new_this,
+            // The salts must be stored with the record, and passed as `thisSalts` to its next calls.
+            // The salts of private fields are null unless `ctx.publicKey` can read them,
+            // or they were generated for the caller, who would lose the record without them
+            "salts": if generated_salts {
+                inputs.this_salts.iter().copied().map(Some).collect::<Vec<_>>()
+            } else {
+                output.run_output.redacted_salts(&req.abi, &inputs.this_salts)
+            },
             "hashes": output.new_hashes.into_iter().map(|h| {
                 [
                     h[0].to_string(),
@@ -101,6 +142,44 @@ pub async fn prove(mut req: ProveRequest) -> Result<serde_json::Value, Box<dyn s
             })
         } else { serde_json::Value::Null },
         "programInfo": base64::engine::general_purpose::STANDARD.encode(program_info),
+        "programHash": program_hash,
+        "callMessage": output
+            .run_output
+            .call_message()
+            .map(|message| hex::encode(polylang_prover::call_message_bytes(&message))),
+        // The private fields of the records are salted hashes unless `ctx.publicKey` can read them
+        "recordUpdates": output.run_output.record_updates(&req.abi)?.into_iter().map(|update| {
+            Ok(serde_json::json!({
+                "contract": update.contract,
+                "record": TryInto::<serde_json::Value>::try_into(update.redacted_record(&req.abi, &inputs)?)?,
+                "hash": update.hash.map(|x| x.to_string()),
+            }))
+        }).collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?,
+        // The salts must be stored with the created records, like `new.salts`.
+        // The private fields and their salts are redacted like those of `this`
+        "createdRecords": output.run_output.created_records(&req.abi)?.into_iter().map(|created| {
+            Ok(serde_json::json!({
+                "contract": created.contract,
+                "record": TryInto::<serde_json::Value>::try_into(created.redacted_record(&req.abi)?)?,
+                "salts": created.redacted_salts(&req.abi)?,
+                "hash": created.hash.map(|x| x.to_string()),
+            }))
+        }).collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?,
+        "events": output.run_output.events(&req.abi)?.into_iter().map(|event| {
+            Ok(serde_json::json!({
+                "contract": event.contract,
+                "name": event.name,
+                "value": TryInto::<serde_json::Value>::try_into(event.value)?,
+            }))
+        }).collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?,
+        "signatureClaims": output.run_output.signature_claims.iter().map(|claim| {
+            serde_json::json!({
+                "publicKey": claim.public_key,
+                "message": hex::encode(&claim.message),
+                "signature": hex::encode(&claim.signature),
+                "valid": claim.valid,
+            })
+        }).collect::<Vec<_>>(),
         "proof": base64::engine::general_purpose::STANDARD.encode(output.proof),
         "debug": {
             "logs": output.run_output.logs(),
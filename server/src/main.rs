@@ -3,19 +3,51 @@ use actix_web::{web, App, HttpResponse, HttpServer, Responder};
 
 async fn prove(
     req: web::Json<server_routes::prove::ProveRequest>,
+    cycle_budget: web::Data<Option<abi::CycleBudget>>,
 ) -> Result<impl Responder, Box<dyn std::error::Error>> {
-    Ok(HttpResponse::Ok().json(server_routes::prove::prove(req.into_inner()).await?))
+    Ok(HttpResponse::Ok()
+        .json(server_routes::prove::prove(req.into_inner(), **cycle_budget).await?))
 }
 
 #[tokio::main]
 async fn main() {
     let port = std::env::var("PORT").unwrap_or("8080".to_string());
     let listen_addr = std::env::var("PROVER_LADDR").unwrap_or(format!("0.0.0.0:{port}"));
+    let cycle_budget = std::env::var("PROVER_CYCLE_BUDGET").ok().map(|max_cycles| {
+        let loop_iterations = std::env::var("PROVER_LOOP_ITERATIONS").unwrap_or("100".to_string());
+        abi::CycleBudget {
+            max_cycles: max_cycles
+                .parse()
+                .expect("PROVER_CYCLE_BUDGET must be a number"),
+            loop_iterations: loop_iterations
+                .parse()
+                .expect("PROVER_LOOP_ITERATIONS must be a number"),
+        }
+    });
+    if let Ok(capacity) = std::env::var("PROVER_PROGRAM_CACHE_SIZE") {
+        polylang_prover::cache::PROGRAM_CACHE
+            .lock()
+            .unwrap()
+            .set_capacity(
+                capacity
+                    .parse()
+                    .expect("PROVER_PROGRAM_CACHE_SIZE must be a number"),
+            );
+    }
+    if let Ok(dir) = std::env::var("PROVER_PROGRAM_CACHE_DIR") {
+        let disk_store = polylang_prover::cache::DiskStore::new(dir)
+            .expect("failed to open PROVER_PROGRAM_CACHE_DIR");
+        polylang_prover::cache::PROGRAM_CACHE
+            .lock()
+            .unwrap()
+            .set_disk_store(Some(disk_store));
+    }
 
-    let app = || {
+    let app = move || {
         let cors = Cors::permissive();
         App::new()
             .wrap(cors)
+            .app_data(web::Data::new(cycle_budget))
             .service(web::resource("/prove").route(web::post().to(prove)))
     };
 
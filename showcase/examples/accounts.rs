@@ -76,6 +76,7 @@ fn run(
         advice_tape_json: advice,
         this_values: HashMap::new(),
         this_json: Some(this_json),
+        this_salts: None,
         other_records: HashMap::new(),
         abi,
         ctx: Ctx::default(),
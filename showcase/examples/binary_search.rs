@@ -60,6 +60,7 @@ fn binary_search(arr: &Vec<i32>, elem: i32) -> Result<(), Box<dyn std::error::Er
         advice_tape_json: Some(format!("[{elem}]")),
         this_values: HashMap::new(),
         this_json: Some(json!({"arr": arr, "found": false, "foundPos": 0 })),
+        this_salts: None,
         other_records: HashMap::new(),
         abi,
         ctx: Ctx::default(),
@@ -40,6 +40,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         ),
         this_values: HashMap::new(),
         this_json: Some(json!({"id": "", "name": "", "country": { "id": "", "name": "" }})),
+        this_salts: None,
         other_records: HashMap::new(),
         abi,
         ctx: Ctx::default(),
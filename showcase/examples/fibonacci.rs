@@ -31,6 +31,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         advice_tape_json: Some("[8, 1, 1]".to_string()),
         this_values: HashMap::new(),
         this_json: Some(json!([])),
+        this_salts: None,
         other_records: HashMap::new(),
         abi,
         ctx: Ctx::default(),
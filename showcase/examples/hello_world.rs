@@ -25,6 +25,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         advice_tape_json: Some("[1, 2]".into()),
         this_values: HashMap::new(),
         this_json: Some(json!({})),
+        this_salts: None,
         other_records: HashMap::new(),
         abi,
         ctx: Ctx::default(),
@@ -42,6 +42,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         advice_tape_json: None,
         this_values: HashMap::new(),
         this_json: Some(json!({"elements": [1, 3, 4, 5, 7, 6, 2, 3]})),
+        this_salts: None,
         other_records: HashMap::new(),
         abi,
         ctx: Ctx::default(),
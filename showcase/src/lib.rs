@@ -1,4 +1,4 @@
-use abi::Abi;
+use abi::{salt::Salt, Abi};
 use std::{collections::HashMap, io::Write};
 
 #[derive(Default, serde::Deserialize)]
@@ -11,7 +11,9 @@ pub struct Args {
     pub advice_tape_json: Option<String>,
     pub this_values: HashMap<String, String>,
     pub this_json: Option<serde_json::Value>,
-    pub other_records: HashMap<String, Vec<(serde_json::Value, Vec<u32>)>>,
+    /// The salts stored with `this`, zero salts are used if `None`
+    pub this_salts: Option<Vec<Salt>>,
+    pub other_records: HashMap<String, Vec<(serde_json::Value, Vec<Salt>)>>,
     pub abi: Abi,
     pub ctx: Ctx,
     pub proof_output: Option<String>,
@@ -23,7 +25,7 @@ impl Args {
         hasher: impl Fn(
             abi::Type,
             &abi::Value,
-            Option<&[u32]>,
+            Option<&[Salt]>,
         ) -> Result<[u64; 4], Box<dyn std::error::Error>>,
     ) -> Result<polylang_prover::Inputs, Box<dyn std::error::Error>> {
         let this = self.this_value()?;
@@ -34,16 +36,20 @@ impl Args {
             abi::Type::Struct(s) => &s.fields,
             _ => unreachable!(),
         };
+        let this_salts = match &self.this_salts {
+            Some(salts) => salts.clone(),
+            None => polylang_prover::zero_salts(&self.abi)?,
+        };
         let this_field_hashes = sv
             .iter()
             .enumerate()
-            .map(|(i, (_, v))| hasher(this_fields[i].1.clone(), &v, Some(&[0])))
+            .map(|(i, (_, v))| hasher(this_fields[i].1.clone(), &v, Some(&[this_salts[i]])))
             .collect::<Result<Vec<_>, _>>()?;
 
         Ok(polylang_prover::Inputs {
             abi: self.abi.clone(),
             ctx_public_key: self.ctx.public_key.clone(),
-            this_salts: sv.iter().map(|_| 0).collect(),
+            this_salts,
             this: this.try_into()?,
             this_field_hashes,
             args: serde_json::from_str(
@@ -54,6 +60,11 @@ impl Args {
                     .unwrap_or("[]"),
             )?,
             other_records: self.other_records.clone(),
+            nonce: 0,
+            signature: None,
+            created_records_seed: Salt::ZERO,
+            stdlib_advice: Default::default(),
+            max_cycles: None,
         })
     }
 
@@ -6,12 +6,28 @@ fn main() {
 
     let mut contract_name = None;
     let mut function_name = "main".to_string();
+    let mut library = false;
+    let mut std_version = abi::StdVersion::default();
+    let mut cycle_budget = None;
+    let mut loop_iterations = 100;
+    let mut manifest_path = None;
+    let mut debug = false;
 
     for arg in std::env::args().skip(1) {
         match arg.split_once(':') {
             Some((key, value)) => match key {
                 "contract" => contract_name = Some(value.to_string()),
                 "function" => function_name = value.to_string(),
+                "library" => library = value.parse().expect("library must be true or false"),
+                "std_version" => std_version = value.parse().unwrap_or_else(|e| panic!("{e}")),
+                "cycle_budget" => {
+                    cycle_budget = Some(value.parse().expect("cycle_budget must be a number"))
+                }
+                "loop_iterations" => {
+                    loop_iterations = value.parse().expect("loop_iterations must be a number")
+                }
+                "manifest" => manifest_path = Some(value.to_string()),
+                "debug" => debug = value.parse().expect("debug must be true or false"),
                 _ => panic!("unknown argument: {}", key),
             },
             None => panic!("invalid argument: {}", arg),
@@ -20,10 +36,71 @@ fn main() {
 
     let program = polylang_parser::parse(&code).unwrap();
 
-    let (miden_code, abi) =
-        polylang::compiler::compile(program, contract_name.as_deref(), &function_name)
+    if let Some(manifest_path) = manifest_path {
+        let manifest = polylang::compiler::compile_manifest(
+            polylang_parser::parse(&code).unwrap(),
+            &code,
+            contract_name.as_deref(),
+            std_version,
+        )
+        .map_err(|e| e.add_source(code.as_str()))
+        .unwrap_or_else(|e| panic!("{e}"));
+        std::fs::write(
+            manifest_path,
+            serde_json::to_string_pretty(&manifest).unwrap(),
+        )
+        .expect("failed to write the manifest");
+    }
+
+    let check_budget = |abi: &abi::Abi| {
+        let Some(max_cycles) = cycle_budget else {
+            return;
+        };
+
+        abi::CycleBudget {
+            max_cycles,
+            loop_iterations,
+        }
+        .check_abi(abi)
+        .unwrap_or_else(|e| panic!("{e}"));
+    };
+
+    if library {
+        let contract_name = contract_name.expect("library requires a contract");
+        assert!(
+            std_version == abi::StdVersion::default(),
+            "libraries can only be compiled for std version {}",
+            abi::StdVersion::default()
+        );
+        let (miden_code, abi) = polylang::compiler::compile_library(program, &contract_name)
             .map_err(|e| e.add_source(code))
             .unwrap_or_else(|e| panic!("{e}"));
+        for entry_point in &abi.entry_points {
+            check_budget(&entry_point.abi);
+        }
+        println!("{}", miden_code);
+        eprintln!("ABI: {}", serde_json::to_string(&abi).unwrap());
+        return;
+    }
+
+    let (miden_code, abi) = if debug {
+        assert!(
+            std_version == abi::StdVersion::default(),
+            "debug programs can only be compiled for std version {}",
+            abi::StdVersion::default()
+        );
+        polylang::compiler::compile_debug(program, contract_name.as_deref(), &function_name)
+    } else {
+        polylang::compiler::compile_for_version(
+            program,
+            contract_name.as_deref(),
+            &function_name,
+            std_version,
+        )
+    }
+    .map_err(|e| e.add_source(code))
+    .unwrap_or_else(|e| panic!("{e}"));
+    check_budget(&abi);
     println!("{}", miden_code);
     eprintln!("ABI: {}", serde_json::to_string(&abi).unwrap());
 }
@@ -30,8 +30,8 @@ pub fn validate_set(ast_json: &str, data_json: &str) -> String {
 
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
-pub fn generate_js_contract(contract_ast_json: &str) -> String {
-    crate::generate_js_contract_out_json(contract_ast_json)
+pub fn generate_js_contract(contract_ast_json: &str, root_ast_json: &str) -> String {
+    crate::generate_js_contract_out_json(contract_ast_json, root_ast_json)
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -65,11 +65,17 @@ pub extern "C" fn validate_set(ast_json: *const c_char, data_json: *const c_char
 
 #[cfg(not(target_arch = "wasm32"))]
 #[no_mangle]
-pub extern "C" fn generate_js_contract(contract_ast_json: *const c_char) -> *mut c_char {
+pub extern "C" fn generate_js_contract(
+    contract_ast_json: *const c_char,
+    root_ast_json: *const c_char,
+) -> *mut c_char {
     let contract_ast_json = unsafe { std::ffi::CStr::from_ptr(contract_ast_json) };
     let contract_ast_json = contract_ast_json.to_str().unwrap();
 
-    let output = crate::generate_js_contract_out_json(contract_ast_json);
+    let root_ast_json = unsafe { std::ffi::CStr::from_ptr(root_ast_json) };
+    let root_ast_json = root_ast_json.to_str().unwrap();
+
+    let output = crate::generate_js_contract_out_json(contract_ast_json, root_ast_json);
     let output = std::ffi::CString::new(output).unwrap();
     output.into_raw()
 }
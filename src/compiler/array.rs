@@ -7,8 +7,9 @@ pub(crate) const WIDTH: u32 = 3;
 
 /// Returns (array_symbol, data_ptr), because data_ptr is known statically
 pub(crate) fn new(compiler: &mut Compiler, len: u32, element_type: Type) -> (Symbol, u32) {
+    let element_width = element_type.miden_width();
     let symbol = Symbol {
-        memory_addr: compiler.memory.allocate(WIDTH),
+        memory_addr: compiler.allocate(WIDTH),
         type_: Type::Array(Box::new(element_type)),
     };
 
@@ -29,7 +30,8 @@ pub(crate) fn new(compiler: &mut Compiler, len: u32, element_type: Type) -> (Sym
         &[ValueSource::Immediate(len)],
     );
 
-    let allocated_ptr = compiler.memory.allocate(len * 2);
+    let allocated_ptr = compiler.allocate(capacity * element_width);
+    compiler.memory.pin();
 
     compiler.memory.write(
         compiler.instructions,
@@ -41,16 +43,14 @@ pub(crate) fn new(compiler: &mut Compiler, len: u32, element_type: Type) -> (Sym
 }
 
 fn dynamic_new(compiler: &mut Compiler, element_type: Type, needed_len: Symbol) -> Result<Symbol> {
-    let array = compiler
-        .memory
-        .allocate_symbol(Type::Array(Box::new(element_type)));
+    let element_width = element_type.miden_width();
+    let array = compiler.allocate_symbol(Type::Array(Box::new(element_type)));
 
-    let cap = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+    let cap = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+    let size = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
     compiler
         .memory
-        .read(compiler.instructions, cap.memory_addr, 1);
+        .read(compiler.instructions, needed_len.memory_addr, 1);
     // [needed_len]
     compiler.instructions.extend([
         Instruction::Push(2),
@@ -59,15 +59,17 @@ fn dynamic_new(compiler: &mut Compiler, element_type: Type, needed_len: Symbol)
         Instruction::Push(16),
         Instruction::U32CheckedAdd,
         // [cap = needed_len * 2 + 16]
+        Instruction::Dup(None),
+        Instruction::MemStore(Some(cap.memory_addr)),
+        // [cap]
+        Instruction::Push(element_width),
+        Instruction::U32CheckedMul,
+        // [size = cap * element_width]
+        Instruction::MemStore(Some(size.memory_addr)),
+        // []
     ]);
-    compiler.memory.write(
-        compiler.instructions,
-        cap.memory_addr,
-        &[ValueSource::Stack],
-    );
-    // []
 
-    let array_data_ptr = dynamic_alloc(compiler, &[needed_len])?;
+    let array_data_ptr = dynamic_alloc(compiler, &[size])?;
 
     compiler.memory.write(
         compiler.instructions,
@@ -130,14 +132,14 @@ pub(crate) fn hash(compiler: &mut Compiler, _scope: &mut Scope, args: &[Symbol])
         let mut insts = Vec::new();
 
         std::mem::swap(compiler.instructions, &mut insts);
-        let input = compiler.memory.allocate_symbol(*inner_type.clone());
+        let input = compiler.allocate_symbol(*inner_type.clone());
         let output = super::hash(compiler, input.clone())?;
         std::mem::swap(compiler.instructions, &mut insts);
 
         (input, insts, output)
     };
 
-    let result = compiler.memory.allocate_symbol(Type::Hash);
+    let result = compiler.allocate_symbol(Type::Hash);
 
     compiler.instructions.extend([
         Instruction::Push(0),
@@ -260,16 +262,14 @@ pub(crate) fn hash_width_1(compiler: &mut Compiler, arr: &Symbol, hash: HashFn)
     };
 
     let result = match n_width {
-        8 => compiler.memory.allocate_symbol(Type::Hash8),
-        4 => compiler.memory.allocate_symbol(Type::Hash),
+        8 => compiler.allocate_symbol(Type::Hash8),
+        4 => compiler.allocate_symbol(Type::Hash),
         _ => unreachable!(),
     };
 
-    let len_div_n = compiler
-        .memory
-        .allocate_symbol(Type::Array(Box::new(Type::PrimitiveType(
-            PrimitiveType::UInt32,
-        ))));
+    let len_div_n = compiler.allocate_symbol(Type::Array(Box::new(Type::PrimitiveType(
+        PrimitiveType::UInt32,
+    ))));
     compiler.memory.read(
         compiler.instructions,
         length(arr).memory_addr,
@@ -284,11 +284,9 @@ pub(crate) fn hash_width_1(compiler: &mut Compiler, arr: &Symbol, hash: HashFn)
         len_div_n.memory_addr,
         &[ValueSource::Stack],
     );
-    let len_mod_n = compiler
-        .memory
-        .allocate_symbol(Type::Array(Box::new(Type::PrimitiveType(
-            PrimitiveType::UInt32,
-        ))));
+    let len_mod_n = compiler.allocate_symbol(Type::Array(Box::new(Type::PrimitiveType(
+        PrimitiveType::UInt32,
+    ))));
     compiler.memory.read(
         compiler.instructions,
         length(arr).memory_addr,
@@ -304,9 +302,7 @@ pub(crate) fn hash_width_1(compiler: &mut Compiler, arr: &Symbol, hash: HashFn)
         &[ValueSource::Stack],
     );
 
-    let index = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+    let index = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
 
     compiler.memory.read(
         compiler.instructions,
@@ -420,9 +416,7 @@ pub(crate) fn get(compiler: &mut Compiler, arr: &Symbol, index: &Symbol) -> Symb
         Type::PrimitiveType(PrimitiveType::UInt32)
     ));
 
-    let result = compiler
-        .memory
-        .allocate_symbol(element_type(&arr.type_).clone());
+    let result = compiler.allocate_symbol(element_type(&arr.type_).clone());
 
     compiler.instructions.extend([
         Instruction::MemLoad(Some(data_ptr(arr).memory_addr)),
@@ -457,6 +451,43 @@ pub(crate) fn get(compiler: &mut Compiler, arr: &Symbol, index: &Symbol) -> Symb
     result
 }
 
+/// Writes `value` to the element of `arr` at `index`, the index must be in bounds.
+pub(crate) fn set(compiler: &mut Compiler, arr: &Symbol, index: &Symbol, value: &Symbol) {
+    assert_eq!(element_type(&arr.type_), &value.type_);
+    assert!(matches!(
+        index.type_,
+        Type::PrimitiveType(PrimitiveType::UInt32)
+    ));
+
+    compiler.instructions.extend([
+        Instruction::MemLoad(Some(data_ptr(arr).memory_addr)),
+        // [data_ptr]
+        Instruction::MemLoad(Some(index.memory_addr)),
+        // [index, data_ptr]
+        Instruction::Push(value.type_.miden_width()),
+        // [element_width, index, data_ptr]
+        Instruction::U32CheckedMul,
+        // [offset = index * element_width, data_ptr]
+        Instruction::U32CheckedAdd,
+        // [ptr = data_ptr + offset]
+    ]);
+
+    for i in 0..value.type_.miden_width() {
+        compiler.instructions.extend([
+            Instruction::MemLoad(Some(value.memory_addr + i)),
+            // [value[i], ptr]
+            Instruction::Dup(Some(1)),
+            Instruction::Push(i),
+            Instruction::U32CheckedAdd,
+            // [ptr + i, value[i], ptr]
+            Instruction::MemStore(None),
+            // [ptr]
+        ]);
+    }
+
+    compiler.instructions.push(Instruction::Drop);
+}
+
 pub(crate) fn find_index(compiler: &mut Compiler, arr: &Symbol, el: &Symbol) -> Result<Symbol> {
     ensure_eq_type!(arr, Type::Array(_));
     let element_type = element_type(&arr.type_);
@@ -464,7 +495,7 @@ pub(crate) fn find_index(compiler: &mut Compiler, arr: &Symbol, el: &Symbol) ->
 
     let result = int32::new(compiler, -1);
 
-    let current_arr_element = compiler.memory.allocate_symbol(element_type.clone());
+    let current_arr_element = compiler.allocate_symbol(element_type.clone());
     let (eq_insts, eq_result) = {
         let mut insts = Vec::new();
 
@@ -475,12 +506,8 @@ pub(crate) fn find_index(compiler: &mut Compiler, arr: &Symbol, el: &Symbol) ->
         (insts, result)
     };
 
-    let current_index = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
-    let finished = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+    let current_index = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+    let finished = boolean::new(compiler, false);
 
     iterate_array_elements(
         compiler,
@@ -564,17 +591,19 @@ pub(crate) fn push(compiler: &mut Compiler, _scope: &Scope, args: &[Symbol]) ->
         .instructions
         .push(encoder::Instruction::U32CheckedAdd);
     // [data_ptr + len * element_width]
-    compiler.memory.read(
-        compiler.instructions,
-        element.memory_addr,
-        element.type_.miden_width(),
-    );
-    // [element, data_ptr + len * element_width]
-    compiler.instructions.push(encoder::Instruction::Swap);
-    // [data_ptr + len * element_width, element]
-    compiler
-        .instructions
-        .push(encoder::Instruction::MemStore(None));
+    for i in 0..element.type_.miden_width() {
+        compiler.instructions.extend([
+            encoder::Instruction::MemLoad(Some(element.memory_addr + i)),
+            // [element[i], ptr]
+            encoder::Instruction::Dup(Some(1)),
+            encoder::Instruction::Push(i),
+            encoder::Instruction::U32CheckedAdd,
+            // [ptr + i, element[i], ptr]
+            encoder::Instruction::MemStore(None),
+            // [ptr]
+        ]);
+    }
+    compiler.instructions.push(encoder::Instruction::Drop);
     // []
 
     // Return the element, same as push does in JS
@@ -663,17 +692,15 @@ fn grow(compiler: &mut Compiler, arr: &Symbol, needed_len: &Symbol) -> Result<Sy
     ensure_eq_type!(arr, Type::Array(_));
     ensure_eq_type!(needed_len, Type::PrimitiveType(PrimitiveType::UInt32));
 
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+    let element_width = element_type(&arr.type_).miden_width();
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
 
     let then_instructions = {
         let mut insts = Vec::new();
         std::mem::swap(compiler.instructions, &mut insts);
 
-        let new_capacity = compiler
-            .memory
-            .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+        let new_capacity = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+        let new_size = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
         compiler
             .memory
             .read(compiler.instructions, array::length(arr).memory_addr, 1);
@@ -687,29 +714,52 @@ fn grow(compiler: &mut Compiler, arr: &Symbol, needed_len: &Symbol) -> Result<Sy
         compiler
             .instructions
             .push(encoder::Instruction::U32CheckedAdd);
-        // [len * 2 + 15]
-        compiler.memory.write(
-            compiler.instructions,
-            new_capacity.memory_addr,
-            &[ValueSource::Stack],
-        );
+        // [len * 2 + 16]
+        compiler.instructions.extend([
+            Instruction::Dup(None),
+            Instruction::MemStore(Some(new_capacity.memory_addr)),
+            Instruction::Push(element_width),
+            Instruction::U32CheckedMul,
+            // [new_size = new_capacity * element_width]
+            Instruction::MemStore(Some(new_size.memory_addr)),
+        ]);
         // []
 
-        let new_data_ptr = super::dynamic_alloc(compiler, &[new_capacity.clone()])?;
-        copy(
-            compiler,
-            &data_ptr(arr),
-            &length(arr),
-            &new_data_ptr,
-            &new_capacity,
-            1,
-        )?;
+        // If the data is at the top of the heap, like when pushing in a loop,
+        // it can grow without being copied
+        let extended = heap::extend(compiler, &data_ptr(arr), &new_size)?;
+
+        let realloc_instructions = {
+            let mut insts = Vec::new();
+            std::mem::swap(compiler.instructions, &mut insts);
+
+            // The old data is not freed, copies of the array can still point to it
+            let new_data_ptr = super::dynamic_alloc(compiler, &[new_size.clone()])?;
+            copy(
+                compiler,
+                &data_ptr(arr),
+                &length(arr),
+                &new_data_ptr,
+                &new_size,
+                element_width,
+            )?;
+
+            compiler.memory.write(
+                compiler.instructions,
+                array::data_ptr(arr).memory_addr,
+                &[ValueSource::Memory(new_data_ptr.memory_addr)],
+            );
+
+            std::mem::swap(compiler.instructions, &mut insts);
+            insts
+        };
+
+        compiler.instructions.push(Instruction::If {
+            condition: vec![Instruction::MemLoad(Some(extended.memory_addr))],
+            then: vec![],
+            else_: realloc_instructions,
+        });
 
-        compiler.memory.write(
-            compiler.instructions,
-            array::data_ptr(arr).memory_addr,
-            &[ValueSource::Memory(new_data_ptr.memory_addr)],
-        );
         compiler.memory.write(
             compiler.instructions,
             array::capacity(arr).memory_addr,
@@ -726,8 +776,8 @@ fn grow(compiler: &mut Compiler, arr: &Symbol, needed_len: &Symbol) -> Result<Sy
             // [capacity]
             Instruction::MemLoad(Some(needed_len.memory_addr)),
             // [needed_len, capacity]
-            Instruction::U32CheckedGTE,
-            // [capacity >= needed_len]
+            Instruction::U32CheckedLT,
+            // [capacity < needed_len]
         ],
         then: then_instructions,
         else_: vec![],
@@ -808,11 +858,38 @@ fn copy(
 pub(crate) fn includes(compiler: &mut Compiler, arr: &Symbol, el: &Symbol) -> Result<Symbol> {
     ensure_eq_type!(arr, Type::Array(_));
     let element_type = element_type(&arr.type_);
-    ensure_eq_type!(el, @element_type);
 
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+
+    // Such as `ctx.publicKey`, null is never included
+    if matches!(&el.type_, Type::Nullable(t) if **t == *element_type) {
+        let (included_insts, included) = {
+            let mut insts = Vec::new();
+
+            std::mem::swap(compiler.instructions, &mut insts);
+            let result = includes(compiler, arr, &super::nullable::value(el.clone()))?;
+            std::mem::swap(compiler.instructions, &mut insts);
+
+            (insts, result)
+        };
+
+        compiler.instructions.push(Instruction::If {
+            condition: vec![Instruction::MemLoad(Some(
+                super::nullable::is_not_null(el).memory_addr,
+            ))],
+            then: included_insts
+                .into_iter()
+                .chain([
+                    Instruction::MemLoad(Some(included.memory_addr)),
+                    Instruction::MemStore(Some(result.memory_addr)),
+                ])
+                .collect(),
+            else_: vec![],
+        });
+
+        return Ok(result);
+    }
+    ensure_eq_type!(el, @element_type);
 
     let index = find_index(compiler, arr, el)?;
     compiler.memory.read(
@@ -897,9 +974,7 @@ pub(crate) fn splice(
     let array_of_deletions = dynamic_new(compiler, element_type.clone(), delete_count.clone())?;
 
     let new_arr_len = {
-        let new_arr_len = compiler
-            .memory
-            .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+        let new_arr_len = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
 
         // length(arr) - delete_count
         compiler
@@ -923,9 +998,7 @@ pub(crate) fn splice(
     let new_arr = dynamic_new(compiler, element_type.clone(), new_arr_len.clone())?;
 
     let start_data_ptr = {
-        let ptr = compiler
-            .memory
-            .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+        let ptr = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
 
         compiler
             .memory
@@ -972,9 +1045,7 @@ pub(crate) fn splice(
     )?;
 
     let second_source_data_ptr = {
-        let ptr = compiler
-            .memory
-            .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+        let ptr = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
 
         compiler
             .memory
@@ -1007,9 +1078,7 @@ pub(crate) fn splice(
     };
 
     let second_target_data_ptr = {
-        let ptr = compiler
-            .memory
-            .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+        let ptr = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
 
         compiler
             .memory
@@ -1034,9 +1103,7 @@ pub(crate) fn splice(
     };
 
     let second_length = {
-        let len = compiler
-            .memory
-            .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+        let len = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
 
         compiler.memory.read(
             compiler.instructions,
@@ -1142,9 +1209,7 @@ pub(crate) fn slice(
         // [new_len = end - start, actual_start]
     ]);
 
-    let new_len = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+    let new_len = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
     compiler.memory.write(
         compiler.instructions,
         new_len.memory_addr,
@@ -1155,9 +1220,7 @@ pub(crate) fn slice(
     let new_arr = dynamic_new(compiler, element_type.clone(), new_len.clone())?;
 
     let source_data_ptr = {
-        let ptr = compiler
-            .memory
-            .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+        let ptr = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
 
         compiler
             .memory
@@ -1265,9 +1328,7 @@ pub(crate) fn unshift(
         .instructions
         .push(encoder::Instruction::U32CheckedAdd);
     // [len + elements.len()]
-    let new_len = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+    let new_len = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
     compiler.memory.write(
         compiler.instructions,
         new_len.memory_addr,
@@ -1287,9 +1348,8 @@ pub(crate) fn unshift(
         )?;
     }
 
-    let data_ptr_after_new_elements = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+    let data_ptr_after_new_elements =
+        compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
     compiler
         .memory
         .read(compiler.instructions, data_ptr(&new_arr).memory_addr, 1);
@@ -0,0 +1,378 @@
+//! Inline Miden assembly, `asm(a, b): u32 { u32checked_add }`.
+//!
+//! The inputs are pushed onto the stack in order, so the last one is on top,
+//! and the output is popped off the stack after the code runs. The code is parsed into
+//! [`encoder::Instruction`]s, so only instructions that the compiler knows are allowed,
+//! and it has to replace exactly the inputs with the output.
+
+use super::*;
+
+/// The standard library procedures that can be executed,
+/// with the number of stack elements they take and return.
+pub(super) const STD_PROCEDURES: &[(&str, u32, u32)] = &[
+    ("u64::checked_add", 4, 2),
+    ("u64::wrapping_add", 4, 2),
+    ("u64::overflowing_add", 4, 3),
+    ("u64::checked_sub", 4, 2),
+    ("u64::wrapping_sub", 4, 2),
+    ("u64::overflowing_sub", 4, 3),
+    ("u64::checked_mul", 4, 2),
+    ("u64::wrapping_mul", 4, 2),
+    ("u64::overflowing_mul", 4, 4),
+    ("u64::checked_div", 4, 2),
+    ("u64::checked_mod", 4, 2),
+    ("u64::checked_divmod", 4, 4),
+    ("u64::checked_lt", 4, 1),
+    ("u64::checked_lte", 4, 1),
+    ("u64::checked_gt", 4, 1),
+    ("u64::checked_gte", 4, 1),
+    ("u64::checked_eq", 4, 1),
+    ("u64::checked_neq", 4, 1),
+    ("u64::checked_eqz", 2, 1),
+    ("u64::checked_min", 4, 2),
+    ("u64::checked_max", 4, 2),
+    ("u64::checked_and", 4, 2),
+    ("u64::checked_or", 4, 2),
+    ("u64::checked_xor", 4, 2),
+    ("u64::checked_shl", 3, 2),
+    ("u64::checked_shr", 3, 2),
+    ("u64::checked_rotl", 3, 2),
+    ("u64::checked_rotr", 3, 2),
+    ("sha256::hash_2to1", 16, 8),
+    ("blake3::hash_2to1", 16, 8),
+    ("rpo_falcon512::verify", 8, 0),
+    ("smt::get", 8, 8),
+    ("smt::insert", 12, 8),
+];
+
+pub(crate) fn compile(
+    compiler: &mut Compiler,
+    inputs: &[Symbol],
+    output: Option<Type>,
+    code: &str,
+) -> Result<Symbol> {
+    let instructions = parse(code)?;
+
+    let input_width = inputs.iter().map(|s| s.type_.miden_width()).sum::<u32>();
+    let output_width = output.as_ref().map_or(0, |t| t.miden_width());
+    let depth = stack_depth(&instructions, input_width)?;
+    if depth != output_width {
+        return Err(Error::simple(format!(
+            "asm block leaves {depth} stack elements, expected {output_width} for the output"
+        )));
+    }
+
+    for input in inputs {
+        compiler.memory.read(
+            compiler.instructions,
+            input.memory_addr,
+            input.type_.miden_width(),
+        );
+    }
+
+    compiler.instructions.extend(instructions);
+
+    let Some(output) = output else {
+        return Ok(Symbol {
+            type_: Type::Nullable(Box::new(Type::PrimitiveType(PrimitiveType::Boolean))),
+            memory_addr: 0,
+        });
+    };
+
+    let symbol = compiler.allocate_symbol(output);
+    compiler.memory.write(
+        compiler.instructions,
+        symbol.memory_addr,
+        &vec![ValueSource::Stack; output_width as usize],
+    );
+
+    Ok(symbol)
+}
+
+fn parse(code: &str) -> Result<Vec<encoder::Instruction<'static>>> {
+    let mut tokens = code
+        .lines()
+        .map(|line| line.split_once('#').map_or(line, |(code, _comment)| code))
+        .flat_map(|line| line.split_whitespace());
+
+    let (instructions, end) = parse_block(&mut tokens)?;
+    match end {
+        None => Ok(instructions),
+        Some(token) => Err(Error::simple(format!("unexpected `{token}` in asm block"))),
+    }
+}
+
+/// Parses instructions up to an `else` or `end`, which is returned with them.
+fn parse_block<'t>(
+    tokens: &mut impl Iterator<Item = &'t str>,
+) -> Result<(Vec<encoder::Instruction<'static>>, Option<&'t str>)> {
+    let mut instructions = Vec::new();
+    while let Some(token) = tokens.next() {
+        let (name, immediate) = match token.split_once('.') {
+            Some((name, immediate)) => (name, Some(immediate)),
+            None => (token, None),
+        };
+        let number = || -> Result<u32> {
+            immediate
+                .and_then(|i| i.parse().ok())
+                .ok_or_else(|| Error::simple(format!("expected a u32 immediate in `{token}`")))
+        };
+        let optional_number = || immediate.map(|_| number()).transpose();
+        let no_immediate = |instruction| match immediate {
+            None => Ok(instruction),
+            Some(_) => Err(Error::simple(format!("unexpected immediate in `{token}`"))),
+        };
+
+        let instruction = match name {
+            "else" | "end" => return Ok((instructions, Some(name))),
+            "if" if immediate == Some("true") => {
+                let (then, end) = parse_block(tokens)?;
+                let else_ = match end {
+                    Some("else") => match parse_block(tokens)? {
+                        (else_, Some("end")) => else_,
+                        _ => return Err(Error::simple("expected `end` after `else`")),
+                    },
+                    Some("end") => vec![],
+                    _ => return Err(Error::simple("expected `end` after `if.true`")),
+                };
+
+                encoder::Instruction::If {
+                    condition: vec![],
+                    then,
+                    else_,
+                }
+            }
+            "while" if immediate == Some("true") => match parse_block(tokens)? {
+                (instructions, Some("end")) => encoder::Instruction::WhileTrueRaw { instructions },
+                _ => return Err(Error::simple("expected `end` after `while.true`")),
+            },
+            "repeat" => {
+                let count = number()?;
+                match parse_block(tokens)? {
+                    (instructions, Some("end")) => encoder::Instruction::Repeat {
+                        count,
+                        instructions,
+                    },
+                    _ => return Err(Error::simple("expected `end` after `repeat`")),
+                }
+            }
+            "exec" => {
+                let procedure = immediate.unwrap_or_default();
+                let (procedure, _, _) = STD_PROCEDURES
+                    .iter()
+                    .find(|(name, _, _)| *name == procedure)
+                    .ok_or_else(|| {
+                        Error::simple(format!("unknown procedure `{procedure}` in asm block"))
+                    })?;
+
+                encoder::Instruction::Exec(procedure)
+            }
+            "push" => encoder::Instruction::Push(number()?),
+            "movup" => encoder::Instruction::MovUp(number()?),
+            "movdn" => encoder::Instruction::MovDown(number()?),
+            "dup" => encoder::Instruction::Dup(optional_number()?),
+            "mem_load" => encoder::Instruction::MemLoad(optional_number()?),
+            "mem_store" => encoder::Instruction::MemStore(optional_number()?),
+            "adv_push" => encoder::Instruction::AdvPush(number()?),
+            "u32checked_mod" => encoder::Instruction::U32CheckedMod(optional_number()?),
+            "u32checked_div" => encoder::Instruction::U32CheckedDiv(optional_number()?),
+            "u32checked_shl" => encoder::Instruction::U32CheckedSHL(optional_number()?),
+            "u32checked_shr" => encoder::Instruction::U32CheckedSHR(optional_number()?),
+            "drop" => no_immediate(encoder::Instruction::Drop)?,
+            "dropw" => no_immediate(encoder::Instruction::Dropw)?,
+            "swap" => no_immediate(encoder::Instruction::Swap)?,
+            "assert" => no_immediate(encoder::Instruction::Assert)?,
+            "assertz" => no_immediate(encoder::Instruction::AssertZero)?,
+            "add" => no_immediate(encoder::Instruction::Add)?,
+            "and" => no_immediate(encoder::Instruction::And)?,
+            "or" => no_immediate(encoder::Instruction::Or)?,
+            "not" => no_immediate(encoder::Instruction::Not)?,
+            "eq" => no_immediate(encoder::Instruction::Eq)?,
+            "cswap" => no_immediate(encoder::Instruction::Cswap)?,
+            "hmerge" => no_immediate(encoder::Instruction::HMerge)?,
+            "u32checked_add" => no_immediate(encoder::Instruction::U32CheckedAdd)?,
+            "u32checked_sub" => no_immediate(encoder::Instruction::U32CheckedSub)?,
+            "u32checked_mul" => no_immediate(encoder::Instruction::U32CheckedMul)?,
+            "u32checked_eq" => no_immediate(encoder::Instruction::U32CheckedEq)?,
+            "u32checked_neq" => no_immediate(encoder::Instruction::U32CheckedNeq)?,
+            "u32checked_lt" => no_immediate(encoder::Instruction::U32CheckedLT)?,
+            "u32checked_lte" => no_immediate(encoder::Instruction::U32CheckedLTE)?,
+            "u32checked_gt" => no_immediate(encoder::Instruction::U32CheckedGT)?,
+            "u32checked_gte" => no_immediate(encoder::Instruction::U32CheckedGTE)?,
+            "u32checked_and" => no_immediate(encoder::Instruction::U32CheckedAnd)?,
+            "u32checked_or" => no_immediate(encoder::Instruction::U32CheckedOr)?,
+            "u32checked_xor" => no_immediate(encoder::Instruction::U32CheckedXOR)?,
+            "u32checked_not" => no_immediate(encoder::Instruction::U32CheckedNot)?,
+            "u32checked_min" => no_immediate(encoder::Instruction::U32CheckedMin)?,
+            "u32wrapping_add" => no_immediate(encoder::Instruction::U32WrappingAdd)?,
+            "u32wrapping_sub" => no_immediate(encoder::Instruction::U32WrappingSub)?,
+            "u32wrapping_mul" => no_immediate(encoder::Instruction::U32WrappingMul)?,
+            "u32overflowing_mul" => no_immediate(encoder::Instruction::U32OverflowingMul)?,
+            _ => {
+                return Err(Error::simple(format!(
+                    "unsupported instruction `{token}` in asm block"
+                )))
+            }
+        };
+
+        instructions.push(instruction);
+    }
+
+    Ok((instructions, None))
+}
+
+/// Returns the number of stack elements taken and returned by an instruction without a body.
+fn stack_effect(instruction: &encoder::Instruction) -> (u32, u32) {
+    use encoder::Instruction;
+
+    match instruction {
+        Instruction::Push(_) | Instruction::MemLoad(Some(_)) => (0, 1),
+        Instruction::AdvPush(n) => (0, *n),
+        Instruction::Drop | Instruction::Assert | Instruction::AssertZero => (1, 0),
+        Instruction::MemStore(Some(_)) => (1, 0),
+        Instruction::MemStore(None) => (2, 0),
+        Instruction::Dropw => (4, 0),
+        Instruction::Dup(None) => (1, 2),
+        Instruction::Dup(Some(n)) => (n + 1, n + 2),
+        Instruction::MovUp(n) | Instruction::MovDown(n) => (n + 1, n + 1),
+        Instruction::Not
+        | Instruction::U32CheckedNot
+        | Instruction::MemLoad(None)
+        | Instruction::U32CheckedMod(Some(_))
+        | Instruction::U32CheckedDiv(Some(_))
+        | Instruction::U32CheckedSHL(Some(_))
+        | Instruction::U32CheckedSHR(Some(_)) => (1, 1),
+        Instruction::Swap | Instruction::U32OverflowingMul => (2, 2),
+        Instruction::Cswap => (3, 2),
+        Instruction::HMerge => (8, 4),
+        Instruction::Exec(procedure) => STD_PROCEDURES
+            .iter()
+            .find(|(name, _, _)| name == procedure)
+            .map(|(_, inputs, outputs)| (*inputs, *outputs))
+            .expect("procedures are checked when parsing"),
+        // The rest are binary operations
+        _ => (2, 1),
+    }
+}
+
+/// Returns the stack depth after running the instructions from `depth`,
+/// fails if they take more elements than there are.
+fn stack_depth(instructions: &[encoder::Instruction], mut depth: u32) -> Result<u32> {
+    use encoder::Instruction;
+
+    let underflow = || Error::simple("asm block takes more stack elements than its inputs");
+    for instruction in instructions {
+        depth = match instruction {
+            Instruction::If { then, else_, .. } => {
+                let depth = depth.checked_sub(1).ok_or_else(underflow)?;
+                let then = stack_depth(then, depth)?;
+                let else_ = stack_depth(else_, depth)?;
+                if then != else_ {
+                    return Err(Error::simple(
+                        "branches of an if.true in asm block leave different stack depths",
+                    ));
+                }
+
+                then
+            }
+            Instruction::WhileTrueRaw { instructions } => {
+                let depth = depth.checked_sub(1).ok_or_else(underflow)?;
+                // The body has to push the condition of the next iteration
+                if stack_depth(instructions, depth)? != depth + 1 {
+                    return Err(Error::simple(
+                        "body of a while.true in asm block must push exactly one condition",
+                    ));
+                }
+
+                depth
+            }
+            Instruction::Repeat { count: 0, .. } => depth,
+            Instruction::Repeat {
+                count,
+                instructions,
+            } => {
+                let after = stack_depth(instructions, depth)?;
+                if after >= depth {
+                    depth + (after - depth) * count
+                } else {
+                    // The stack is the shallowest at the start of the last iteration
+                    let last = (depth - after)
+                        .checked_mul(count - 1)
+                        .and_then(|taken| depth.checked_sub(taken))
+                        .ok_or_else(underflow)?;
+                    stack_depth(instructions, last)?
+                }
+            }
+            instruction => {
+                let (inputs, outputs) = stack_effect(instruction);
+                depth.checked_sub(inputs).ok_or_else(underflow)? + outputs
+            }
+        };
+    }
+
+    Ok(depth)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse() {
+        let instructions = parse(
+            "
+            # add one if not zero
+            dup if.true
+                push.1 u32checked_add
+            else
+                exec.u64::checked_eqz
+            end
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(
+            instructions,
+            vec![
+                encoder::Instruction::Dup(None),
+                encoder::Instruction::If {
+                    condition: vec![],
+                    then: vec![
+                        encoder::Instruction::Push(1),
+                        encoder::Instruction::U32CheckedAdd
+                    ],
+                    else_: vec![encoder::Instruction::Exec("u64::checked_eqz")],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown() {
+        assert_eq!(
+            parse("u32checked_add mem_stream").unwrap_err().to_string(),
+            "unsupported instruction `mem_stream` in asm block"
+        );
+        assert_eq!(
+            parse("exec.foo::bar").unwrap_err().to_string(),
+            "unknown procedure `foo::bar` in asm block"
+        );
+        assert!(parse("if.true drop").is_err());
+        assert!(parse("drop end").is_err());
+    }
+
+    #[test]
+    fn test_stack_depth() {
+        let depth = |code| stack_depth(&parse(code).unwrap(), 2);
+
+        assert_eq!(depth("u32checked_add").unwrap(), 1);
+        assert_eq!(depth("repeat.2 dup end").unwrap(), 4);
+        assert_eq!(depth("repeat.2 drop end").unwrap(), 0);
+        assert!(depth("repeat.3 drop end").is_err());
+        assert_eq!(depth("push.0 push.0 exec.u64::checked_add").unwrap(), 2);
+        assert!(depth("drop drop drop").is_err());
+        assert!(depth("if.true dup end").is_err());
+        assert!(depth("while.true drop end").is_err());
+    }
+}
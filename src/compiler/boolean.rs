@@ -2,9 +2,7 @@ use super::*;
 
 // Layout: [value] - where value is 0 or 1
 pub(crate) fn new(compiler: &mut Compiler, value: bool) -> Symbol {
-    let symbol = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+    let symbol = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
 
     // memory is zero-initialized, so we don't need to write for false
     if value {
@@ -22,9 +20,7 @@ pub(crate) fn compile_and(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Sy
     assert_eq!(a.type_, b.type_);
     assert_eq!(a.type_, Type::PrimitiveType(PrimitiveType::Boolean));
 
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
     compiler
         .memory
         .read(compiler.instructions, a.memory_addr, a.type_.miden_width());
@@ -45,9 +41,7 @@ pub(crate) fn compile_or(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Sym
     assert_eq!(a.type_, b.type_);
     assert_eq!(a.type_, Type::PrimitiveType(PrimitiveType::Boolean));
 
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
     compiler
         .memory
         .read(compiler.instructions, a.memory_addr, a.type_.miden_width());
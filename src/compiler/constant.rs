@@ -0,0 +1,469 @@
+//! Compile-time evaluation of `const` declarations.
+//!
+//! Constants are folded when the scope is prepared, every use of a constant
+//! compiles to the folded literal. The same evaluator folds pure expressions,
+//! such as `60 * 60` or `if (false)` conditions, while compiling functions.
+//!
+//! Numbers are rounded to `f32` after every operation, as they are at runtime,
+//! but constants of integer types are evaluated exactly.
+
+use std::cmp::Ordering;
+
+use super::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    /// Integer literals and the results of integer arithmetic, which are exact up to the range of `u64`.
+    Integer(i128),
+    Number(f64),
+    String(String),
+    Boolean(bool),
+}
+
+impl From<&Value> for serde_json::Value {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Integer(n) => match (u64::try_from(*n), i64::try_from(*n)) {
+                (Ok(n), _) => serde_json::json!(n),
+                (_, Ok(n)) => serde_json::json!(n),
+                _ => serde_json::json!(*n as f64),
+            },
+            // Integers are emitted without the decimal point, as they would be written in JSON
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < (1u64 << 53) as f64 => {
+                serde_json::json!(*n as i64)
+            }
+            Value::Number(n) => serde_json::json!(n),
+            Value::String(s) => serde_json::Value::String(s.clone()),
+            Value::Boolean(b) => serde_json::Value::Bool(*b),
+        }
+    }
+}
+
+impl Value {
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(n) => Some(*n as f64),
+            Value::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// The exact integer value, numbers from 2^53 up may have been rounded so they are not integers.
+    fn as_integer<T: TryFrom<i128>>(&self) -> Option<T> {
+        let n = match self {
+            Value::Integer(n) => *n,
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < (1u64 << 53) as f64 => *n as i128,
+            _ => return None,
+        };
+
+        T::try_from(n).ok()
+    }
+
+    fn fits_float32(&self) -> bool {
+        match self {
+            Value::Integer(n) => *n as f32 as i128 == *n,
+            Value::Number(n) => convert_f64_to_f32(*n).is_some(),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Constant {
+    pub(crate) type_: Type,
+    pub(crate) value: Value,
+}
+
+/// How numbers are evaluated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Arithmetic {
+    /// Integers are exact, for constants of integer types.
+    Exact,
+    /// Every result is rounded to a `f32`, as numbers are computed at runtime.
+    Float32,
+}
+
+/// An expression that was either folded to a constant, or compiled to a symbol.
+pub(crate) enum Folded {
+    Constant(Constant),
+    Symbol(Symbol),
+}
+
+impl Folded {
+    pub(crate) fn into_symbol(self, compiler: &mut Compiler) -> Result<Symbol> {
+        match self {
+            Folded::Constant(constant) => materialize(compiler, &constant),
+            Folded::Symbol(symbol) => Ok(symbol),
+        }
+    }
+}
+
+/// Evaluates `const_.value`, `constants` are the constants declared before it.
+pub(crate) fn new(const_: &ast::Const, constants: &[(String, Constant)]) -> Result<Constant> {
+    maybe_start!(const_.value.span());
+
+    let declared_type = const_.type_.as_ref().map(|t| ast_type_to_type(true, t));
+    let arithmetic = match declared_type {
+        Some(Type::PrimitiveType(
+            PrimitiveType::UInt32 | PrimitiveType::UInt64 | PrimitiveType::Int32,
+        )) => Arithmetic::Exact,
+        _ => Arithmetic::Float32,
+    };
+
+    let value = evaluate_with(&const_.value, arithmetic, &|id| {
+        constants
+            .iter()
+            .rev()
+            .find(|(name, _)| name == id)
+            .map(|(_, c)| c.value.clone())
+    })?;
+    let type_ = declared_type.unwrap_or_else(|| literal_type(&value));
+
+    let fits = match &type_ {
+        Type::PrimitiveType(PrimitiveType::UInt32) => value.as_integer::<u32>().is_some(),
+        Type::PrimitiveType(PrimitiveType::UInt64) => value.as_integer::<u64>().is_some(),
+        Type::PrimitiveType(PrimitiveType::Int32) => value.as_integer::<i32>().is_some(),
+        Type::PrimitiveType(PrimitiveType::Float32) => value.fits_float32(),
+        Type::String => matches!(value, Value::String(_)),
+        Type::PrimitiveType(PrimitiveType::Boolean) => matches!(value, Value::Boolean(_)),
+        t => {
+            return Err(Error::unimplemented(format!(
+                "constant {} of type {t:?}",
+                const_.name
+            )))
+        }
+    };
+
+    ensure!(
+        fits,
+        TypeMismatchSnafu {
+            context: format!(
+                "constant {} value {value:?} does not fit in {type_:?}",
+                const_.name
+            ),
+        }
+    );
+
+    let value = match (&type_, value.as_integer(), value.as_f64()) {
+        (
+            Type::PrimitiveType(
+                PrimitiveType::UInt32 | PrimitiveType::UInt64 | PrimitiveType::Int32,
+            ),
+            Some(n),
+            _,
+        ) => Value::Integer(n),
+        (Type::PrimitiveType(PrimitiveType::Float32), _, Some(n)) => Value::Number(n),
+        _ => value,
+    };
+
+    Ok(Constant { type_, value })
+}
+
+/// The type a literal of this value compiles to, e.g. `1` is a `Float32`.
+pub(crate) fn literal_type(value: &Value) -> Type {
+    match value {
+        Value::Integer(_) | Value::Number(_) => Type::PrimitiveType(PrimitiveType::Float32),
+        Value::String(_) => Type::String,
+        Value::Boolean(_) => Type::PrimitiveType(PrimitiveType::Boolean),
+    }
+}
+
+/// Folds a pure expression in a function body, returning None if it has to be computed at runtime.
+///
+/// Only constants that have the same type as a literal of their value are folded,
+/// so that folding never changes the type of an expression.
+pub(crate) fn fold(expr: &Expression, scope: &Scope) -> Option<Constant> {
+    let value = evaluate_with(expr, Arithmetic::Float32, &|id| {
+        if scope.find_symbol(id).is_some() {
+            return None;
+        }
+
+        scope
+            .find_constant(id)
+            .filter(|c| c.type_ == literal_type(&c.value))
+            .map(|c| c.value.clone())
+    })
+    .ok()?;
+
+    literal(value)
+}
+
+/// Folds an operator whose operands were folded, returning None if any of them is a symbol.
+pub(crate) fn fold_operator(expr: &Expression, operands: &[Folded]) -> Option<Constant> {
+    let values = operands
+        .iter()
+        .map(|operand| match operand {
+            Folded::Constant(c) => Some(c.value.clone()),
+            Folded::Symbol(_) => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    literal(apply(expr, Arithmetic::Float32, values).ok()?)
+}
+
+/// The value of a condition that is always true or always false.
+pub(crate) fn fold_condition(condition: &Expression, scope: &Scope) -> Option<bool> {
+    match fold(condition, scope)?.value {
+        Value::Boolean(b) => Some(b),
+        _ => None,
+    }
+}
+
+fn literal(value: Value) -> Option<Constant> {
+    if matches!(value, Value::Integer(_) | Value::Number(_)) && !value.fits_float32() {
+        return None;
+    }
+
+    Some(Constant {
+        type_: literal_type(&value),
+        value,
+    })
+}
+
+/// The operands of an operator that can be folded, or None if `expr` is not one.
+pub(crate) fn operands(expr: &Expression) -> Option<Vec<&Expression>> {
+    use ast::ExpressionKind;
+
+    Some(match &**expr {
+        ExpressionKind::Not(a) => vec![a],
+        ExpressionKind::Add(a, b)
+        | ExpressionKind::Subtract(a, b)
+        | ExpressionKind::Multiply(a, b)
+        | ExpressionKind::Exponent(a, b)
+        | ExpressionKind::Divide(a, b)
+        | ExpressionKind::Modulo(a, b)
+        | ExpressionKind::Equal(a, b)
+        | ExpressionKind::NotEqual(a, b)
+        | ExpressionKind::LessThan(a, b)
+        | ExpressionKind::LessThanOrEqual(a, b)
+        | ExpressionKind::GreaterThan(a, b)
+        | ExpressionKind::GreaterThanOrEqual(a, b)
+        | ExpressionKind::And(a, b)
+        | ExpressionKind::Or(a, b) => vec![a, b],
+        _ => return None,
+    })
+}
+
+fn evaluate_with(
+    expr: &Expression,
+    arithmetic: Arithmetic,
+    lookup: &dyn Fn(&str) -> Option<Value>,
+) -> Result<Value> {
+    use ast::ExpressionKind;
+
+    if let Some(operands) = operands(expr) {
+        let values = operands
+            .into_iter()
+            .map(|operand| evaluate_with(operand, arithmetic, lookup))
+            .collect::<Result<Vec<_>>>()?;
+
+        return apply(expr, arithmetic, values);
+    }
+
+    Ok(match (&**expr, arithmetic) {
+        (ExpressionKind::Primitive(ast::Primitive::Number(n, false)), Arithmetic::Exact) => {
+            Value::Integer(*n as i128)
+        }
+        (ExpressionKind::Primitive(ast::Primitive::Integer(n)), Arithmetic::Exact) => {
+            Value::Integer(*n as i128)
+        }
+        (ExpressionKind::Primitive(ast::Primitive::Number(n, true)), Arithmetic::Exact) => {
+            Value::Number(*n)
+        }
+        (ExpressionKind::Primitive(ast::Primitive::Number(n, _)), Arithmetic::Float32) => {
+            ensure!(
+                convert_f64_to_f32(*n).is_some(),
+                TypeMismatchSnafu {
+                    context: format!("number {n} does not fit in a f32"),
+                }
+            );
+
+            Value::Number(*n)
+        }
+        (ExpressionKind::Primitive(ast::Primitive::Integer(n)), Arithmetic::Float32) => {
+            return Err(ErrorKind::TypeMismatch {
+                context: format!("number {n} does not fit in a f32"),
+            }
+            .into())
+        }
+        (ExpressionKind::Primitive(ast::Primitive::String(s)), _) => Value::String(s.clone()),
+        (ExpressionKind::Boolean(b), _) => Value::Boolean(*b),
+        (ExpressionKind::Ident(id), _) => lookup(id).not_found("constant", id)?,
+        (e, _) => {
+            return Err(Error::simple(format!(
+                "expression is not a constant expression: {e:?}"
+            )))
+        }
+    })
+}
+
+/// Applies an operator to the values of its `operands`.
+fn apply(expr: &Expression, arithmetic: Arithmetic, operands: Vec<Value>) -> Result<Value> {
+    use ast::ExpressionKind;
+
+    let mut operands = operands.into_iter();
+    let a = operands
+        .next()
+        .expect("operators have at least one operand");
+    if let ExpressionKind::Not(_) = &**expr {
+        return match a {
+            Value::Boolean(a) => Ok(Value::Boolean(!a)),
+            a => Err(type_mismatch("!", &a, &a)),
+        };
+    }
+    let b = operands.next().expect("binary operators have two operands");
+
+    Ok(match &**expr {
+        ExpressionKind::Add(_, _) => match (a, b) {
+            (Value::String(a), Value::String(b)) => Value::String(a + &b),
+            values => arithmetic_op("+", values, arithmetic, i128::checked_add, |a, b| a + b)?,
+        },
+        ExpressionKind::Subtract(_, _) => {
+            arithmetic_op("-", (a, b), arithmetic, i128::checked_sub, |a, b| a - b)?
+        }
+        ExpressionKind::Multiply(_, _) => {
+            arithmetic_op("*", (a, b), arithmetic, i128::checked_mul, |a, b| a * b)?
+        }
+        ExpressionKind::Exponent(_, _) => match (a, b) {
+            // Negative exponents are fractions
+            (a, Value::Integer(b)) if b < 0 => {
+                let b = Value::Number(b as f64);
+                arithmetic_op("**", (a, b), arithmetic, |_, _| None, f64::powf)?
+            }
+            values => arithmetic_op(
+                "**",
+                values,
+                arithmetic,
+                |a, b| a.checked_pow(u32::try_from(b).ok()?),
+                f64::powf,
+            )?,
+        },
+        ExpressionKind::Divide(_, _) | ExpressionKind::Modulo(_, _) => {
+            if b == Value::Integer(0) || b == Value::Number(0.0) {
+                return Err(Error::simple("division by zero in constant expression"));
+            }
+
+            match (&**expr, arithmetic, a, b) {
+                // Integer division is exact, otherwise the result is a fraction
+                (
+                    ExpressionKind::Divide(_, _),
+                    Arithmetic::Exact,
+                    Value::Integer(a),
+                    Value::Integer(b),
+                ) if a.checked_rem(b) == Some(0) => Value::Integer(a / b),
+                (ExpressionKind::Divide(_, _), Arithmetic::Exact, a, b) => {
+                    Value::Number(numbers("/", (a, b), |a, b| a / b)?)
+                }
+                (ExpressionKind::Divide(_, _), _, a, b) => {
+                    arithmetic_op("/", (a, b), arithmetic, i128::checked_div, |a, b| a / b)?
+                }
+                (_, _, a, b) => {
+                    arithmetic_op("%", (a, b), arithmetic, i128::checked_rem, |a, b| a % b)?
+                }
+            }
+        }
+        ExpressionKind::Equal(_, _) => Value::Boolean(equal((a, b))),
+        ExpressionKind::NotEqual(_, _) => Value::Boolean(!equal((a, b))),
+        ExpressionKind::LessThan(_, _) => {
+            Value::Boolean(compare("<", (a, b))? == Some(Ordering::Less))
+        }
+        ExpressionKind::LessThanOrEqual(_, _) => Value::Boolean(matches!(
+            compare("<=", (a, b))?,
+            Some(Ordering::Less | Ordering::Equal)
+        )),
+        ExpressionKind::GreaterThan(_, _) => {
+            Value::Boolean(compare(">", (a, b))? == Some(Ordering::Greater))
+        }
+        ExpressionKind::GreaterThanOrEqual(_, _) => Value::Boolean(matches!(
+            compare(">=", (a, b))?,
+            Some(Ordering::Greater | Ordering::Equal)
+        )),
+        ExpressionKind::And(_, _) => match (a, b) {
+            (Value::Boolean(a), Value::Boolean(b)) => Value::Boolean(a && b),
+            (a, b) => return Err(type_mismatch("&&", &a, &b)),
+        },
+        ExpressionKind::Or(_, _) => match (a, b) {
+            (Value::Boolean(a), Value::Boolean(b)) => Value::Boolean(a || b),
+            (a, b) => return Err(type_mismatch("||", &a, &b)),
+        },
+        e => unreachable!("{e:?} is not an operator"),
+    })
+}
+
+fn numbers<T>(op: &str, (a, b): (Value, Value), f: impl FnOnce(f64, f64) -> T) -> Result<T> {
+    match (a.as_f64(), b.as_f64()) {
+        (Some(a), Some(b)) => Ok(f(a, b)),
+        _ => Err(type_mismatch(op, &a, &b)),
+    }
+}
+
+/// Applies `integer` to integers in exact arithmetic, failing on overflow, or `float` otherwise.
+fn arithmetic_op(
+    op: &str,
+    values: (Value, Value),
+    arithmetic: Arithmetic,
+    integer: impl FnOnce(i128, i128) -> Option<i128>,
+    float: impl FnOnce(f64, f64) -> f64,
+) -> Result<Value> {
+    match (arithmetic, values) {
+        (Arithmetic::Exact, (Value::Integer(a), Value::Integer(b))) => integer(a, b)
+            .map(Value::Integer)
+            .ok_or_else(|| Error::simple(format!("overflow in constant expression {a} {op} {b}"))),
+        (Arithmetic::Exact, values) => Ok(Value::Number(numbers(op, values, float)?)),
+        (Arithmetic::Float32, values) => Ok(Value::Number(numbers(op, values, |a, b| {
+            float(a as f32 as f64, b as f32 as f64) as f32 as f64
+        })?)),
+    }
+}
+
+fn compare(op: &str, values: (Value, Value)) -> Result<Option<Ordering>> {
+    match values {
+        (Value::Integer(a), Value::Integer(b)) => Ok(Some(a.cmp(&b))),
+        values => numbers(op, values, |a, b| a.partial_cmp(&b)),
+    }
+}
+
+/// Values of different types are not equal, integers and numbers are compared by value.
+fn equal(values: (Value, Value)) -> bool {
+    match values {
+        (
+            a @ (Value::Integer(_) | Value::Number(_)),
+            b @ (Value::Integer(_) | Value::Number(_)),
+        ) => compare("==", (a, b)).ok().flatten() == Some(Ordering::Equal),
+        (a, b) => a == b,
+    }
+}
+
+fn type_mismatch(op: &str, a: &Value, b: &Value) -> Error {
+    ErrorKind::TypeMismatch {
+        context: format!("cannot apply {op} to {a:?} and {b:?} in a constant expression"),
+    }
+    .into()
+}
+
+/// Writes the folded value of a constant to a new symbol.
+pub(crate) fn materialize(compiler: &mut Compiler, constant: &Constant) -> Result<Symbol> {
+    Ok(match (&constant.type_, &constant.value) {
+        (Type::PrimitiveType(PrimitiveType::UInt32), Value::Integer(n)) => {
+            uint32::new(compiler, *n as u32)
+        }
+        (Type::PrimitiveType(PrimitiveType::UInt64), Value::Integer(n)) => {
+            uint64::new(compiler, *n as u64)
+        }
+        (Type::PrimitiveType(PrimitiveType::Int32), Value::Integer(n)) => {
+            int32::new(compiler, *n as i32)
+        }
+        (Type::PrimitiveType(PrimitiveType::Float32), Value::Number(n)) => {
+            float32::new(compiler, *n as f32)
+        }
+        (Type::String, Value::String(s)) => string::new(compiler, s).0,
+        (Type::PrimitiveType(PrimitiveType::Boolean), Value::Boolean(b)) => {
+            boolean::new(compiler, *b)
+        }
+        (t, v) => {
+            return Err(Error::unimplemented(format!(
+                "materialize constant {v:?} of type {t:?}"
+            )))
+        }
+    })
+}
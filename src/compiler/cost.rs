@@ -0,0 +1,386 @@
+//! Static estimate of the number of cycles a program takes.
+//!
+//! The costs are those of Miden VM 0.7 instructions, plus the overhead of the
+//! blocks that join them. Loops can't be bounded statically, so the worst case is a polynomial
+//! of the number of iterations of every loop, nested loops are of a higher degree.
+//! Programs that execute procedures of unknown cost have no estimate.
+
+use super::encoder::Instruction;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Cost {
+    best: u64,
+    /// Coefficients of the worst case, `worst[k]` is the coefficient of `iterations^k`.
+    worst: Vec<u64>,
+    /// Whether the code executes something that can't be estimated.
+    unbounded: bool,
+}
+
+impl Cost {
+    fn constant(cycles: u64) -> Self {
+        Cost {
+            best: cycles,
+            worst: vec![cycles],
+            unbounded: false,
+        }
+    }
+
+    fn unbounded() -> Self {
+        Cost {
+            unbounded: true,
+            ..Cost::default()
+        }
+    }
+
+    fn then(mut self, other: Cost) -> Self {
+        self.best += other.best;
+        self.unbounded |= other.unbounded;
+        if self.worst.len() < other.worst.len() {
+            self.worst.resize(other.worst.len(), 0);
+        }
+        for (a, b) in self.worst.iter_mut().zip(other.worst) {
+            *a += b;
+        }
+        self
+    }
+
+    /// The cost of taking one of two branches.
+    fn or(mut self, other: Cost) -> Self {
+        self.best = self.best.min(other.best);
+        self.unbounded |= other.unbounded;
+        if self.worst.len() < other.worst.len() {
+            self.worst.resize(other.worst.len(), 0);
+        }
+        for (a, b) in self.worst.iter_mut().zip(other.worst) {
+            *a = (*a).max(b);
+        }
+        self
+    }
+
+    fn repeat(self, count: u64) -> Self {
+        Cost {
+            best: self.best * count,
+            worst: self.worst.into_iter().map(|c| c * count).collect(),
+            unbounded: self.unbounded,
+        }
+    }
+
+    /// The cost of running this in a loop, which might not run at all.
+    fn looped(self) -> Self {
+        Cost {
+            best: 0,
+            worst: [0].into_iter().chain(self.worst).collect(),
+            unbounded: self.unbounded,
+        }
+    }
+}
+
+/// Returns `None` if the instructions execute a procedure of unknown cost.
+pub(crate) fn estimate(instructions: &[Instruction]) -> Option<abi::CycleEstimate> {
+    let cost = cost(instructions);
+    if cost.unbounded {
+        return None;
+    }
+
+    Some(abi::CycleEstimate {
+        best: cost.best,
+        worst: cost.worst,
+    })
+}
+
+fn cost(instructions: &[Instruction]) -> Cost {
+    let mut total = Cost::default();
+    // Straight-line code is grouped into basic blocks, which are joined with control flow
+    let mut nodes: u64 = 0;
+    let mut in_basic_block = false;
+    let mut i = 0;
+    while i < instructions.len() {
+        let instruction = &instructions[i];
+        let is_control_flow = matches!(
+            instruction,
+            Instruction::While { .. }
+                | Instruction::WhileTrueRaw { .. }
+                | Instruction::If { .. }
+                | Instruction::IfTrue
+        );
+        if is_control_flow {
+            nodes += 1;
+            in_basic_block = false;
+        } else if !in_basic_block && !is_decorator(instruction) {
+            nodes += 1;
+            in_basic_block = true;
+            // span and end
+            total = total.then(Cost::constant(2));
+        }
+
+        if *instruction == Instruction::IfTrue {
+            let (then, else_, len) = split_raw_if(&instructions[i + 1..]);
+            // if.true and end
+            total = total.then(Cost::constant(2).then(cost(then).or(cost(else_))));
+            i += len + 1;
+        } else {
+            total = total.then(instruction_cost(instruction));
+            i += 1;
+        }
+    }
+
+    // join and end
+    total.then(Cost::constant(nodes.saturating_sub(1) * 2))
+}
+
+/// Decorators don't execute any operations.
+fn is_decorator(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Comment(_) | Instruction::AdvPushMapval
+    )
+}
+
+/// Splits the instructions after an `IfTrue` into the then and else branches,
+/// returns them and the number of instructions up to and including the matching `IfEnd`.
+fn split_raw_if<'i, 'a>(
+    instructions: &'i [Instruction<'a>],
+) -> (&'i [Instruction<'a>], &'i [Instruction<'a>], usize) {
+    let mut depth = 0;
+    let mut else_at = None;
+    for (i, instruction) in instructions.iter().enumerate() {
+        match instruction {
+            Instruction::IfTrue => depth += 1,
+            Instruction::IfElse if depth == 0 => else_at = Some(i),
+            Instruction::IfEnd if depth == 0 => {
+                return match else_at {
+                    Some(else_at) => (
+                        &instructions[..else_at],
+                        &instructions[else_at + 1..i],
+                        i + 1,
+                    ),
+                    None => (&instructions[..i], &[], i + 1),
+                };
+            }
+            Instruction::IfEnd => depth -= 1,
+            _ => {}
+        }
+    }
+
+    unreachable!("if.true without a matching end")
+}
+
+fn instruction_cost(instruction: &Instruction) -> Cost {
+    let cycles = match instruction {
+        Instruction::While { condition, body } => {
+            // The condition is evaluated before the loop and after every iteration.
+            // while.true, end and a repeat per iteration
+            let condition = cost(condition);
+            let iteration = cost(body).then(condition.clone()).then(Cost::constant(1));
+            return condition.then(Cost::constant(2)).then(iteration.looped());
+        }
+        Instruction::WhileTrueRaw { instructions } => {
+            let iteration = cost(instructions).then(Cost::constant(1));
+            return Cost::constant(2).then(iteration.looped());
+        }
+        Instruction::Repeat {
+            count,
+            instructions,
+        } => return cost(instructions).repeat(*count as u64),
+        Instruction::If {
+            condition,
+            then,
+            else_,
+        } => {
+            // Empty branches are encoded as `push.0 drop`
+            let then = if then.is_empty() {
+                Cost::constant(2)
+            } else {
+                cost(then)
+            };
+            return cost(condition)
+                .then(Cost::constant(2))
+                .then(then.or(cost(else_)));
+        }
+        Instruction::Comment(_) | Instruction::AdvPushMapval => 0,
+        Instruction::Drop
+        | Instruction::Push(_)
+        | Instruction::MovUp(_)
+        | Instruction::MovDown(_)
+        | Instruction::Swap
+        | Instruction::Assert
+        | Instruction::Dup(_)
+        | Instruction::Add
+        | Instruction::And
+        | Instruction::Or
+        | Instruction::Not
+        | Instruction::Eq
+        | Instruction::Cswap
+        | Instruction::U32CheckedAnd
+        | Instruction::U32CheckedXOR
+        | Instruction::U32OverflowingMul
+        | Instruction::MemLoad(None) => 1,
+        Instruction::AssertZero
+        | Instruction::U32CheckedEq
+        | Instruction::U32WrappingAdd
+        | Instruction::U32WrappingSub
+        | Instruction::U32WrappingMul
+        | Instruction::MemLoad(Some(_))
+        | Instruction::MemStore(None) => 2,
+        Instruction::U32CheckedDiv(None)
+        | Instruction::U32CheckedNeq
+        | Instruction::MemStore(Some(_)) => 3,
+        Instruction::Dropw
+        | Instruction::U32CheckedAdd
+        | Instruction::U32CheckedSub
+        | Instruction::U32CheckedMul
+        | Instruction::U32CheckedDiv(Some(_))
+        | Instruction::U32CheckedMod(None)
+        | Instruction::U32CheckedSHL(Some(_))
+        | Instruction::U32CheckedSHR(Some(_)) => 4,
+        Instruction::U32CheckedMod(Some(_)) | Instruction::U32CheckedNot => 5,
+        Instruction::U32CheckedLT | Instruction::U32CheckedGTE | Instruction::U32CheckedOr => 6,
+        Instruction::U32CheckedGT => 7,
+        Instruction::U32CheckedLTE => 8,
+        Instruction::U32CheckedMin => 9,
+        Instruction::HMerge => 16,
+        Instruction::U32CheckedSHL(None) | Instruction::U32CheckedSHR(None) => 47,
+        Instruction::AdvPush(n) => *n as u64,
+        Instruction::Exec(procedure) => match procedure_cost(procedure) {
+            Some(cycles) => cycles,
+            None => return Cost::unbounded(),
+        },
+        Instruction::IfTrue | Instruction::IfElse | Instruction::IfEnd => {
+            unreachable!("raw ifs are estimated together with their branches")
+        }
+        Instruction::Abstract(_) => {
+            unreachable!("abstract instructions should be unabstracted before estimating")
+        }
+    };
+
+    Cost::constant(cycles)
+}
+
+/// Rough costs of the standard library procedures that the compiler uses.
+fn procedure_cost(procedure: &str) -> Option<u64> {
+    let cycles = match procedure {
+        "u64::checked_eqz" => 6,
+        "u64::wrapping_add" | "u64::checked_and" | "u64::checked_or" | "u64::checked_xor" => 7,
+        "u64::checked_eq" | "u64::checked_neq" | "u64::overflowing_add" => 8,
+        "u64::wrapping_sub" | "u64::overflowing_sub" => 10,
+        "u64::checked_lt" | "u64::checked_gt" => 13,
+        "u64::checked_lte" | "u64::checked_gte" | "u64::wrapping_mul" => 14,
+        "u64::checked_add" => 15,
+        "u64::checked_sub" => 16,
+        "u64::overflowing_mul" => 18,
+        "u64::unchecked_clz" | "u64::unchecked_ctz" => 20,
+        "u64::unchecked_clo" | "u64::unchecked_cto" => 22,
+        "u64::checked_min" | "u64::checked_max" => 25,
+        "u64::checked_mul" => 28,
+        "u64::checked_shl" => 35,
+        "u64::checked_rotl" | "u64::checked_rotr" => 40,
+        "u64::checked_shr" => 45,
+        "u64::checked_div" | "u64::checked_mod" | "u64::checked_divmod" => 60,
+        "mmr::get" => 150,
+        "mmr::unpack" | "mmr::pack" | "mmr::add" => 400,
+        "smt::get" => 600,
+        "smt::insert" => 1500,
+        "blake3::hash_2to1" => 3700,
+        "sha256::hash_2to1" => 10000,
+        "rpo_falcon512::verify" => 60000,
+        _ => return None,
+    };
+
+    Some(cycles)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_straight_line() {
+        let estimate = estimate(&[
+            Instruction::Push(1),
+            Instruction::MemStore(Some(10)),
+            Instruction::MemLoad(Some(10)),
+        ])
+        .unwrap();
+
+        // span and end
+        assert_eq!(estimate.best, 2 + 6);
+        assert_eq!(estimate.worst, vec![2 + 6]);
+    }
+
+    #[test]
+    fn test_if_takes_cheapest_and_most_expensive_branch() {
+        let estimate = estimate(&[Instruction::If {
+            condition: vec![Instruction::Push(1)],
+            then: vec![Instruction::U32CheckedMin],
+            else_: vec![Instruction::Drop],
+        }])
+        .unwrap();
+
+        // the branches are basic blocks
+        assert_eq!(estimate.best, 3 + 2 + 3);
+        assert_eq!(estimate.worst, vec![3 + 2 + 11]);
+    }
+
+    #[test]
+    fn test_raw_if() {
+        let estimate = estimate(&[
+            Instruction::Push(1),
+            Instruction::IfTrue,
+            Instruction::IfTrue,
+            Instruction::U32CheckedMin,
+            Instruction::IfEnd,
+            Instruction::IfElse,
+            Instruction::Drop,
+            Instruction::IfEnd,
+            Instruction::Push(2),
+        ])
+        .unwrap();
+
+        // two basic blocks joined with the if
+        let overhead = 2 + 2 + 2 * 2;
+        assert_eq!(estimate.best, overhead + 1 + 2 + 2 + 1);
+        assert_eq!(estimate.worst, vec![overhead + 1 + 2 + 2 + 2 + 9 + 1]);
+    }
+
+    #[test]
+    fn test_nested_loops() {
+        let estimate = estimate(&[Instruction::While {
+            condition: vec![Instruction::Push(1)],
+            body: vec![Instruction::While {
+                condition: vec![Instruction::Push(1)],
+                body: vec![Instruction::U32CheckedMin],
+            }],
+        }])
+        .unwrap();
+
+        // condition, while.true and end, then the body, condition and repeat per iteration
+        let inner = abi::CycleEstimate {
+            best: 3 + 2,
+            worst: vec![3 + 2, 11 + 3 + 1],
+        };
+        assert_eq!(estimate.best, 3 + 2);
+        assert_eq!(estimate.worst, vec![3 + 2, 5 + 3 + 1, 15]);
+        assert_eq!(
+            estimate.worst_case(10),
+            3 + 2 + 10 * (inner.worst_case(10) + 3 + 1)
+        );
+    }
+
+    #[test]
+    fn test_unknown_procedure_has_no_estimate() {
+        let estimate = estimate(&[Instruction::While {
+            condition: vec![Instruction::Push(1)],
+            body: vec![Instruction::Exec("foo::bar")],
+        }]);
+
+        assert_eq!(estimate, None);
+    }
+
+    #[test]
+    fn test_asm_procedures_have_costs() {
+        for (procedure, _, _) in crate::compiler::asm::STD_PROCEDURES {
+            assert!(procedure_cost(procedure).is_some(), "{procedure}");
+        }
+    }
+}
@@ -72,9 +72,12 @@ pub(crate) enum AbstractInstruction<'a> {
     Break,
     Return,
     InlinedFunction(Vec<Instruction<'a>>),
+    /// Marks the start of a statement, so that a failure can be traced back to it.
+    Statement(abi::SourceSpan),
 }
 
 impl Instruction<'_> {
+    /// Encodes the instruction for Miden VM 0.7.0, the only version the compiler targets.
     pub(crate) fn encode(&self, f: &mut impl std::io::Write, depth: usize) -> std::io::Result<()> {
         // write_indent wraps write! but first writes depth*2 spaces
         macro_rules! write_indent {
@@ -263,9 +266,25 @@ pub(crate) fn unabstract<'a>(
                             }
                         }
                         AbstractInstruction::InlinedFunction(func) => {
+                            let mut return_ptr = None;
                             result.extend(unabstract(
-                                func, allocate, &mut None, &mut None, &mut false, false,
+                                func,
+                                allocate,
+                                &mut None,
+                                &mut return_ptr,
+                                &mut false,
+                                false,
                             ));
+                            // Reset the flag, otherwise the next call,
+                            // for example in a loop, would skip the whole function
+                            if let Some(ptr) = return_ptr {
+                                result.push(Instruction::Push(0));
+                                result.push(Instruction::MemStore(Some(ptr)));
+                            }
+                        }
+                        AbstractInstruction::Statement(span) => {
+                            result.push(Instruction::Push(span.start as u32));
+                            result.push(Instruction::MemStore(Some(super::STATEMENT_ADDR)));
                         }
                     },
                     Instruction::While { condition, body } => {
@@ -287,6 +306,11 @@ pub(crate) fn unabstract<'a>(
                             true,
                         );
                         result.push(Instruction::While { condition, body });
+                        // Reset the flag, so the loop runs again if it's entered again
+                        if let Some(ptr) = break_ptr {
+                            result.push(Instruction::Push(0));
+                            result.push(Instruction::MemStore(Some(ptr)));
+                        }
                     }
                     Instruction::Repeat {
                         count,
@@ -422,6 +446,43 @@ pub(crate) fn walk(instructions: &[Instruction], f: &mut impl FnMut(&Instruction
     }
 }
 
+/// Removes the statement markers, for programs that don't need to trace failures back to the source.
+pub(crate) fn remove_statements(instructions: &mut Vec<Instruction>) {
+    instructions.retain(|instruction| {
+        !matches!(
+            instruction,
+            Instruction::Abstract(AbstractInstruction::Statement(_))
+        )
+    });
+    for instruction in instructions {
+        match instruction {
+            Instruction::While { condition, body } => {
+                remove_statements(condition);
+                remove_statements(body);
+            }
+            Instruction::WhileTrueRaw { instructions } => {
+                remove_statements(instructions);
+            }
+            Instruction::Repeat { instructions, .. } => {
+                remove_statements(instructions);
+            }
+            Instruction::If {
+                condition,
+                then,
+                else_,
+            } => {
+                remove_statements(condition);
+                remove_statements(then);
+                remove_statements(else_);
+            }
+            Instruction::Abstract(AbstractInstruction::InlinedFunction(func)) => {
+                remove_statements(func);
+            }
+            _ => {}
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -449,45 +510,49 @@ mod test {
             ],
         }];
 
-        let expected = vec![Instruction::While {
-            condition: vec![Instruction::If {
-                condition: vec![Instruction::MemLoad(Some(1))],
-                then: vec![Instruction::Push(0)],
-                else_: vec![Instruction::Push(1)],
-            }],
-            body: vec![
-                Instruction::If {
-                    condition: vec![Instruction::Push(1)],
-                    then: vec![
-                        Instruction::Push(1),
-                        Instruction::MemStore(Some(1)),
-                        Instruction::If {
-                            condition: vec![Instruction::MemLoad(Some(1))],
-                            then: vec![],
-                            else_: vec![Instruction::Push(3)],
-                        },
-                    ],
-                    else_: vec![],
-                },
-                Instruction::If {
+        let expected = vec![
+            Instruction::While {
+                condition: vec![Instruction::If {
                     condition: vec![Instruction::MemLoad(Some(1))],
-                    then: vec![],
-                    else_: vec![
-                        Instruction::If {
-                            condition: vec![Instruction::Push(1)],
-                            then: vec![Instruction::If {
-                                // TODO: this if is not needed
+                    then: vec![Instruction::Push(0)],
+                    else_: vec![Instruction::Push(1)],
+                }],
+                body: vec![
+                    Instruction::If {
+                        condition: vec![Instruction::Push(1)],
+                        then: vec![
+                            Instruction::Push(1),
+                            Instruction::MemStore(Some(1)),
+                            Instruction::If {
                                 condition: vec![Instruction::MemLoad(Some(1))],
                                 then: vec![],
-                                else_: vec![Instruction::Push(1)],
-                            }],
-                            else_: vec![],
-                        },
-                        Instruction::Push(2),
-                    ],
-                },
-            ],
-        }];
+                                else_: vec![Instruction::Push(3)],
+                            },
+                        ],
+                        else_: vec![],
+                    },
+                    Instruction::If {
+                        condition: vec![Instruction::MemLoad(Some(1))],
+                        then: vec![],
+                        else_: vec![
+                            Instruction::If {
+                                condition: vec![Instruction::Push(1)],
+                                then: vec![Instruction::If {
+                                    // TODO: this if is not needed
+                                    condition: vec![Instruction::MemLoad(Some(1))],
+                                    then: vec![],
+                                    else_: vec![Instruction::Push(1)],
+                                }],
+                                else_: vec![],
+                            },
+                            Instruction::Push(2),
+                        ],
+                    },
+                ],
+            },
+            Instruction::Push(0),
+            Instruction::MemStore(Some(1)),
+        ];
 
         let mut ptr = 1;
         let unabstracted = unabstract(
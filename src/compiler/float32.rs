@@ -6,6 +6,7 @@
 use super::*;
 
 use encoder::Instruction;
+use ir::ExpressionRef;
 
 const SIGN_MASK: u32 = 0x8000_0000;
 const EXP_MASK: u32 = 0x7f80_0000;
@@ -17,9 +18,7 @@ const INFINITY: u32 = EXP_MASK;
 const LEADING_ONE_BIT: u32 = 0x0080_0000;
 
 pub(crate) fn new(compiler: &mut Compiler, value: f32) -> Symbol {
-    let symbol = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Float32));
+    let symbol = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Float32));
 
     compiler.memory.write(
         compiler.instructions,
@@ -30,1565 +29,599 @@ pub(crate) fn new(compiler: &mut Compiler, value: f32) -> Symbol {
     symbol
 }
 
-// [a, b] -> [a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-fn prepare_stack_for_arithmetic(compiler: &mut Compiler, a: &Symbol, b: &Symbol) {
-    compiler
-        .memory
-        .read(compiler.instructions, a.memory_addr, a.type_.miden_width());
-    decompose(compiler);
-    // [a_mant, a_sign^, a_exp]
-    compiler
-        .memory
-        .read(compiler.instructions, b.memory_addr, b.type_.miden_width());
-    decompose(compiler);
-    // [b_mant, b_sign^, b_exp, a_mant, a_sign^, a_exp]
-
-    compiler.instructions.push(Instruction::MovDown(5));
-    // [b_sign^, b_exp, a_mant, a_sign^, a_exp, b_mant]
-    compiler.instructions.push(Instruction::MovDown(4));
-    // [b_exp, a_mant, a_sign^, a_exp, b_sign^, b_mant]
-    compiler.instructions.push(Instruction::MovDown(3));
-    // [a_mant, a_sign^, a_exp, b_exp, b_sign^, b_mant]
-    compiler.instructions.push(Instruction::MovDown(4));
-    // [a_sign^, a_exp, b_exp, b_sign^, a_mant, b_mant]
-    compiler.instructions.push(Instruction::MovDown(2));
-    // [a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
+/// Calls the binary function `name` with `a` and `b`, i.e. computes `a name b`.
+fn binary(
+    builder: &mut ir::Builder,
+    name: &str,
+    a: ExpressionRef,
+    b: ExpressionRef,
+) -> Result<ExpressionRef> {
+    builder.call_one(name, &[b, a])
 }
 
-// [a] -> [a_mant, a_sign^, a_exp]
-fn decompose(compiler: &mut Compiler) {
-    compiler.instructions.extend([
-        // [a]
-        Instruction::Dup(None),
-        // [a, a]
-        Instruction::Push(EXP_MASK),
-        Instruction::U32CheckedAnd,
-        // [a_exp^, a]
-        Instruction::U32CheckedSHR(Some(EXP_SHIFT)),
-        // [a_exp, a]
-        Instruction::Dup(Some(1)),
-        Instruction::Push(SIGN_MASK),
-        Instruction::U32CheckedAnd,
-        // [a_sign^, a_exp, a]
-        Instruction::Dup(Some(2)),
-        Instruction::Push(MANT_MASK),
-        Instruction::U32CheckedAnd,
-        // [a_mant, a_sign^, a_exp, a]
-        Instruction::MovUp(3),
-        Instruction::Drop,
-        // [a_mant, a_sign^, a_exp]
-    ]);
+/// Computes `a name b` for a constant `b`.
+fn binary_const(
+    builder: &mut ir::Builder,
+    name: &str,
+    a: ExpressionRef,
+    b: u32,
+) -> Result<ExpressionRef> {
+    let b = builder.number(b as u64);
+    builder.call_one(name, &[b, a])
 }
 
-// [.., a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-//      ^ stack_offset
-// ->
-// [a_is_zero, b_is_zero, .., a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-pub(crate) fn add_is_zero(compiler: &mut Compiler, stack_offset: u32) {
-    compiler.instructions.extend([
-        Instruction::Dup(Some(stack_offset + 1)),
-        Instruction::Push(0),
-        Instruction::U32CheckedEq,
-        // [b_exp == 0, ..]
-        Instruction::Dup(Some(stack_offset + 6)),
-        Instruction::Push(0),
-        Instruction::U32CheckedEq,
-        Instruction::U32CheckedAnd,
-        // b is zero?
-        // [b_exp == 0 & b_mant == 0, a_exp, b_exp, ..]
-        Instruction::Dup(Some(stack_offset + 1)),
-        Instruction::Push(0),
-        Instruction::U32CheckedEq,
-        // [a_exp == 0, b_is_zero, ..]
-        Instruction::Dup(Some(stack_offset + 6)),
-        Instruction::Push(0),
-        Instruction::U32CheckedEq,
-        Instruction::U32CheckedAnd,
-        // a is zero?
-        // [a_exp == 0 & a_mant == 0, b_is_inf, b_is_zero, ..]
-    ]);
-}
-
-// [.., a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-//      ^ stack_offset
-// ->
-// [a_is_inf, b_is_inf, .., a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-pub(crate) fn add_is_inf(compiler: &mut Compiler, stack_offset: u32) {
-    compiler.instructions.extend([
-        Instruction::Dup(Some(stack_offset + 1)),
-        Instruction::Push(0xff),
-        Instruction::U32CheckedEq,
-        // b is inf?
-        // [b_exp == 0xff, ..]
-        Instruction::Dup(Some(stack_offset + 1)),
-        Instruction::Push(0xff),
-        Instruction::U32CheckedEq,
-        // a is inf?
-        // [a_exp == 0xff, b_is_inf, ..]
-    ]);
+/// Builds the float32 with `sign^`, `exp` and the low 23 bits of `mant`.
+/// If `exp` underflowed, the result is a signed zero, if it overflowed, a signed infinity.
+fn pack(
+    builder: &mut ir::Builder,
+    sign: ExpressionRef,
+    exp: ExpressionRef,
+    mant: ExpressionRef,
+) -> Result<ExpressionRef> {
+    let exp_is_negative = binary_const(builder, "u32checked_shr", exp, 31)?;
+    select(
+        builder,
+        exp_is_negative,
+        |_| Ok(sign),
+        |builder| {
+            let overflowed = binary_const(builder, "u32checked_gte", exp, 0xff)?;
+            select(
+                builder,
+                overflowed,
+                |builder| binary_const(builder, "u32checked_or", sign, INFINITY),
+                |builder| {
+                    let exp = binary_const(builder, "u32checked_shl", exp, EXP_SHIFT)?;
+                    let mant = binary_const(builder, "u32checked_and", mant, MANT_MASK)?;
+                    let value = binary(builder, "u32checked_or", exp, mant)?;
+                    binary(builder, "u32checked_or", value, sign)
+                },
+            )
+        },
+    )
 }
 
-// [.., a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-//      ^ stack_offset
-// ->
-// [a_is_nan || b_is_nan, .., a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-pub(crate) fn add_is_nan(compiler: &mut Compiler, stack_offset: u32) {
-    compiler.instructions.extend([
-        Instruction::Dup(Some(stack_offset + 1)),
-        // [b_exp, ..]
-        Instruction::Push(EXP_MASK >> EXP_SHIFT),
-        Instruction::U32CheckedEq,
-        // [b_exp^ == EXP_MASK, ..]
-        Instruction::Dup(Some(stack_offset + 6)),
-        // [b_mant, b_exp^ == EXP_MASK, ..]
-        Instruction::Push(0),
-        Instruction::U32CheckedNeq,
-        // [b_mant != 0, b_exp^ == EXP_MASK, ..]
-        Instruction::U32CheckedAnd,
-        // b is nan?
-        // [b_mant != 0 & b_exp^ == EXP_MASK, ..]
-        Instruction::Dup(Some(stack_offset + 1)),
-        // [a_exp, ..]
-        Instruction::Push(EXP_MASK >> EXP_SHIFT),
-        Instruction::U32CheckedEq,
-        // [a_exp^ == EXP_MASK, ..]
-        Instruction::Dup(Some(stack_offset + 6)),
-        // [a_mant, a_exp^ == EXP_MASK, ..]
-        Instruction::Push(0),
-        Instruction::U32CheckedNeq,
-        // [a_mant != 0, a_exp^ == EXP_MASK, ..]
-        Instruction::U32CheckedAnd,
-        // a is nan?
-        // [a_mant != 0 & a_exp^ == EXP_MASK, ..]
-        Instruction::U32CheckedOr,
-        // [a_is_nan | b_is_nan, ..]
-    ]);
+pub(crate) fn mul(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    compile_operation(compiler, a, b, PrimitiveType::Float32, |builder, a, b| {
+        let either_is_nan = either_is_nan(builder, a, b)?;
+        let a_is_zero = a.is_zero(builder)?;
+        let b_is_zero = b.is_zero(builder)?;
+        let a_is_inf = a.is_inf(builder)?;
+        let b_is_inf = b.is_inf(builder)?;
+        let zero_times_inf = binary(builder, "and", a_is_zero, b_is_inf)?;
+        let inf_times_zero = binary(builder, "and", a_is_inf, b_is_zero)?;
+        let is_nan = binary(builder, "or", either_is_nan, zero_times_inf)?;
+        let is_nan = binary(builder, "or", is_nan, inf_times_zero)?;
+
+        let a_sign = a.sign(builder)?;
+        let b_sign = b.sign(builder)?;
+        let sign = binary(builder, "u32checked_xor", a_sign, b_sign)?;
+
+        select(
+            builder,
+            is_nan,
+            |builder| Ok(builder.number(NAN as u64)),
+            |builder| {
+                let either_is_inf = binary(builder, "or", a_is_inf, b_is_inf)?;
+                select(
+                    builder,
+                    either_is_inf,
+                    |builder| binary_const(builder, "u32checked_or", sign, INFINITY),
+                    |builder| {
+                        let either_is_zero = binary(builder, "or", a_is_zero, b_is_zero)?;
+                        select(
+                            builder,
+                            either_is_zero,
+                            |_| Ok(sign),
+                            |builder| mul_normal(builder, a, b, sign),
+                        )
+                    },
+                )
+            },
+        )
+    })
 }
 
-//                                                             [a_sign^, b_sign^, a_exp^, b_exp^, a_mant, b_mant]
-// ->
-// [a_is_nan || b_is_nan, a_is_inf, b_is_inf, a_is_zero, b_is_zero, a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-pub(crate) fn add_nan_inf_zero(compiler: &mut Compiler) {
-    add_is_zero(compiler, 0);
-    // [a_is_zero, b_is_zero, ..]
-    add_is_inf(compiler, 2);
-    // [a_is_inf, b_is_inf, a_is_zero, b_is_zero, ..]
-    add_is_nan(compiler, 4);
-    // [a_is_nan || b_is_nan, a_is_inf, b_is_inf, a_is_zero, b_is_zero, ..]
+/// Multiplies the 24 bit mantissas and keeps the top 24 bits of the product.
+fn mul_normal(
+    builder: &mut ir::Builder,
+    a: Float,
+    b: Float,
+    sign: ExpressionRef,
+) -> Result<ExpressionRef> {
+    let exp = binary(builder, "u32checked_add", a.exp, b.exp)?;
+    let exp = binary_const(builder, "u32wrapping_sub", exp, EXP_BIAS)?;
+
+    let a_mant = binary_const(builder, "u32checked_add", a.mant, LEADING_ONE_BIT)?;
+    let b_mant = binary_const(builder, "u32checked_add", b.mant, LEADING_ONE_BIT)?;
+    let [high, low] = builder.call("u32overflowing_mul", &[b_mant, a_mant])?[..] else {
+        unreachable!()
+    };
+    let high = binary_const(builder, "u32checked_shl", high, 9)?;
+    let low = binary_const(builder, "u32checked_shr", low, 23)?;
+    let mant = binary(builder, "u32wrapping_add", high, low)?;
+
+    let carry = binary_const(builder, "u32checked_and", mant, 0x0100_0000)?;
+    let carry = binary_const(builder, "u32checked_neq", carry, 0)?;
+    let [exp, mant] = builder.if_(
+        carry,
+        |builder| {
+            Ok([
+                binary_const(builder, "u32checked_add", exp, 1)?,
+                binary_const(builder, "u32checked_shr", mant, 1)?,
+            ])
+        },
+        |_| Ok([exp, mant]),
+    )?;
+
+    pack(builder, sign, exp, mant)
 }
 
-pub(crate) fn mul(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Float32));
-
-    prepare_stack_for_arithmetic(compiler, a, b);
-    // [a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-
-    add_nan_inf_zero(compiler);
-    // [a_is_nan || b_is_nan, a_is_inf, b_is_inf, a_is_zero, b_is_zero, a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-
-    compiler.instructions.extend([Instruction::If {
-        condition: vec![
-            Instruction::Dup(Some(3)),
-            Instruction::Dup(Some(3)),
-            Instruction::U32CheckedAnd,
-            Instruction::U32CheckedOr,
-            // [a_is_nan || b_is_nan | a_is_zero & b_is_inf, ..]
-            Instruction::Dup(Some(4)),
-            Instruction::Dup(Some(2)),
-            Instruction::U32CheckedAnd,
-            Instruction::U32CheckedOr,
-            // [a_is_nan || b_is_nan | a_is_zero & b_is_inf | b_is_zero & a_is_inf, ..]
-        ],
-        then: vec![
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Push(NAN),
-        ],
-        else_: vec![Instruction::If {
-            condition: vec![
-                Instruction::U32CheckedOr,
-                // [b_is_inf | a_is_inf, a_is_zero, b_is_zero, a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-            ],
-            then: vec![
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::U32CheckedXOR,
-                Instruction::MovDown(2),
-                // [a_mant, b_mant, sign_result]
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Push(INFINITY),
-                Instruction::U32CheckedOr,
-                // [sign_result | INFINITY]
-            ],
-            else_: vec![Instruction::If {
-                condition: vec![
-                    Instruction::U32CheckedOr,
-                    // [a_is_zero | b_is_zero, a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-                ],
-                then: vec![
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    Instruction::MovDown(2),
-                    // [a_mant, b_mant, a_sign^, b_sign^]
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    Instruction::U32CheckedXOR,
-                    // [a_sign^ ^ b_sign^]
-                ],
-                else_: vec![
-                    Instruction::U32CheckedAdd,
-                    Instruction::Push(EXP_BIAS),
-                    Instruction::U32WrappingSub,
-                    // exp_result
-                    // [a_exp + b_exp - EXP_BIAS, a_sign^, b_sign^, a_mant, b_mant]
-                    Instruction::MovUp(4),
-                    Instruction::Push(LEADING_ONE_BIT),
-                    Instruction::U32CheckedAdd,
-                    Instruction::MovUp(4),
-                    Instruction::Push(LEADING_ONE_BIT),
-                    Instruction::U32CheckedAdd,
-                    // [a_mant | LEADING_ONE_BIT, b_mant | LEADING_ONE_BIT, exp_result, a_sign^, b_sign^]
-                    Instruction::U32OverflowingMul,
-                    Instruction::U32CheckedSHL(Some(9)),
-                    Instruction::Swap,
-                    Instruction::U32CheckedSHR(Some(23)),
-                    Instruction::U32WrappingAdd,
-                    // mant_result
-                    // [((a_mant | LEADING_ONE_BIT) * (b_mant | LEADING_ONE_BIT)) >> 23, exp_result, a_sign^, b_sign^]
-                    Instruction::If {
-                        condition: vec![
-                            Instruction::Dup(None),
-                            Instruction::Push(0x0100_0000),
-                            Instruction::U32CheckedAnd,
-                            // [mant_result & 0x0100_0000, mant_result, exp_result, a_sign^, b_sign^]
-                            Instruction::Push(0),
-                            Instruction::U32CheckedNeq,
-                            // mant_result & 0x0100_0000 != 0
-                        ],
-                        then: vec![
-                            Instruction::U32CheckedSHR(Some(1)),
-                            Instruction::Swap,
-                            Instruction::Push(1),
-                            Instruction::U32CheckedAdd,
-                            // [exp_result + 1, mant_result >> 1, a_sign^, b_sign^]
-                        ],
-                        else_: vec![Instruction::Swap],
+pub(crate) fn div(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    compile_operation(compiler, a, b, PrimitiveType::Float32, |builder, a, b| {
+        let either_is_nan = either_is_nan(builder, a, b)?;
+        let a_is_zero = a.is_zero(builder)?;
+        let b_is_zero = b.is_zero(builder)?;
+        let a_is_inf = a.is_inf(builder)?;
+        let b_is_inf = b.is_inf(builder)?;
+        let inf_by_inf = binary(builder, "and", a_is_inf, b_is_inf)?;
+        let zero_by_zero = binary(builder, "and", a_is_zero, b_is_zero)?;
+        let is_nan = binary(builder, "or", either_is_nan, inf_by_inf)?;
+        let is_nan = binary(builder, "or", is_nan, zero_by_zero)?;
+
+        let a_sign = a.sign(builder)?;
+        let b_sign = b.sign(builder)?;
+        let sign = binary(builder, "u32checked_xor", a_sign, b_sign)?;
+
+        select(
+            builder,
+            is_nan,
+            |builder| Ok(builder.number(NAN as u64)),
+            |builder| {
+                let is_inf = binary(builder, "or", a_is_inf, b_is_zero)?;
+                select(
+                    builder,
+                    is_inf,
+                    |builder| binary_const(builder, "u32checked_or", sign, INFINITY),
+                    |builder| {
+                        let is_zero = binary(builder, "or", b_is_inf, a_is_zero)?;
+                        select(
+                            builder,
+                            is_zero,
+                            |_| Ok(sign),
+                            |builder| div_normal(builder, a, b, sign),
+                        )
                     },
-                    // [exp_result, mant_result, a_sign^, b_sign^]
-                    Instruction::If {
-                        condition: vec![
-                            Instruction::Dup(None),
-                            Instruction::Push(SIGN_MASK),
-                            Instruction::U32CheckedAnd,
-                            Instruction::Push(SIGN_MASK),
-                            Instruction::U32CheckedEq,
-                            // int32(exp_result) < 0
-                        ],
-                        then: vec![
-                            Instruction::Drop,
-                            Instruction::Drop,
-                            Instruction::U32CheckedXOR,
-                            // [a_sign^ ^ b_sign^]
-                        ],
-                        else_: vec![Instruction::If {
-                            condition: vec![
-                                Instruction::Dup(None),
-                                Instruction::Push(0xff),
-                                Instruction::U32CheckedGTE,
-                                // mant_result >= 0xff
-                            ],
-                            then: vec![
-                                Instruction::Drop,
-                                Instruction::Drop,
-                                Instruction::U32CheckedXOR,
-                                // sign_result
-                                // [a_sign^ ^ b_sign^]
-                                Instruction::Push(INFINITY),
-                                Instruction::U32CheckedAdd,
-                            ],
-                            else_: vec![
-                                Instruction::U32CheckedSHL(Some(23)),
-                                // [exp_result^, mant_result, a_sign^ ^ b_sign^]
-                                Instruction::Swap,
-                                // [mant_result, exp_result^, a_sign^ ^ b_sign^]
-                                Instruction::Push(MANT_MASK),
-                                Instruction::U32CheckedAnd,
-                                Instruction::U32CheckedAdd,
-                                // [mant_result & MANT_MASK + exp_result^, a_sign^ ^ b_sign^]
-                                Instruction::MovDown(2),
-                                Instruction::U32CheckedXOR,
-                                Instruction::U32CheckedAdd,
-                                // [sign_result + mant_result & MANT_MASK + exp_result^]
-                            ],
-                        }],
-                    },
-                ],
-            }],
-        }],
-    }]);
-
-    compiler.memory.write(
-        compiler.instructions,
-        result.memory_addr,
-        &[ValueSource::Stack],
-    );
-
-    result
+                )
+            },
+        )
+    })
 }
 
-pub(crate) fn div(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Float32));
-
-    prepare_stack_for_arithmetic(compiler, a, b);
-    // [a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-
-    add_nan_inf_zero(compiler);
-    // [a_is_nan || b_is_nan, a_is_inf, b_is_inf, a_is_zero, b_is_zero, a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-
-    compiler.instructions.extend([Instruction::If {
-        condition: vec![
-            Instruction::Dup(Some(2)),
-            Instruction::Dup(Some(2)),
-            Instruction::U32CheckedAnd,
-            Instruction::U32CheckedOr,
-            // [a_is_nan || b_is_nan | a_is_inf & b_is_inf]
-            Instruction::Dup(Some(4)),
-            Instruction::Dup(Some(4)),
-            Instruction::U32CheckedAnd,
-            Instruction::U32CheckedOr,
-            // [a_is_nan || b_is_nan | a_is_inf & b_is_inf | a_is_zero & b_is_zero]
-        ],
-        then: vec![
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Push(NAN),
-        ],
-        else_: vec![Instruction::If {
-            condition: vec![
-                Instruction::MovUp(3),
-                Instruction::U32CheckedOr,
-                // [a_is_inf | b_is_zero, b_is_inf, a_is_zero, a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-            ],
-            then: vec![
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::U32CheckedXOR,
-                Instruction::MovDown(2),
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Push(INFINITY),
-                Instruction::U32CheckedAdd,
-                // a_sign^ ^ b_sign^ + INFINITY
-            ],
-            else_: vec![Instruction::If {
-                condition: vec![Instruction::U32CheckedOr],
-                then: vec![
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    Instruction::U32CheckedXOR,
-                    Instruction::MovDown(2),
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    // a_sign^ ^ b_sign^
-                ],
-                else_: vec![
-                    // [a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-                    Instruction::Push(EXP_BIAS),
-                    Instruction::U32CheckedAdd,
-                    Instruction::Swap,
-                    Instruction::U32WrappingSub,
-                    // exp_result
-                    // [a_exp + EXP_BIAS - b_exp, a_sign^, b_sign^, a_mant, b_mant]
-                    Instruction::MovDown(4),
-                    Instruction::U32CheckedXOR,
-                    Instruction::MovDown(3),
-                    // [a_mant, b_mant, exp_result, sign_result]
-                    Instruction::Push(LEADING_ONE_BIT),
-                    Instruction::U32CheckedAdd,
-                    Instruction::U32CheckedSHL(Some(8)),
-                    Instruction::Swap,
-                    Instruction::Push(LEADING_ONE_BIT),
-                    Instruction::U32CheckedAdd,
-                    Instruction::U32CheckedSHL(Some(8)),
-                    Instruction::Push(0),
-                    // mant_result, divisor, remainder
-                    // [0, b_mant | LEADING_ONE_BIT, a_mant | LEADING_ONE_BIT, exp_result, sign_result]
-                    Instruction::Repeat {
-                        count: 24,
-                        instructions: vec![
-                            Instruction::U32CheckedSHL(Some(1)),
-                            Instruction::If {
-                                condition: vec![
-                                    Instruction::Dup(Some(2)),
-                                    Instruction::Dup(Some(2)),
-                                    Instruction::U32CheckedGTE,
-                                    // divisor >= remainder
-                                ],
-                                then: vec![
-                                    Instruction::MovUp(2),
-                                    Instruction::Dup(Some(2)),
-                                    Instruction::U32CheckedSub,
-                                    // [remainder - divisor, mant_result, divisor, ..]
-                                    Instruction::MovDown(2),
-                                    Instruction::Push(1),
-                                    Instruction::U32CheckedAdd,
-                                    // [mant_result, divisor, remainder - divisor, ..]
-                                ],
-                                else_: vec![],
-                            },
-                            Instruction::Swap,
-                            Instruction::U32CheckedSHR(Some(1)),
-                            Instruction::Swap,
-                            // [mant_result, divisor >> 1, remainder, ..]
-                        ],
-                    },
-                    Instruction::MovDown(2),
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    // [mant_result, exp_result, sign_result]
-                    Instruction::While {
-                        condition: vec![
-                            Instruction::Dup(Some(0)),
-                            Instruction::Push(LEADING_ONE_BIT),
-                            Instruction::U32CheckedAnd,
-                            Instruction::Push(LEADING_ONE_BIT),
-                            Instruction::U32CheckedNeq,
-                            // mant_result & LEADING_ONE_BIT != LEADING_ONE_BIT
-                        ],
-                        body: vec![
-                            Instruction::U32CheckedSHL(Some(1)),
-                            Instruction::Swap,
-                            Instruction::Push(1),
-                            Instruction::U32WrappingSub,
-                            Instruction::Swap,
-                            // [mant_result << 1, exp_result - 1, remainder, ..]
-                        ],
-                    },
-                    Instruction::Push(MANT_MASK),
-                    Instruction::U32CheckedAnd,
-                    Instruction::Swap,
-                    // [exp_result, mant_result & MANT_MASK, sign_result]
-                    Instruction::If {
-                        condition: vec![
-                            Instruction::Dup(Some(0)),
-                            Instruction::Push(SIGN_MASK),
-                            Instruction::U32CheckedAnd,
-                            Instruction::Push(SIGN_MASK),
-                            Instruction::U32CheckedEq,
-                            // exp_result & SIGN_MASK == SIGN_MASK
-                        ],
-                        then: vec![
-                            Instruction::Drop,
-                            Instruction::Drop,
-                            // sign_result
-                        ],
-                        else_: vec![Instruction::If {
-                            condition: vec![
-                                Instruction::Dup(Some(0)),
-                                Instruction::Push(0xff),
-                                Instruction::U32CheckedGTE,
-                                // exp_result >= 0xff
-                            ],
-                            then: vec![
-                                Instruction::Drop,
-                                Instruction::Drop,
-                                Instruction::Push(INFINITY),
-                                Instruction::U32CheckedOr,
-                                // sign_result + INFINITY
-                            ],
-                            else_: vec![
-                                Instruction::U32CheckedSHL(Some(23)),
-                                Instruction::U32CheckedOr,
-                                Instruction::U32CheckedOr,
-                            ],
-                        }],
-                    },
-                ],
-            }],
-        }],
-    }]);
-
-    compiler.memory.write(
-        compiler.instructions,
-        result.memory_addr,
-        &[ValueSource::Stack],
-    );
-
-    result
+/// Divides the mantissas bit by bit, shifting the divisor instead of the remainder,
+/// then shifts the quotient until its leading one is in place.
+fn div_normal(
+    builder: &mut ir::Builder,
+    a: Float,
+    b: Float,
+    sign: ExpressionRef,
+) -> Result<ExpressionRef> {
+    let exp = binary_const(builder, "u32checked_add", a.exp, EXP_BIAS)?;
+    let exp = binary(builder, "u32wrapping_sub", exp, b.exp)?;
+
+    let remainder = binary_const(builder, "u32checked_add", a.mant, LEADING_ONE_BIT)?;
+    let remainder = binary_const(builder, "u32checked_shl", remainder, 8)?;
+    let divisor = binary_const(builder, "u32checked_add", b.mant, LEADING_ONE_BIT)?;
+    let divisor = binary_const(builder, "u32checked_shl", divisor, 8)?;
+    let mant = builder.number(0);
+
+    let [mant, _, _] = builder.repeat(
+        24,
+        [mant, divisor, remainder],
+        |builder, [mant, divisor, remainder]| {
+            let fits = binary(builder, "u32checked_gte", remainder, divisor)?;
+            let mant = binary_const(builder, "u32checked_shl", mant, 1)?;
+            let mant = binary(builder, "u32checked_add", mant, fits)?;
+            let subtrahend = binary(builder, "u32checked_mul", divisor, fits)?;
+            let remainder = binary(builder, "u32checked_sub", remainder, subtrahend)?;
+            let divisor = binary_const(builder, "u32checked_shr", divisor, 1)?;
+
+            Ok([mant, divisor, remainder])
+        },
+    )?;
+
+    let [mant, exp] = builder.while_(
+        [mant, exp],
+        |builder, [mant, _]| {
+            let leading_one = binary_const(builder, "u32checked_and", mant, LEADING_ONE_BIT)?;
+            binary_const(builder, "u32checked_eq", leading_one, 0)
+        },
+        |builder, [mant, exp]| {
+            Ok([
+                binary_const(builder, "u32checked_shl", mant, 1)?,
+                binary_const(builder, "u32wrapping_sub", exp, 1)?,
+            ])
+        },
+    )?;
+
+    pack(builder, sign, exp, mant)
 }
 
-// [a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-// ->
-// [a + b]
-fn add_impl(compiler: &mut Compiler) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Float32));
-
-    add_nan_inf_zero(compiler);
-    // [a_is_nan || b_is_nan, a_is_inf, b_is_inf, a_is_zero, b_is_zero, a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-
-    compiler.instructions.extend([Instruction::If {
-        condition: vec![
-            Instruction::Dup(Some(2)),
-            Instruction::Dup(Some(2)),
-            Instruction::U32CheckedAnd,
-            // [a_is_inf & b_is_inf, ..]
-            Instruction::Dup(Some(9)),
-            Instruction::Dup(Some(9)),
-            Instruction::U32CheckedNeq,
-            Instruction::U32CheckedAnd,
-            // [a_sign^ != b_sign^ & a_is_inf & b_is_inf, ..]
-            Instruction::U32CheckedOr,
-            // [a_sign^ != b_sign^ & a_is_inf & b_is_inf | a_is_nan || b_is_nan]
-        ],
-        then: vec![
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Push(NAN),
-        ],
-        else_: vec![Instruction::If {
-            condition: vec![
-                // [a_is_inf, b_is_inf, a_is_zero, b_is_zero, a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-            ],
-            then: vec![
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::MovDown(3),
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                // [a_sign^]
-                Instruction::Push(INFINITY),
-                Instruction::U32CheckedOr,
-            ],
-            else_: vec![Instruction::If {
-                condition: vec![
-                    // [b_is_inf, a_is_zero, b_is_zero, a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-                ],
-                then: vec![
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    Instruction::MovDown(2),
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    // [b_sign^]
-                    Instruction::Push(INFINITY),
-                    Instruction::U32CheckedOr,
-                ],
-                else_: vec![Instruction::If {
-                    condition: vec![
-                        // [a_is_zero, b_is_zero, a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-                    ],
-                    then: vec![
-                        Instruction::Drop,
-                        Instruction::Drop,
-                        Instruction::U32CheckedSHL(Some(23)),
-                        Instruction::MovDown(4),
-                        // [a_sign^, b_sign^, a_mant, b_mant, b_exp^]
-                        Instruction::Drop,
-                        Instruction::Swap,
-                        Instruction::Drop,
-                        Instruction::U32CheckedOr,
-                        Instruction::U32CheckedOr,
-                        // [b]
-                    ],
-                    else_: vec![Instruction::If {
-                        condition: vec![
-                            // [b_is_zero, a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-                        ],
-                        then: vec![
-                            Instruction::U32CheckedSHL(Some(23)),
-                            Instruction::MovDown(3),
-                            // [b_exp, a_sign^, b_sign^, a_exp^, a_mant, b_mant]
-                            Instruction::Drop,
-                            Instruction::Swap,
-                            Instruction::Drop,
-                            // [a_sign^, a_exp^, a_mant, b_mant]
-                            Instruction::U32CheckedOr,
-                            Instruction::U32CheckedOr,
-                            Instruction::Swap,
-                            Instruction::Drop,
-                        ],
-                        // [a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-                        else_: vec![
-                            Instruction::If {
-                                condition: vec![
-                                    Instruction::Dup(Some(1)),
-                                    Instruction::Dup(Some(1)),
-                                    Instruction::U32CheckedGT,
-                                    // [b_exp > a_exp]
-                                ],
-                                then: vec![
-                                    Instruction::MovDown(5),
-                                    // [b_exp, a_sign^, b_sign^, a_mant, b_mant, a_exp]
-                                    Instruction::MovDown(4),
-                                    // [a_sign^, b_sign^, a_mant, b_mant, b_exp, a_exp]
-                                    Instruction::MovDown(5),
-                                    Instruction::MovDown(4),
-                                    // [a_mant, b_mant, b_exp, a_exp, b_sign^, a_sign^]
-                                    Instruction::MovDown(5),
-                                    Instruction::MovDown(4),
-                                    // a <-> b
-                                    // [b_exp, a_exp, b_sign^, a_sign^, b_mant, a_mant]
-                                ],
-                                else_: vec![],
-                            },
-                            Instruction::If {
-                                condition: vec![
-                                    Instruction::Dup(Some(0)),
-                                    Instruction::MovUp(2),
-                                    // [b_exp, a_exp, a_exp, ..]
-                                    Instruction::U32CheckedSub,
-                                    // exp_diff
-                                    // [a_exp - b_exp, a_exp, a_sign^, b_sign^, a_mant, b_mant]
-                                    Instruction::Dup(Some(0)),
-                                    Instruction::Push(24),
-                                    Instruction::U32CheckedGT,
-                                    // exp_diff > 24
-                                ],
-                                then: vec![
-                                    Instruction::Drop,
-                                    Instruction::U32CheckedSHL(Some(23)),
-                                    Instruction::U32CheckedOr,
-                                    Instruction::Swap,
-                                    Instruction::Drop,
-                                    Instruction::U32CheckedOr,
-                                    Instruction::Swap,
-                                    Instruction::Drop,
-                                ],
-                                else_: vec![
-                                    // [exp_diff, a_exp, a_sign^, b_sign^, a_mant, b_mant]
-                                    Instruction::Swap,
-                                    Instruction::MovDown(5),
-                                    // [exp_diff, a_sign^, b_sign^, a_mant, b_mant, a_exp]
-                                    Instruction::MovUp(4),
-                                    // [b_mant, exp_diff, a_sign^, b_sign^, a_mant, a_exp]
-                                    Instruction::Push(LEADING_ONE_BIT),
-                                    Instruction::U32CheckedOr,
-                                    Instruction::Swap,
-                                    Instruction::U32CheckedSHR(None),
-                                    Instruction::If {
-                                        condition: vec![
-                                            Instruction::Dup(Some(0)),
-                                            Instruction::Push(0),
-                                            Instruction::U32CheckedEq,
-                                        ],
-                                        then: vec![Instruction::Push(1), Instruction::U32CheckedOr],
-                                        else_: vec![],
-                                    },
-                                    Instruction::MovDown(3),
-                                    Instruction::MovUp(2),
-                                    Instruction::Push(LEADING_ONE_BIT),
-                                    Instruction::U32CheckedOr,
-                                    Instruction::MovDown(2),
-                                    // [a_sign^, b_sign^, a_mant, b_mant, a_exp]
-                                    Instruction::If {
-                                        condition: vec![
-                                            Instruction::Dup(Some(1)),
-                                            Instruction::Dup(Some(1)),
-                                            Instruction::U32CheckedEq,
-                                            // a_sign == b_sign
-                                        ],
-                                        then: vec![
-                                            Instruction::MovDown(3),
-                                            Instruction::Drop,
-                                            Instruction::U32CheckedAdd,
-                                            // sum
-                                            // [a_mant + b_mant, a_sign, a_exp]
-                                        ],
-                                        else_: vec![Instruction::If {
-                                            condition: vec![
-                                                Instruction::Dup(Some(3)),
-                                                Instruction::Dup(Some(3)),
-                                                Instruction::U32CheckedLTE,
-                                                // b_mant <= a_mant
-                                            ],
-                                            then: vec![
-                                                Instruction::MovDown(3),
-                                                Instruction::Drop,
-                                                Instruction::Swap,
-                                                Instruction::U32CheckedSub,
-                                                // sum
-                                                // [a_mant - b_mant, a_sign, a_exp]
-                                            ],
-                                            else_: vec![
-                                                Instruction::Drop,
-                                                Instruction::MovDown(2),
-                                                Instruction::U32CheckedSub,
-                                                // sum
-                                                // [a_mant - b_mant, b_sign, a_exp]
-                                            ],
-                                        }],
-                                    },
-                                    // [mant_result, sign_result, a_exp]
-                                    Instruction::If {
-                                        condition: vec![
-                                            Instruction::Dup(Some(0)),
-                                            Instruction::Push(0),
-                                            Instruction::U32CheckedEq,
-                                        ],
-                                        then: vec![
-                                            Instruction::Drop,
-                                            Instruction::Drop,
-                                            Instruction::Drop,
-                                            Instruction::Push(0),
-                                        ],
-                                        else_: vec![
-                                            Instruction::Push(32),
-                                            Instruction::Dup(Some(1)),
-                                            // [mant_result, clz, mant_result, sign_result, a_exp]
-                                            Instruction::While {
-                                                condition: vec![
-                                                    Instruction::Dup(Some(0)),
-                                                    Instruction::Push(0),
-                                                    Instruction::U32CheckedNeq,
-                                                    // mant_result != 0
-                                                ],
-                                                body: vec![
-                                                    Instruction::U32CheckedSHR(Some(1)),
-                                                    Instruction::Swap,
-                                                    Instruction::Push(1),
-                                                    Instruction::U32CheckedSub,
-                                                    Instruction::Swap,
-                                                    // [mant_result >> 1, clz - 1, ..]
-                                                ],
-                                            },
-                                            Instruction::Drop,
-                                            Instruction::If {
-                                                condition: vec![
-                                                    Instruction::Dup(Some(0)),
-                                                    Instruction::Push(8),
-                                                    Instruction::U32CheckedLTE,
-                                                    // clz <= 8
-                                                ],
-                                                then: vec![
-                                                    Instruction::Push(8),
-                                                    Instruction::Swap,
-                                                    Instruction::U32CheckedSub,
-                                                    // extra_exp
-                                                    // [8 - clz, mant_result, sign_result, a_exp]
-                                                    Instruction::Swap,
-                                                    Instruction::Dup(Some(1)),
-                                                    Instruction::U32CheckedSHR(None),
-                                                    // [mant_result >> extra_exp, extra_exp, sign_result, a_exp]
-                                                    Instruction::Push(MANT_MASK),
-                                                    Instruction::U32CheckedAnd,
-                                                    Instruction::Swap,
-                                                    Instruction::MovUp(3),
-                                                    Instruction::U32CheckedAdd,
-                                                    // exp_result, mant_result
-                                                    // [a_exp + extra_exp, mant_result >> extra_exp & MANT_MASK, sign_result]
-                                                ],
-                                                else_: vec![
-                                                    Instruction::Push(8),
-                                                    Instruction::U32CheckedSub,
-                                                    // missing_exp
-                                                    // [clz - 8, mant_result, sign_result, a_exp]
-                                                    Instruction::Swap,
-                                                    Instruction::Dup(Some(1)),
-                                                    Instruction::U32CheckedSHL(None),
-                                                    // [mant_result << missing_exp, missing_exp, sign_result, a_exp]
-                                                    Instruction::Push(MANT_MASK),
-                                                    Instruction::U32CheckedAnd,
-                                                    Instruction::Swap,
-                                                    Instruction::MovUp(3),
-                                                    Instruction::Swap,
-                                                    Instruction::U32WrappingSub,
-                                                    // exp_result, mant_result
-                                                    // [a_exp + missing_exp, mant_result << missing_exp & MANT_MASK, sign_result]
-                                                ],
-                                            },
-                                            // [exp_result, mant_result & MANT_MASK, sign_result]
-                                            Instruction::If {
-                                                condition: vec![
-                                                    Instruction::Dup(Some(0)),
-                                                    Instruction::Push(SIGN_MASK),
-                                                    Instruction::U32CheckedAnd,
-                                                    Instruction::Push(SIGN_MASK),
-                                                    Instruction::U32CheckedEq,
-                                                    // exp_result & SIGN_MASK == SIGN_MASK
-                                                ],
-                                                then: vec![
-                                                    Instruction::Drop,
-                                                    Instruction::Drop,
-                                                    // sign_result
-                                                ],
-                                                else_: vec![Instruction::If {
-                                                    condition: vec![
-                                                        Instruction::Dup(Some(0)),
-                                                        Instruction::Push(0xff),
-                                                        Instruction::U32CheckedGTE,
-                                                        // exp_result >= 0xff
-                                                    ],
-                                                    then: vec![
-                                                        Instruction::Drop,
-                                                        Instruction::Drop,
-                                                        Instruction::Push(INFINITY),
-                                                        Instruction::U32CheckedOr,
-                                                        // sign_result + INFINITY
-                                                    ],
-                                                    else_: vec![
-                                                        Instruction::U32CheckedSHL(Some(23)),
-                                                        Instruction::U32CheckedOr,
-                                                        Instruction::U32CheckedOr,
-                                                    ],
-                                                }],
-                                            },
-                                        ],
-                                    },
-                                ],
-                            },
-                        ],
-                    }],
-                }],
-            }],
-        }],
-    }]);
-
-    compiler.memory.write(
-        compiler.instructions,
-        result.memory_addr,
-        &[ValueSource::Stack],
-    );
-
-    result
+fn add_impl(builder: &mut ir::Builder, a: Float, b: Float) -> Result<ExpressionRef> {
+    let either_is_nan = either_is_nan(builder, a, b)?;
+    let a_is_inf = a.is_inf(builder)?;
+    let b_is_inf = b.is_inf(builder)?;
+    let a_sign = a.sign(builder)?;
+    let b_sign = b.sign(builder)?;
+    let signs_differ = binary(builder, "u32checked_neq", a_sign, b_sign)?;
+    let both_are_inf = binary(builder, "and", a_is_inf, b_is_inf)?;
+    let inf_minus_inf = binary(builder, "and", both_are_inf, signs_differ)?;
+    let is_nan = binary(builder, "or", either_is_nan, inf_minus_inf)?;
+
+    select(
+        builder,
+        is_nan,
+        |builder| Ok(builder.number(NAN as u64)),
+        |builder| {
+            select(
+                builder,
+                a_is_inf,
+                |builder| binary_const(builder, "u32checked_or", a_sign, INFINITY),
+                |builder| {
+                    select(
+                        builder,
+                        b_is_inf,
+                        |builder| binary_const(builder, "u32checked_or", b_sign, INFINITY),
+                        |builder| {
+                            let a_is_zero = a.is_zero(builder)?;
+                            select(
+                                builder,
+                                a_is_zero,
+                                |builder| {
+                                    let b_is_zero = b.is_zero(builder)?;
+                                    select(
+                                        builder,
+                                        b_is_zero,
+                                        // The sum of zeros is only -0 if both are -0
+                                        |builder| {
+                                            binary(builder, "u32checked_and", a.value, b.value)
+                                        },
+                                        |_| Ok(b.value),
+                                    )
+                                },
+                                |builder| {
+                                    let b_is_zero = b.is_zero(builder)?;
+                                    select(
+                                        builder,
+                                        b_is_zero,
+                                        |_| Ok(a.value),
+                                        |builder| add_normal(builder, a, b),
+                                    )
+                                },
+                            )
+                        },
+                    )
+                },
+            )
+        },
+    )
 }
 
-pub(crate) fn add(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    prepare_stack_for_arithmetic(compiler, a, b);
-    // [a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-
-    add_impl(compiler)
+/// Aligns the mantissa of the smaller number to the larger one, adds or subtracts them,
+/// then normalizes the result by its number of leading zeros.
+fn add_normal(builder: &mut ir::Builder, a: Float, b: Float) -> Result<ExpressionRef> {
+    let b_exp_is_greater = binary(builder, "u32checked_gt", b.exp, a.exp)?;
+    let [a_value, a_exp, a_mant, b_value, b_exp, b_mant] = builder.if_(
+        b_exp_is_greater,
+        |_| Ok([b.value, b.exp, b.mant, a.value, a.exp, a.mant]),
+        |_| Ok([a.value, a.exp, a.mant, b.value, b.exp, b.mant]),
+    )?;
+
+    let exp_diff = binary(builder, "u32checked_sub", a_exp, b_exp)?;
+    let b_is_negligible = binary_const(builder, "u32checked_gt", exp_diff, 24)?;
+    select(
+        builder,
+        b_is_negligible,
+        |_| Ok(a_value),
+        |builder| {
+            let b_mant = binary_const(builder, "u32checked_or", b_mant, LEADING_ONE_BIT)?;
+            let b_mant = binary(builder, "u32checked_shr", b_mant, exp_diff)?;
+            // Keep a sticky bit, so that a tiny b doesn't vanish
+            let b_mant_is_zero = binary_const(builder, "u32checked_eq", b_mant, 0)?;
+            let b_mant = select(
+                builder,
+                b_mant_is_zero,
+                |builder| Ok(builder.number(1)),
+                |_| Ok(b_mant),
+            )?;
+            let a_mant = binary_const(builder, "u32checked_or", a_mant, LEADING_ONE_BIT)?;
+
+            let a_sign = binary_const(builder, "u32checked_and", a_value, SIGN_MASK)?;
+            let b_sign = binary_const(builder, "u32checked_and", b_value, SIGN_MASK)?;
+            let same_sign = binary(builder, "u32checked_eq", a_sign, b_sign)?;
+            let [mant, sign] = builder.if_(
+                same_sign,
+                |builder| Ok([binary(builder, "u32checked_add", a_mant, b_mant)?, a_sign]),
+                |builder| {
+                    let b_is_smaller = binary(builder, "u32checked_lte", b_mant, a_mant)?;
+                    builder.if_(
+                        b_is_smaller,
+                        |builder| Ok([binary(builder, "u32checked_sub", a_mant, b_mant)?, a_sign]),
+                        |builder| Ok([binary(builder, "u32checked_sub", b_mant, a_mant)?, b_sign]),
+                    )
+                },
+            )?;
+
+            let mant_is_zero = binary_const(builder, "u32checked_eq", mant, 0)?;
+            select(
+                builder,
+                mant_is_zero,
+                |builder| Ok(builder.number(0)),
+                |builder| {
+                    // Counts the leading zeros of the mantissa
+                    let bits = builder.number(32);
+                    let [_, leading_zeros] = builder.while_(
+                        [mant, bits],
+                        |builder, [mant, _]| binary_const(builder, "u32checked_neq", mant, 0),
+                        |builder, [mant, leading_zeros]| {
+                            Ok([
+                                binary_const(builder, "u32checked_shr", mant, 1)?,
+                                binary_const(builder, "u32checked_sub", leading_zeros, 1)?,
+                            ])
+                        },
+                    )?;
+
+                    let mant_is_too_long =
+                        binary_const(builder, "u32checked_lte", leading_zeros, 8)?;
+                    let [exp, mant] = builder.if_(
+                        mant_is_too_long,
+                        |builder| {
+                            let eight = builder.number(8);
+                            let extra_exp =
+                                binary(builder, "u32checked_sub", eight, leading_zeros)?;
+                            Ok([
+                                binary(builder, "u32checked_add", a_exp, extra_exp)?,
+                                binary(builder, "u32checked_shr", mant, extra_exp)?,
+                            ])
+                        },
+                        |builder| {
+                            let missing_exp =
+                                binary_const(builder, "u32checked_sub", leading_zeros, 8)?;
+                            Ok([
+                                binary(builder, "u32wrapping_sub", a_exp, missing_exp)?,
+                                binary(builder, "u32checked_shl", mant, missing_exp)?,
+                            ])
+                        },
+                    )?;
+
+                    pack(builder, sign, exp, mant)
+                },
+            )
+        },
+    )
 }
 
-pub(crate) fn sub(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    prepare_stack_for_arithmetic(compiler, a, b);
-    // [a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-
-    compiler.instructions.extend([
-        Instruction::MovUp(3),
-        Instruction::Push(SIGN_MASK),
-        Instruction::U32CheckedXOR,
-        Instruction::MovDown(3),
-    ]);
-
-    add_impl(compiler)
+pub(crate) fn add(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    compile_operation(compiler, a, b, PrimitiveType::Float32, add_impl)
 }
 
-pub(crate) fn eq(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
-
-    prepare_stack_for_arithmetic(compiler, a, b);
-    // [a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-
-    add_is_zero(compiler, 0);
-    // [a_is_zero, b_is_zero, ..]
-
-    compiler.instructions.push(Instruction::U32CheckedAnd);
-
-    add_is_nan(compiler, 4);
-    // [a_is_nan || b_is_nan, a_is_zero && b_is_zero, ..]
-
-    compiler.instructions.extend([Instruction::If {
-        condition: vec![
-            // [a_is_nan || b_is_nan]
-        ],
-        then: vec![
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Push(0),
-        ],
-        else_: vec![Instruction::If {
-            condition: vec![
-                // [a_is_zero && b_is_zero]
-            ],
-            then: vec![
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Push(1),
-            ],
-            else_: vec![
-                // [a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-                Instruction::U32CheckedEq,
-                Instruction::MovDown(4),
-                Instruction::U32CheckedEq,
-                Instruction::Swap,
-                Instruction::U32CheckedEq,
-                // [a_mant == b_mant, a_sign^ == b_sign^, a_exp == b_exp]
-                Instruction::U32CheckedAnd,
-                Instruction::U32CheckedAnd,
-            ],
-        }],
-    }]);
+pub(crate) fn sub(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    compile_operation(compiler, a, b, PrimitiveType::Float32, |builder, a, b| {
+        let b = binary_const(builder, "u32checked_xor", b.value, SIGN_MASK)?;
+        let b = Float::new(builder, b)?;
+        add_impl(builder, a, b)
+    })
+}
 
-    compiler.memory.write(
-        compiler.instructions,
-        result.memory_addr,
-        &[ValueSource::Stack],
-    );
+/// Loads the operands, computes `op` on the stack and stores the result.
+fn compile_operation(
+    compiler: &mut Compiler,
+    a: &Symbol,
+    b: &Symbol,
+    result_type: PrimitiveType,
+    op: impl FnOnce(&mut ir::Builder, Float, Float) -> Result<ExpressionRef>,
+) -> Result<Symbol> {
+    ensure_eq_type!(a, Type::PrimitiveType(PrimitiveType::Float32));
+    ensure_eq_type!(b, Type::PrimitiveType(PrimitiveType::Float32));
+
+    let result = compiler.allocate_symbol(Type::PrimitiveType(result_type));
+
+    ir::build(compiler.instructions, 0, |builder, _| {
+        let a = builder.mem_load(a.memory_addr);
+        let a = Float::new(builder, a)?;
+        let b = builder.mem_load(b.memory_addr);
+        let b = Float::new(builder, b)?;
+        let value = op(builder, a, b)?;
+        builder.mem_store(result.memory_addr, value);
+
+        Ok(vec![])
+    })?;
+
+    Ok(result)
+}
 
-    result
+/// A float32 on the IR stack, together with its exponent and mantissa.
+#[derive(Clone, Copy)]
+struct Float {
+    value: ExpressionRef,
+    exp: ExpressionRef,
+    mant: ExpressionRef,
 }
 
-pub(crate) fn ne(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+impl Float {
+    fn new(builder: &mut ir::Builder, value: ExpressionRef) -> Result<Self> {
+        let exp_mask = builder.number(EXP_MASK as u64);
+        let exp = builder.call_one("u32checked_and", &[exp_mask, value])?;
+        let exp_shift = builder.number(EXP_SHIFT as u64);
+        let exp = builder.call_one("u32checked_shr", &[exp_shift, exp])?;
+        let mant_mask = builder.number(MANT_MASK as u64);
+        let mant = builder.call_one("u32checked_and", &[mant_mask, value])?;
 
-    prepare_stack_for_arithmetic(compiler, a, b);
-    // [a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
+        Ok(Self { value, exp, mant })
+    }
 
-    add_is_zero(compiler, 0);
-    // [a_is_zero, b_is_zero, ..]
+    /// Returns 1 if the sign bit is set, 0 otherwise.
+    fn is_negative(&self, builder: &mut ir::Builder) -> Result<ExpressionRef> {
+        let shift = builder.number(31);
+        builder.call_one("u32checked_shr", &[shift, self.value])
+    }
 
-    compiler.instructions.push(Instruction::U32CheckedAnd);
+    /// x_exp^ | x_mant, the value without the sign bit.
+    fn magnitude(&self, builder: &mut ir::Builder) -> Result<ExpressionRef> {
+        let mask = builder.number((EXP_MASK | MANT_MASK) as u64);
+        builder.call_one("u32checked_and", &[mask, self.value])
+    }
 
-    add_is_nan(compiler, 4);
-    // [a_is_nan || b_is_nan, a_is_zero && b_is_zero, ..]
+    fn is_zero(&self, builder: &mut ir::Builder) -> Result<ExpressionRef> {
+        let zero = builder.number(0);
+        let exp_is_zero = builder.call_one("u32checked_eq", &[zero, self.exp])?;
+        let zero = builder.number(0);
+        let mant_is_zero = builder.call_one("u32checked_eq", &[zero, self.mant])?;
+        builder.call_one("and", &[mant_is_zero, exp_is_zero])
+    }
 
-    compiler.instructions.extend([Instruction::If {
-        condition: vec![
-            Instruction::U32CheckedOr,
-            // [a_is_nan || b_is_nan | a_is_zero && b_is_zero]
-        ],
-        then: vec![
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Push(0),
-        ],
-        else_: vec![
-            // [a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-            Instruction::U32CheckedNeq,
-            Instruction::MovDown(4),
-            Instruction::U32CheckedNeq,
-            Instruction::MovDown(2),
-            Instruction::U32CheckedNeq,
-            // [a_mant == b_mant, a_sign^ == b_sign^, a_exp == b_exp]
-            Instruction::U32CheckedOr,
-            Instruction::U32CheckedOr,
-        ],
-    }]);
+    /// x_sign^, the sign bit in place.
+    fn sign(&self, builder: &mut ir::Builder) -> Result<ExpressionRef> {
+        binary_const(builder, "u32checked_and", self.value, SIGN_MASK)
+    }
 
-    compiler.memory.write(
-        compiler.instructions,
-        result.memory_addr,
-        &[ValueSource::Stack],
-    );
+    /// Whether the exponent is all ones, i.e. the float is infinite or NaN.
+    fn is_inf(&self, builder: &mut ir::Builder) -> Result<ExpressionRef> {
+        binary_const(builder, "u32checked_eq", self.exp, EXP_MASK >> EXP_SHIFT)
+    }
 
-    result
+    fn is_nan(&self, builder: &mut ir::Builder) -> Result<ExpressionRef> {
+        let max_exp = builder.number((EXP_MASK >> EXP_SHIFT) as u64);
+        let exp_is_max = builder.call_one("u32checked_eq", &[max_exp, self.exp])?;
+        let zero = builder.number(0);
+        let mant_is_not_zero = builder.call_one("u32checked_neq", &[zero, self.mant])?;
+        builder.call_one("and", &[mant_is_not_zero, exp_is_max])
+    }
 }
 
-pub(crate) fn lt(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
-
-    prepare_stack_for_arithmetic(compiler, a, b);
-    // [a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-
-    add_nan_inf_zero(compiler);
-    // [a_is_nan || b_is_nan, a_is_inf, b_is_inf, a_is_zero, b_is_zero, a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-
-    compiler.instructions.extend([Instruction::If {
-        condition: vec![
-            Instruction::MovUp(4),
-            Instruction::MovUp(4),
-            Instruction::U32CheckedAnd,
-            Instruction::U32CheckedOr,
-            // [a_is_zero & b_is_zero | a_is_nan || b_is_nan]
-        ],
-        then: vec![
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Push(0),
-        ],
-        // [a_is_inf, b_is_inf, a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-        else_: vec![Instruction::If {
-            condition: vec![
-                // a_is_inf
-            ],
-            then: vec![
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::MovDown(3),
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Push(0),
-                Instruction::U32CheckedNeq,
-                // a_sign^ != 0
-            ],
-            else_: vec![Instruction::If {
-                condition: vec![
-                    // b_is_inf
-                ],
-                then: vec![
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    Instruction::MovDown(2),
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    Instruction::Push(0),
-                    Instruction::U32CheckedEq,
-                    // b_sign^ == 0
-                ],
-                else_: vec![
-                    // [a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-                    Instruction::U32CheckedSHL(Some(EXP_SHIFT)),
-                    Instruction::MovDown(3),
-                    // [b_exp, a_sign^, b_sign^, a_mant, a_exp^, b_mant]
-                    Instruction::U32CheckedSHL(Some(EXP_SHIFT)),
-                    Instruction::MovDown(5),
-                    // [a_sign^, b_sign^, a_mant, a_exp^, b_mant, b_exp^]
-                    Instruction::Dup(Some(0)),
-                    Instruction::Push(0),
-                    Instruction::U32CheckedNeq,
-                    Instruction::MovDown(6),
-                    // [a_sign^, b_sign^, a_mant, a_exp^, b_mant, b_exp^, a_sign^ != 0]
-                    Instruction::If {
-                        condition: vec![
-                            Instruction::U32CheckedEq,
-                            // a_sign^ == b_sign^
-                        ],
-                        then: vec![
-                            Instruction::U32CheckedOr,
-                            Instruction::MovDown(2),
-                            Instruction::U32CheckedOr,
-                            // [b_mant | b_exp^, a_mant | a_exp^, a_sign^ != 0]
-                            Instruction::U32CheckedLT,
-                            // a < b
-                            Instruction::U32CheckedXOR,
-                            // a_sign^ != 0 ^ a < b
-                        ],
-                        else_: vec![
-                            Instruction::Drop,
-                            Instruction::Drop,
-                            Instruction::Drop,
-                            Instruction::Drop,
-                        ],
-                    },
-                ],
-            }],
-        }],
-    }]);
-
-    compiler.memory.write(
-        compiler.instructions,
-        result.memory_addr,
-        &[ValueSource::Stack],
-    );
-
-    result
+fn either_is_nan(builder: &mut ir::Builder, a: Float, b: Float) -> Result<ExpressionRef> {
+    let a_is_nan = a.is_nan(builder)?;
+    let b_is_nan = b.is_nan(builder)?;
+    builder.call_one("or", &[b_is_nan, a_is_nan])
 }
 
-pub(crate) fn lte(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
-
-    prepare_stack_for_arithmetic(compiler, a, b);
-    // [a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-
-    add_nan_inf_zero(compiler);
-    // [a_is_nan || b_is_nan, a_is_inf, b_is_inf, a_is_zero, b_is_zero, a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-
-    compiler.instructions.extend([Instruction::If {
-        condition: vec![
-            // [a_is_nan || b_is_nan]
-        ],
-        then: vec![
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Push(0),
-        ],
-        // [a_is_inf, b_is_inf, a_is_zero, b_is_zero, a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-        else_: vec![Instruction::If {
-            condition: vec![
-                Instruction::Dup(Some(5)),
-                Instruction::Dup(Some(5)),
-                Instruction::U32CheckedEq,
-                // [a_exp == b_exp]
-                Instruction::Dup(Some(8)),
-                Instruction::Dup(Some(8)),
-                Instruction::U32CheckedEq,
-                Instruction::U32CheckedAnd,
-                // [a_sign^ == b_sign^ & a_exp == b_exp]
-                Instruction::Dup(Some(10)),
-                Instruction::Dup(Some(10)),
-                Instruction::U32CheckedEq,
-                Instruction::U32CheckedAnd,
-                // [a_mant == b_mant & a_sign^ == b_sign^ & a_exp == b_exp]
-            ],
-            then: vec![
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Push(1),
-            ],
-            else_: vec![Instruction::If {
-                condition: vec![
-                    // a_is_inf
-                ],
-                then: vec![
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    Instruction::MovDown(3),
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    Instruction::Push(0),
-                    Instruction::U32CheckedNeq,
-                    // a_sign^ != 0
-                ],
-                else_: vec![Instruction::If {
-                    condition: vec![
-                        // b_is_inf
-                    ],
-                    then: vec![
-                        Instruction::Drop,
-                        Instruction::Drop,
-                        Instruction::Drop,
-                        Instruction::Drop,
-                        Instruction::Drop,
-                        Instruction::MovDown(2),
-                        // [a_mant, b_mant, b_sign^]
-                        Instruction::Drop,
-                        Instruction::Drop,
-                        Instruction::Push(0),
-                        Instruction::U32CheckedEq,
-                        // b_sign^ == 0
-                    ],
-                    else_: vec![Instruction::If {
-                        // [a_is_zero, b_is_zero, a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-                        condition: vec![
-                            Instruction::U32CheckedAnd,
-                            // a_is_zero && b_is_zero
-                        ],
-                        then: vec![
-                            Instruction::Drop,
-                            Instruction::Drop,
-                            Instruction::Drop,
-                            Instruction::Drop,
-                            Instruction::Drop,
-                            Instruction::Drop,
-                            Instruction::Push(1),
-                        ],
-                        else_: vec![
-                            // [a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-                            Instruction::U32CheckedSHL(Some(EXP_SHIFT)),
-                            Instruction::MovDown(3),
-                            // [b_exp, a_sign^, b_sign^, a_mant, a_exp^, b_mant]
-                            Instruction::U32CheckedSHL(Some(EXP_SHIFT)),
-                            Instruction::MovDown(5),
-                            // [a_sign^, b_sign^, a_mant, a_exp^, b_mant, b_exp^]
-                            Instruction::Dup(Some(0)),
-                            Instruction::Push(0),
-                            Instruction::U32CheckedNeq,
-                            Instruction::MovDown(6),
-                            // [a_sign^, b_sign^, a_mant, a_exp^, b_mant, b_exp^, a_sign^ != 0]
-                            Instruction::If {
-                                condition: vec![
-                                    Instruction::U32CheckedEq,
-                                    // a_sign^ == b_sign^
-                                ],
-                                then: vec![
-                                    Instruction::U32CheckedOr,
-                                    Instruction::MovDown(2),
-                                    Instruction::U32CheckedOr,
-                                    // [b_mant | b_exp^, a_mant | a_exp^, a_sign^ != 0]
-                                    Instruction::U32CheckedLT,
-                                    // a < b
-                                    Instruction::U32CheckedXOR,
-                                    // a_sign^ != 0 ^ a < b
-                                ],
-                                else_: vec![
-                                    Instruction::Drop,
-                                    Instruction::Drop,
-                                    Instruction::Drop,
-                                    Instruction::Drop,
-                                ],
-                            },
-                        ],
-                    }],
-                }],
-            }],
-        }],
-    }]);
-
-    compiler.memory.write(
-        compiler.instructions,
-        result.memory_addr,
-        &[ValueSource::Stack],
-    );
-
-    result
+fn both_are_zero(builder: &mut ir::Builder, a: Float, b: Float) -> Result<ExpressionRef> {
+    let a_is_zero = a.is_zero(builder)?;
+    let b_is_zero = b.is_zero(builder)?;
+    builder.call_one("and", &[b_is_zero, a_is_zero])
 }
 
-pub(crate) fn gt(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+/// Returns the value computed by `then` if `condition` is true,
+/// otherwise the value computed by `otherwise`.
+fn select(
+    builder: &mut ir::Builder,
+    condition: ExpressionRef,
+    then: impl FnOnce(&mut ir::Builder) -> Result<ExpressionRef>,
+    otherwise: impl FnOnce(&mut ir::Builder) -> Result<ExpressionRef>,
+) -> Result<ExpressionRef> {
+    let [result] = builder.if_(
+        condition,
+        |builder| Ok([then(builder)?]),
+        |builder| Ok([otherwise(builder)?]),
+    )?;
+
+    Ok(result)
+}
 
-    prepare_stack_for_arithmetic(compiler, a, b);
-    // [a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
+/// a < b for floats that are not NaN and not both zero.
+/// Floats are sign-magnitude, so if the signs are equal, the magnitudes are compared,
+/// otherwise a is less than b if it's negative.
+fn less_than(builder: &mut ir::Builder, a: Float, b: Float) -> Result<ExpressionRef> {
+    let a_is_negative = a.is_negative(builder)?;
+    let b_is_negative = b.is_negative(builder)?;
+    let signs_differ = builder.call_one("u32checked_neq", &[b_is_negative, a_is_negative])?;
+
+    select(
+        builder,
+        signs_differ,
+        |_| Ok(a_is_negative),
+        |builder| {
+            let a_magnitude = a.magnitude(builder)?;
+            let b_magnitude = b.magnitude(builder)?;
+            select(
+                builder,
+                a_is_negative,
+                |builder| builder.call_one("u32checked_gt", &[b_magnitude, a_magnitude]),
+                |builder| builder.call_one("u32checked_lt", &[b_magnitude, a_magnitude]),
+            )
+        },
+    )
+}
 
-    add_nan_inf_zero(compiler);
-    // [a_is_nan || b_is_nan, a_is_inf, b_is_inf, a_is_zero, b_is_zero, a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
+/// a == b, where NaN is not equal to anything and 0 is equal to -0.
+fn equal(builder: &mut ir::Builder, a: Float, b: Float) -> Result<ExpressionRef> {
+    let either_is_nan = either_is_nan(builder, a, b)?;
+    let is_ordered = builder.call_one("not", &[either_is_nan])?;
+    let both_are_zero = both_are_zero(builder, a, b)?;
+    let same_bits = builder.call_one("u32checked_eq", &[b.value, a.value])?;
+    let is_equal = builder.call_one("or", &[same_bits, both_are_zero])?;
+    builder.call_one("and", &[is_equal, is_ordered])
+}
 
-    compiler.instructions.extend([Instruction::If {
-        condition: vec![
-            Instruction::MovUp(4),
-            Instruction::MovUp(4),
-            Instruction::U32CheckedAnd,
-            Instruction::U32CheckedOr,
-            // [a_is_zero & b_is_zero | a_is_nan || b_is_nan]
-        ],
-        then: vec![
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Push(0),
-        ],
-        // [a_is_inf, b_is_inf, a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-        else_: vec![Instruction::If {
-            condition: vec![
-                // a_is_inf
-            ],
-            then: vec![
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::MovDown(3),
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Push(0),
-                Instruction::U32CheckedEq,
-                // a_sign^ == 0
-            ],
-            else_: vec![Instruction::If {
-                condition: vec![
-                    // b_is_inf
-                ],
-                then: vec![
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    Instruction::MovDown(2),
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    Instruction::Push(0),
-                    Instruction::U32CheckedNeq,
-                    // b_sign^ != 0
-                ],
-                else_: vec![
-                    // [a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-                    Instruction::U32CheckedSHL(Some(EXP_SHIFT)),
-                    Instruction::MovDown(3),
-                    // [b_exp, a_sign^, b_sign^, a_mant, a_exp^, b_mant]
-                    Instruction::U32CheckedSHL(Some(EXP_SHIFT)),
-                    Instruction::MovDown(5),
-                    // [a_sign^, b_sign^, a_mant, a_exp^, b_mant, b_exp^]
-                    Instruction::Dup(Some(0)),
-                    Instruction::MovDown(6),
-                    // [a_sign^, b_sign^, a_mant, a_exp^, b_mant, b_exp^, a_sign^ != 0]
-                    Instruction::If {
-                        condition: vec![
-                            Instruction::U32CheckedEq,
-                            // a_sign^ == b_sign^
-                        ],
-                        then: vec![
-                            Instruction::U32CheckedOr,
-                            Instruction::MovDown(2),
-                            Instruction::U32CheckedOr,
-                            // [b_mant | b_exp^, a_mant | a_exp^, a_sign^ != 0]
-                            Instruction::U32CheckedGT,
-                            // a_mant > b_mant
-                            Instruction::Swap,
-                            Instruction::Push(0),
-                            Instruction::U32CheckedNeq,
-                            Instruction::U32CheckedXOR,
-                            // a_sign^ != 0 ^ b < a
-                        ],
-                        else_: vec![
-                            Instruction::Drop,
-                            Instruction::Drop,
-                            Instruction::Drop,
-                            Instruction::Drop,
-                            Instruction::Push(0),
-                            Instruction::U32CheckedEq,
-                        ],
+/// a < b, or a <= b if `or_equal` is set. Comparisons with NaN are always false.
+fn compare(compiler: &mut Compiler, a: &Symbol, b: &Symbol, or_equal: bool) -> Result<Symbol> {
+    compile_operation(compiler, a, b, PrimitiveType::Boolean, |builder, a, b| {
+        let either_is_nan = either_is_nan(builder, a, b)?;
+        select(
+            builder,
+            either_is_nan,
+            |builder| Ok(builder.boolean(false)),
+            |builder| {
+                let both_are_zero = both_are_zero(builder, a, b)?;
+                select(
+                    builder,
+                    both_are_zero,
+                    |builder| Ok(builder.boolean(or_equal)),
+                    |builder| {
+                        let less_than = less_than(builder, a, b)?;
+                        if !or_equal {
+                            return Ok(less_than);
+                        }
+
+                        let same_bits = builder.call_one("u32checked_eq", &[b.value, a.value])?;
+                        builder.call_one("or", &[same_bits, less_than])
                     },
-                ],
-            }],
-        }],
-    }]);
-
-    compiler.memory.write(
-        compiler.instructions,
-        result.memory_addr,
-        &[ValueSource::Stack],
-    );
-
-    result
+                )
+            },
+        )
+    })
 }
 
-pub(crate) fn gte(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+pub(crate) fn eq(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    compile_operation(compiler, a, b, PrimitiveType::Boolean, equal)
+}
 
-    prepare_stack_for_arithmetic(compiler, a, b);
-    // [a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
+pub(crate) fn ne(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    compile_operation(compiler, a, b, PrimitiveType::Boolean, |builder, a, b| {
+        let is_equal = equal(builder, a, b)?;
+        builder.call_one("not", &[is_equal])
+    })
+}
 
-    add_nan_inf_zero(compiler);
-    // [a_is_nan || b_is_nan, a_is_inf, b_is_inf, a_is_zero, b_is_zero, a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
+pub(crate) fn lt(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    compare(compiler, a, b, false)
+}
 
-    compiler.instructions.extend([Instruction::If {
-        condition: vec![
-            // [a_is_nan || b_is_nan]
-        ],
-        then: vec![
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Drop,
-            Instruction::Push(0),
-        ],
-        // [a_is_inf, b_is_inf, a_is_zero, b_is_zero, a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-        else_: vec![Instruction::If {
-            condition: vec![
-                Instruction::Dup(Some(5)),
-                Instruction::Dup(Some(5)),
-                Instruction::U32CheckedEq,
-                // [a_exp == b_exp]
-                Instruction::Dup(Some(8)),
-                Instruction::Dup(Some(8)),
-                Instruction::U32CheckedEq,
-                Instruction::U32CheckedAnd,
-                // [a_sign^ == b_sign^ & a_exp == b_exp]
-                Instruction::Dup(Some(10)),
-                Instruction::Dup(Some(10)),
-                Instruction::U32CheckedEq,
-                Instruction::U32CheckedAnd,
-                // [a_mant == b_mant & a_sign^ == b_sign^ & a_exp == b_exp]
-            ],
-            then: vec![
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Drop,
-                Instruction::Push(1),
-            ],
-            else_: vec![Instruction::If {
-                condition: vec![
-                    // a_is_inf
-                ],
-                then: vec![
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    Instruction::MovDown(3),
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    Instruction::Drop,
-                    Instruction::Push(0),
-                    Instruction::U32CheckedEq,
-                    // a_sign^ == 0
-                ],
-                else_: vec![Instruction::If {
-                    condition: vec![
-                        // b_is_inf
-                    ],
-                    then: vec![
-                        Instruction::Drop,
-                        Instruction::Drop,
-                        Instruction::Drop,
-                        Instruction::Drop,
-                        Instruction::Drop,
-                        Instruction::MovDown(2),
-                        // [a_mant, b_mant, b_sign^]
-                        Instruction::Drop,
-                        Instruction::Drop,
-                        Instruction::Push(0),
-                        Instruction::U32CheckedNeq,
-                        // b_sign^ == 0
-                    ],
-                    else_: vec![Instruction::If {
-                        // [a_is_zero, b_is_zero, a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-                        condition: vec![
-                            Instruction::U32CheckedAnd,
-                            // a_is_zero && b_is_zero
-                        ],
-                        then: vec![
-                            Instruction::Drop,
-                            Instruction::Drop,
-                            Instruction::Drop,
-                            Instruction::Drop,
-                            Instruction::Drop,
-                            Instruction::Drop,
-                            Instruction::Push(1),
-                        ],
-                        else_: vec![
-                            // [a_exp, b_exp, a_sign^, b_sign^, a_mant, b_mant]
-                            Instruction::U32CheckedSHL(Some(EXP_SHIFT)),
-                            Instruction::MovDown(3),
-                            // [b_exp, a_sign^, b_sign^, a_mant, a_exp^, b_mant]
-                            Instruction::U32CheckedSHL(Some(EXP_SHIFT)),
-                            Instruction::MovDown(5),
-                            // [a_sign^, b_sign^, a_mant, a_exp^, b_mant, b_exp^]
-                            Instruction::Dup(Some(0)),
-                            Instruction::MovDown(6),
-                            // [a_sign^, b_sign^, a_mant, a_exp^, b_mant, b_exp^, a_sign^]
-                            Instruction::If {
-                                condition: vec![
-                                    Instruction::U32CheckedEq,
-                                    // a_sign^ == b_sign^
-                                ],
-                                then: vec![
-                                    Instruction::U32CheckedOr,
-                                    Instruction::MovDown(2),
-                                    Instruction::U32CheckedOr,
-                                    // [b_mant | b_exp^, a_mant | a_exp^, a_sign^ != 0]
-                                    Instruction::U32CheckedGT,
-                                    // a > b
-                                    Instruction::Swap,
-                                    Instruction::Push(0),
-                                    Instruction::U32CheckedNeq,
-                                    Instruction::U32CheckedXOR,
-                                    // a_sign^ != 0 ^ a < b
-                                ],
-                                else_: vec![
-                                    Instruction::Drop,
-                                    Instruction::Drop,
-                                    Instruction::Drop,
-                                    Instruction::Drop,
-                                    Instruction::Push(0),
-                                    Instruction::U32CheckedEq,
-                                ],
-                            },
-                        ],
-                    }],
-                }],
-            }],
-        }],
-    }]);
+pub(crate) fn lte(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    compare(compiler, a, b, true)
+}
 
-    compiler.memory.write(
-        compiler.instructions,
-        result.memory_addr,
-        &[ValueSource::Stack],
-    );
+pub(crate) fn gt(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    compare(compiler, b, a, false)
+}
 
-    result
+pub(crate) fn gte(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    compare(compiler, b, a, true)
 }
 
 pub(crate) fn from_uint32(compiler: &mut Compiler, num: &Symbol) -> Symbol {
     assert_eq!(&num.type_, &Type::PrimitiveType(PrimitiveType::UInt32));
 
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Float32));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Float32));
 
     compiler.memory.read(
         compiler.instructions,
@@ -1753,16 +786,14 @@ pub(crate) fn from_uint32(compiler: &mut Compiler, num: &Symbol) -> Symbol {
     result
 }
 
-pub(crate) fn from_int32(compiler: &mut Compiler, num: &Symbol) -> Symbol {
+pub(crate) fn from_int32(compiler: &mut Compiler, num: &Symbol) -> Result<Symbol> {
     assert_eq!(num.type_, Type::PrimitiveType(PrimitiveType::Int32));
 
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Float32));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Float32));
 
     let unsigned_number = uint32::new(compiler, 0);
 
-    int32::decompose(compiler, num);
+    int32::decompose(compiler, num)?;
     // [sign_mask, number]
     compiler.instructions.extend([
         Instruction::Swap,
@@ -1783,7 +814,7 @@ pub(crate) fn from_int32(compiler: &mut Compiler, num: &Symbol) -> Symbol {
         // []
     ]);
 
-    result
+    Ok(result)
 }
 
 #[cfg(test)]
@@ -1818,9 +849,7 @@ mod tests {
     ];
 
     fn new(compiler: &mut Compiler, value: f32) -> Symbol {
-        let symbol = compiler
-            .memory
-            .allocate_symbol(Type::PrimitiveType(PrimitiveType::Float32));
+        let symbol = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Float32));
 
         compiler.memory.write(
             compiler.instructions,
@@ -1887,7 +916,8 @@ mod tests {
     impl BinaryOp for Mul {
         const STR: &'static str = "*";
         const RUST_FN: fn(f32, f32) -> f32 = <f32 as std::ops::Mul>::mul;
-        const VM_FN: fn(&mut Compiler, &Symbol, &Symbol) -> Symbol = super::mul;
+        const VM_FN: fn(&mut Compiler, &Symbol, &Symbol) -> Symbol =
+            |compiler, a, b| super::mul(compiler, a, b).unwrap();
     }
 
     #[derive(Clone, Copy)]
@@ -1895,7 +925,8 @@ mod tests {
     impl BinaryOp for Div {
         const STR: &'static str = "/";
         const RUST_FN: fn(f32, f32) -> f32 = <f32 as std::ops::Div>::div;
-        const VM_FN: fn(&mut Compiler, &Symbol, &Symbol) -> Symbol = super::div;
+        const VM_FN: fn(&mut Compiler, &Symbol, &Symbol) -> Symbol =
+            |compiler, a, b| super::div(compiler, a, b).unwrap();
     }
 
     #[derive(Clone, Copy)]
@@ -1903,7 +934,8 @@ mod tests {
     impl BinaryOp for Add {
         const STR: &'static str = "+";
         const RUST_FN: fn(f32, f32) -> f32 = <f32 as std::ops::Add>::add;
-        const VM_FN: fn(&mut Compiler, &Symbol, &Symbol) -> Symbol = super::add;
+        const VM_FN: fn(&mut Compiler, &Symbol, &Symbol) -> Symbol =
+            |compiler, a, b| super::add(compiler, a, b).unwrap();
     }
 
     #[derive(Clone, Copy)]
@@ -1911,7 +943,8 @@ mod tests {
     impl BinaryOp for Sub {
         const STR: &'static str = "-";
         const RUST_FN: fn(f32, f32) -> f32 = <f32 as std::ops::Sub>::sub;
-        const VM_FN: fn(&mut Compiler, &Symbol, &Symbol) -> Symbol = super::sub;
+        const VM_FN: fn(&mut Compiler, &Symbol, &Symbol) -> Symbol =
+            |compiler, a, b| super::sub(compiler, a, b).unwrap();
     }
 
     fn eq(a: f32, b: f32) -> f32 {
@@ -1927,7 +960,8 @@ mod tests {
     impl BinaryOp for Eq {
         const STR: &'static str = "==";
         const RUST_FN: fn(f32, f32) -> f32 = self::eq;
-        const VM_FN: fn(&mut Compiler, &Symbol, &Symbol) -> Symbol = super::eq;
+        const VM_FN: fn(&mut Compiler, &Symbol, &Symbol) -> Symbol =
+            |compiler, a, b| super::eq(compiler, a, b).unwrap();
 
         const INACCURATE: bool = false;
     }
@@ -1945,7 +979,8 @@ mod tests {
     impl BinaryOp for Ne {
         const STR: &'static str = "!=";
         const RUST_FN: fn(f32, f32) -> f32 = self::ne;
-        const VM_FN: fn(&mut Compiler, &Symbol, &Symbol) -> Symbol = super::ne;
+        const VM_FN: fn(&mut Compiler, &Symbol, &Symbol) -> Symbol =
+            |compiler, a, b| super::ne(compiler, a, b).unwrap();
 
         const INACCURATE: bool = false;
     }
@@ -1963,7 +998,8 @@ mod tests {
     impl BinaryOp for Lt {
         const STR: &'static str = "<";
         const RUST_FN: fn(f32, f32) -> f32 = self::lt;
-        const VM_FN: fn(&mut Compiler, &Symbol, &Symbol) -> Symbol = super::lt;
+        const VM_FN: fn(&mut Compiler, &Symbol, &Symbol) -> Symbol =
+            |compiler, a, b| super::lt(compiler, a, b).unwrap();
 
         const INACCURATE: bool = false;
     }
@@ -1981,7 +1017,8 @@ mod tests {
     impl BinaryOp for Lte {
         const STR: &'static str = "<=";
         const RUST_FN: fn(f32, f32) -> f32 = self::lte;
-        const VM_FN: fn(&mut Compiler, &Symbol, &Symbol) -> Symbol = super::lte;
+        const VM_FN: fn(&mut Compiler, &Symbol, &Symbol) -> Symbol =
+            |compiler, a, b| super::lte(compiler, a, b).unwrap();
 
         const INACCURATE: bool = false;
     }
@@ -1999,7 +1036,8 @@ mod tests {
     impl BinaryOp for Gt {
         const STR: &'static str = ">";
         const RUST_FN: fn(f32, f32) -> f32 = self::gt;
-        const VM_FN: fn(&mut Compiler, &Symbol, &Symbol) -> Symbol = super::gt;
+        const VM_FN: fn(&mut Compiler, &Symbol, &Symbol) -> Symbol =
+            |compiler, a, b| super::gt(compiler, a, b).unwrap();
 
         const INACCURATE: bool = false;
     }
@@ -2017,7 +1055,8 @@ mod tests {
     impl BinaryOp for Gte {
         const STR: &'static str = ">=";
         const RUST_FN: fn(f32, f32) -> f32 = self::gte;
-        const VM_FN: fn(&mut Compiler, &Symbol, &Symbol) -> Symbol = super::gte;
+        const VM_FN: fn(&mut Compiler, &Symbol, &Symbol) -> Symbol =
+            |compiler, a, b| super::gte(compiler, a, b).unwrap();
 
         const INACCURATE: bool = false;
     }
@@ -2058,6 +1097,56 @@ mod tests {
             .for_each(|x| assert_bin_op(x[0], x[1], bin_op));
     }
 
+    /// Asserts that the result has the same bits as Rust's, so the sign of zeros must match too.
+    fn assert_bin_op_bits<T: BinaryOp>(a: f32, b: f32, _bin_op: T) {
+        let expected = T::RUST_FN(a, b);
+        let result = helper_bin_op(a, b, T::VM_FN).unwrap();
+        assert_eq!(
+            result.to_bits(),
+            expected.to_bits(),
+            "{a} {} {b}, result: {result}, expected: {expected}",
+            T::STR
+        );
+    }
+
+    #[test_case(Eq; "eq")]
+    #[test_case(Ne; "ne")]
+    #[test_case(Lt; "lt")]
+    #[test_case(Lte; "lte")]
+    #[test_case(Gt; "gt")]
+    #[test_case(Gte; "gte")]
+    fn test_negative_zero_equals_zero(bin_op: impl BinaryOp) {
+        assert_bin_op_bits(-0.0, 0.0, bin_op);
+        assert_bin_op_bits(0.0, -0.0, bin_op);
+        assert_bin_op_bits(-0.0, -0.0, bin_op);
+    }
+
+    #[test_case(Eq; "eq")]
+    #[test_case(Ne; "ne")]
+    #[test_case(Lt; "lt")]
+    #[test_case(Lte; "lte")]
+    #[test_case(Gt; "gt")]
+    #[test_case(Gte; "gte")]
+    fn test_nan_comparisons(bin_op: impl BinaryOp) {
+        for x in [f32::NAN, 0.0, 1.0, f32::INFINITY, f32::NEG_INFINITY] {
+            assert_bin_op_bits(f32::NAN, x, bin_op);
+            assert_bin_op_bits(x, f32::NAN, bin_op);
+        }
+    }
+
+    #[test_case(Mul; "mul")]
+    #[test_case(Div; "div")]
+    #[test_case(Add; "add")]
+    #[test_case(Sub; "sub")]
+    fn test_sign_of_zero_results<T: BinaryOp>(bin_op: T) {
+        let operands = [0.0, -0.0, 1.0, -1.0, f32::INFINITY, f32::NEG_INFINITY];
+        for (a, b) in operands.into_iter().cartesian_product(operands) {
+            if !T::RUST_FN(a, b).is_nan() {
+                assert_bin_op_bits(a, b, bin_op);
+            }
+        }
+    }
+
     #[quickcheck]
     fn test_mul(a: f32, b: f32) {
         assert_bin_op(a, b, Mul)
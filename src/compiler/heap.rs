@@ -0,0 +1,104 @@
+//! Heap for dynamically sized data, such as arrays that grow.
+//!
+//! Every block is preceded by its size: [size, data...], pointers point to the data.
+//! Address 3 is the top of the heap and address 8 is where the heap starts.
+//!
+//! Blocks are never freed, because arrays are copied by copying their pointer,
+//! such as in `let b = this.arr;`, so the old block of an array that grew
+//! can still be used by its copies. The block at the top of the heap grows in place,
+//! and other blocks are reallocated with at least double the capacity,
+//! so the heap grows linearly with the length of the arrays.
+//!
+//! Freeing blocks, with a free list to reuse them, needs to know when the last copy
+//! of an array is gone. The compiler doesn't track copies, so there is no `free`.
+
+use super::{encoder::Instruction, *};
+
+const HEAP_TOP: u32 = 3;
+const HEAP_START: u32 = 8;
+
+/// Allocates `size` words at the top of the heap.
+pub(crate) fn alloc(compiler: &mut Compiler, size: &Symbol) -> Result<Symbol> {
+    ensure_eq_type!(size, Type::PrimitiveType(PrimitiveType::UInt32));
+
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+
+    compiler.instructions.extend([
+        Instruction::MemLoad(Some(HEAP_TOP)),
+        // [top]
+        Instruction::Dup(None),
+        // [top, top]
+        Instruction::MemLoad(Some(size.memory_addr)),
+        // [size, top, top]
+        Instruction::Swap,
+        // [top, size, top]
+        Instruction::MemStore(None),
+        // [top]
+        Instruction::Push(1),
+        Instruction::U32CheckedAdd,
+        // [data = top + 1]
+        Instruction::Dup(None),
+        Instruction::MemStore(Some(result.memory_addr)),
+        // [data]
+        Instruction::MemLoad(Some(size.memory_addr)),
+        Instruction::U32CheckedAdd,
+        // [data + size]
+        Instruction::MemStore(Some(HEAP_TOP)),
+        // []
+    ]);
+
+    Ok(result)
+}
+
+/// Grows the block at `ptr` to `new_size` words if it is at the top of the heap.
+///
+/// Returns a boolean symbol, false if the block has to be reallocated instead.
+pub(crate) fn extend(compiler: &mut Compiler, ptr: &Symbol, new_size: &Symbol) -> Result<Symbol> {
+    ensure_eq_type!(ptr, Type::PrimitiveType(PrimitiveType::UInt32));
+    ensure_eq_type!(new_size, Type::PrimitiveType(PrimitiveType::UInt32));
+
+    let extended = boolean::new(compiler, false);
+
+    compiler.instructions.push(Instruction::If {
+        condition: vec![
+            Instruction::MemLoad(Some(ptr.memory_addr)),
+            Instruction::MemLoad(Some(HEAP_START)),
+            Instruction::U32CheckedGTE,
+            // [ptr >= heap_start]
+        ],
+        then: vec![Instruction::If {
+            condition: vec![
+                Instruction::MemLoad(Some(ptr.memory_addr)),
+                Instruction::Push(1),
+                Instruction::U32CheckedSub,
+                Instruction::MemLoad(None),
+                // [size]
+                Instruction::MemLoad(Some(ptr.memory_addr)),
+                Instruction::U32CheckedAdd,
+                // [ptr + size]
+                Instruction::MemLoad(Some(HEAP_TOP)),
+                Instruction::U32CheckedEq,
+                // [ptr + size == top]
+            ],
+            then: vec![
+                Instruction::MemLoad(Some(new_size.memory_addr)),
+                Instruction::MemLoad(Some(ptr.memory_addr)),
+                Instruction::Push(1),
+                Instruction::U32CheckedSub,
+                // [ptr - 1, new_size]
+                Instruction::MemStore(None),
+                // []
+                Instruction::MemLoad(Some(ptr.memory_addr)),
+                Instruction::MemLoad(Some(new_size.memory_addr)),
+                Instruction::U32CheckedAdd,
+                Instruction::MemStore(Some(HEAP_TOP)),
+                Instruction::Push(1),
+                Instruction::MemStore(Some(extended.memory_addr)),
+            ],
+            else_: vec![],
+        }],
+        else_: vec![],
+    });
+
+    Ok(extended)
+}
@@ -1,12 +1,9 @@
 use super::*;
 
-// TODO: optimize the instructions for int32 artihmetic operations
+use ir::ExpressionRef;
 
-#[allow(dead_code)]
 pub(crate) fn new(compiler: &mut Compiler, value: i32) -> Symbol {
-    let symbol = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Int32));
+    let symbol = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Int32));
 
     // memory is zero-initialized, so we don't need to write for 0
     if value != 0 {
@@ -21,830 +18,286 @@ pub(crate) fn new(compiler: &mut Compiler, value: i32) -> Symbol {
 }
 
 // Stack output: [sign_mask, unsigned_result]
-pub(crate) fn decompose(compiler: &mut Compiler, n: &Symbol) {
+pub(crate) fn decompose(compiler: &mut Compiler, n: &Symbol) -> Result<()> {
     assert_eq!(n.type_, Type::PrimitiveType(PrimitiveType::Int32));
 
-    compiler
-        .memory
-        .read(compiler.instructions, n.memory_addr, n.type_.miden_width());
-    // [n]
-    compiler.instructions.push(encoder::Instruction::Dup(None));
-    // [n, n]
-    abs_stack(compiler);
-    // [abs(n), n]
-    compiler.instructions.push(encoder::Instruction::Swap);
-    // [n, abs(n)]
-    compiler
-        .instructions
-        .push(encoder::Instruction::Push(0x8000_0000));
-    // [0x8000_0000, n, abs(n)]
-    compiler
-        .instructions
-        .push(encoder::Instruction::U32CheckedAnd);
-    // [sign = n & 0x8000_0000, abs(n)]
+    ir::build(compiler.instructions, 0, |builder, _| {
+        let n = builder.mem_load(n.memory_addr);
+        let abs = abs(builder, n)?;
+        let sign_mask = builder.number(0x8000_0000);
+        let sign = builder.call_one("u32checked_and", &[sign_mask, n])?;
+
+        Ok(vec![sign, abs])
+    })
+}
+
+/// Loads the operands, computes `op` on the stack and stores the result.
+fn compile_op(
+    compiler: &mut Compiler,
+    a: &Symbol,
+    b: &Symbol,
+    result_type: PrimitiveType,
+    op: impl FnOnce(&mut ir::Builder, ExpressionRef, ExpressionRef) -> Result<ExpressionRef>,
+) -> Result<Symbol> {
+    ensure_eq_type!(a, Type::PrimitiveType(PrimitiveType::Int32));
+    ensure_eq_type!(b, Type::PrimitiveType(PrimitiveType::Int32));
+
+    let result = compiler.allocate_symbol(Type::PrimitiveType(result_type));
+
+    ir::build(compiler.instructions, 0, |builder, _| {
+        let a = builder.mem_load(a.memory_addr);
+        let b = builder.mem_load(b.memory_addr);
+        let value = op(builder, a, b)?;
+        builder.mem_store(result.memory_addr, value);
+
+        Ok(vec![])
+    })?;
+
+    Ok(result)
 }
 
-/// Extracts signs for both operands.
-/// Stack will look like this: [b, a, b_sign, a_sign]
-fn prepare_stack_for_arithmetic(compiler: &mut Compiler, a: &Symbol, b: &Symbol) {
-    compiler
-        .memory
-        .read(compiler.instructions, a.memory_addr, a.type_.miden_width());
-    // [a]
-    compiler.instructions.push(encoder::Instruction::Dup(None));
-    // [a, a]
-    compiler
-        .instructions
-        .push(encoder::Instruction::U32CheckedSHR(Some(31)));
-    // [a_sign, a]
-    compiler
-        .memory
-        .read(compiler.instructions, b.memory_addr, b.type_.miden_width());
-    // [b, a_sign, a]
-    compiler.instructions.push(encoder::Instruction::Dup(None));
-    // [b, b, a_sign, a]
-    compiler
-        .instructions
-        .push(encoder::Instruction::U32CheckedSHR(Some(31)));
-    // [b_sign, b, a_sign, a]
-    compiler.instructions.push(encoder::Instruction::MovUp(3));
-    // [a, b_sign, b, a_sign]
-    compiler.instructions.push(encoder::Instruction::MovUp(2));
-    // [b, a, b_sign, a_sign]
+/// Returns 1 if the value is negative, 0 otherwise.
+fn sign(builder: &mut ir::Builder, value: ExpressionRef) -> Result<ExpressionRef> {
+    let shift = builder.number(31);
+    builder.call_one("u32checked_shr", &[shift, value])
 }
 
-/// abs_stack returns (on the stack) the absolute value of the value at the top of the stack.
-fn abs_stack(compiler: &mut Compiler) {
-    // current stack: [value]
-    compiler.instructions.extend([
-        encoder::Instruction::Dup(None),
-        // [value, value]
-        encoder::Instruction::Push(i32::MIN as u32),
-        // [-2147483648, value, value]
-        encoder::Instruction::U32CheckedEq,
-        // [value == -2147483648, value]
-        encoder::Instruction::AssertZero,
-        // [value]
-        encoder::Instruction::If {
-            condition: vec![
-                encoder::Instruction::Dup(None),
-                // [value, value]
-                encoder::Instruction::U32CheckedSHR(Some(31)),
-                // [sign, value]
-            ],
-            then: vec![
-                // [value]
-                encoder::Instruction::U32CheckedNot,
-                // [~value]
-                encoder::Instruction::Push(1),
-                // [1, ~value]
-                encoder::Instruction::U32CheckedAdd,
-                // [~value + 1]
-            ],
-            else_: vec![],
+/// Returns the absolute value, fails for i32::MIN.
+fn abs(builder: &mut ir::Builder, value: ExpressionRef) -> Result<ExpressionRef> {
+    let min = builder.number(i32::MIN as u32 as u64);
+    let is_min = builder.call_one("u32checked_eq", &[min, value])?;
+    builder.call("assertz", &[is_min])?;
+
+    let is_negative = sign(builder, value)?;
+    let [abs] = builder.if_(
+        is_negative,
+        |builder| {
+            let not = builder.call_one("u32checked_not", &[value])?;
+            let one = builder.number(1);
+            Ok([builder.call_one("u32checked_add", &[one, not])?])
         },
-    ]);
-    // [abs(value)]
+        |_| Ok([value]),
+    )?;
+
+    Ok(abs)
+}
+
+/// Returns -value, fails for i32::MIN.
+fn negate(builder: &mut ir::Builder, value: ExpressionRef) -> Result<ExpressionRef> {
+    let not = builder.call_one("u32checked_not", &[value])?;
+    let one = builder.number(1);
+    add_values(builder, not, one)
 }
 
-fn negate_stack(compiler: &mut Compiler) {
-    // current stack: [value]
-    compiler
-        .instructions
-        .push(encoder::Instruction::Push(-1i32 as u32));
-    // [4294967295, value]
-    compiler.instructions.push(encoder::Instruction::Swap);
-    // [value, 4294967295]
-    compiler
-        .instructions
-        .push(encoder::Instruction::U32WrappingSub);
-    // [value - 4294967295]
-    compiler.instructions.push(encoder::Instruction::Push(1u32));
-    // [1, value - 4294967295]
-    add_stack(compiler);
-    // [value - 4294967295 + 1]
+/// Negates the value if `condition` is true.
+fn negate_if(
+    builder: &mut ir::Builder,
+    condition: ExpressionRef,
+    value: ExpressionRef,
+) -> Result<ExpressionRef> {
+    let [result] = builder.if_(
+        condition,
+        |builder| Ok([negate(builder, value)?]),
+        |_| Ok([value]),
+    )?;
+
+    Ok(result)
 }
 
-fn add_stack(compiler: &mut Compiler) {
-    // current stack: [b, a]
-    compiler
-        .instructions
-        .push(encoder::Instruction::Dup(Some(1)));
-    // current stack: [a, b, a]
-    compiler
-        .instructions
-        .push(encoder::Instruction::U32CheckedSHR(Some(31)));
-    // current stack: [sign_a, b, a]
-    compiler.instructions.push(encoder::Instruction::Dup(None));
-    // current stack: [sign_a, sign_a, b, a]
-    compiler.instructions.push(encoder::Instruction::MovUp(2));
-    // [b, sign_a, sign_a, a]
-    compiler.instructions.push(encoder::Instruction::Dup(None));
-    // [b, b, sign_a, sign_a, a]
-    compiler
-        .instructions
-        .push(encoder::Instruction::U32CheckedSHR(Some(31)));
-    // [sign_b, b, sign_a, sign_a, a]
-    compiler.instructions.push(encoder::Instruction::MovUp(2));
-    // [sign_a, sign_b, b, sign_a, a]
-    compiler.instructions.push(encoder::Instruction::If {
-        condition: vec![encoder::Instruction::U32CheckedEq],
-        then: vec![
-            // the result needs to be the same sign as a
-            // current stack: [b, sign_a, a]
-            encoder::Instruction::MovUp(2),
-            // [a, b, sign_a]
-            encoder::Instruction::U32WrappingAdd,
-            // [result, sign_a]
-            encoder::Instruction::Dup(None),
-            // [result, result, sign_a]
-            encoder::Instruction::U32CheckedSHR(Some(31)),
-            // [sign_result, result, sign_a]
-            encoder::Instruction::MovUp(2),
-            // [sign_a, sign_result, result]
-            encoder::Instruction::U32CheckedEq,
-            // [sign_a == sign_result, result]
-            encoder::Instruction::Assert,
-            // [result]
-        ],
-        else_: vec![
-            // we're adding values of different signs, overflow is impossible
-            // current stack: [b, sign_a, a]
-            encoder::Instruction::Swap,
-            // [sign_a, b, a]
-            encoder::Instruction::Drop,
-            // [b, a]
-            encoder::Instruction::U32WrappingAdd,
-            // [result]
-        ],
-    });
+// Overflow happens if the result has a different sign than both of the operands.
+fn add_values(
+    builder: &mut ir::Builder,
+    a: ExpressionRef,
+    b: ExpressionRef,
+) -> Result<ExpressionRef> {
+    let result = builder.call_one("u32wrapping_add", &[b, a])?;
+
+    let a_changed = builder.call_one("u32checked_xor", &[result, a])?;
+    let b_changed = builder.call_one("u32checked_xor", &[result, b])?;
+    let both_changed = builder.call_one("u32checked_and", &[b_changed, a_changed])?;
+    let overflow = sign(builder, both_changed)?;
+    builder.call("assertz", &[overflow])?;
+
+    Ok(result)
 }
 
 /// adds two int32s with overflow checking.
-// If a and b are the same sign, then the result must be the same sign, otherwise we have an overflow.
-pub(crate) fn add(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Int32));
-
-    compiler
-        .memory
-        .read(compiler.instructions, a.memory_addr, a.type_.miden_width());
-    // [a]
-    compiler
-        .memory
-        .read(compiler.instructions, b.memory_addr, b.type_.miden_width());
-    // [b, a]
-
-    add_stack(compiler);
-
-    compiler.memory.write(
-        compiler.instructions,
-        result.memory_addr,
-        &[ValueSource::Stack],
-    );
-
-    result
+pub(crate) fn add(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    compile_op(compiler, a, b, PrimitiveType::Int32, add_values)
 }
 
 /// subtracts two int32s with overflow checking.
-// If a and b are of different signs, then the result can't be the same sign as b, otherwise we have an overflow.
-pub(crate) fn sub(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Int32));
-
-    prepare_stack_for_arithmetic(compiler, a, b);
-    // current stack: [b, a, b_sign, a_sign]
-    compiler.instructions.extend([
-        encoder::Instruction::U32WrappingSub,
-        // [result, b_sign, a_sign]
-        encoder::Instruction::Dup(None),
-        // [result, result, b_sign, a_sign]
-        encoder::Instruction::U32CheckedSHR(Some(31)),
-        // [result_sign, result, b_sign, a_sign]
-        encoder::Instruction::Dup(Some(3)),
-        // [b_sign, result_sign, result, b_sign, a_sign]
-        encoder::Instruction::MovUp(4),
-        // [a_sign, b_sign, b_sign, result_sign, result, b_sign]
-        encoder::Instruction::MovUp(4),
-        // [b_sign, a_sign, b_sign, result_sign, result]
-        encoder::Instruction::If {
-            condition: vec![
-                encoder::Instruction::U32CheckedEq,
-                encoder::Instruction::Not,
-            ],
-            // [b_sign, result_sign, result]
-            then: vec![
-                encoder::Instruction::U32CheckedEq,
-                // [b_sign == result_sign, result]
-                encoder::Instruction::Assert,
-            ],
-            else_: vec![
-                // [b_sign, result_sign, result]
-                encoder::Instruction::Drop,
-                // [result_sign, result]
-                encoder::Instruction::Drop,
-                // [result]
-            ],
-        },
-    ]);
-
-    compiler.memory.write(
-        compiler.instructions,
-        result.memory_addr,
-        &[ValueSource::Stack],
-    );
-
-    result
+// If a and b are of different signs, then the result can't have a different sign than a, otherwise we have an overflow.
+pub(crate) fn sub(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    compile_op(compiler, a, b, PrimitiveType::Int32, |builder, a, b| {
+        let result = builder.call_one("u32wrapping_sub", &[b, a])?;
+
+        let signs_differ = builder.call_one("u32checked_xor", &[b, a])?;
+        let result_changed = builder.call_one("u32checked_xor", &[result, a])?;
+        let both = builder.call_one("u32checked_and", &[result_changed, signs_differ])?;
+        let overflow = sign(builder, both)?;
+        builder.call("assertz", &[overflow])?;
+
+        Ok(result)
+    })
 }
 
 /// multiplies two int32s with overflow checking.
 // The sign of the result must be (sign_a + sign_b) % 2,
 // and if neither of the operands is 0, then the result can't be 0.
 // We basically do u32CheckedMul(abs(a), abs(b)) and negate if the sign should be negative.
-// TODO: a lot of opportunities for optimization here.
-pub(crate) fn mul(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Int32));
-
-    prepare_stack_for_arithmetic(compiler, a, b);
-    // current stack: [b, a, b_sign, a_sign]
-
-    let if_zero = vec![
-        encoder::Instruction::Drop,
-        encoder::Instruction::Drop,
-        encoder::Instruction::Drop,
-        encoder::Instruction::Drop,
-        encoder::Instruction::Push(0),
-        // [0]
-    ];
-    let if_not_zero = {
-        let mut instructions = Vec::new();
-        let mut compiler = Compiler::new(&mut instructions, compiler.memory, compiler.root_scope);
-        // [b, a, b_sign, a_sign]
-        abs_stack(&mut compiler);
-        // [abs(b), a, b_sign, a_sign]
-        compiler.instructions.push(encoder::Instruction::Swap);
-        // [a, abs(b), b_sign, a_sign]
-        abs_stack(&mut compiler);
-        // [abs(a), abs(b), b_sign, a_sign]
-        compiler.instructions.push(encoder::Instruction::Swap);
-        // [abs(b), abs(a), b_sign, a_sign]
-        compiler
-            .instructions
-            .push(encoder::Instruction::U32CheckedMul);
-        // [result, b_sign, a_sign]
-        compiler.instructions.push(encoder::Instruction::Dup(None));
-        // [result, result, b_sign, a_sign]
-        compiler
-            .instructions
-            .push(encoder::Instruction::U32CheckedSHR(Some(31)));
-        // [result_bit, result, b_sign, a_sign]
-        compiler.instructions.push(encoder::Instruction::AssertZero);
-        // [result, b_sign, a_sign]
-
-        let if_expected_negative = {
-            let mut instructions = vec![];
-            let mut compiler =
-                Compiler::new(&mut instructions, compiler.memory, compiler.root_scope);
-            // [result]
-            negate_stack(&mut compiler);
-            // [negate(result)]
-
-            instructions
-        };
-
-        compiler.instructions.push(encoder::Instruction::If {
-            condition: vec![
-                encoder::Instruction::MovDown(2),
-                // [b_sign, a_sign, result]
-                encoder::Instruction::U32CheckedAdd,
-                // [b_sign + a_sign, result]
-                encoder::Instruction::Push(1),
-                encoder::Instruction::U32CheckedEq,
-            ],
-            // [result]
-            then: if_expected_negative,
-            else_: vec![
-                // do nothing, return the result as is
-            ],
-        });
-
-        instructions
-    };
-
-    compiler.instructions.extend([
-        encoder::Instruction::Dup(Some(1)),
-        // [a, b, a, b_sign, a_sign]
-        encoder::Instruction::Push(0),
-        // [0, a, b, a, b_sign, a_sign]
-        encoder::Instruction::U32CheckedEq,
-        // [a == 0, b, a, b_sign, a_sign]
-        encoder::Instruction::Dup(Some(1)),
-        // [b, a == 0, b, a, b_sign, a_sign]
-        encoder::Instruction::Push(0),
-        // [0, b, a == 0, b, a, b_sign, a_sign]
-        encoder::Instruction::U32CheckedEq,
-        // [b == 0, a == 0, b, a, b_sign, a_sign]
-        encoder::Instruction::If {
-            condition: vec![
-                encoder::Instruction::Or,
-                // [a == 0 || b == 0, b, a, b_sign, a_sign]
-            ],
-            //  [b, a, b_sign, a_sign]
-            then: if_zero,
-            else_: if_not_zero,
-        },
-    ]);
-
-    compiler.memory.write(
-        compiler.instructions,
-        result.memory_addr,
-        &[ValueSource::Stack],
-    );
+pub(crate) fn mul(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    compile_op(compiler, a, b, PrimitiveType::Int32, |builder, a, b| {
+        let zero = builder.number(0);
+        let a_is_zero = builder.call_one("u32checked_eq", &[zero, a])?;
+        let b_is_zero = builder.call_one("u32checked_eq", &[zero, b])?;
+        let either_is_zero = builder.call_one("or", &[b_is_zero, a_is_zero])?;
+
+        let [result] = builder.if_(
+            either_is_zero,
+            |builder| Ok([builder.number(0)]),
+            |builder| {
+                let abs_a = abs(builder, a)?;
+                let abs_b = abs(builder, b)?;
+                let result = builder.call_one("u32checked_mul", &[abs_b, abs_a])?;
+                let result_sign = sign(builder, result)?;
+                builder.call("assertz", &[result_sign])?;
+
+                let a_sign = sign(builder, a)?;
+                let b_sign = sign(builder, b)?;
+                let is_negative = builder.call_one("u32checked_neq", &[b_sign, a_sign])?;
+                Ok([negate_if(builder, is_negative, result)?])
+            },
+        )?;
 
-    result
+        Ok(result)
+    })
 }
 
 /// divides two int32s with overflow checking.
 // First overflow check: b == 0
 // Second overflow check: a == i32::MIN && b == -1
-pub(crate) fn div(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Int32));
-
-    prepare_stack_for_arithmetic(compiler, a, b);
-    // current stack: [b, a, b_sign, a_sign]
-
-    // fail if b == 0
-    compiler.instructions.push(encoder::Instruction::Dup(None));
-    // [b, b, a, b_sign, a_sign]
-    compiler.instructions.push(encoder::Instruction::Push(0));
-    // [0, b, b, a, b_sign, a_sign]
-    compiler
-        .instructions
-        .push(encoder::Instruction::U32CheckedEq);
-    // [b == 0, b, a, b_sign, a_sign]
-    compiler.instructions.push(encoder::Instruction::AssertZero);
-    // [b, a, b_sign, a_sign]
-
-    // fail if a == i32::MIN && b == -1
-    compiler.instructions.push(encoder::Instruction::Dup(None));
-    // [b, b, a, b_sign, a_sign]
-    compiler
-        .instructions
-        .push(encoder::Instruction::Push(-1i32 as u32));
-    // [-1, b, b, a, b_sign, a_sign]
-    compiler
-        .instructions
-        .push(encoder::Instruction::U32CheckedEq);
-    // [b == -1, b, a, b_sign, a_sign]
-    compiler
-        .instructions
-        .push(encoder::Instruction::Dup(Some(2)));
-    // [a, b == -1, b, a, b_sign, a_sign]
-    compiler
-        .instructions
-        .push(encoder::Instruction::Push(i32::MIN as u32));
-    // [i32::MIN, a, b == -1, b, a, b_sign, a_sign]
-    compiler
-        .instructions
-        .push(encoder::Instruction::U32CheckedEq);
-    // [a == i32::MIN, b == -1, b, a, b_sign, a_sign]
-    compiler.instructions.push(encoder::Instruction::And);
-    // [a == i32::MIN && b == -1, b, a, b_sign, a_sign]
-    compiler.instructions.push(encoder::Instruction::AssertZero);
-
-    // [b, a, b_sign, a_sign]
-    abs_stack(compiler);
-    // [abs(b), a, b_sign, a_sign]
-    compiler.instructions.push(encoder::Instruction::Swap);
-    // [a, abs(b), b_sign, a_sign]
-    abs_stack(compiler);
-    // [abs(a), abs(b), b_sign, a_sign]
-    compiler.instructions.push(encoder::Instruction::Swap);
-    // [abs(b), abs(a), b_sign, a_sign]
-    compiler
-        .instructions
-        .push(encoder::Instruction::U32CheckedDiv(None));
-    // [result, b_sign, a_sign]
-
-    let negation = {
-        let mut instructions = Vec::new();
-        let mut compiler = Compiler::new(&mut instructions, compiler.memory, compiler.root_scope);
-
-        // [result]
-        negate_stack(&mut compiler);
-        // [negate(result)]
-
-        instructions
-    };
-
-    // negate result if signA + signB == 1
-    compiler.instructions.push(encoder::Instruction::If {
-        condition: vec![
-            encoder::Instruction::MovDown(2),
-            // [b_sign, a_sign, result]
-            encoder::Instruction::U32CheckedAdd,
-            // [b_sign + a_sign, result]
-            encoder::Instruction::Push(1),
-            // [1, b_sign + a_sign, result]
-            encoder::Instruction::U32CheckedEq,
-            // [b_sign + a_sign == 1, result]
-        ],
-        // [result]
-        then: negation,
-        else_: vec![
-            // do nothing, return the result as is
-        ],
-    });
-
-    compiler.memory.write(
-        compiler.instructions,
-        result.memory_addr,
-        &[ValueSource::Stack],
-    );
-
-    result
+pub(crate) fn div(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    compile_op(compiler, a, b, PrimitiveType::Int32, |builder, a, b| {
+        assert_no_division_overflow(builder, a, b)?;
+
+        let abs_a = abs(builder, a)?;
+        let abs_b = abs(builder, b)?;
+        let result = builder.call_one("u32checked_div", &[abs_b, abs_a])?;
+
+        let a_sign = sign(builder, a)?;
+        let b_sign = sign(builder, b)?;
+        let is_negative = builder.call_one("u32checked_neq", &[b_sign, a_sign])?;
+        negate_if(builder, is_negative, result)
+    })
 }
 
 /// calculates the modulo of two int32s with overflow checking.
 // First overflow check: b == 0
 // Second overflow check: a == i32::MIN && b == -1
-pub(crate) fn modulo(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Int32));
-
-    compiler
-        .memory
-        .read(compiler.instructions, a.memory_addr, a.type_.miden_width());
-    // [a]
-    compiler
-        .memory
-        .read(compiler.instructions, b.memory_addr, b.type_.miden_width());
-    // [b, a]
-
-    compiler.instructions.push(encoder::Instruction::Dup(None));
-    // [b, b, a]
-    compiler.instructions.push(encoder::Instruction::Push(0));
-    // [0, b, b, a]
-    compiler
-        .instructions
-        .push(encoder::Instruction::U32CheckedEq);
-    // [b == 0, b, a]
-    compiler.instructions.push(encoder::Instruction::Not);
-    // [b != 0, b, a]
-    compiler.instructions.push(encoder::Instruction::Assert);
-    // [b, a]
-    // fails on a % 0
-
-    // assert(a != min() || b != negate(1), 'modInt32 overflow, dividing min by -1');
-    compiler.instructions.push(encoder::Instruction::Dup(None));
-    // [b, b, a]
-    compiler
-        .instructions
-        .push(encoder::Instruction::Push(-1i32 as u32));
-    // [-1, b, b, a]
-    compiler
-        .instructions
-        .push(encoder::Instruction::U32CheckedEq);
-    // [b == -1, b, a]
-    compiler.instructions.push(encoder::Instruction::Not);
-    // [b != -1, b, a]
-    compiler
-        .instructions
-        .push(encoder::Instruction::Dup(Some(2)));
-    // [a, b != -1, b, a]
-    compiler
-        .instructions
-        .push(encoder::Instruction::Push(i32::MIN as u32));
-    // [i32::MIN, a, b != -1, b, a]
-    compiler
-        .instructions
-        .push(encoder::Instruction::U32CheckedEq);
-    // [a == i32::MIN, b != -1, b, a]
-    compiler.instructions.push(encoder::Instruction::Not);
-    // [a != i32::MIN, b != -1, b, a]
-    compiler.instructions.push(encoder::Instruction::Or);
-    // [a != i32::MIN || b != -1, b, a]
-    compiler.instructions.push(encoder::Instruction::Assert);
-    // [b, a]
-    // fails on i32::MIN by -1
-
-    compiler
-        .instructions
-        .push(encoder::Instruction::Dup(Some(1)));
-    // [a, b, a]
-    compiler
-        .instructions
-        .push(encoder::Instruction::U32CheckedSHR(Some(31)));
-    // [a_sign, b, a]
-    compiler.instructions.push(encoder::Instruction::MovDown(2));
-    // [b, a, a_sign]
-
-    abs_stack(compiler);
-    // [abs(b), a, a_sign]
-    compiler.instructions.push(encoder::Instruction::Swap);
-    // [a, abs(b), a_sign]
-    abs_stack(compiler);
-    // [abs(a), abs(b), a_sign]
-    compiler.instructions.push(encoder::Instruction::Swap);
-    // [abs(b), abs(a), a_sign]
-    compiler
-        .instructions
-        .push(encoder::Instruction::U32CheckedMod(None));
-    // [abs(a) % abs(b), a_sign]
-
-    compiler.instructions.push(encoder::Instruction::Swap);
-    // [a_sign, abs(a) % abs(b)]
-
-    let negation = {
-        let mut instructions = Vec::new();
-        let mut compiler = Compiler::new(&mut instructions, compiler.memory, compiler.root_scope);
-
-        // [result]
-        negate_stack(&mut compiler);
-        // [negate(result)]
-
-        instructions
-    };
-
-    compiler.instructions.push(encoder::Instruction::If {
-        // if a_sign == 1
-        condition: vec![],
-        // [abs(a) % abs(b)]
-        then: negation,
-        else_: vec![
-            // do nothing, return the result as is
-        ],
-    });
-
-    compiler.memory.write(
-        compiler.instructions,
-        result.memory_addr,
-        &[ValueSource::Stack],
-    );
-
-    result
+pub(crate) fn modulo(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    compile_op(compiler, a, b, PrimitiveType::Int32, |builder, a, b| {
+        assert_no_division_overflow(builder, a, b)?;
+
+        let abs_a = abs(builder, a)?;
+        let abs_b = abs(builder, b)?;
+        let result = builder.call_one("u32checked_mod", &[abs_b, abs_a])?;
+
+        // the result has the sign of a
+        let a_sign = sign(builder, a)?;
+        negate_if(builder, a_sign, result)
+    })
 }
 
-fn shift(compiler: &mut Compiler, a: &Symbol, b: &Symbol, is_right: bool) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Int32));
-
-    compiler
-        .memory
-        .read(compiler.instructions, a.memory_addr, a.type_.miden_width());
-    // [a]
-    compiler
-        .memory
-        .read(compiler.instructions, b.memory_addr, b.type_.miden_width());
-    // [b, a]
-
-    compiler.instructions.push(encoder::Instruction::Dup(None));
-    // [b, b, a]
-    compiler
-        .instructions
-        .push(encoder::Instruction::U32CheckedSHR(Some(31)));
-    // [b_sign, b, a]
-    compiler.instructions.push(encoder::Instruction::AssertZero);
-    // [b, a]
-    // fails if shifting by a negative number
-
-    compiler
-        .instructions
-        .push(encoder::Instruction::Dup(Some(1)));
-    // [a, b, a]
-    compiler
-        .instructions
-        .push(encoder::Instruction::U32CheckedSHR(Some(31)));
-    // [a_sign, b, a]
-    compiler.instructions.push(encoder::Instruction::MovDown(2));
-    // [b, a, a_sign]
-
-    abs_stack(compiler);
-    // [abs(b), a, a_sign]
-    compiler.instructions.push(encoder::Instruction::Swap);
-    // [a, abs(b), a_sign]
-    abs_stack(compiler);
-    // [abs(a), abs(b), a_sign]
-    compiler.instructions.push(encoder::Instruction::Swap);
-    // [abs(b), abs(a), a_sign]
-
-    compiler.instructions.push(if is_right {
-        encoder::Instruction::U32CheckedSHR(None)
-    } else {
-        encoder::Instruction::U32CheckedSHL(None)
-    });
-    // [abs(a) >> abs(b), a_sign]
-    compiler.instructions.push(encoder::Instruction::Swap);
-    // [a_sign, abs(a) >> abs(b)]
-
-    let negation = {
-        let mut instructions = Vec::new();
-        let mut compiler = Compiler::new(&mut instructions, compiler.memory, compiler.root_scope);
-
-        // [result]
-        negate_stack(&mut compiler);
-        // [negate(result)]
-
-        instructions
-    };
-
-    compiler.instructions.push(encoder::Instruction::If {
-        // if a_sign == 1
-        condition: vec![],
-        // [abs(a) >> abs(b)]
-        then: negation,
-        else_: vec![
-            // do nothing, return the result as is
-        ],
-    });
-
-    compiler.memory.write(
-        compiler.instructions,
-        result.memory_addr,
-        &[ValueSource::Stack],
-    );
-
-    result
+fn assert_no_division_overflow(
+    builder: &mut ir::Builder,
+    a: ExpressionRef,
+    b: ExpressionRef,
+) -> Result<()> {
+    let zero = builder.number(0);
+    let b_is_zero = builder.call_one("u32checked_eq", &[zero, b])?;
+    builder.call("assertz", &[b_is_zero])?;
+
+    let minus_one = builder.number(-1i32 as u32 as u64);
+    let b_is_minus_one = builder.call_one("u32checked_eq", &[minus_one, b])?;
+    let min = builder.number(i32::MIN as u32 as u64);
+    let a_is_min = builder.call_one("u32checked_eq", &[min, a])?;
+    let overflow = builder.call_one("and", &[a_is_min, b_is_minus_one])?;
+    builder.call("assertz", &[overflow])?;
+
+    Ok(())
+}
+
+fn shift(compiler: &mut Compiler, a: &Symbol, b: &Symbol, is_right: bool) -> Result<Symbol> {
+    compile_op(compiler, a, b, PrimitiveType::Int32, |builder, a, b| {
+        // fails if shifting by a negative number
+        let b_sign = sign(builder, b)?;
+        builder.call("assertz", &[b_sign])?;
+
+        let abs_a = abs(builder, a)?;
+        let shift = if is_right {
+            "u32checked_shr"
+        } else {
+            "u32checked_shl"
+        };
+        let result = builder.call_one(shift, &[b, abs_a])?;
+
+        let a_sign = sign(builder, a)?;
+        negate_if(builder, a_sign, result)
+    })
 }
 
-pub(crate) fn shift_right(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
+pub(crate) fn shift_right(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
     shift(compiler, a, b, true)
 }
 
-pub(crate) fn shift_left(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
+pub(crate) fn shift_left(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
     shift(compiler, a, b, false)
 }
 
-/// Turns stack [b, a, b_sign, a_sign] into [a > b]
-pub(crate) fn gt_stack(compiler: &mut Compiler) {
-    // [b, a, b_sign, a_sign]
-
-    compiler
-        .instructions
-        .push(encoder::Instruction::Dup(Some(3)));
-    // [a_sign, b, a, b_sign, a_sign]
-    compiler
-        .instructions
-        .push(encoder::Instruction::Dup(Some(3)));
-    // [b_sign, a_sign, b, a, b_sign, a_sign]
-    compiler
-        .instructions
-        .push(encoder::Instruction::U32CheckedEq);
-    // [a_sign == b_sign, b, a, b_sign, a_sign]
-    compiler.instructions.push(encoder::Instruction::MovUp(2));
-    // [a, a_sign == b_sign, b, b_sign, a_sign]
-    compiler.instructions.push(encoder::Instruction::MovUp(2));
-    // [b, a, a_sign == b_sign, b_sign, a_sign]
-    compiler
-        .instructions
-        .push(encoder::Instruction::U32CheckedGT);
-    // [a > b, a_sign == b_sign, b_sign, a_sign]
-    compiler.instructions.push(encoder::Instruction::And);
-    // [a_sign == b_sign && a > b, b_sign, a_sign]
-    compiler.instructions.push(encoder::Instruction::Swap);
-    // [b_sign, a_sign == b_sign && a > b, a_sign]
-    compiler.instructions.push(encoder::Instruction::MovUp(2));
-    // [a_sign, b_sign, a_sign == b_sign && a > b]
-    compiler
-        .instructions
-        .push(encoder::Instruction::U32CheckedGT);
-    // [b_sign > a_sign, a_sign == b_sign && a > b]
-    compiler.instructions.push(encoder::Instruction::Or);
-    // [(a_sign == b_sign && a > b) || b_sign > a_sign]
+/// Returns (a_sign == b_sign && a > b) || b_sign > a_sign
+fn gt_values(
+    builder: &mut ir::Builder,
+    a: ExpressionRef,
+    b: ExpressionRef,
+) -> Result<ExpressionRef> {
+    let a_sign = sign(builder, a)?;
+    let b_sign = sign(builder, b)?;
+
+    let same_sign = builder.call_one("u32checked_eq", &[b_sign, a_sign])?;
+    let unsigned_gt = builder.call_one("u32checked_gt", &[b, a])?;
+    let same_sign_gt = builder.call_one("and", &[unsigned_gt, same_sign])?;
+    let only_b_negative = builder.call_one("u32checked_gt", &[a_sign, b_sign])?;
+
+    builder.call_one("or", &[only_b_negative, same_sign_gt])
 }
 
-pub(crate) fn gt(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
-
-    prepare_stack_for_arithmetic(compiler, a, b);
-    // [b, a, b_sign, a_sign]
-
-    gt_stack(compiler);
-
-    compiler.memory.write(
-        compiler.instructions,
-        result.memory_addr,
-        &[ValueSource::Stack],
-    );
-
-    result
+pub(crate) fn gt(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    compile_op(compiler, a, b, PrimitiveType::Boolean, gt_values)
 }
 
-pub(crate) fn gte(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
-
-    prepare_stack_for_arithmetic(compiler, a, b);
-    // [b, a, b_sign, a_sign]
-
-    compiler
-        .instructions
-        .push(encoder::Instruction::Dup(Some(1)));
-    // [a, b, a, b_sign, a_sign]
-    compiler
-        .instructions
-        .push(encoder::Instruction::Dup(Some(1)));
-    // [b, a, b, a, b_sign, a_sign]
-    compiler
-        .instructions
-        .push(encoder::Instruction::U32CheckedEq);
-    // [a == b, b, a, b_sign, a_sign]
-    compiler.instructions.push(encoder::Instruction::MovDown(4));
-    // [b, a, b_sign, a_sign, a == b]
-
-    gt_stack(compiler);
-    // [a > b, a == b]
-
-    compiler.instructions.push(encoder::Instruction::Or);
-    // [a > b || a == b]
-
-    compiler.memory.write(
-        compiler.instructions,
-        result.memory_addr,
-        &[ValueSource::Stack],
-    );
-
-    result
+pub(crate) fn gte(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    compile_op(compiler, a, b, PrimitiveType::Boolean, |builder, a, b| {
+        let eq = builder.call_one("u32checked_eq", &[b, a])?;
+        let gt = gt_values(builder, a, b)?;
+        builder.call_one("or", &[gt, eq])
+    })
 }
 
-pub(crate) fn lte(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
-
-    prepare_stack_for_arithmetic(compiler, a, b);
-    // [b, a, b_sign, a_sign]
-
-    gt_stack(compiler);
-    // [a > b]
-
-    compiler.instructions.push(encoder::Instruction::Not);
-    // [a <= b]
-
-    compiler.memory.write(
-        compiler.instructions,
-        result.memory_addr,
-        &[ValueSource::Stack],
-    );
-
-    result
+pub(crate) fn lte(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    compile_op(compiler, a, b, PrimitiveType::Boolean, |builder, a, b| {
+        let gt = gt_values(builder, a, b)?;
+        builder.call_one("not", &[gt])
+    })
 }
 
-pub(crate) fn lt(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
-
-    prepare_stack_for_arithmetic(compiler, a, b);
-    // [b, a, b_sign, a_sign]
-
-    compiler
-        .instructions
-        .push(encoder::Instruction::Dup(Some(1)));
-    // [a, b, a, b_sign, a_sign]
-    compiler
-        .instructions
-        .push(encoder::Instruction::Dup(Some(1)));
-    // [b, a, b, a, b_sign, a_sign]
-
-    compiler
-        .instructions
-        .push(encoder::Instruction::U32CheckedEq);
-    // [a == b, b, a, b_sign, a_sign]
-
-    compiler.instructions.push(encoder::Instruction::Not);
-    // [a != b, b, a, b_sign, a_sign]
-
-    compiler.instructions.push(encoder::Instruction::MovDown(4));
-    // [b, a, b_sign, a_sign, a != b]
-
-    gt_stack(compiler);
-    // [a > b, a != b]
-
-    compiler.instructions.push(encoder::Instruction::Not);
-    // [a <= b, a != b]
-
-    compiler.instructions.push(encoder::Instruction::And);
-    // [a != b && a <= b]
-
-    compiler.memory.write(
-        compiler.instructions,
-        result.memory_addr,
-        &[ValueSource::Stack],
-    );
-
-    result
+pub(crate) fn lt(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    compile_op(compiler, a, b, PrimitiveType::Boolean, |builder, a, b| {
+        let ne = builder.call_one("u32checked_neq", &[b, a])?;
+        let gt = gt_values(builder, a, b)?;
+        let lte = builder.call_one("not", &[gt])?;
+        builder.call_one("and", &[lte, ne])
+    })
 }
 
 #[cfg(test)]
@@ -854,9 +307,7 @@ mod test {
     use super::*;
 
     fn new(compiler: &mut Compiler, value: i32) -> Symbol {
-        let symbol = compiler
-            .memory
-            .allocate_symbol(Type::PrimitiveType(PrimitiveType::Int32));
+        let symbol = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Int32));
 
         // memory is zero-initialized, so we don't need to write for 0
         if value != 0 {
@@ -871,15 +322,14 @@ mod test {
     }
 
     #[test]
-    fn test_prepare_stack_for_arithmetic() {
+    fn test_decompose() {
         let mut instructions = Vec::new();
         let mut memory = Memory::new();
         let scope = Scope::new();
         let mut compiler = Compiler::new(&mut instructions, &mut memory, &scope);
-        let a = new(&mut compiler, 2);
-        let b = new(&mut compiler, -2);
+        let n = new(&mut compiler, -2);
 
-        prepare_stack_for_arithmetic(&mut compiler, &a, &b);
+        decompose(&mut compiler, &n).unwrap();
 
         let mut program = "begin\n".to_string();
         for instruction in &instructions {
@@ -899,7 +349,7 @@ mod test {
         .unwrap();
 
         let stack = outputs.stack_outputs().stack();
-        assert_eq!(&stack[..5], &[4294967294, 2, 1, 0, 0]);
+        assert_eq!(&stack[..2], &[2147483648, 2]);
     }
 
     fn add(a: i32, b: i32) -> Result<i32, miden::ExecutionError> {
@@ -910,7 +360,7 @@ mod test {
         let a = new(&mut compiler, a);
         let b = new(&mut compiler, b);
 
-        let result = super::add(&mut compiler, &a, &b);
+        let result = super::add(&mut compiler, &a, &b).unwrap();
         compiler.memory.read(
             compiler.instructions,
             result.memory_addr,
@@ -984,7 +434,10 @@ mod test {
         compiler
             .memory
             .read(compiler.instructions, a.memory_addr, a.type_.miden_width());
-        super::abs_stack(&mut compiler);
+        ir::build(compiler.instructions, 1, |builder, inputs| {
+            Ok(vec![super::abs(builder, inputs[0])?])
+        })
+        .unwrap();
 
         let mut program = "begin\n".to_string();
         for instruction in &instructions {
@@ -1042,7 +495,10 @@ mod test {
         compiler
             .memory
             .read(compiler.instructions, a.memory_addr, a.type_.miden_width());
-        super::negate_stack(&mut compiler);
+        ir::build(compiler.instructions, 1, |builder, inputs| {
+            Ok(vec![super::negate(builder, inputs[0])?])
+        })
+        .unwrap();
 
         let mut program = "begin\n".to_string();
         for instruction in &instructions {
@@ -1098,7 +554,7 @@ mod test {
         let a = new(&mut compiler, a);
         let b = new(&mut compiler, b);
 
-        let result = super::sub(&mut compiler, &a, &b);
+        let result = super::sub(&mut compiler, &a, &b).unwrap();
         compiler.memory.read(
             compiler.instructions,
             result.memory_addr,
@@ -1175,7 +631,7 @@ mod test {
         let a = new(&mut compiler, a);
         let b = new(&mut compiler, b);
 
-        let result = super::mul(&mut compiler, &a, &b);
+        let result = super::mul(&mut compiler, &a, &b).unwrap();
         compiler.memory.read(
             compiler.instructions,
             result.memory_addr,
@@ -1281,7 +737,7 @@ mod test {
         let a = new(&mut compiler, a);
         let b = new(&mut compiler, b);
 
-        let result = super::div(&mut compiler, &a, &b);
+        let result = super::div(&mut compiler, &a, &b).unwrap();
         compiler.memory.read(
             compiler.instructions,
             result.memory_addr,
@@ -1360,7 +816,7 @@ mod test {
         let a = new(&mut compiler, a);
         let b = new(&mut compiler, b);
 
-        let result = super::modulo(&mut compiler, &a, &b);
+        let result = super::modulo(&mut compiler, &a, &b).unwrap();
         compiler.memory.read(
             compiler.instructions,
             result.memory_addr,
@@ -1432,7 +888,7 @@ mod test {
         let a = new(&mut compiler, a);
         let b = new(&mut compiler, b);
 
-        let result = super::shift_right(&mut compiler, &a, &b);
+        let result = super::shift_right(&mut compiler, &a, &b).unwrap();
         compiler.memory.read(
             compiler.instructions,
             result.memory_addr,
@@ -1495,7 +951,7 @@ mod test {
         let a = new(&mut compiler, a);
         let b = new(&mut compiler, b);
 
-        let result = super::shift_left(&mut compiler, &a, &b);
+        let result = super::shift_left(&mut compiler, &a, &b).unwrap();
         compiler.memory.read(
             compiler.instructions,
             result.memory_addr,
@@ -1555,7 +1011,7 @@ mod test {
         let a = new(&mut compiler, a);
         let b = new(&mut compiler, b);
 
-        let result = super::gt(&mut compiler, &a, &b);
+        let result = super::gt(&mut compiler, &a, &b).unwrap();
         compiler.memory.read(
             compiler.instructions,
             result.memory_addr,
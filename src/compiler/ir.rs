@@ -1,7 +1,22 @@
-// TODO: remove
-#![allow(unused)]
-
-use std::{borrow::Cow, collections::HashMap};
+//! A stack-based intermediate representation.
+//!
+//! Code is described as a graph of expressions using [`Builder`],
+//! and [`Compiler`] schedules it onto the Miden stack, so that values stay on the stack
+//! instead of being stored to memory between operations.
+//!
+//! Pure expressions are computed only when they are needed, values are duplicated
+//! only if they are used again, otherwise they are moved into place.
+//! Memory stores, impure functions, ifs and loops run in the order they were built in.
+//! Memory loads are computed lazily, but never after a store that was built after them.
+//!
+//! The condition and body of a loop are separate programs, that only see the values
+//! that the loop carries on the top of the stack.
+
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
 
 use super::encoder;
 use error::prelude::*;
@@ -9,6 +24,13 @@ use error::prelude::*;
 #[derive(Debug, Clone)]
 enum Expression {
     Number(u64),
+    /// A value that was already on the stack before the built code, 0 is the top of the stack.
+    Input(usize),
+    MemLoad(u32),
+    MemStore {
+        addr: u32,
+        value: ExpressionRef,
+    },
     FunctionCall {
         name: String,
         // args are indexes to expression values
@@ -16,15 +38,58 @@ enum Expression {
     },
     If {
         condition: ExpressionRef,
-        then: Vec<ExpressionRef>,
-        then_dependencies: Vec<ExpressionRef>,
-        otherwise: Vec<ExpressionRef>,
-        otherwise_dependencies: Vec<ExpressionRef>,
+        then: Block,
+        otherwise: Block,
+        /// Values from outside of the if that are used by the branches.
+        dependencies: Vec<ExpressionRef>,
+    },
+    While {
+        state: Vec<ExpressionRef>,
+        /// Leaves the condition on top of the state.
+        condition: Program,
+        body: Program,
     },
+    Repeat {
+        state: Vec<ExpressionRef>,
+        count: u32,
+        body: Program,
+    },
+}
+
+impl Expression {
+    /// Values that the expression uses in the block that it belongs to.
+    fn uses(&self) -> Vec<ExpressionRef> {
+        match self {
+            Expression::Number(_) | Expression::Input(_) | Expression::MemLoad(_) => vec![],
+            Expression::MemStore { value, .. } => vec![*value],
+            Expression::FunctionCall { args, .. } => args.clone(),
+            Expression::If {
+                condition,
+                dependencies,
+                ..
+            } => std::iter::once(*condition)
+                .chain(dependencies.iter().copied())
+                .collect(),
+            Expression::While { state, .. } | Expression::Repeat { state, .. } => state.clone(),
+        }
+    }
+}
+
+/// Code that is built separately, its inputs are the values on the top of the stack.
+#[derive(Debug, Clone)]
+struct Program {
+    expressions: Vec<Expression>,
+    outputs: Vec<ExpressionRef>,
+}
+
+#[derive(Debug, Clone)]
+struct Block {
+    expressions: Range<usize>,
+    outputs: Vec<ExpressionRef>,
 }
 
 #[derive(Debug, PartialEq, Clone, Copy, Eq, Hash)]
-struct ExpressionRef {
+pub(crate) struct ExpressionRef {
     expr_index: usize,
     nth_element: usize,
 }
@@ -38,22 +103,117 @@ impl ExpressionRef {
     }
 }
 
-impl ToString for ExpressionRef {
-    fn to_string(&self) -> String {
-        format!("{}[{}]", self.expr_index, self.nth_element)
-    }
-}
-
 #[derive(Clone)]
 struct Function<'a> {
     name: Cow<'static, str>,
     num_args: usize,
     num_outputs: usize,
     instruction: encoder::Instruction<'a>,
+    /// Pure functions are only called if their outputs are used.
     pure: bool,
 }
 
-struct Builder<'a> {
+/// Functions available to the built code.
+/// Arguments are in stack order, the first argument is the top of the stack.
+fn functions() -> Vec<Function<'static>> {
+    use encoder::Instruction;
+
+    let function = |name, num_args, num_outputs, instruction, pure| Function {
+        name: Cow::Borrowed(name),
+        num_args,
+        num_outputs,
+        instruction,
+        pure,
+    };
+
+    vec![
+        function("and", 2, 1, Instruction::And, true),
+        function("or", 2, 1, Instruction::Or, true),
+        function("not", 1, 1, Instruction::Not, true),
+        function("assert", 1, 0, Instruction::Assert, false),
+        function("assertz", 1, 0, Instruction::AssertZero, false),
+        function("u32checked_add", 2, 1, Instruction::U32CheckedAdd, true),
+        function("u32checked_sub", 2, 1, Instruction::U32CheckedSub, true),
+        function("u32checked_mul", 2, 1, Instruction::U32CheckedMul, true),
+        function(
+            "u32checked_div",
+            2,
+            1,
+            Instruction::U32CheckedDiv(None),
+            true,
+        ),
+        function(
+            "u32checked_mod",
+            2,
+            1,
+            Instruction::U32CheckedMod(None),
+            true,
+        ),
+        function(
+            "u32checked_shl",
+            2,
+            1,
+            Instruction::U32CheckedSHL(None),
+            true,
+        ),
+        function(
+            "u32checked_shr",
+            2,
+            1,
+            Instruction::U32CheckedSHR(None),
+            true,
+        ),
+        function("u32checked_and", 2, 1, Instruction::U32CheckedAnd, true),
+        function("u32checked_or", 2, 1, Instruction::U32CheckedOr, true),
+        function("u32checked_xor", 2, 1, Instruction::U32CheckedXOR, true),
+        function("u32checked_not", 1, 1, Instruction::U32CheckedNot, true),
+        function("u32checked_eq", 2, 1, Instruction::U32CheckedEq, true),
+        function("u32checked_neq", 2, 1, Instruction::U32CheckedNeq, true),
+        function("u32checked_lt", 2, 1, Instruction::U32CheckedLT, true),
+        function("u32checked_lte", 2, 1, Instruction::U32CheckedLTE, true),
+        function("u32checked_gt", 2, 1, Instruction::U32CheckedGT, true),
+        function("u32checked_gte", 2, 1, Instruction::U32CheckedGTE, true),
+        function("u32wrapping_add", 2, 1, Instruction::U32WrappingAdd, true),
+        function("u32wrapping_sub", 2, 1, Instruction::U32WrappingSub, true),
+        // The outputs are the high and the low 32 bits of the product
+        function(
+            "u32overflowing_mul",
+            2,
+            2,
+            Instruction::U32OverflowingMul,
+            true,
+        ),
+        function("mem_load", 1, 1, Instruction::MemLoad(None), false),
+        function("mem_store", 2, 0, Instruction::MemStore(None), false),
+    ]
+}
+
+/// Builds code with `f` and appends it to `instructions`.
+///
+/// `f` gets the top `inputs` values of the stack, top first,
+/// and returns the values that should be left on the stack, top first.
+/// The inputs are consumed.
+pub(crate) fn build<'a>(
+    instructions: &mut Vec<encoder::Instruction<'a>>,
+    inputs: usize,
+    f: impl FnOnce(&mut Builder, &[ExpressionRef]) -> Result<Vec<ExpressionRef>>,
+) -> Result<()> {
+    let functions = functions();
+    let mut builder = Builder::new(&functions);
+    let inputs = (0..inputs).map(|i| builder.input(i)).collect::<Vec<_>>();
+    let outputs = f(&mut builder, &inputs)?;
+
+    let compiler = Compiler {
+        expressions: builder.build(),
+        functions: functions.clone(),
+        ..Default::default()
+    };
+    instructions.extend(compiler.compile(&outputs)?);
+
+    Ok(())
+}
+
+pub(crate) struct Builder<'a> {
     start_expr_index: usize,
     expressions: Vec<Expression>,
     functions: &'a [Function<'a>],
@@ -71,18 +231,43 @@ impl<'a> Builder<'a> {
     fn add_expression(&mut self, expr: Expression) -> Result<Vec<ExpressionRef>> {
         let expr_index = self.last_expr_index();
         let outputs = match expr {
-            Expression::Number(_) => 1,
-            Expression::FunctionCall { ref name, args: _ } => {
+            Expression::Number(_) | Expression::Input(_) | Expression::MemLoad(_) => 1,
+            Expression::MemStore { .. } => 0,
+            Expression::FunctionCall { ref name, ref args } => {
                 let func = self.find_function(name)?;
+                ensure!(
+                    func.num_args == args.len(),
+                    TypeMismatchSnafu {
+                        context: format!(
+                            "expected {} but found {} arguments in {}",
+                            func.num_args,
+                            args.len(),
+                            name
+                        )
+                    }
+                );
                 func.num_outputs
             }
             Expression::If {
-                condition: _,
-                then: _,
-                then_dependencies: _,
-                otherwise: _,
-                otherwise_dependencies: _,
-            } => return Err(Error::unimplemented("builder if's".into())),
+                ref then,
+                ref otherwise,
+                ..
+            } => {
+                ensure!(
+                    then.outputs.len() == otherwise.outputs.len(),
+                    TypeMismatchSnafu {
+                        context: format!(
+                            "num exprs of then branch ({}) mismatches the else one ({})",
+                            then.outputs.len(),
+                            otherwise.outputs.len()
+                        )
+                    }
+                );
+                then.outputs.len()
+            }
+            Expression::While { ref state, .. } | Expression::Repeat { ref state, .. } => {
+                state.len()
+            }
         };
         self.expressions.push(expr);
 
@@ -94,7 +279,14 @@ impl<'a> Builder<'a> {
         Ok(refs)
     }
 
-    fn find_function(&self, name: &str) -> Result<&Function> {
+    /// Adds an expression that has exactly one output.
+    fn add_value(&mut self, expr: Expression) -> ExpressionRef {
+        let expr_index = self.last_expr_index();
+        self.expressions.push(expr);
+        ExpressionRef::new(expr_index, 0)
+    }
+
+    fn find_function(&self, name: &str) -> Result<&Function<'a>> {
         self.functions
             .iter()
             .rev()
@@ -102,93 +294,177 @@ impl<'a> Builder<'a> {
             .not_found("function", name)
     }
 
-    fn number(&mut self, n: u64) -> ExpressionRef {
-        let expr = Expression::Number(n);
-        let expr_index = self.last_expr_index();
-        self.expressions.push(expr);
-        ExpressionRef::new(expr_index, 0)
+    fn input(&mut self, position: usize) -> ExpressionRef {
+        self.add_value(Expression::Input(position))
     }
 
-    fn boolean(&mut self, b: bool) -> ExpressionRef {
-        let expr = Expression::Number(b as u64);
-        let expr_index = self.last_expr_index();
-        self.expressions.push(expr);
-        ExpressionRef::new(expr_index, 0)
+    pub(crate) fn number(&mut self, n: u64) -> ExpressionRef {
+        self.add_value(Expression::Number(n))
+    }
+
+    pub(crate) fn boolean(&mut self, b: bool) -> ExpressionRef {
+        self.add_value(Expression::Number(b as u64))
+    }
+
+    pub(crate) fn mem_load(&mut self, addr: u32) -> ExpressionRef {
+        self.add_value(Expression::MemLoad(addr))
+    }
+
+    pub(crate) fn mem_store(&mut self, addr: u32, value: ExpressionRef) {
+        self.add_value(Expression::MemStore { addr, value });
+    }
+
+    pub(crate) fn call(
+        &mut self,
+        func_name: &str,
+        args: &[ExpressionRef],
+    ) -> Result<Vec<ExpressionRef>> {
+        self.add_expression(Expression::FunctionCall {
+            name: func_name.to_string(),
+            args: args.to_vec(),
+        })
     }
 
-    fn call_func(
+    /// Calls a function that has exactly one output.
+    pub(crate) fn call_one(
         &mut self,
         func_name: &str,
         args: &[ExpressionRef],
-    ) -> Result<(usize, Vec<ExpressionRef>)> {
-        let func = self.find_function(func_name)?;
-        let num_outputs = func.num_outputs;
+    ) -> Result<ExpressionRef> {
+        let outputs = self.call(func_name, args)?;
         ensure!(
-            func.num_args == args.len(),
+            outputs.len() == 1,
             TypeMismatchSnafu {
                 context: format!(
-                    "expected {} but found {} arguments in {}",
-                    func.num_args,
-                    args.len(),
-                    func_name
+                    "expected {func_name} to have 1 output, found {}",
+                    outputs.len()
                 )
             }
         );
 
-        let expr = Expression::FunctionCall {
-            name: func_name.to_string(),
-            args: args.to_vec(),
-        };
-        let expr_index = self.last_expr_index();
-        self.expressions.push(expr);
-
-        let mut refs = Vec::with_capacity(num_outputs);
-        for nth_element in 0..num_outputs {
-            let expr_ref = ExpressionRef::new(expr_index, nth_element);
-            refs.push(expr_ref);
-        }
-        Ok((expr_index, refs))
-    }
-
-    fn call(&mut self, func_name: &str, args: &[ExpressionRef]) -> Result<Vec<ExpressionRef>> {
-        self.call_func(func_name, args).map(|(_, e)| e)
-    }
-
-    fn call_for_expr_index(&mut self, func_name: &str, args: &[ExpressionRef]) -> Result<usize> {
-        self.call_func(func_name, args).map(|(i, _)| i)
+        Ok(outputs[0])
     }
 
-    fn if_<const OUTPUTS: usize>(
+    pub(crate) fn if_<const OUTPUTS: usize>(
         &mut self,
         condition: ExpressionRef,
-        then: impl FnOnce(&mut Self) -> [ExpressionRef; OUTPUTS],
-        otherwise: impl FnOnce(&mut Self) -> [ExpressionRef; OUTPUTS],
-    ) -> [ExpressionRef; OUTPUTS] {
+        then: impl FnOnce(&mut Self) -> Result<[ExpressionRef; OUTPUTS]>,
+        otherwise: impl FnOnce(&mut Self) -> Result<[ExpressionRef; OUTPUTS]>,
+    ) -> Result<[ExpressionRef; OUTPUTS]> {
+        let then_start = self.last_expr_index();
         let mut then_builder = Self::new(self.functions);
-        then_builder.start_expr_index = self.last_expr_index();
-        let then_refs = then(&mut then_builder);
+        then_builder.start_expr_index = then_start;
+        let then_refs = then(&mut then_builder)?;
 
+        let otherwise_start = then_builder.last_expr_index();
         let mut otherwise_builder = Self::new(self.functions);
-        otherwise_builder.start_expr_index = then_builder.last_expr_index();
-        let otherwise_refs = otherwise(&mut otherwise_builder);
+        otherwise_builder.start_expr_index = otherwise_start;
+        let otherwise_refs = otherwise(&mut otherwise_builder)?;
 
+        let mut dependencies = Vec::new();
+        for expr_ref in then_builder
+            .expressions
+            .iter()
+            .chain(&otherwise_builder.expressions)
+            .flat_map(Expression::uses)
+            .chain(then_refs)
+            .chain(otherwise_refs)
+        {
+            if expr_ref.expr_index < then_start && !dependencies.contains(&expr_ref) {
+                dependencies.push(expr_ref);
+            }
+        }
+
+        let end = otherwise_builder.last_expr_index();
         self.expressions.append(&mut then_builder.expressions);
         self.expressions.append(&mut otherwise_builder.expressions);
 
-        let expr_index = self.last_expr_index();
-        self.expressions.push(Expression::If {
+        let refs = self.add_expression(Expression::If {
             condition,
-            then: then_refs.to_vec(),
-            then_dependencies: vec![],
-            otherwise: otherwise_refs.to_vec(),
-            otherwise_dependencies: vec![],
-        });
-
-        let mut refs = [ExpressionRef::new(0, 0); OUTPUTS];
-        for (i, ref_) in refs.iter_mut().enumerate() {
-            *ref_ = ExpressionRef::new(expr_index, i);
+            then: Block {
+                expressions: then_start..otherwise_start,
+                outputs: then_refs.to_vec(),
+            },
+            otherwise: Block {
+                expressions: otherwise_start..end,
+                outputs: otherwise_refs.to_vec(),
+            },
+            dependencies,
+        })?;
+
+        let mut outputs = [ExpressionRef::new(0, 0); OUTPUTS];
+        outputs.copy_from_slice(&refs);
+        Ok(outputs)
+    }
+
+    /// Runs `body` while `condition` is true, and returns the final `state`.
+    ///
+    /// `condition` and `body` are built separately and can only use the state that they are given,
+    /// `body` returns the state of the next iteration.
+    pub(crate) fn while_<const STATE: usize>(
+        &mut self,
+        state: [ExpressionRef; STATE],
+        condition: impl FnOnce(&mut Self, [ExpressionRef; STATE]) -> Result<ExpressionRef>,
+        body: impl FnOnce(&mut Self, [ExpressionRef; STATE]) -> Result<[ExpressionRef; STATE]>,
+    ) -> Result<[ExpressionRef; STATE]> {
+        let condition = self.program(|builder, state| {
+            let condition = condition(builder, state)?;
+            Ok(std::iter::once(condition).chain(state).collect())
+        })?;
+        let body = self.program(|builder, state| Ok(body(builder, state)?.to_vec()))?;
+
+        self.loop_outputs(Expression::While {
+            state: state.to_vec(),
+            condition,
+            body,
+        })
+    }
+
+    /// Runs `body` `count` times, and returns the final `state`.
+    ///
+    /// `body` is built separately and can only use the state that it is given,
+    /// it returns the state of the next iteration.
+    pub(crate) fn repeat<const STATE: usize>(
+        &mut self,
+        count: u32,
+        state: [ExpressionRef; STATE],
+        body: impl FnOnce(&mut Self, [ExpressionRef; STATE]) -> Result<[ExpressionRef; STATE]>,
+    ) -> Result<[ExpressionRef; STATE]> {
+        let body = self.program(|builder, state| Ok(body(builder, state)?.to_vec()))?;
+
+        self.loop_outputs(Expression::Repeat {
+            state: state.to_vec(),
+            count,
+            body,
+        })
+    }
+
+    fn program<const INPUTS: usize>(
+        &self,
+        f: impl FnOnce(&mut Self, [ExpressionRef; INPUTS]) -> Result<Vec<ExpressionRef>>,
+    ) -> Result<Program> {
+        let mut builder = Self::new(self.functions);
+        let mut inputs = [ExpressionRef::new(0, 0); INPUTS];
+        for (position, input) in inputs.iter_mut().enumerate() {
+            *input = builder.input(position);
         }
-        refs
+        let outputs = f(&mut builder, inputs)?;
+
+        Ok(Program {
+            expressions: builder.build(),
+            outputs,
+        })
+    }
+
+    fn loop_outputs<const STATE: usize>(
+        &mut self,
+        expr: Expression,
+    ) -> Result<[ExpressionRef; STATE]> {
+        let refs = self.add_expression(expr)?;
+
+        let mut outputs = [ExpressionRef::new(0, 0); STATE];
+        outputs.copy_from_slice(&refs);
+        Ok(outputs)
     }
 
     fn last_expr_index(&self) -> usize {
@@ -207,12 +483,17 @@ struct Compiler<'a> {
     /// It's consistent with the stack of the miden code that is being generated.
     stack: Vec<ExpressionRef>,
     /// Expressions that have already been compiled.
-    used_exprs: Vec<usize>,
-    /// Expression -> dependency.
-    expr_to_dependency: Vec<(ExpressionRef, ExpressionRef)>,
-    dependencies: Vec<ExpressionRef>,
-    /// Expression -> usage count.
-    expr_ref_use_count: HashMap<ExpressionRef, usize>,
+    used_exprs: HashSet<usize>,
+    /// Expression -> the block that it belongs to.
+    /// The top level block is 0, the branches of the if at index `i` are `2i + 1` and `2i + 2`.
+    blocks: Vec<usize>,
+    /// The block that is being compiled.
+    block: usize,
+    /// Values that an if moved into the block of one of its branches,
+    /// because they are not used after the if.
+    adopted: HashMap<ExpressionRef, usize>,
+    /// (Block, expression) -> remaining usage count in the block.
+    expr_ref_use_count: HashMap<(usize, ExpressionRef), usize>,
     /// Functions available to call.
     functions: Vec<Function<'a>>,
     /// Generated miden code.
@@ -228,145 +509,250 @@ impl<'a> Compiler<'a> {
             .not_found("function", name)
     }
 
-    fn find_expr_on_stack(&self, expr_ref: &ExpressionRef) -> Result<usize> {
+    /// Returns the position of the topmost `expr_ref` on the stack, ignoring the top `skip` elements.
+    fn find_expr_on_stack(&self, expr_ref: &ExpressionRef, skip: usize) -> Result<usize> {
         self.stack
             .iter()
             .rev()
             .enumerate()
+            .skip(skip)
             .find_map(|(i, e)| if e == expr_ref { Some(i) } else { None })
             .not_found("expr", &format!("expr {expr_ref:?}"))
     }
 
-    fn movup(&mut self, expr_ref: &ExpressionRef) -> Result<()> {
-        let position = self.find_expr_on_stack(expr_ref)?;
+    fn owner(&self, expr_ref: &ExpressionRef) -> usize {
+        self.adopted
+            .get(expr_ref)
+            .copied()
+            .unwrap_or(self.blocks[expr_ref.expr_index])
+    }
+
+    fn is_owned(&self, expr_ref: &ExpressionRef) -> bool {
+        self.owner(expr_ref) == self.block
+    }
+
+    fn remaining_uses(&self, expr_ref: &ExpressionRef) -> usize {
+        self.expr_ref_use_count
+            .get(&(self.block, *expr_ref))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn use_expr(&mut self, expr_ref: &ExpressionRef) {
+        if let Some(count) = self.expr_ref_use_count.get_mut(&(self.block, *expr_ref)) {
+            *count = count.saturating_sub(1);
+        }
+    }
+
+    /// Whether the expr can be consumed, instead of copied.
+    fn is_last_use(&self, expr_ref: &ExpressionRef) -> bool {
+        self.is_owned(expr_ref) && self.remaining_uses(expr_ref) == 1
+    }
+
+    fn is_effect(&self, expr_index: usize) -> Result<bool> {
+        Ok(match &self.expressions[expr_index] {
+            Expression::Number(_) | Expression::Input(_) | Expression::MemLoad(_) => false,
+            Expression::MemStore { .. }
+            | Expression::If { .. }
+            | Expression::While { .. }
+            | Expression::Repeat { .. } => true,
+            Expression::FunctionCall { name, .. } => !self.find_function(name)?.pure,
+        })
+    }
+
+    /// Moves the element at `position` to the top of the stack.
+    fn movup(&mut self, position: usize) -> Result<()> {
+        let stack_len = self.stack.len();
+        ensure!(
+            stack_len > position,
+            StackSnafu {
+                stack_len,
+                expected: Some(1 + position),
+            }
+        );
+
         match position {
             0 => {
                 // The element is already on the top of the stack.
             }
-            1 => {
-                self.miden_code.push(encoder::Instruction::Swap);
-                let stack_len = self.stack.len();
-                ensure!(
-                    stack_len >= 2,
-                    StackSnafu {
-                        stack_len,
-                        expected: Some(2),
-                    }
-                );
-                self.stack.swap(stack_len - 1, stack_len - 2);
-            }
-            n if n < 16 => {
-                self.miden_code.push(encoder::Instruction::MovUp(n as u32));
-                let stack_len = self.stack.len();
-                ensure!(
-                    stack_len > position,
-                    StackSnafu {
-                        stack_len,
-                        expected: Some(1 + position),
-                    }
-                );
-                let element = self.stack.remove(stack_len - 1 - position);
-                self.stack.push(element);
-            }
+            1 => self.miden_code.push(encoder::Instruction::Swap),
+            n if n < 16 => self.miden_code.push(encoder::Instruction::MovUp(n as u32)),
             n => {
                 return Err(Error::simple(format!(
                     "Cannot move up further than to the 16th position: {n}"
                 )))
             }
         }
+
+        let element = self.stack.remove(stack_len - 1 - position);
+        self.stack.push(element);
         Ok(())
     }
 
-    /// Exprs is highest to lowest.
-    fn movup_many(&mut self, exprs: &[ExpressionRef]) -> Result<()> {
+    /// Copies the element at `position` to the top of the stack.
+    fn dup(&mut self, position: usize) -> Result<()> {
         let stack_len = self.stack.len();
         ensure!(
-            stack_len >= exprs.len(),
+            stack_len > position,
             StackSnafu {
                 stack_len,
-                expected: Some(exprs.len()),
+                expected: Some(1 + position),
             }
         );
 
-        // Check if the elements are already in the right order
-        // exprs[0] is the top element
-        let in_order = exprs
-            .iter()
-            .enumerate()
-            .all(|(i, expr_ref)| self.stack[self.stack.len() - 1 - i] == *expr_ref);
-
-        if in_order {
-            return Ok(());
+        match position {
+            0 => self.miden_code.push(encoder::Instruction::Dup(None)),
+            n if n < 16 => self
+                .miden_code
+                .push(encoder::Instruction::Dup(Some(n as u32))),
+            n => {
+                return Err(Error::simple(format!(
+                    "Cannot duplicate further than the 16th position: {n}"
+                )))
+            }
         }
 
-        if let Some(expr) = exprs.last() {
-            self.movup(expr)?;
-            self.movup_many(&exprs[..exprs.len() - 1])?;
-        }
+        self.stack.push(self.stack[stack_len - 1 - position]);
+        Ok(())
+    }
 
+    /// Drops the element at `position`.
+    fn drop_at(&mut self, position: usize) -> Result<()> {
+        self.movup(position)?;
+        self.miden_code.push(encoder::Instruction::Drop);
+        self.stack.pop();
         Ok(())
     }
 
-    /// Moves the top stack element to the nth position.
-    fn movdn(&mut self, n: usize) -> Result<()> {
-        match n {
-            0 => {}
-            1 => {
-                self.miden_code.push(encoder::Instruction::Swap);
-                let stack_len = self.stack.len();
-                ensure!(
-                    stack_len >= 2,
-                    StackSnafu {
-                        stack_len,
-                        expected: Some(2),
-                    }
-                );
-                self.stack.swap(stack_len - 1, stack_len - 2);
+    /// Puts `args` on the top of the stack, `args[0]` being the top.
+    fn prepare_args(&mut self, args: &[ExpressionRef]) -> Result<()> {
+        // `prepared` args have a copy on the stack that is already accounted for in the use counts
+        let mut prepared = vec![false; args.len()];
+
+        let in_place = self.in_place_args(args, &prepared);
+        for (arg, prepared) in args[in_place..].iter().zip(&mut prepared[in_place..]) {
+            self.use_expr(arg);
+            *prepared = true;
+        }
+
+        // Place args starting from the deepest one, computing the ones that are not on the stack yet.
+        // If computing an arg leaves anything else above the placed args, the rest is moved into place afterwards.
+        let mut placed = args.len() - in_place;
+        let mut aligned = true;
+        for i in (0..in_place).rev() {
+            let arg = &args[i];
+            if self.used_exprs.contains(&arg.expr_index) {
+                if aligned {
+                    self.place_arg(arg, placed, false)?;
+                    prepared[i] = true;
+                    placed += 1;
+                }
+                continue;
             }
-            n if n < 16 => {
-                self.miden_code
-                    .push(encoder::Instruction::MovDown(n as u32));
-                let stack_len = self.stack.len();
-                ensure!(
-                    stack_len > n,
-                    StackSnafu {
-                        stack_len,
-                        expected: Some(1 + n),
-                    }
-                );
-                let el = self.stack.remove(stack_len - 1);
-                self.stack.insert(stack_len - 1 - n, el);
+
+            let stack_len = self.stack.len();
+            self.compile_expr(arg)?;
+            // Computing the arg can also consume the copy of a placed arg, as its last use,
+            // and leave another value on the stack
+            let pushed_only_arg = self.stack.len() == stack_len + 1
+                && self.stack.last() == Some(arg)
+                && self
+                    .stack
+                    .iter()
+                    .rev()
+                    .skip(1)
+                    .take(placed)
+                    .eq(&args[i + 1..i + 1 + placed]);
+            if self.is_last_use(arg) {
+                aligned &= pushed_only_arg;
+            } else {
+                // the original stays below the copy
+                aligned &= pushed_only_arg && placed == 0;
+                let position = self.find_expr_on_stack(arg, 0)?;
+                self.dup(position)?;
             }
-            n => {
-                return Err(Error::simple(format!(
-                    "Cannot move down further than to the 16th position: {n}"
-                )))
+            self.use_expr(arg);
+            prepared[i] = true;
+            if aligned {
+                placed += 1;
             }
         }
+
+        if !aligned {
+            let in_place = self.in_place_args(args, &prepared);
+            for (placed, i) in (0..in_place).rev().enumerate() {
+                self.place_arg(&args[i], placed, prepared[i])?;
+            }
+        }
+
+        debug_assert!(
+            self.stack.iter().rev().take(args.len()).eq(args),
+            "invalid stack alignment"
+        );
+        Ok(())
+    }
+
+    /// Returns the index of the first arg of the longest suffix of `args`
+    /// that is already on the top of the stack, in order.
+    fn in_place_args(&self, args: &[ExpressionRef], prepared: &[bool]) -> usize {
+        let stack_len = self.stack.len();
+        (0..args.len())
+            .find(|&start| {
+                stack_len >= args.len() - start
+                    && args[start..].iter().enumerate().all(|(i, arg)| {
+                        self.stack[stack_len - 1 - i] == *arg
+                            && (prepared[start + i] || self.is_last_use(arg))
+                    })
+            })
+            .unwrap_or(args.len())
+    }
+
+    /// Moves or copies `arg` above the top `placed` elements of the stack.
+    fn place_arg(&mut self, arg: &ExpressionRef, placed: usize, prepared: bool) -> Result<()> {
+        let position = self.find_expr_on_stack(arg, placed)?;
+        if prepared || self.is_last_use(arg) {
+            self.movup(position)?;
+        } else {
+            self.dup(position)?;
+        }
+
+        if !prepared {
+            self.use_expr(arg);
+        }
         Ok(())
     }
 
-    /// Removes all stack elements after the top `n` elements.
-    fn cleanup_stack(&mut self, n: usize) -> Result<()> {
-        // We need to first movdn the top `n` elements to the bottom of the stack.
-        // Then we can start dropping elements from the top of the stack, until self.stack.len() is n.
-        for _ in 0..n.min(self.stack.len()) {
-            self.movdn(self.stack.len() - 1)?;
+    /// Pushes the outputs of an expression and drops the ones that are never used.
+    fn push_outputs(&mut self, expr_index: usize, num_outputs: usize) -> Result<()> {
+        for i in (0..num_outputs).rev() {
+            self.stack.push(ExpressionRef::new(expr_index, i));
         }
-        while self.stack.len() > n {
-            self.miden_code.push(encoder::Instruction::Drop);
-            // draw an edge from top element to drop
-            self.stack.pop().unwrap();
+
+        for i in 0..num_outputs {
+            let expr_ref = ExpressionRef::new(expr_index, i);
+            if self.remaining_uses(&expr_ref) == 0 {
+                let position = self.find_expr_on_stack(&expr_ref, 0)?;
+                self.drop_at(position)?;
+            }
         }
+
         Ok(())
     }
 
-    fn align_stack(&mut self, expr_refs: &[ExpressionRef]) -> Result<()> {
-        self.movup_many(expr_refs)?;
-        debug_assert!(
-            self.stack.iter().rev().take(expr_refs.len()).eq(expr_refs),
-            "invalid stack alignment"
-        );
+    /// Compiles loads of the block that were built before `expr_index`,
+    /// so that they don't observe stores that were built after them.
+    fn compile_pending_loads(&mut self, range: Range<usize>) -> Result<()> {
+        for expr_index in range {
+            let expr_ref = ExpressionRef::new(expr_index, 0);
+            if self.blocks[expr_index] == self.block
+                && matches!(self.expressions[expr_index], Expression::MemLoad(_))
+                && !self.used_exprs.contains(&expr_index)
+                && self.remaining_uses(&expr_ref) > 0
+            {
+                self.compile_expr(&expr_ref)?;
+            }
+        }
+
         Ok(())
     }
 
@@ -375,20 +761,32 @@ impl<'a> Compiler<'a> {
             return Ok(());
         }
 
-        self.used_exprs.push(expr_ref.expr_index);
+        self.used_exprs.insert(expr_ref.expr_index);
 
-        match &self.expressions[expr_ref.expr_index] {
+        match self.expressions[expr_ref.expr_index].clone() {
             Expression::Number(n) => {
-                self.miden_code.push(encoder::Instruction::Push(*n as u32));
-
-                self.stack.push(ExpressionRef {
-                    expr_index: expr_ref.expr_index,
-                    nth_element: 0,
-                });
+                self.miden_code.push(encoder::Instruction::Push(n as u32));
+                self.push_outputs(expr_ref.expr_index, 1)?;
+            }
+            Expression::Input(_) => {
+                return Err(Error::simple(format!(
+                    "input {expr_ref:?} is not on the stack"
+                )))
+            }
+            Expression::MemLoad(addr) => {
+                self.miden_code
+                    .push(encoder::Instruction::MemLoad(Some(addr)));
+                self.push_outputs(expr_ref.expr_index, 1)?;
+            }
+            Expression::MemStore { addr, value } => {
+                self.prepare_args(&[value])
+                    .nest_err(|| format!("value of store to {addr}"))?;
+                self.miden_code
+                    .push(encoder::Instruction::MemStore(Some(addr)));
+                self.stack.pop();
             }
             Expression::FunctionCall { name, args } => {
-                let func = self.find_function(name)?.clone();
-                let args = args.clone();
+                let func = self.find_function(&name)?.clone();
 
                 ensure!(
                     func.num_args == args.len(),
@@ -402,13 +800,8 @@ impl<'a> Compiler<'a> {
                     }
                 );
 
-                let name = name.clone();
-                for (i, arg) in args.iter().enumerate().rev() {
-                    self.compile_expr(arg)
-                        .nest_err(|| format!("{i}-th arg of {name}"))?;
-                }
-                self.align_stack(&args)
-                    .nest_err(|| format!("at align of fn {name}"))?;
+                self.prepare_args(&args)
+                    .nest_err(|| format!("args of fn {name}"))?;
 
                 self.miden_code.push(func.instruction);
 
@@ -416,82 +809,268 @@ impl<'a> Compiler<'a> {
                     self.stack.pop();
                 }
 
-                for i in (0..func.num_outputs).rev() {
-                    let expr_ref = ExpressionRef::new(expr_ref.expr_index, i);
-                    self.stack.push(expr_ref);
-                }
+                self.push_outputs(expr_ref.expr_index, func.num_outputs)?;
             }
             Expression::If {
                 condition,
                 then,
-                then_dependencies: _,
                 otherwise,
-                otherwise_dependencies: _,
+                dependencies,
             } => {
-                let condition = *condition;
-                let then = then.clone();
-                let otherwise = otherwise.clone();
+                self.compile_if(
+                    expr_ref.expr_index,
+                    condition,
+                    then,
+                    otherwise,
+                    dependencies,
+                )?;
+            }
+            Expression::While {
+                state,
+                condition,
+                body,
+            } => {
+                self.prepare_args(&state)
+                    .nest_err(|| "while state".to_string())?;
+                let condition = self.compile_program(condition)?;
+                let body = self.compile_program(body)?;
+                self.miden_code
+                    .push(encoder::Instruction::While { condition, body });
 
-                self.compile_expr(&condition)?;
-                self.movup(&condition);
-                self.miden_code.push(encoder::Instruction::IfTrue);
-                self.stack.pop();
+                self.stack.truncate(self.stack.len() - state.len());
+                self.push_outputs(expr_ref.expr_index, state.len())?;
+            }
+            Expression::Repeat { state, count, body } => {
+                self.prepare_args(&state)
+                    .nest_err(|| "repeat state".to_string())?;
+                let instructions = self.compile_program(body)?;
+                self.miden_code.push(encoder::Instruction::Repeat {
+                    count,
+                    instructions,
+                });
 
-                for expr in &then {
-                    self.compile_expr(expr)?;
-                }
-                self.align_stack(&then)?;
+                self.stack.truncate(self.stack.len() - state.len());
+                self.push_outputs(expr_ref.expr_index, state.len())?;
+            }
+        }
 
-                for _ in &then {
-                    self.stack.pop();
-                }
+        Ok(())
+    }
 
-                self.miden_code.push(encoder::Instruction::IfElse);
+    fn compile_program(&self, program: Program) -> Result<Vec<encoder::Instruction<'a>>> {
+        let compiler = Compiler {
+            expressions: program.expressions,
+            functions: self.functions.clone(),
+            ..Default::default()
+        };
+        compiler.compile(&program.outputs)
+    }
 
-                for expr in &otherwise {
-                    self.compile_expr(expr)?;
-                }
-                self.align_stack(&otherwise);
+    fn compile_if(
+        &mut self,
+        expr_index: usize,
+        condition: ExpressionRef,
+        then: Block,
+        otherwise: Block,
+        dependencies: Vec<ExpressionRef>,
+    ) -> Result<()> {
+        for dependency in dependencies.iter().rev() {
+            self.compile_expr(dependency)?;
+        }
+        self.compile_expr(&condition)?;
 
-                for _ in &otherwise {
-                    self.stack.pop();
-                }
+        // Dependencies that are not used after the if are given to the branches,
+        // they need to be right below the condition.
+        let consumed = dependencies
+            .iter()
+            .copied()
+            .filter(|d| self.is_last_use(d))
+            .collect::<Vec<_>>();
+        let offset =
+            usize::from(self.stack.last() == Some(&condition) && self.is_last_use(&condition));
+        let in_place = self.stack.len() >= offset + consumed.len()
+            && self
+                .stack
+                .iter()
+                .rev()
+                .skip(offset)
+                .take(consumed.len())
+                .all(|e| consumed.contains(e));
+        if !in_place {
+            let mut positions = consumed
+                .iter()
+                .map(|d| self.find_expr_on_stack(d, 0).map(|p| (p, *d)))
+                .collect::<Result<Vec<_>>>()?;
+            positions.sort_by(|a, b| b.0.cmp(&a.0));
+            for (_, dependency) in positions {
+                let position = self.find_expr_on_stack(&dependency, 0)?;
+                self.movup(position)?;
+            }
+        }
+
+        self.prepare_args(&[condition])
+            .nest_err(|| "if condition".to_string())?;
+        self.stack.pop();
+
+        for dependency in &consumed {
+            self.use_expr(dependency);
+        }
+        for dependency in &dependencies {
+            if !consumed.contains(dependency) && self.is_owned(dependency) {
+                self.use_expr(dependency);
+            }
+        }
+
+        let stack = self.stack.clone();
+        let base = stack.len() - consumed.len();
 
+        let then_code = self.compile_branch(2 * expr_index + 1, &then, &consumed, base)?;
+        self.stack = stack.clone();
+        let otherwise_code =
+            self.compile_branch(2 * expr_index + 2, &otherwise, &consumed, base)?;
+
+        match (then_code.is_empty(), otherwise_code.is_empty()) {
+            (true, true) => self.miden_code.push(encoder::Instruction::Drop),
+            (true, false) => {
+                self.miden_code.push(encoder::Instruction::Not);
+                self.miden_code.push(encoder::Instruction::IfTrue);
+                self.miden_code.extend(otherwise_code);
                 self.miden_code.push(encoder::Instruction::IfEnd);
+            }
+            (false, true) => {
+                self.miden_code.push(encoder::Instruction::IfTrue);
+                self.miden_code.extend(then_code);
+                self.miden_code.push(encoder::Instruction::IfEnd);
+            }
+            (false, false) => {
+                self.miden_code.push(encoder::Instruction::IfTrue);
+                self.miden_code.extend(then_code);
+                self.miden_code.push(encoder::Instruction::IfElse);
+                self.miden_code.extend(otherwise_code);
+                self.miden_code.push(encoder::Instruction::IfEnd);
+            }
+        }
 
-                ensure!(
-                    then.len() == otherwise.len(),
-                    TypeMismatchSnafu {
-                        context: format!(
-                            "num exprs of then branch ({}) mismatches the else one ({})",
-                            then.len(),
-                            otherwise.len()
-                        )
-                    }
-                );
-                for (i, _) in then.iter().enumerate().rev() {
-                    self.stack.push(ExpressionRef {
-                        expr_index: expr_ref.expr_index,
-                        nth_element: i,
-                    });
-                }
+        self.stack.truncate(base);
+        self.push_outputs(expr_index, then.outputs.len())
+    }
+
+    fn compile_branch(
+        &mut self,
+        block_id: usize,
+        block: &Block,
+        adopted: &[ExpressionRef],
+        base: usize,
+    ) -> Result<Vec<encoder::Instruction<'a>>> {
+        let previous_owners = adopted
+            .iter()
+            .map(|d| (*d, self.adopted.insert(*d, block_id)))
+            .collect::<Vec<_>>();
+
+        let code = std::mem::take(&mut self.miden_code);
+        self.compile_block(block_id, block.expressions.clone(), &block.outputs, base)?;
+        let code = std::mem::replace(&mut self.miden_code, code);
+
+        for (dependency, owner) in previous_owners {
+            match owner {
+                Some(owner) => self.adopted.insert(dependency, owner),
+                None => self.adopted.remove(&dependency),
+            };
+        }
+
+        Ok(code)
+    }
+
+    /// Compiles the effects of a block and leaves only its outputs above `base`.
+    fn compile_block(
+        &mut self,
+        block: usize,
+        expressions: Range<usize>,
+        outputs: &[ExpressionRef],
+        base: usize,
+    ) -> Result<()> {
+        let parent = std::mem::replace(&mut self.block, block);
+
+        for expr_index in expressions.clone() {
+            if self.blocks[expr_index] == block && self.is_effect(expr_index)? {
+                self.compile_pending_loads(expressions.start..expr_index)?;
+                self.compile_expr(&ExpressionRef::new(expr_index, 0))?;
             }
         }
 
+        self.prepare_args(outputs)
+            .nest_err(|| format!("outputs of block {block}"))?;
+        while self.stack.len() > base + outputs.len() {
+            self.drop_at(outputs.len())?;
+        }
+
+        self.block = parent;
         Ok(())
     }
 
+    fn assign_blocks(&mut self) {
+        self.blocks = vec![0; self.expressions.len()];
+
+        // Outer ifs come after the inner ones, so they are assigned first.
+        for (expr_index, expr) in self.expressions.iter().enumerate().rev() {
+            if let Expression::If {
+                then, otherwise, ..
+            } = expr
+            {
+                for i in then.expressions.clone() {
+                    self.blocks[i] = 2 * expr_index + 1;
+                }
+                for i in otherwise.expressions.clone() {
+                    self.blocks[i] = 2 * expr_index + 2;
+                }
+            }
+        }
+    }
+
+    fn count_uses(&mut self, outputs: &[ExpressionRef]) {
+        let mut uses = outputs.iter().map(|o| (0, *o)).collect::<Vec<_>>();
+        for (expr_index, expr) in self.expressions.iter().enumerate() {
+            let block = self.blocks[expr_index];
+            uses.extend(expr.uses().into_iter().map(|u| (block, u)));
+
+            if let Expression::If {
+                then, otherwise, ..
+            } = expr
+            {
+                uses.extend(then.outputs.iter().map(|o| (2 * expr_index + 1, *o)));
+                uses.extend(otherwise.outputs.iter().map(|o| (2 * expr_index + 2, *o)));
+            }
+        }
+
+        for key in uses {
+            *self.expr_ref_use_count.entry(key).or_default() += 1;
+        }
+    }
+
     fn compile(mut self, outputs: &[ExpressionRef]) -> Result<Vec<encoder::Instruction<'a>>> {
-        for output in outputs {
-            self.compile_expr(output)?;
+        self.assign_blocks();
+        self.count_uses(outputs);
+
+        let mut inputs = self
+            .expressions
+            .iter()
+            .enumerate()
+            .filter_map(|(expr_index, expr)| match expr {
+                Expression::Input(position) => Some((*position, expr_index)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        inputs.sort_by(|a, b| b.0.cmp(&a.0));
+        for (_, expr_index) in inputs {
+            self.used_exprs.insert(expr_index);
+            self.stack.push(ExpressionRef::new(expr_index, 0));
         }
-        self.align_stack(outputs)?;
-        self.cleanup_stack(outputs.len())?;
+
+        self.compile_block(0, 0..self.expressions.len(), outputs, 0)?;
 
         Ok(self.miden_code)
     }
 }
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -636,17 +1215,19 @@ mod tests {
         let mut builder = Builder::new(&[]);
 
         let boolean = builder.boolean(true);
-        let [a_from_if] = builder.if_(
-            boolean,
-            |builder| {
-                let a = builder.number(1);
-                [a]
-            },
-            |builder| {
-                let a = builder.number(2);
-                [a]
-            },
-        );
+        let [a_from_if] = builder
+            .if_(
+                boolean,
+                |builder| {
+                    let a = builder.number(1);
+                    Ok([a])
+                },
+                |builder| {
+                    let a = builder.number(2);
+                    Ok([a])
+                },
+            )
+            .unwrap();
 
         let compiler = Compiler {
             expressions: builder.build(),
@@ -702,4 +1283,180 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_if_consumes_dependency() {
+        let mut code = Vec::new();
+        build(&mut code, 1, |builder, inputs| {
+            let value = builder.mem_load(1);
+            let [result] = builder.if_(
+                inputs[0],
+                |builder| {
+                    let one = builder.number(1);
+                    Ok([builder.call_one("u32wrapping_add", &[one, value])?])
+                },
+                |_| Ok([value]),
+            )?;
+
+            Ok(vec![result])
+        })
+        .unwrap_or_else(|e| panic!("{e}"));
+
+        assert_eq!(
+            code,
+            vec![
+                encoder::Instruction::MemLoad(Some(1)),
+                encoder::Instruction::Swap,
+                encoder::Instruction::IfTrue,
+                encoder::Instruction::Push(1),
+                encoder::Instruction::U32WrappingAdd,
+                encoder::Instruction::IfEnd,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_before_store() {
+        let mut code = Vec::new();
+        build(&mut code, 0, |builder, _| {
+            let old = builder.mem_load(1);
+            let new = builder.number(2);
+            builder.mem_store(1, new);
+
+            Ok(vec![old])
+        })
+        .unwrap_or_else(|e| panic!("{e}"));
+
+        assert_eq!(
+            code,
+            vec![
+                encoder::Instruction::MemLoad(Some(1)),
+                encoder::Instruction::Push(2),
+                encoder::Instruction::MemStore(Some(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_computing_arg_consumes_placed_arg() {
+        let mut code = Vec::new();
+        build(&mut code, 3, |builder, inputs| {
+            let [quotient, divisor, remainder] = [inputs[0], inputs[1], inputs[2]];
+            let fits = builder.call_one("u32checked_gte", &[divisor, remainder])?;
+            let quotient = builder.call_one("u32checked_add", &[fits, quotient])?;
+            let subtrahend = builder.call_one("u32checked_mul", &[fits, divisor])?;
+            let remainder = builder.call_one("u32checked_sub", &[subtrahend, remainder])?;
+
+            Ok(vec![quotient, divisor, remainder])
+        })
+        .unwrap_or_else(|e| panic!("{e}"));
+
+        assert_eq!(
+            code,
+            vec![
+                encoder::Instruction::Dup(Some(2)),
+                encoder::Instruction::Dup(Some(2)),
+                encoder::Instruction::Swap,
+                encoder::Instruction::Dup(Some(1)),
+                encoder::Instruction::U32CheckedGTE,
+                encoder::Instruction::Dup(None),
+                encoder::Instruction::MovUp(2),
+                encoder::Instruction::Swap,
+                encoder::Instruction::U32CheckedMul,
+                encoder::Instruction::MovUp(4),
+                encoder::Instruction::Swap,
+                encoder::Instruction::U32CheckedSub,
+                encoder::Instruction::MovUp(3),
+                encoder::Instruction::MovUp(3),
+                encoder::Instruction::MovUp(3),
+                encoder::Instruction::U32CheckedAdd,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_while() {
+        let mut code = Vec::new();
+        build(&mut code, 1, |builder, inputs| {
+            let sum = builder.number(0);
+            let [_, sum] = builder.while_(
+                [inputs[0], sum],
+                |builder, [n, _]| {
+                    let zero = builder.number(0);
+                    builder.call_one("u32checked_neq", &[zero, n])
+                },
+                |builder, [n, sum]| {
+                    let one = builder.number(1);
+                    let n = builder.call_one("u32checked_sub", &[one, n])?;
+                    let sum = builder.call_one("u32checked_add", &[n, sum])?;
+                    Ok([n, sum])
+                },
+            )?;
+
+            Ok(vec![sum])
+        })
+        .unwrap_or_else(|e| panic!("{e}"));
+
+        assert_eq!(
+            code,
+            vec![
+                encoder::Instruction::Push(0),
+                encoder::Instruction::Swap,
+                encoder::Instruction::While {
+                    condition: vec![
+                        encoder::Instruction::Swap,
+                        encoder::Instruction::Dup(Some(1)),
+                        encoder::Instruction::Push(0),
+                        encoder::Instruction::U32CheckedNeq,
+                        encoder::Instruction::Swap,
+                        encoder::Instruction::MovUp(2),
+                        encoder::Instruction::MovUp(2),
+                    ],
+                    body: vec![
+                        encoder::Instruction::Swap,
+                        encoder::Instruction::Swap,
+                        encoder::Instruction::Push(1),
+                        encoder::Instruction::U32CheckedSub,
+                        encoder::Instruction::Dup(None),
+                        encoder::Instruction::MovUp(2),
+                        encoder::Instruction::Swap,
+                        encoder::Instruction::U32CheckedAdd,
+                        encoder::Instruction::Swap,
+                    ],
+                },
+                encoder::Instruction::Drop,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repeat() {
+        let mut code = Vec::new();
+        build(&mut code, 0, |builder, _| {
+            let value = builder.mem_load(1);
+            let [value] = builder.repeat(3, [value], |builder, [value]| {
+                let two = builder.number(2);
+                Ok([builder.call_one("u32checked_mul", &[two, value])?])
+            })?;
+            builder.mem_store(1, value);
+
+            Ok(vec![])
+        })
+        .unwrap_or_else(|e| panic!("{e}"));
+
+        assert_eq!(
+            code,
+            vec![
+                encoder::Instruction::MemLoad(Some(1)),
+                encoder::Instruction::Repeat {
+                    count: 3,
+                    instructions: vec![
+                        encoder::Instruction::Push(2),
+                        encoder::Instruction::U32CheckedMul,
+                    ],
+                },
+                encoder::Instruction::MemStore(Some(1)),
+            ]
+        );
+    }
 }
@@ -16,9 +16,11 @@ pub(crate) fn new_map(
     let (values_array, _) = array::new(compiler, len, value_type.clone());
 
     let map_symbol = Symbol {
-        memory_addr: compiler.memory.allocate(WIDTH),
+        memory_addr: compiler.allocate(WIDTH),
         type_: Type::Map(Box::new(key_type), Box::new(value_type)),
     };
+    // the map points to the array headers
+    compiler.memory.pin();
 
     let (map_keys_ptr, map_values_ptr) = key_values_arr(&map_symbol)?;
 
@@ -104,7 +106,7 @@ pub(crate) fn get(
     let (keys_ptr, values_ptr) = key_values_arr(map_symbol)?;
 
     let result = Symbol {
-        memory_addr: compiler.memory.allocate(1),
+        memory_addr: compiler.allocate(1),
         type_: match &keys_ptr.type_ {
             Type::Array(t) => *t.clone(),
             _ => {
@@ -117,7 +119,7 @@ pub(crate) fn get(
         },
     };
 
-    let current_key_symbol = compiler.memory.allocate_symbol(result.type_);
+    let current_key_symbol = compiler.allocate_symbol(result.type_);
     let (key_equality_bool, key_equality_instructions) = {
         let mut inst = vec![];
         std::mem::swap(compiler.instructions, &mut inst);
@@ -139,10 +141,12 @@ pub(crate) fn get(
             .map_err(Into::into)
         }
     };
-    let found_value_symbol = compiler.memory.allocate_symbol(value_type);
-    let found_value_ptr_symbol = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+    let found_value_symbol = compiler.allocate_symbol(value_type);
+    let found_value_ptr_symbol =
+        compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+
+    // Missing keys, and the keys of empty maps, are not found and their value is zero
+    compiler.zero(&key_equality_bool);
 
     compiler.instructions.extend(vec![
         // []
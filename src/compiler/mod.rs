@@ -1,15 +1,21 @@
 mod array;
+mod asm;
 mod boolean;
 mod bytes;
+pub(crate) mod constant;
+mod cost;
 mod encoder;
 mod float32;
 mod float64;
+mod heap;
 mod int32;
 mod int64;
 mod ir;
 mod map;
 mod nullable;
+mod peephole;
 mod publickey;
+mod stdlib;
 mod string;
 mod uint32;
 mod uint64;
@@ -18,6 +24,7 @@ use std::collections::HashMap;
 
 use abi::{Abi, PrimitiveType, StdVersion, Struct, Type};
 use error::prelude::*;
+use miden_processor::utils::Serializable;
 
 use crate::ast::{self, Expression, Statement};
 
@@ -166,7 +173,6 @@ lazy_static::lazy_static! {
             None,
             Function::Builtin(|compiler, _, _| {
                 let symbol = compiler
-                    .memory
                     .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
 
                 compiler.instructions.push(encoder::Instruction::AdvPush(1));
@@ -232,7 +238,7 @@ lazy_static::lazy_static! {
                 ensure_eq_type!(length, Type::PrimitiveType(PrimitiveType::UInt32));
                 ensure_eq_type!(address_ptr, Type::PrimitiveType(PrimitiveType::UInt32));
 
-                let s = compiler.memory.allocate_symbol(Type::Bytes);
+                let s = compiler.allocate_symbol(Type::Bytes);
 
                 compiler.memory.read(
                     compiler.instructions,
@@ -273,7 +279,7 @@ lazy_static::lazy_static! {
                 ensure_eq_type!(use_, Type::PrimitiveType(PrimitiveType::UInt32));
                 ensure_eq_type!(extra_ptr, Type::PrimitiveType(PrimitiveType::UInt32));
 
-                let pk = compiler.memory.allocate_symbol(Type::PublicKey);
+                let pk = compiler.allocate_symbol(Type::PublicKey);
 
                 compiler.memory.read(
                     compiler.instructions,
@@ -346,7 +352,6 @@ lazy_static::lazy_static! {
             ensure_eq_type!(address, Type::PrimitiveType(PrimitiveType::UInt32));
 
             let result = compiler
-                .memory
                 .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
 
             compiler.memory.read(
@@ -392,7 +397,6 @@ lazy_static::lazy_static! {
            let values_hash = compile_function_call(compiler, hash_array_fn, &[values], None)?.unwrap();
 
            let result = compiler
-               .memory
                .allocate_symbol(Type::Hash);
 
            compiler.memory.read(
@@ -435,7 +439,7 @@ lazy_static::lazy_static! {
            None,
            Function::Builtin(|compiler, _scope, args| {
                ensure!(args.len() == 1, ArgumentsCountSnafu { found: args.len(), expected: 1usize });
-               Ok(float32::from_int32(compiler, &args[0]))
+               float32::from_int32(compiler, &args[0])
            })
        ));
 
@@ -597,7 +601,7 @@ lazy_static::lazy_static! {
 
             compiler.instructions.push(encoder::Instruction::AdvPush(1));
             // TODO: assert that the number is actually a u32
-            let symbol = compiler.memory.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+            let symbol = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
             compiler.memory.write(compiler.instructions, symbol.memory_addr, &[ValueSource::Stack]);
             Ok(symbol)
         })));
@@ -605,7 +609,7 @@ lazy_static::lazy_static! {
         builtins.push(("readAdviceUInt64".to_string(), None, Function::Builtin(|compiler, _, args| {
             ensure!(args.is_empty(), ArgumentsCountSnafu { found: args.len(), expected: 0usize });
 
-            let result = compiler.memory.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
+            let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
 
             // high
             compiler.instructions.push(encoder::Instruction::AdvPush(1));
@@ -623,7 +627,7 @@ lazy_static::lazy_static! {
 
             compiler.instructions.push(encoder::Instruction::AdvPush(1));
             // TODO: assert that the number is actually a u32
-            let symbol = compiler.memory.allocate_symbol(Type::PrimitiveType(PrimitiveType::Int32));
+            let symbol = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Int32));
             compiler.memory.write(compiler.instructions, symbol.memory_addr, &[ValueSource::Stack]);
             Ok(symbol)
         })));
@@ -631,7 +635,7 @@ lazy_static::lazy_static! {
         builtins.push(("readAdviceInt64".to_string(), None, Function::Builtin(|compiler, _, args| {
             ensure!(args.is_empty(), ArgumentsCountSnafu { found: args.len(), expected: 0usize });
 
-            let result = compiler.memory.allocate_symbol(Type::PrimitiveType(PrimitiveType::Int64));
+            let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Int64));
 
             // high
             compiler.instructions.push(encoder::Instruction::AdvPush(1));
@@ -649,7 +653,7 @@ lazy_static::lazy_static! {
 
             compiler.instructions.push(encoder::Instruction::AdvPush(1));
             // TODO: assert that the number is actually a u32
-            let symbol = compiler.memory.allocate_symbol(Type::PrimitiveType(PrimitiveType::Float32));
+            let symbol = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Float32));
             compiler.memory.write(compiler.instructions, symbol.memory_addr, &[ValueSource::Stack]);
             Ok(symbol)
         })));
@@ -658,7 +662,7 @@ lazy_static::lazy_static! {
         builtins.push(("readAdviceFloat64".to_string(), None, Function::Builtin(|compiler, _, args| {
             ensure!(args.is_empty(), ArgumentsCountSnafu { found: args.len(), expected: 0usize });
 
-            let result = compiler.memory.allocate_symbol(Type::PrimitiveType(PrimitiveType::Float64));
+            let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Float64));
 
             // high
             compiler.instructions.push(encoder::Instruction::AdvPush(1));
@@ -671,12 +675,25 @@ lazy_static::lazy_static! {
             Ok(result)
         })));
 
+        builtins.push(("readAdviceHash".to_string(), None, Function::Builtin(|compiler, _, args| {
+            ensure!(args.is_empty(), ArgumentsCountSnafu { found: args.len(), expected: 0usize });
+
+            compiler.instructions.push(encoder::Instruction::AdvPush(4));
+            let symbol = compiler.allocate_symbol(Type::Hash);
+            compiler.memory.write(compiler.instructions, symbol.memory_addr, &vec![ValueSource::Stack; 4]);
+            Ok(symbol)
+        })));
+
+        builtins.push(("verifySignature".to_string(), None, Function::Builtin(|compiler, scope, args| {
+            publickey::verify_signature(compiler, scope, args)
+        })));
+
         builtins.push(("readAdviceBoolean".to_string(), None, Function::Builtin(|compiler, _, args| {
             ensure!(args.is_empty(), ArgumentsCountSnafu { found: args.len(), expected: 0usize });
 
             compiler.instructions.push(encoder::Instruction::AdvPush(1));
             // TODO: assert that the number is actually a boolean
-            let symbol = compiler.memory.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+            let symbol = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
             compiler.memory.write(compiler.instructions, symbol.memory_addr, &[ValueSource::Stack]);
             Ok(symbol)
         })));
@@ -696,7 +713,7 @@ lazy_static::lazy_static! {
             ensure_eq_type!(a, Type::PrimitiveType(PrimitiveType::UInt32));
             ensure_eq_type!(b, Type::PrimitiveType(PrimitiveType::UInt32));
 
-            let symbol = compiler.memory.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+            let symbol = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
 
             compiler.memory.read(
                 compiler.instructions,
@@ -720,7 +737,7 @@ lazy_static::lazy_static! {
             ensure_eq_type!(a, Type::PrimitiveType(PrimitiveType::UInt32));
             ensure_eq_type!(b, Type::PrimitiveType(PrimitiveType::UInt32));
 
-            let symbol = compiler.memory.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+            let symbol = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
 
             compiler.memory.read(
                 compiler.instructions,
@@ -744,7 +761,7 @@ lazy_static::lazy_static! {
             ensure_eq_type!(a, Type::PrimitiveType(PrimitiveType::UInt32));
             ensure_eq_type!(b, Type::PrimitiveType(PrimitiveType::UInt32));
 
-            let symbol = compiler.memory.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+            let symbol = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
 
             compiler.memory.read(
                 compiler.instructions,
@@ -768,7 +785,7 @@ lazy_static::lazy_static! {
             ensure_eq_type!(a, Type::PrimitiveType(PrimitiveType::UInt32));
             ensure_eq_type!(b, Type::PrimitiveType(PrimitiveType::UInt32));
 
-            let result = compiler.memory.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+            let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
 
             compiler.memory.read(
                 compiler.instructions,
@@ -790,7 +807,7 @@ lazy_static::lazy_static! {
             let a = &args[0];
             ensure_eq_type!(a, Type::PrimitiveType(PrimitiveType::UInt32));
 
-            let symbol = compiler.memory.allocate_symbol(Type::PrimitiveType(PrimitiveType::Int32));
+            let symbol = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Int32));
 
             compiler.memory.read(
                 compiler.instructions,
@@ -948,6 +965,7 @@ lazy_static::lazy_static! {
         ));
 
         builtins.extend(string::builtins());
+        builtins.extend(stdlib::builtins());
 
         Box::leak(Box::new(builtins))
     };
@@ -1012,6 +1030,8 @@ pub(crate) struct ContractField {
     type_: Type,
     delegate: bool,
     read: bool,
+    /// Only readers that pass the `@read` fields can see the value of the field.
+    private: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -1019,8 +1039,13 @@ struct Contract<'ast> {
     name: String,
     fields: Vec<ContractField>,
     functions: Vec<(String, &'ast ast::Function)>,
+    constants: Vec<(String, constant::Constant)>,
     call_directive: bool,
     read_directive: bool,
+    /// Calls must be signed by `ctx.publicKey`, see `compile_call_message`.
+    signed: bool,
+    /// The events that the functions can emit, see `compile_emit`.
+    events: Vec<Struct>,
 }
 
 impl From<Contract<'_>> for Struct {
@@ -1059,6 +1084,7 @@ pub(crate) struct Scope<'ast, 'b> {
     parent: Option<&'b Scope<'ast, 'b>>,
     symbols: Vec<(String, Symbol)>,
     non_null_symbol_addrs: Vec<u32>,
+    constants: Vec<(String, constant::Constant)>,
     functions: Vec<(String, Function<'ast>)>,
     methods: Vec<(TypeConstraint, String, Function<'ast>)>,
     contracts: Vec<(String, Contract<'ast>)>,
@@ -1070,6 +1096,7 @@ impl<'ast> Scope<'ast, '_> {
             parent: None,
             symbols: vec![],
             non_null_symbol_addrs: vec![],
+            constants: vec![],
             functions: vec![],
             methods: vec![],
             contracts: vec![],
@@ -1081,6 +1108,7 @@ impl<'ast> Scope<'ast, '_> {
             parent: Some(self),
             symbols: vec![],
             non_null_symbol_addrs: vec![],
+            constants: vec![],
             functions: vec![],
             methods: vec![],
             contracts: vec![],
@@ -1109,6 +1137,31 @@ impl<'ast> Scope<'ast, '_> {
         None
     }
 
+    fn find_constant(&self, name: &str) -> Option<&constant::Constant> {
+        if let Some(constant) = self
+            .constants
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .map(|(_, c)| c)
+        {
+            return Some(constant);
+        }
+
+        self.parent.and_then(|p| p.find_constant(name))
+    }
+
+    /// Returns true if the nullable at `addr` was narrowed to non-null,
+    /// e.g. by an enclosing `if (x != null)`.
+    fn is_non_null(&self, addr: u32) -> bool {
+        self.non_null_symbol_addrs.contains(&addr)
+            || self
+                .parent
+                .as_ref()
+                .map(|parent| parent.is_non_null(addr))
+                .unwrap_or(false)
+    }
+
     fn add_function(&mut self, name: String, function: Function<'ast>) {
         self.functions.push((name, function));
     }
@@ -1195,36 +1248,260 @@ impl ValueSource {
     }
 }
 
+/// Programs compiled with [`compile_debug`] store the start of the statement they are executing at this address.
+const STATEMENT_ADDR: u32 = 9;
+
 struct Memory {
     static_alloc_ptr: u32,
+    /// The highest address that was ever allocated.
+    peak: u32,
+    /// Memory below this address is referenced by pointers, such as string literal data,
+    /// so it is never released.
+    pinned: u32,
+    /// Memory at or above this address was never released, so it is still zero-initialized.
+    zeroed_from: u32,
+    /// How many loop bodies are being compiled, memory written in a loop body
+    /// is not zero in the next iteration.
+    loop_depth: u32,
+    /// The address of the `abi::signature_claims_struct`, see `publickey::verify_signature`.
+    signature_claims: Option<u32>,
+    /// The hashes of the records of every contract, see `Compiler::get_record_dependency`.
+    record_depenencies: Vec<(abi::RecordHashes, Symbol)>,
+    /// The contracts and addresses of the arrays of `abi::record_update_struct`,
+    /// see `compile_foreign_call`.
+    record_updates: Vec<(String, u32)>,
+    /// The contracts and addresses of the arrays of booleans, whether `ctx.publicKey`
+    /// can read the `@private` fields of each of the updated records.
+    record_updates_read_auth: Vec<(String, u32)>,
+    /// The contracts and addresses of the `record_cache_struct`s, see `read_record`.
+    record_cache: Vec<(String, u32)>,
+    /// The contracts and addresses of the arrays of `abi::created_record_struct`,
+    /// see `compile_new`.
+    created_records: Vec<(String, u32)>,
+    /// The contracts and addresses of the arrays of booleans, whether `ctx.publicKey`
+    /// can read the `@private` fields of each of the created records.
+    created_records_read_auth: Vec<(String, u32)>,
+    /// The address and types of the array of `abi::event_struct`, see `compile_emit`.
+    events: Option<abi::Events>,
 }
 
 impl Memory {
     fn new() -> Self {
+        // 0 is reserved for the null pointer
+        // 1, 2 and reserved for the error string
+        // 3 is reserved for the dynamic allocation pointer
+        // 4, 5 are reserved for logging
+        // 6 is reserved for the selfdestruct flag
+        // 7 is unused
+        // 8 is reserved for the start of the heap
+        // 9 is reserved for the start of the statement being executed
+        let static_alloc_ptr = 10;
+
         Memory {
-            // 0 is reserved for the null pointer
-            // 1, 2 and reserved for the error string
-            // 3 is reserved for the dynamic allocation pointer
-            // 4, 5 are reserved for logging
-            // 6 is reserved for the selfdestruct flag
-            static_alloc_ptr: 7,
+            static_alloc_ptr,
+            peak: static_alloc_ptr,
+            pinned: static_alloc_ptr,
+            zeroed_from: static_alloc_ptr,
+            loop_depth: 0,
+            signature_claims: None,
+            record_depenencies: Vec::new(),
+            record_updates: Vec::new(),
+            record_updates_read_auth: Vec::new(),
+            record_cache: Vec::new(),
+            created_records: Vec::new(),
+            created_records_read_auth: Vec::new(),
+            events: None,
         }
     }
 
+    /// Allocates memory without zeroing it, which is only zero if it was never used,
+    /// see [`Compiler::allocate`].
     fn allocate(&mut self, size: u32) -> u32 {
         let addr = self.static_alloc_ptr;
         self.static_alloc_ptr += size;
+        self.peak = self.peak.max(self.static_alloc_ptr);
         addr
     }
 
-    fn allocate_symbol(&mut self, type_: Type) -> Symbol {
-        let addr = self.allocate(type_.miden_width());
+    /// Allocates memory above everything allocated so far,
+    /// used for memory that must not overlap with any released temporaries.
+    fn allocate_fresh(&mut self, size: u32) -> u32 {
+        self.static_alloc_ptr = self.peak;
+        self.allocate(size)
+    }
+
+    /// Returns the signature claims of the program, allocated the first time they are used.
+    fn signature_claims(&mut self) -> Symbol {
+        let type_ = Type::Struct(abi::signature_claims_struct());
+        let memory_addr = match self.signature_claims {
+            Some(addr) => addr,
+            None => {
+                let addr = self.allocate_fresh(type_.miden_width());
+                self.pin();
+                self.signature_claims = Some(addr);
+                addr
+            }
+        };
+
         Symbol {
             type_,
-            memory_addr: addr,
+            memory_addr,
+        }
+    }
+
+    /// Returns the updates of the records of a contract, allocated the first time
+    /// one of its functions is called.
+    fn record_updates(&mut self, record: &Struct) -> Symbol {
+        let type_ = Type::Array(Box::new(Type::Struct(abi::record_update_struct(
+            record.clone(),
+        ))));
+        let memory_addr = match self
+            .record_updates
+            .iter()
+            .find(|(contract, _)| *contract == record.name)
+        {
+            Some((_, addr)) => *addr,
+            None => {
+                let addr = self.allocate_fresh(type_.miden_width());
+                self.pin();
+                self.record_updates.push((record.name.clone(), addr));
+                addr
+            }
+        };
+
+        Symbol { type_, memory_addr }
+    }
+
+    /// Returns whether `ctx.publicKey` can read the private fields of each of the updated
+    /// records of a contract, allocated the first time one of them is updated.
+    fn record_updates_read_auth(&mut self, record: &Struct) -> Symbol {
+        let type_ = Type::Array(Box::new(Type::PrimitiveType(PrimitiveType::Boolean)));
+        let memory_addr = match self
+            .record_updates_read_auth
+            .iter()
+            .find(|(contract, _)| *contract == record.name)
+        {
+            Some((_, addr)) => *addr,
+            None => {
+                let addr = self.allocate_fresh(type_.miden_width());
+                self.pin();
+                self.record_updates_read_auth
+                    .push((record.name.clone(), addr));
+                addr
+            }
+        };
+
+        Symbol { type_, memory_addr }
+    }
+
+    /// Returns the cache of the records of a contract updated by foreign calls,
+    /// allocated the first time one of its records is read.
+    fn record_cache(&mut self, record: &Struct) -> Symbol {
+        let type_ = Type::Struct(record_cache_struct(record.clone()));
+        let memory_addr = match self
+            .record_cache
+            .iter()
+            .find(|(contract, _)| *contract == record.name)
+        {
+            Some((_, addr)) => *addr,
+            None => {
+                let addr = self.allocate_fresh(type_.miden_width());
+                self.pin();
+                self.record_cache.push((record.name.clone(), addr));
+                addr
+            }
+        };
+
+        Symbol { type_, memory_addr }
+    }
+
+    /// Returns the records of a contract created with `new`, allocated the first time
+    /// one is created.
+    fn created_records(&mut self, record: &Struct) -> Symbol {
+        let type_ = Type::Array(Box::new(Type::Struct(abi::created_record_struct(
+            record.clone(),
+        ))));
+        let memory_addr = match self
+            .created_records
+            .iter()
+            .find(|(contract, _)| *contract == record.name)
+        {
+            Some((_, addr)) => *addr,
+            None => {
+                let addr = self.allocate_fresh(type_.miden_width());
+                self.pin();
+                self.created_records.push((record.name.clone(), addr));
+                addr
+            }
+        };
+
+        Symbol { type_, memory_addr }
+    }
+
+    /// Returns whether `ctx.publicKey` can read the private fields of each of the created
+    /// records of a contract, allocated the first time one of them is created.
+    fn created_records_read_auth(&mut self, record: &Struct) -> Symbol {
+        let type_ = Type::Array(Box::new(Type::PrimitiveType(PrimitiveType::Boolean)));
+        let memory_addr = match self
+            .created_records_read_auth
+            .iter()
+            .find(|(contract, _)| *contract == record.name)
+        {
+            Some((_, addr)) => *addr,
+            None => {
+                let addr = self.allocate_fresh(type_.miden_width());
+                self.pin();
+                self.created_records_read_auth
+                    .push((record.name.clone(), addr));
+                addr
+            }
+        };
+
+        Symbol { type_, memory_addr }
+    }
+
+    /// Returns the events emitted by the program, allocated the first time one is emitted.
+    fn events(&mut self, types: Vec<abi::EventType>) -> Symbol {
+        let events = match &self.events {
+            Some(events) => events.clone(),
+            None => {
+                let type_ = Type::Array(Box::new(Type::Struct(abi::event_struct(&types))));
+                let addr = self.allocate_fresh(type_.miden_width());
+                self.pin();
+                let events = abi::Events { addr, types };
+                self.events = Some(events.clone());
+                events
+            }
+        };
+
+        Symbol {
+            type_: Type::Array(Box::new(Type::Struct(abi::event_struct(&events.types)))),
+            memory_addr: events.addr,
         }
     }
 
+    /// Prevents everything allocated so far from being released.
+    fn pin(&mut self) {
+        self.pinned = self.pinned.max(self.static_alloc_ptr);
+    }
+
+    /// Returns the current allocation pointer, pass it to `release` when the symbols
+    /// allocated after it are no longer live.
+    fn mark(&self) -> u32 {
+        self.static_alloc_ptr
+    }
+
+    /// Releases the memory allocated since `mark`, so that it can be reused.
+    fn release(&mut self, mark: u32) {
+        self.static_alloc_ptr = mark.max(self.pinned);
+        self.zeroed_from = self.peak;
+    }
+
+    /// Returns true if `addr` was never used before, so it doesn't need to be written to be zero.
+    fn is_zeroed(&self, addr: u32) -> bool {
+        self.loop_depth == 0 && addr >= self.zeroed_from
+    }
+
     /// write(vec![], addr, &[ValueSource::Immediate(0), ValueSource::Immediate(1)])
     /// will set addr to 0 and addr + 1 to 1
     fn write(
@@ -1257,7 +1534,6 @@ pub(crate) struct Compiler<'ast, 'c, 's> {
     instructions: &'c mut Vec<encoder::Instruction<'ast>>,
     memory: &'c mut Memory,
     root_scope: &'c Scope<'ast, 's>,
-    record_depenencies: Vec<(abi::RecordHashes, Symbol)>,
 }
 
 impl<'ast, 'c, 's> Compiler<'ast, 'c, 's> {
@@ -1270,7 +1546,6 @@ impl<'ast, 'c, 's> Compiler<'ast, 'c, 's> {
             instructions,
             memory,
             root_scope,
-            record_depenencies: Vec::new(),
         }
     }
 
@@ -1279,14 +1554,91 @@ impl<'ast, 'c, 's> Compiler<'ast, 'c, 's> {
             .push(encoder::Instruction::Comment(comment));
     }
 
+    /// Allocates `size` words of static memory, which read as zero like memory that was
+    /// never used. Memory that was released, or that is allocated in a loop body,
+    /// is written with zeros first.
+    fn allocate(&mut self, size: u32) -> u32 {
+        let addr = self.memory.allocate(size);
+        let used = (addr..addr + size).filter(|addr| !self.memory.is_zeroed(*addr));
+        for addr in used {
+            self.instructions.extend([
+                encoder::Instruction::Push(0),
+                encoder::Instruction::MemStore(Some(addr)),
+            ]);
+        }
+
+        addr
+    }
+
+    fn allocate_symbol(&mut self, type_: Type) -> Symbol {
+        let addr = self.allocate(type_.miden_width());
+        Symbol {
+            type_,
+            memory_addr: addr,
+        }
+    }
+
+    /// Writes zeros to `symbol`. Symbols allocated while compiling the instructions of a branch
+    /// are only zeroed in that branch, so they must be zeroed before it if it can be skipped.
+    fn zero(&mut self, symbol: &Symbol) {
+        self.memory.write(
+            self.instructions,
+            symbol.memory_addr,
+            &vec![ValueSource::Immediate(0); symbol.type_.miden_width() as usize],
+        );
+    }
+
     fn get_record_dependency(&mut self, col: &Contract) -> Option<Symbol> {
-        self.record_depenencies
+        self.memory
+            .record_depenencies
             .iter()
             .find(|(hashes, _)| hashes.contract == col.name)
             .map(|(_, symbol)| symbol.clone())
     }
 }
 
+/// Matches `x != null`, `x == null` and their mirrored forms,
+/// returning `x` and whether the check is for non-null.
+fn null_check(expr: &Expression) -> Option<(&Expression, bool)> {
+    let is_null = |e: &Expression| matches!(&**e, ast::ExpressionKind::Ident(id) if id == "null");
+    let (a, b, is_not_null_check) = match &**expr {
+        ast::ExpressionKind::Equal(a, b) => (a, b, false),
+        ast::ExpressionKind::NotEqual(a, b) => (a, b, true),
+        _ => return None,
+    };
+
+    match (is_null(a), is_null(b)) {
+        (false, true) => Some((a.as_ref(), is_not_null_check)),
+        (true, false) => Some((b.as_ref(), is_not_null_check)),
+        _ => None,
+    }
+}
+
+fn compile_null_check(compiler: &mut Compiler, x: &Symbol, is_not_null_check: bool) -> Symbol {
+    match x.type_ {
+        Type::Nullable(_) => {
+            compiler.memory.read(
+                compiler.instructions,
+                nullable::is_not_null(x).memory_addr,
+                1,
+            );
+            if !is_not_null_check {
+                compiler.instructions.push(encoder::Instruction::Not);
+            }
+
+            let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+            compiler.memory.write(
+                compiler.instructions,
+                result.memory_addr,
+                &[ValueSource::Stack],
+            );
+            result
+        }
+        // Non-nullable values, including narrowed ones, are never null
+        _ => boolean::new(compiler, is_not_null_check),
+    }
+}
+
 /// Returns None if converting would result in silent truncation
 fn convert_f64_to_f32(n: f64) -> Option<f32> {
     if n as f32 as f64 != n {
@@ -1297,64 +1649,116 @@ fn convert_f64_to_f32(n: f64) -> Option<f32> {
 }
 
 fn compile_expression(expr: &Expression, compiler: &mut Compiler, scope: &Scope) -> Result<Symbol> {
+    compile_or_fold_expression(expr, compiler, scope)?.into_symbol(compiler)
+}
+
+/// Compiles an expression, or folds it if it only depends on constants.
+///
+/// The operands of an operator are folded before the operator, so every subexpression is evaluated once.
+fn compile_or_fold_expression(
+    expr: &Expression,
+    compiler: &mut Compiler,
+    scope: &Scope,
+) -> Result<constant::Folded> {
     comment!(compiler, "Compiling expression {expr:?}");
 
     maybe_start!(expr.span());
 
     use ast::ExpressionKind;
-    let symbol: Symbol = match &**expr {
-        ExpressionKind::Ident(id) => scope.find_symbol(id).not_found("symbol", id)?,
-        ExpressionKind::Primitive(ast::Primitive::Number(n, _has_decimal_point)) => {
-            let n = convert_f64_to_f32(*n).ok_or_else(|| Error::simple("silent f64 truncation"))?;
 
-            float32::new(compiler, n)
-        }
-        ExpressionKind::Primitive(ast::Primitive::String(s)) => string::new(compiler, s).0,
-        ExpressionKind::Boolean(b) => boolean::new(compiler, *b),
-        ExpressionKind::Add(a, b) => {
-            let a = compile_expression(a, compiler, scope)?;
-            let b = compile_expression(b, compiler, scope)?;
+    let operands = match constant::operands(expr) {
+        // `null` is not a constant
+        Some(_) if null_check(expr).is_some() => vec![],
+        Some(operands) => {
+            let operands = operands
+                .into_iter()
+                .map(|operand| compile_or_fold_expression(operand, compiler, scope))
+                .collect::<Result<Vec<_>>>()?;
+            if let Some(folded) = constant::fold_operator(expr, &operands) {
+                comment!(compiler, "Folded expression to {:?}", folded.value);
+                return Ok(constant::Folded::Constant(folded));
+            }
 
-            compile_add(compiler, &a, &b)?
+            operands
+                .into_iter()
+                .map(|operand| operand.into_symbol(compiler))
+                .collect::<Result<Vec<_>>>()?
         }
-        ExpressionKind::Subtract(a, b) => {
-            let a = compile_expression(a, compiler, scope)?;
-            let b = compile_expression(b, compiler, scope)?;
+        None if matches!(
+            &**expr,
+            ExpressionKind::Primitive(_) | ExpressionKind::Boolean(_) | ExpressionKind::Ident(_)
+        ) =>
+        {
+            if let Some(folded) = constant::fold(expr, scope) {
+                return Ok(constant::Folded::Constant(folded));
+            }
 
-            compile_sub(compiler, &a, &b)
+            vec![]
         }
-        ExpressionKind::Modulo(a, b) => {
-            let a = compile_expression(a, compiler, scope)?;
-            let b = compile_expression(b, compiler, scope)?;
+        None => vec![],
+    };
 
-            compile_mod(compiler, &a, &b)
-        }
-        ExpressionKind::Divide(a, b) => {
-            let a = compile_expression(a, compiler, scope)?;
-            let b = compile_expression(b, compiler, scope)?;
+    compile_operator_or_expression(expr, compiler, scope, &operands).map(constant::Folded::Symbol)
+}
 
-            compile_div(compiler, &a, &b)
-        }
-        ExpressionKind::Multiply(a, b) => {
-            let a = compile_expression(a, compiler, scope)?;
-            let b = compile_expression(b, compiler, scope)?;
+/// Compiles an expression that was not folded, `operands` are the compiled operands of an operator.
+fn compile_operator_or_expression(
+    expr: &Expression,
+    compiler: &mut Compiler,
+    scope: &Scope,
+    operands: &[Symbol],
+) -> Result<Symbol> {
+    use ast::ExpressionKind;
 
-            compile_mul(compiler, &a, &b)
+    if let ExpressionKind::Assign(target, _)
+    | ExpressionKind::AssignAdd(target, _)
+    | ExpressionKind::AssignSub(target, _)
+    | ExpressionKind::Increment(target) = &**expr
+    {
+        if let ExpressionKind::Ident(id) = &***target {
+            if scope.find_symbol(id).is_none() && scope.find_constant(id).is_some() {
+                return Err(Error::simple(format!("cannot assign to constant {id}")));
+            }
         }
-        ExpressionKind::Equal(a, b) => {
-            let a = compile_expression(a, compiler, scope)?;
-            let b = compile_expression(b, compiler, scope)?;
+    }
 
-            compile_eq(compiler, &a, &b)?
-        }
-        ExpressionKind::NotEqual(a, b) => {
-            let a = compile_expression(a, compiler, scope)?;
-            let b = compile_expression(b, compiler, scope)?;
+    let symbol: Symbol = match &**expr {
+        ExpressionKind::Ident(id) => match scope.find_symbol(id) {
+            Some(symbol) => symbol,
+            None => {
+                let constant = scope.find_constant(id).not_found("symbol", id)?;
+                constant::materialize(compiler, constant)?
+            }
+        },
+        ExpressionKind::Primitive(ast::Primitive::Number(n, _has_decimal_point)) => {
+            let n = convert_f64_to_f32(*n).ok_or_else(|| Error::simple("silent f64 truncation"))?;
 
-            compile_neq(compiler, &a, &b)
+            float32::new(compiler, n)
         }
-        ExpressionKind::Not(x) => {
+        ExpressionKind::Primitive(ast::Primitive::Integer(n)) => {
+            return Err(Error::simple(format!(
+                "integer literal {n} can only be used in a constant of an integer type"
+            )))
+        }
+        ExpressionKind::Primitive(ast::Primitive::String(s)) => string::new(compiler, s).0,
+        ExpressionKind::Boolean(b) => boolean::new(compiler, *b),
+        ExpressionKind::Add(_, _) => compile_add(compiler, &operands[0], &operands[1])?,
+        ExpressionKind::Subtract(_, _) => compile_sub(compiler, &operands[0], &operands[1])?,
+        ExpressionKind::Modulo(_, _) => compile_mod(compiler, &operands[0], &operands[1])?,
+        ExpressionKind::Divide(_, _) => compile_div(compiler, &operands[0], &operands[1])?,
+        ExpressionKind::Multiply(_, _) => compile_mul(compiler, &operands[0], &operands[1])?,
+        ExpressionKind::Equal(_, _) | ExpressionKind::NotEqual(_, _)
+            if null_check(expr).is_some() =>
+        {
+            let (x, is_not_null_check) = null_check(expr).unwrap();
             let x = compile_expression(x, compiler, scope)?;
+
+            compile_null_check(compiler, &x, is_not_null_check)
+        }
+        ExpressionKind::Equal(_, _) => compile_eq(compiler, &operands[0], &operands[1])?,
+        ExpressionKind::NotEqual(_, _) => compile_neq(compiler, &operands[0], &operands[1])?,
+        ExpressionKind::Not(_) => {
+            let x = &operands[0];
             match x.type_ {
                 Type::PrimitiveType(PrimitiveType::Boolean) => {
                     compiler.memory.read(
@@ -1364,9 +1768,8 @@ fn compile_expression(expr: &Expression, compiler: &mut Compiler, scope: &Scope)
                     );
                     compiler.instructions.push(encoder::Instruction::Not);
 
-                    let result = compiler
-                        .memory
-                        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+                    let result =
+                        compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
                     compiler.memory.write(
                         compiler.instructions,
                         result.memory_addr,
@@ -1377,14 +1780,13 @@ fn compile_expression(expr: &Expression, compiler: &mut Compiler, scope: &Scope)
                 Type::Nullable(_) => {
                     compiler.memory.read(
                         compiler.instructions,
-                        nullable::is_not_null(&x).memory_addr,
-                        nullable::is_not_null(&x).type_.miden_width(),
+                        nullable::is_not_null(x).memory_addr,
+                        nullable::is_not_null(x).type_.miden_width(),
                     );
                     compiler.instructions.push(encoder::Instruction::Not);
 
-                    let result = compiler
-                        .memory
-                        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+                    let result =
+                        compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
                     compiler.memory.write(
                         compiler.instructions,
                         result.memory_addr,
@@ -1432,6 +1834,28 @@ fn compile_expression(expr: &Expression, compiler: &mut Compiler, scope: &Scope)
                 ExpressionKind::Dot(obj_expr, func_name) => {
                     let obj = compile_expression(obj_expr, compiler, scope)?;
 
+                    if let Type::ContractReference { contract } = &obj.type_ {
+                        let args_symbols = args
+                            .iter()
+                            .map(|arg| compile_expression(arg, compiler, scope))
+                            .collect::<Result<Vec<_>>>()?;
+
+                        return Ok(compile_foreign_call(
+                            compiler,
+                            scope,
+                            &obj,
+                            contract,
+                            func_name,
+                            &args_symbols,
+                        )?
+                        .unwrap_or(Symbol {
+                            type_: Type::Nullable(Box::new(Type::PrimitiveType(
+                                PrimitiveType::Boolean,
+                            ))),
+                            memory_addr: 0,
+                        }));
+                    }
+
                     let func = scope
                         .find_method(&obj.type_, func_name)
                         .not_found("object method", func_name)?;
@@ -1458,6 +1882,24 @@ fn compile_expression(expr: &Expression, compiler: &mut Compiler, scope: &Scope)
                 memory_addr: 0,
             })
         }
+        ExpressionKind::Asm(asm) => {
+            let inputs = asm
+                .inputs
+                .iter()
+                .map(|input| compile_expression(input, compiler, scope))
+                .collect::<Result<Vec<_>>>()?;
+            let output = asm.output.as_ref().map(|type_| ast_type_to_type(true, type_));
+
+            asm::compile(compiler, &inputs, output, &asm.code)?
+        }
+        ExpressionKind::New(contract, args) => {
+            let args = args
+                .iter()
+                .map(|arg| compile_expression(arg, compiler, scope))
+                .collect::<Result<Vec<_>>>()?;
+
+            compile_new(compiler, scope, contract, &args)?
+        }
         ExpressionKind::Assign(a, b) => {
             if let (ExpressionKind::Index(a, index), b) = (&***a, b) {
                 let a = compile_expression(a, compiler, scope)?;
@@ -1625,54 +2067,44 @@ fn compile_expression(expr: &Expression, compiler: &mut Compiler, scope: &Scope)
 
             struct_field(compiler, &a, b)?
         }
-        ExpressionKind::GreaterThanOrEqual(a, b) => {
-            let a = compile_expression(a, compiler, scope)?;
-            let b = compile_expression(b, compiler, scope)?;
-
-            compile_gte(compiler, &a, &b)
-        }
-        ExpressionKind::GreaterThan(a, b) => {
+        ExpressionKind::OptionalDot(a, b) => {
             let a = compile_expression(a, compiler, scope)?;
-            let b = compile_expression(b, compiler, scope)?;
 
-            compile_gt(compiler, &a, &b)
+            match a.type_ {
+                Type::Nullable(_) => nullable::optional_field(compiler, &a, b)?,
+                _ => struct_field(compiler, &a, b)?,
+            }
         }
-        ExpressionKind::LessThanOrEqual(a, b) => {
+        ExpressionKind::NullishCoalesce(a, b) => {
             let a = compile_expression(a, compiler, scope)?;
-            let b = compile_expression(b, compiler, scope)?;
 
-            compile_lte(compiler, &a, &b)
+            match a.type_ {
+                Type::Nullable(_) => nullable::coalesce(compiler, &a, |compiler| {
+                    compile_expression(b, compiler, scope)
+                })?,
+                _ => a,
+            }
         }
-        ExpressionKind::LessThan(a, b) => {
-            let a = compile_expression(a, compiler, scope)?;
-            let b = compile_expression(b, compiler, scope)?;
-
-            compile_lt(compiler, &a, &b)
+        ExpressionKind::GreaterThanOrEqual(_, _) => {
+            compile_gte(compiler, &operands[0], &operands[1])?
         }
+        ExpressionKind::GreaterThan(_, _) => compile_gt(compiler, &operands[0], &operands[1])?,
+        ExpressionKind::LessThanOrEqual(_, _) => compile_lte(compiler, &operands[0], &operands[1])?,
+        ExpressionKind::LessThan(_, _) => compile_lt(compiler, &operands[0], &operands[1])?,
         ExpressionKind::ShiftLeft(a, b) => {
             let a = compile_expression(a, compiler, scope)?;
             let b = compile_expression(b, compiler, scope)?;
 
-            compile_shift_left(compiler, &a, &b)
+            compile_shift_left(compiler, &a, &b)?
         }
         ExpressionKind::ShiftRight(a, b) => {
             let a = compile_expression(a, compiler, scope)?;
             let b = compile_expression(b, compiler, scope)?;
 
-            compile_shift_right(compiler, &a, &b)
-        }
-        ExpressionKind::And(a, b) => {
-            let a = compile_expression(a, compiler, scope)?;
-            let b = compile_expression(b, compiler, scope)?;
-
-            boolean::compile_and(compiler, &a, &b)
-        }
-        ExpressionKind::Or(a, b) => {
-            let a = compile_expression(a, compiler, scope)?;
-            let b = compile_expression(b, compiler, scope)?;
-
-            boolean::compile_or(compiler, &a, &b)
+            compile_shift_right(compiler, &a, &b)?
         }
+        ExpressionKind::And(_, _) => boolean::compile_and(compiler, &operands[0], &operands[1]),
+        ExpressionKind::Or(_, _) => boolean::compile_or(compiler, &operands[0], &operands[1]),
         ExpressionKind::Array(exprs) => {
             let all_zeros = exprs.iter().all(|expr| match &**expr {
                 ExpressionKind::Primitive(ast::Primitive::Number(n, _has_decimal_point)) => {
@@ -1739,7 +2171,7 @@ fn compile_expression(expr: &Expression, compiler: &mut Compiler, scope: &Scope)
                 fields: types,
             });
 
-            let symbol = compiler.memory.allocate_symbol(struct_type);
+            let symbol = compiler.allocate_symbol(struct_type);
             for (field, expr_symbol) in values {
                 let field = struct_field(compiler, &symbol, field)?;
                 compiler.memory.read(
@@ -1766,14 +2198,7 @@ fn compile_expression(expr: &Expression, compiler: &mut Compiler, scope: &Scope)
     };
 
     let symbol = match &symbol.type_ {
-        Type::Nullable(_)
-            if scope
-                .non_null_symbol_addrs
-                .iter()
-                .any(|addr| *addr == symbol.memory_addr) =>
-        {
-            nullable::value(symbol)
-        }
+        Type::Nullable(_) if scope.is_non_null(symbol.memory_addr) => nullable::value(symbol),
         _ => symbol,
     };
 
@@ -1785,6 +2210,29 @@ fn compile_expression(expr: &Expression, compiler: &mut Compiler, scope: &Scope)
     Ok(symbol)
 }
 
+/// Compiles a block, statements after a `return`, `throw` or `break` are unreachable and skipped.
+fn compile_statements(
+    statements: &[Statement],
+    compiler: &mut Compiler,
+    scope: &mut Scope,
+    return_result: &Option<&mut Symbol>,
+) -> Result<()> {
+    for statement in statements {
+        compile_statement(statement, compiler, scope, return_result)?;
+
+        if matches!(
+            &**statement,
+            ast::StatementKind::Return(_)
+                | ast::StatementKind::Throw(_)
+                | ast::StatementKind::Break
+        ) {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
 fn compile_statement(
     statement: &Statement,
     compiler: &mut Compiler,
@@ -1792,6 +2240,14 @@ fn compile_statement(
     return_result: &Option<&mut Symbol>,
 ) -> Result<()> {
     maybe_start!(statement.span());
+    let start = compiler.instructions.len();
+    let constant_condition = match &**statement {
+        ast::StatementKind::If(ast::If { condition, .. })
+        | ast::StatementKind::While(ast::While { condition, .. }) => {
+            constant::fold_condition(condition, scope)
+        }
+        _ => None,
+    };
     match &**statement {
         ast::StatementKind::Return(expr) => {
             let symbol = compile_expression(expr, compiler, scope)?;
@@ -1817,80 +2273,138 @@ fn compile_statement(
                 encoder::AbstractInstruction::Break,
             ));
         }
+        ast::StatementKind::If(ast::If {
+            condition: _,
+            then_statements,
+            else_statements,
+        }) if constant_condition.is_some() => {
+            // Only the taken branch is compiled
+            let taken = constant_condition == Some(true);
+
+            let mut scope = scope.deeper();
+            let mark = compiler.memory.mark();
+            compile_statements(
+                if taken {
+                    then_statements
+                } else {
+                    else_statements
+                },
+                compiler,
+                &mut scope,
+                return_result,
+            )?;
+            compiler.memory.release(mark);
+        }
         ast::StatementKind::If(ast::If {
             condition,
             then_statements,
             else_statements,
         }) => {
-            let mut scope = scope.deeper();
-            let mut condition_instructions = vec![];
-            let mut condition_compiler = Compiler::new(
-                &mut condition_instructions,
-                compiler.memory,
-                compiler.root_scope,
-            );
-            let condition_symbol = compile_expression(condition, &mut condition_compiler, &scope)?;
-            // let mut then_cleanup = None;
-            let mut then_scope = scope.deeper();
-            let condition_symbol = match condition_symbol.type_ {
-                Type::PrimitiveType(PrimitiveType::Boolean) => condition_symbol,
-                Type::Nullable(ref _t) => {
-                    then_scope
-                        .non_null_symbol_addrs
-                        .push(condition_symbol.memory_addr);
-
-                    nullable::is_not_null(&condition_symbol)
-                }
-                _ => panic!(
-                    "if condition must be a boolean or optional, got {:?}",
-                    condition_symbol.type_
-                ),
-            };
-            condition_compiler.memory.read(
-                condition_compiler.instructions,
-                condition_symbol.memory_addr,
-                condition_symbol.type_.miden_width(),
-            );
+            // `if (x == null) { return/throw }` narrows `x` for the rest of the block
+            let narrowed_after_if = {
+                let mut narrowed_after_if = None;
+                let if_scope = scope.deeper();
+                let mut condition_instructions = vec![];
+                let mut condition_compiler = Compiler::new(
+                    &mut condition_instructions,
+                    compiler.memory,
+                    compiler.root_scope,
+                );
+                // let mut then_cleanup = None;
+                let mut then_scope = if_scope.deeper();
+                let mut else_scope = if_scope.deeper();
+                let condition_symbol = match null_check(condition) {
+                    Some((x, is_not_null_check)) => {
+                        let x = compile_expression(x, &mut condition_compiler, &if_scope)?;
+                        if let Type::Nullable(_) = x.type_ {
+                            if is_not_null_check {
+                                then_scope.non_null_symbol_addrs.push(x.memory_addr);
+                            } else {
+                                else_scope.non_null_symbol_addrs.push(x.memory_addr);
+
+                                if matches!(
+                                    then_statements.last().map(|s| &**s),
+                                    Some(
+                                        ast::StatementKind::Return(_)
+                                            | ast::StatementKind::Throw(_)
+                                    )
+                                ) {
+                                    narrowed_after_if = Some(x.memory_addr);
+                                }
+                            }
+                        }
 
-            let mut body_instructions = vec![];
-            let mut body_compiler =
-                Compiler::new(&mut body_instructions, compiler.memory, compiler.root_scope);
-            for statement in then_statements {
-                compile_statement(
-                    statement,
+                        compile_null_check(&mut condition_compiler, &x, is_not_null_check)
+                    }
+                    None => compile_expression(condition, &mut condition_compiler, &if_scope)?,
+                };
+                let condition_symbol = match condition_symbol.type_ {
+                    Type::PrimitiveType(PrimitiveType::Boolean) => condition_symbol,
+                    Type::Nullable(ref _t) => {
+                        then_scope
+                            .non_null_symbol_addrs
+                            .push(condition_symbol.memory_addr);
+
+                        nullable::is_not_null(&condition_symbol)
+                    }
+                    _ => panic!(
+                        "if condition must be a boolean or optional, got {:?}",
+                        condition_symbol.type_
+                    ),
+                };
+                condition_compiler.memory.read(
+                    condition_compiler.instructions,
+                    condition_symbol.memory_addr,
+                    condition_symbol.type_.miden_width(),
+                );
+
+                // The branches are never both executed, so they share the same memory
+                let mark = compiler.memory.mark();
+                let mut body_instructions = vec![];
+                let mut body_compiler =
+                    Compiler::new(&mut body_instructions, compiler.memory, compiler.root_scope);
+                compile_statements(
+                    then_statements,
                     &mut body_compiler,
                     &mut then_scope,
                     return_result,
                 )?;
-            }
-            // then_cleanup.map(|f| f());
-
-            let mut else_body_instructions = vec![];
-            let mut else_body_compiler = Compiler::new(
-                &mut else_body_instructions,
-                compiler.memory,
-                compiler.root_scope,
-            );
-            for statement in else_statements {
-                compile_statement(
-                    statement,
+                // then_cleanup.map(|f| f());
+                body_compiler.memory.release(mark);
+
+                let mut else_body_instructions = vec![];
+                let mut else_body_compiler = Compiler::new(
+                    &mut else_body_instructions,
+                    compiler.memory,
+                    compiler.root_scope,
+                );
+                compile_statements(
+                    else_statements,
                     &mut else_body_compiler,
-                    &mut scope,
+                    &mut else_scope,
                     return_result,
                 )?;
-            }
+                else_body_compiler.memory.release(mark);
 
-            compiler.instructions.push(encoder::Instruction::If {
-                condition: condition_instructions,
-                then: body_instructions,
-                else_: else_body_instructions,
-            })
+                compiler.instructions.push(encoder::Instruction::If {
+                    condition: condition_instructions,
+                    then: body_instructions,
+                    else_: else_body_instructions,
+                });
+
+                narrowed_after_if
+            };
+            if let Some(addr) = narrowed_after_if {
+                scope.non_null_symbol_addrs.push(addr);
+            }
         }
+        ast::StatementKind::While(_) if constant_condition == Some(false) => {}
         ast::StatementKind::While(ast::While {
             condition,
             statements,
         }) => {
             let mut scope = scope.deeper();
+            let mark = compiler.memory.mark();
             let mut condition_instructions = vec![];
             let mut condition_compiler = Compiler::new(
                 &mut condition_instructions,
@@ -1911,8 +2425,13 @@ fn compile_statement(
             let mut body_instructions = vec![];
             let mut body_compiler =
                 Compiler::new(&mut body_instructions, compiler.memory, compiler.root_scope);
-            for statement in statements {
-                compile_statement(statement, &mut body_compiler, &mut scope, return_result)?;
+            body_compiler.memory.loop_depth += 1;
+            compile_statements(statements, &mut body_compiler, &mut scope, return_result)?;
+            body_compiler.memory.loop_depth -= 1;
+            body_compiler.memory.release(mark);
+            // The condition is evaluated again after the body
+            if let Some(span) = statement.span() {
+                body_instructions.push(statement_marker(span));
             }
 
             compiler.instructions.push(encoder::Instruction::While {
@@ -1926,6 +2445,7 @@ fn compile_statement(
         }) => {
             // There is no `for` instruction, we have to use `while` instead
             let mut scope = scope.deeper();
+            let mark = compiler.memory.mark();
 
             let mut initial_instructions = vec![];
             let mut condition_instructions = vec![];
@@ -2081,18 +2601,24 @@ fn compile_statement(
                 let mut body_compiler =
                     Compiler::new(&mut body_instructions, compiler.memory, compiler.root_scope);
                 let mut body_scope = scope.deeper();
-                for statement in statements {
-                    compile_statement(
-                        statement,
-                        &mut body_compiler,
-                        &mut body_scope,
-                        return_result,
-                    )?;
+                body_compiler.memory.loop_depth += 1;
+                compile_statements(
+                    statements,
+                    &mut body_compiler,
+                    &mut body_scope,
+                    return_result,
+                )?;
+                body_compiler.memory.loop_depth -= 1;
+                // The post statement and the condition are evaluated after the body
+                if let Some(span) = statement.span() {
+                    body_instructions.push(statement_marker(span));
                 }
                 body_instructions.extend(post_instructions);
                 body_instructions
             };
 
+            compiler.memory.release(mark);
+
             compiler.instructions.extend(initial_instructions);
             compiler.instructions.push(encoder::Instruction::While {
                 condition: condition_instructions,
@@ -2103,21 +2629,46 @@ fn compile_statement(
             compile_let_statement(let_statement, compiler, scope)?
         }
         ast::StatementKind::Expression(expr) => {
+            // The temporaries of an expression statement are dead after it
+            let mark = compiler.memory.mark();
             compile_expression(expr, compiler, scope)?;
+            compiler.memory.release(mark);
         }
         ast::StatementKind::Throw(expr) => {
             compile_expression(expr, compiler, scope)?;
         }
+        ast::StatementKind::Emit(emit) => {
+            let mark = compiler.memory.mark();
+            compile_emit(emit, compiler, scope)?;
+            compiler.memory.release(mark);
+        }
+    }
+
+    // Statements that compiled to nothing, such as dead branches, cost nothing at runtime
+    if let Some(span) = statement.span() {
+        if compiler.instructions.len() > start {
+            compiler.instructions.insert(start, statement_marker(span));
+        }
     }
 
     Ok(())
 }
 
+/// Stores the start of the statement at `STATEMENT_ADDR` when executed,
+/// so that the prover can report which statement failed.
+/// Markers are removed unless compiling with [`compile_debug`].
+fn statement_marker<'a>(span: ast::Span) -> encoder::Instruction<'a> {
+    encoder::Instruction::Abstract(encoder::AbstractInstruction::Statement(abi::SourceSpan {
+        start: span.start,
+        end: span.end - 1,
+    }))
+}
+
 fn add_new_symbol(expr: &Expression, compiler: &mut Compiler, scope: &Scope) -> Result<Symbol> {
     let symbol = compile_expression(expr, compiler, scope)?;
     // we need to copy symbol to a new symbol,
     // because Ident expressions return symbols of variables
-    let new_symbol = compiler.memory.allocate_symbol(symbol.type_);
+    let new_symbol = compiler.allocate_symbol(symbol.type_);
     compiler.memory.read(
         compiler.instructions,
         symbol.memory_addr,
@@ -2183,19 +2734,26 @@ fn compile_ast_function_call(
     scope.parent = Some(compiler.root_scope);
 
     if let Some(this) = this {
+        if let Type::Struct(struct_) = &this.type_ {
+            if let Some(contract) = compiler.root_scope.find_contract(&struct_.name) {
+                scope.constants.extend(contract.constants.iter().cloned());
+            }
+        }
+
         scope.add_symbol("this".to_string(), this);
     }
 
-    let mut return_result = function.return_type.as_ref().map(|ty| {
-        function_compiler
-            .memory
-            .allocate_symbol(ast_type_to_type(true, ty))
-    });
+    let mut return_result = function
+        .return_type
+        .as_ref()
+        .map(|ty| function_compiler.allocate_symbol(ast_type_to_type(true, ty)));
+    // Only the return value outlives the function body
+    let mark = function_compiler.memory.mark();
     for (arg, param) in args.iter().zip(function.parameters.iter()) {
         // We need to make a copy of the arg, because Ident expressions return symbols of variables.
         // Modifying them in a function would modify the original variable.
         // TODO: fix this
-        let new_arg = function_compiler.memory.allocate_symbol(arg.type_.clone());
+        let new_arg = function_compiler.allocate_symbol(arg.type_.clone());
         function_compiler.memory.read(
             function_compiler.instructions,
             arg.memory_addr,
@@ -2210,14 +2768,13 @@ fn compile_ast_function_call(
         scope.add_symbol(param.name.clone(), new_arg);
     }
 
-    for statement in &function.statements {
-        compile_statement(
-            statement,
-            &mut function_compiler,
-            scope,
-            &return_result.as_mut(),
-        )?;
-    }
+    compile_statements(
+        &function.statements,
+        &mut function_compiler,
+        scope,
+        &return_result.as_mut(),
+    )?;
+    function_compiler.memory.release(mark);
 
     compiler.instructions.push(encoder::Instruction::Abstract(
         encoder::AbstractInstruction::InlinedFunction(function_instructions),
@@ -2259,15 +2816,13 @@ fn compile_add(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol
             Type::PrimitiveType(PrimitiveType::UInt64),
         ) => uint64::add(compiler, a, b),
         (Type::PrimitiveType(PrimitiveType::Int32), Type::PrimitiveType(PrimitiveType::Int32)) => {
-            int32::add(compiler, a, b)
+            int32::add(compiler, a, b)?
         }
         (
             Type::PrimitiveType(PrimitiveType::UInt64),
             Type::PrimitiveType(PrimitiveType::UInt32),
         ) => {
-            let b_u64 = compiler
-                .memory
-                .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
+            let b_u64 = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
             cast(compiler, b, &b_u64);
 
             uint64::add(compiler, a, &b_u64)
@@ -2275,14 +2830,14 @@ fn compile_add(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol
         (
             Type::PrimitiveType(PrimitiveType::Float32),
             Type::PrimitiveType(PrimitiveType::Float32),
-        ) => float32::add(compiler, a, b),
+        ) => float32::add(compiler, a, b)?,
         (Type::String, Type::String) => string::concat(compiler, a, b)?,
         (a, b) => return Err(Error::unimplemented(format!("{a:?} add {b:?}"))),
     })
 }
 
-fn compile_sub(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    match (&a.type_, &b.type_) {
+fn compile_sub(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    Ok(match (&a.type_, &b.type_) {
         (
             Type::PrimitiveType(PrimitiveType::UInt32),
             Type::PrimitiveType(PrimitiveType::UInt32),
@@ -2292,15 +2847,13 @@ fn compile_sub(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
             Type::PrimitiveType(PrimitiveType::UInt64),
         ) => uint64::sub(compiler, a, b),
         (Type::PrimitiveType(PrimitiveType::Int32), Type::PrimitiveType(PrimitiveType::Int32)) => {
-            int32::sub(compiler, a, b)
+            int32::sub(compiler, a, b)?
         }
         (
             Type::PrimitiveType(PrimitiveType::UInt64),
             Type::PrimitiveType(PrimitiveType::UInt32),
         ) => {
-            let b_u64 = compiler
-                .memory
-                .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
+            let b_u64 = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
             cast(compiler, b, &b_u64);
 
             uint64::sub(compiler, a, &b_u64)
@@ -2308,13 +2861,13 @@ fn compile_sub(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
         (
             Type::PrimitiveType(PrimitiveType::Float32),
             Type::PrimitiveType(PrimitiveType::Float32),
-        ) => float32::sub(compiler, a, b),
+        ) => float32::sub(compiler, a, b)?,
         e => unimplemented!("{:?}", e),
-    }
+    })
 }
 
-fn compile_mod(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    match (&a.type_, &b.type_) {
+fn compile_mod(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    Ok(match (&a.type_, &b.type_) {
         (
             Type::PrimitiveType(PrimitiveType::UInt32),
             Type::PrimitiveType(PrimitiveType::UInt32),
@@ -2324,25 +2877,23 @@ fn compile_mod(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
             Type::PrimitiveType(PrimitiveType::UInt64),
         ) => uint64::modulo(compiler, a, b),
         (Type::PrimitiveType(PrimitiveType::Int32), Type::PrimitiveType(PrimitiveType::Int32)) => {
-            int32::modulo(compiler, a, b)
+            int32::modulo(compiler, a, b)?
         }
         (
             Type::PrimitiveType(PrimitiveType::UInt64),
             Type::PrimitiveType(PrimitiveType::UInt32),
         ) => {
-            let b_u64 = compiler
-                .memory
-                .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
+            let b_u64 = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
             cast(compiler, b, &b_u64);
 
             uint64::modulo(compiler, a, &b_u64)
         }
         e => unimplemented!("{:?}", e),
-    }
+    })
 }
 
-fn compile_div(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    match (&a.type_, &b.type_) {
+fn compile_div(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    Ok(match (&a.type_, &b.type_) {
         (
             Type::PrimitiveType(PrimitiveType::UInt32),
             Type::PrimitiveType(PrimitiveType::UInt32),
@@ -2352,15 +2903,13 @@ fn compile_div(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
             Type::PrimitiveType(PrimitiveType::UInt64),
         ) => uint64::div(compiler, a, b),
         (Type::PrimitiveType(PrimitiveType::Int32), Type::PrimitiveType(PrimitiveType::Int32)) => {
-            int32::div(compiler, a, b)
+            int32::div(compiler, a, b)?
         }
         (
             Type::PrimitiveType(PrimitiveType::UInt64),
             Type::PrimitiveType(PrimitiveType::UInt32),
         ) => {
-            let b_u64 = compiler
-                .memory
-                .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
+            let b_u64 = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
             cast(compiler, b, &b_u64);
 
             uint64::div(compiler, a, &b_u64)
@@ -2368,13 +2917,13 @@ fn compile_div(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
         (
             Type::PrimitiveType(PrimitiveType::Float32),
             Type::PrimitiveType(PrimitiveType::Float32),
-        ) => float32::div(compiler, a, b),
+        ) => float32::div(compiler, a, b)?,
         e => unimplemented!("{:?}", e),
-    }
+    })
 }
 
-fn compile_mul(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    match (&a.type_, &b.type_) {
+fn compile_mul(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    Ok(match (&a.type_, &b.type_) {
         (
             Type::PrimitiveType(PrimitiveType::UInt32),
             Type::PrimitiveType(PrimitiveType::UInt32),
@@ -2384,15 +2933,13 @@ fn compile_mul(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
             Type::PrimitiveType(PrimitiveType::UInt64),
         ) => uint64::mul(compiler, a, b),
         (Type::PrimitiveType(PrimitiveType::Int32), Type::PrimitiveType(PrimitiveType::Int32)) => {
-            int32::mul(compiler, a, b)
+            int32::mul(compiler, a, b)?
         }
         (
             Type::PrimitiveType(PrimitiveType::UInt64),
             Type::PrimitiveType(PrimitiveType::UInt32),
         ) => {
-            let b_u64 = compiler
-                .memory
-                .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
+            let b_u64 = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
             cast(compiler, b, &b_u64);
 
             uint64::mul(compiler, a, &b_u64)
@@ -2400,9 +2947,9 @@ fn compile_mul(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
         (
             Type::PrimitiveType(PrimitiveType::Float32),
             Type::PrimitiveType(PrimitiveType::Float32),
-        ) => float32::mul(compiler, a, b),
+        ) => float32::mul(compiler, a, b)?,
         e => unimplemented!("{:?}", e),
-    }
+    })
 }
 
 fn compile_eq(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
@@ -2419,9 +2966,7 @@ fn compile_eq(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol>
             Type::PrimitiveType(PrimitiveType::UInt64),
             Type::PrimitiveType(PrimitiveType::UInt32),
         ) => {
-            let b_u64 = compiler
-                .memory
-                .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
+            let b_u64 = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
             cast(compiler, b, &b_u64);
 
             uint64::eq(compiler, a, &b_u64)
@@ -2432,11 +2977,9 @@ fn compile_eq(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol>
         (
             Type::PrimitiveType(PrimitiveType::Float32),
             Type::PrimitiveType(PrimitiveType::Float32),
-        ) => float32::eq(compiler, a, b),
+        ) => float32::eq(compiler, a, b)?,
         (Type::Hash, Type::Hash) => {
-            let result = compiler
-                .memory
-                .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+            let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
 
             compiler
                 .instructions
@@ -2491,17 +3034,15 @@ fn compile_eq(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol>
     })
 }
 
-fn compile_neq(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
+fn compile_neq(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
     if a.type_ == Type::PrimitiveType(PrimitiveType::Float32)
         && b.type_ == Type::PrimitiveType(PrimitiveType::Float32)
     {
         return float32::ne(compiler, a, b);
     }
 
-    let eq = compile_eq(compiler, a, b).unwrap();
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+    let eq = compile_eq(compiler, a, b)?;
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
     compiler.memory.read(
         compiler.instructions,
         eq.memory_addr,
@@ -2513,11 +3054,11 @@ fn compile_neq(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
         result.memory_addr,
         &vec![ValueSource::Stack; result.type_.miden_width() as _],
     );
-    result
+    Ok(result)
 }
 
-fn compile_gte(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    match (&a.type_, &b.type_) {
+fn compile_gte(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    Ok(match (&a.type_, &b.type_) {
         (
             Type::PrimitiveType(PrimitiveType::UInt32),
             Type::PrimitiveType(PrimitiveType::UInt32),
@@ -2527,15 +3068,13 @@ fn compile_gte(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
             Type::PrimitiveType(PrimitiveType::UInt64),
         ) => uint64::gte(compiler, a, b),
         (Type::PrimitiveType(PrimitiveType::Int32), Type::PrimitiveType(PrimitiveType::Int32)) => {
-            int32::gte(compiler, a, b)
+            int32::gte(compiler, a, b)?
         }
         (
             Type::PrimitiveType(PrimitiveType::UInt64),
             Type::PrimitiveType(PrimitiveType::UInt32),
         ) => {
-            let b_u64 = compiler
-                .memory
-                .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
+            let b_u64 = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
             cast(compiler, b, &b_u64);
 
             uint64::gte(compiler, a, &b_u64)
@@ -2543,13 +3082,13 @@ fn compile_gte(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
         (
             Type::PrimitiveType(PrimitiveType::Float32),
             Type::PrimitiveType(PrimitiveType::Float32),
-        ) => float32::gte(compiler, a, b),
+        ) => float32::gte(compiler, a, b)?,
         e => unimplemented!("{:?}", e),
-    }
+    })
 }
 
-fn compile_gt(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    match (&a.type_, &b.type_) {
+fn compile_gt(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    Ok(match (&a.type_, &b.type_) {
         (
             Type::PrimitiveType(PrimitiveType::UInt32),
             Type::PrimitiveType(PrimitiveType::UInt32),
@@ -2559,15 +3098,13 @@ fn compile_gt(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
             Type::PrimitiveType(PrimitiveType::UInt64),
         ) => uint64::gt(compiler, a, b),
         (Type::PrimitiveType(PrimitiveType::Int32), Type::PrimitiveType(PrimitiveType::Int32)) => {
-            int32::gt(compiler, a, b)
+            int32::gt(compiler, a, b)?
         }
         (
             Type::PrimitiveType(PrimitiveType::UInt64),
             Type::PrimitiveType(PrimitiveType::UInt32),
         ) => {
-            let b_u64 = compiler
-                .memory
-                .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
+            let b_u64 = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
             cast(compiler, b, &b_u64);
 
             uint64::gt(compiler, a, &b_u64)
@@ -2575,13 +3112,13 @@ fn compile_gt(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
         (
             Type::PrimitiveType(PrimitiveType::Float32),
             Type::PrimitiveType(PrimitiveType::Float32),
-        ) => float32::gt(compiler, a, b),
+        ) => float32::gt(compiler, a, b)?,
         e => unimplemented!("{:?}", e),
-    }
+    })
 }
 
-fn compile_lte(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    match (&a.type_, &b.type_) {
+fn compile_lte(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    Ok(match (&a.type_, &b.type_) {
         (
             Type::PrimitiveType(PrimitiveType::UInt32),
             Type::PrimitiveType(PrimitiveType::UInt32),
@@ -2591,15 +3128,13 @@ fn compile_lte(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
             Type::PrimitiveType(PrimitiveType::UInt64),
         ) => uint64::lte(compiler, a, b),
         (Type::PrimitiveType(PrimitiveType::Int32), Type::PrimitiveType(PrimitiveType::Int32)) => {
-            int32::lte(compiler, a, b)
+            int32::lte(compiler, a, b)?
         }
         (
             Type::PrimitiveType(PrimitiveType::UInt64),
             Type::PrimitiveType(PrimitiveType::UInt32),
         ) => {
-            let b_u64 = compiler
-                .memory
-                .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
+            let b_u64 = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
             cast(compiler, b, &b_u64);
 
             uint64::lte(compiler, a, &b_u64)
@@ -2607,13 +3142,13 @@ fn compile_lte(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
         (
             Type::PrimitiveType(PrimitiveType::Float32),
             Type::PrimitiveType(PrimitiveType::Float32),
-        ) => float32::lte(compiler, a, b),
+        ) => float32::lte(compiler, a, b)?,
         e => unimplemented!("{:?}", e),
-    }
+    })
 }
 
-fn compile_lt(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    match (&a.type_, &b.type_) {
+fn compile_lt(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    Ok(match (&a.type_, &b.type_) {
         (
             Type::PrimitiveType(PrimitiveType::UInt32),
             Type::PrimitiveType(PrimitiveType::UInt32),
@@ -2623,15 +3158,13 @@ fn compile_lt(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
             Type::PrimitiveType(PrimitiveType::UInt64),
         ) => uint64::lt(compiler, a, b),
         (Type::PrimitiveType(PrimitiveType::Int32), Type::PrimitiveType(PrimitiveType::Int32)) => {
-            int32::lt(compiler, a, b)
+            int32::lt(compiler, a, b)?
         }
         (
             Type::PrimitiveType(PrimitiveType::UInt64),
             Type::PrimitiveType(PrimitiveType::UInt32),
         ) => {
-            let b_u64 = compiler
-                .memory
-                .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
+            let b_u64 = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
             cast(compiler, b, &b_u64);
 
             uint64::lt(compiler, a, &b_u64)
@@ -2639,13 +3172,13 @@ fn compile_lt(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
         (
             Type::PrimitiveType(PrimitiveType::Float32),
             Type::PrimitiveType(PrimitiveType::Float32),
-        ) => float32::lt(compiler, a, b),
+        ) => float32::lt(compiler, a, b)?,
         e => unimplemented!("{:?}", e),
-    }
+    })
 }
 
-fn compile_shift_left(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    match (&a.type_, &b.type_) {
+fn compile_shift_left(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    Ok(match (&a.type_, &b.type_) {
         (
             Type::PrimitiveType(PrimitiveType::UInt32),
             Type::PrimitiveType(PrimitiveType::UInt32),
@@ -2655,25 +3188,23 @@ fn compile_shift_left(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol
             Type::PrimitiveType(PrimitiveType::UInt64),
         ) => uint64::shift_left(compiler, a, b),
         (Type::PrimitiveType(PrimitiveType::Int32), Type::PrimitiveType(PrimitiveType::Int32)) => {
-            int32::shift_left(compiler, a, b)
+            int32::shift_left(compiler, a, b)?
         }
         (
             Type::PrimitiveType(PrimitiveType::UInt64),
             Type::PrimitiveType(PrimitiveType::UInt32),
         ) => {
-            let b_u64 = compiler
-                .memory
-                .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
+            let b_u64 = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
             cast(compiler, b, &b_u64);
 
             uint64::shift_left(compiler, a, &b_u64)
         }
         e => unimplemented!("{:?}", e),
-    }
+    })
 }
 
-fn compile_shift_right(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    match (&a.type_, &b.type_) {
+fn compile_shift_right(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
+    Ok(match (&a.type_, &b.type_) {
         (
             Type::PrimitiveType(PrimitiveType::UInt32),
             Type::PrimitiveType(PrimitiveType::UInt32),
@@ -2683,21 +3214,19 @@ fn compile_shift_right(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbo
             Type::PrimitiveType(PrimitiveType::UInt64),
         ) => uint64::shift_right(compiler, a, b),
         (Type::PrimitiveType(PrimitiveType::Int32), Type::PrimitiveType(PrimitiveType::Int32)) => {
-            int32::shift_right(compiler, a, b)
+            int32::shift_right(compiler, a, b)?
         }
         (
             Type::PrimitiveType(PrimitiveType::UInt64),
             Type::PrimitiveType(PrimitiveType::UInt32),
         ) => {
-            let b_u64 = compiler
-                .memory
-                .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
+            let b_u64 = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
             cast(compiler, b, &b_u64);
 
             uint64::shift_right(compiler, a, &b_u64)
         }
         e => unimplemented!("{:?}", e),
-    }
+    })
 }
 
 fn compile_index(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<Symbol> {
@@ -2736,36 +3265,7 @@ fn dynamic_alloc(compiler: &mut Compiler, args: &[Symbol]) -> Result<Symbol> {
         }
     );
 
-    let addr = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
-
-    compiler
-        .instructions
-        .push(encoder::Instruction::MemLoad(Some(3)));
-    compiler.instructions.push(encoder::Instruction::Dup(None));
-    compiler.memory.write(
-        compiler.instructions,
-        addr.memory_addr,
-        &[ValueSource::Stack],
-    );
-    compiler.memory.read(
-        compiler.instructions,
-        size.memory_addr,
-        size.type_.miden_width(),
-    );
-    // old addr + size
-    compiler
-        .instructions
-        .push(encoder::Instruction::U32CheckedAdd);
-
-    // store new addr
-    compiler
-        .instructions
-        .push(encoder::Instruction::MemStore(Some(3)));
-
-    // return old addr
-    Ok(addr)
+    heap::alloc(compiler, size)
 }
 
 fn log(compiler: &mut Compiler, scope: &mut Scope, args: &[Symbol]) -> Result<Symbol> {
@@ -2829,7 +3329,7 @@ fn read_advice_contract_reference(compiler: &mut Compiler, contract: String) ->
 }
 
 fn read_advice_public_key(compiler: &mut Compiler) -> Result<Symbol> {
-    let result = compiler.memory.allocate_symbol(Type::PublicKey);
+    let result = compiler.allocate_symbol(Type::PublicKey);
 
     compiler.instructions.push(encoder::Instruction::AdvPush(1));
     compiler.memory.write(
@@ -2903,7 +3403,7 @@ fn read_advice_public_key(compiler: &mut Compiler) -> Result<Symbol> {
 }
 
 fn read_advice_string(compiler: &mut Compiler) -> Result<Symbol> {
-    let result = compiler.memory.allocate_symbol(Type::String);
+    let result = compiler.allocate_symbol(Type::String);
 
     compiler.instructions.push(encoder::Instruction::AdvPush(1));
     // [str_len]
@@ -2983,9 +3483,7 @@ fn read_advice_array(compiler: &mut Compiler, element_type: &Type) -> Result<Sym
 
     compiler.instructions.push(encoder::Instruction::Dup(None));
     // [array_len, array_len]
-    let array_len = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+    let array_len = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
     compiler.memory.write(
         compiler.instructions,
         array_len.memory_addr,
@@ -2993,9 +3491,7 @@ fn read_advice_array(compiler: &mut Compiler, element_type: &Type) -> Result<Sym
     );
     // [array_len]
 
-    let capacity = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+    let size = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
     compiler.instructions.push(encoder::Instruction::Push(2));
     // [2, array_len]
     // capacity is 2x the length, because reallocating is expensive
@@ -3003,14 +3499,21 @@ fn read_advice_array(compiler: &mut Compiler, element_type: &Type) -> Result<Sym
         .instructions
         .push(encoder::Instruction::U32CheckedMul);
     // [capacity = array_len * 2]
+    compiler
+        .instructions
+        .push(encoder::Instruction::Push(element_type.miden_width()));
+    compiler
+        .instructions
+        .push(encoder::Instruction::U32CheckedMul);
+    // [size = capacity * width]
     compiler.memory.write(
         compiler.instructions,
-        capacity.memory_addr,
+        size.memory_addr,
         &[ValueSource::Stack],
     );
     // []
 
-    let data_ptr = dynamic_alloc(compiler, &[capacity])?;
+    let data_ptr = dynamic_alloc(compiler, &[size])?;
 
     let read_element_advice_insts = {
         let mut insts = vec![];
@@ -3092,9 +3595,7 @@ fn read_advice_array(compiler: &mut Compiler, element_type: &Type) -> Result<Sym
     compiler.instructions.push(encoder::Instruction::Drop);
     // []
 
-    let arr = compiler
-        .memory
-        .allocate_symbol(Type::Array(Box::new(element_type.clone())));
+    let arr = compiler.allocate_symbol(Type::Array(Box::new(element_type.clone())));
 
     compiler.memory.write(
         compiler.instructions,
@@ -3130,7 +3631,7 @@ fn read_advice_array(compiler: &mut Compiler, element_type: &Type) -> Result<Sym
 
 fn read_advice_map(compiler: &mut Compiler, key_type: &Type, value_type: &Type) -> Result<Symbol> {
     // Maps are serialized as [keys_arr..., values_arr...]
-    let result = compiler.memory.allocate_symbol(Type::Map(
+    let result = compiler.allocate_symbol(Type::Map(
         Box::new(key_type.clone()),
         Box::new(value_type.clone()),
     ));
@@ -3198,7 +3699,7 @@ fn read_advice_nullable(compiler: &mut Compiler, type_: Type) -> Result<Symbol>
         else_: vec![],
     });
 
-    let s = compiler.memory.allocate_symbol(type_);
+    let s = compiler.allocate_symbol(type_);
     compiler.memory.read(
         compiler.instructions,
         is_not_null.memory_addr,
@@ -3226,7 +3727,7 @@ fn read_advice_nullable(compiler: &mut Compiler, type_: Type) -> Result<Symbol>
 /// A generic hash function that can hash any symbol by hashing each of it's field elements.
 /// Not useful for hashing strings, or any data structure that uses pointers.
 fn generic_hash(compiler: &mut Compiler, value: &Symbol) -> Symbol {
-    let result = compiler.memory.allocate_symbol(Type::Hash);
+    let result = compiler.allocate_symbol(Type::Hash);
 
     compiler.instructions.extend([
         encoder::Instruction::Push(0),
@@ -3266,7 +3767,7 @@ fn generic_hash(compiler: &mut Compiler, value: &Symbol) -> Symbol {
 fn hash(compiler: &mut Compiler, value: Symbol) -> Result<Symbol> {
     let result = match &value.type_ {
         Type::Nullable(_) => {
-            let h = compiler.memory.allocate_symbol(Type::Hash);
+            let h = compiler.allocate_symbol(Type::Hash);
 
             let mut hash_value_instructions = vec![];
             std::mem::swap(compiler.instructions, &mut hash_value_instructions);
@@ -3352,7 +3853,7 @@ fn hash(compiler: &mut Compiler, value: Symbol) -> Result<Symbol> {
         .unwrap(),
         Type::Struct(s) => {
             let mut offset = 0;
-            let struct_hash = compiler.memory.allocate_symbol(Type::Hash);
+            let struct_hash = compiler.allocate_symbol(Type::Hash);
             for (_, field_type) in &s.fields {
                 let width = field_type.miden_width();
                 let field = Symbol {
@@ -3399,9 +3900,9 @@ fn hash(compiler: &mut Compiler, value: Symbol) -> Result<Symbol> {
 
 fn add_salt_to_hash(compiler: &mut Compiler, hash: &Symbol, salt: &Symbol) -> Result<Symbol> {
     ensure_eq_type!(hash, Type::Hash);
-    ensure_eq_type!(salt, Type::PrimitiveType(PrimitiveType::UInt32));
+    ensure_eq_type!(salt, Type::Hash);
 
-    let result = compiler.memory.allocate_symbol(Type::Hash);
+    let result = compiler.allocate_symbol(Type::Hash);
 
     compiler.memory.read(
         compiler.instructions,
@@ -3413,9 +3914,6 @@ fn add_salt_to_hash(compiler: &mut Compiler, hash: &Symbol, salt: &Symbol) -> Re
         salt.memory_addr,
         salt.type_.miden_width(),
     );
-    for _ in 0..3 {
-        compiler.instructions.push(encoder::Instruction::Push(0));
-    }
     compiler.instructions.push(encoder::Instruction::HMerge);
 
     compiler.memory.write(
@@ -3442,7 +3940,7 @@ fn hash_record_with_salts(
         unreachable!()
     };
 
-    let result = compiler.memory.allocate_symbol(Type::Hash);
+    let result = compiler.allocate_symbol(Type::Hash);
     for (i, (field_name, _)) in struct_.fields.iter().enumerate() {
         let salt = &field_salts[i];
         let field_symbol = struct_field(compiler, struct_symbol, field_name)?;
@@ -3553,7 +4051,7 @@ fn read_advice_generic(compiler: &mut Compiler, type_: &Type) -> Result<Symbol>
         }
         Type::Array(t) => read_advice_array(compiler, t)?,
         Type::Struct(s) => {
-            let symbol = compiler.memory.allocate_symbol(type_.clone());
+            let symbol = compiler.allocate_symbol(type_.clone());
             read_struct_from_advice_tape(compiler, &symbol, s, None)?;
             symbol
         }
@@ -3565,6 +4063,13 @@ fn read_advice_generic(compiler: &mut Compiler, type_: &Type) -> Result<Symbol>
         )?
         .unwrap(),
         Type::Map(k, v) => read_advice_map(compiler, k, v)?,
+        Type::Hash => compile_function_call(
+            compiler,
+            BUILTINS_SCOPE.find_function("readAdviceHash").unwrap(),
+            &[],
+            None,
+        )?
+        .unwrap(),
         _ => {
             return Err(Error::unimplemented(format!(
                 "read_advice_generic {type_:?}"
@@ -3636,7 +4141,7 @@ fn read_contract_inputs(
     args: &[Type],
     lazy: Option<&[Symbol]>,
 ) -> Result<ContractInputsReadOutputType> {
-    let this = this_struct.map(|ts| compiler.memory.allocate_symbol(Type::Struct(ts)));
+    let this = this_struct.map(|ts| compiler.allocate_symbol(Type::Struct(ts)));
     let mut salts = vec![];
 
     if let Some(this) = this.as_ref() {
@@ -3648,7 +4153,7 @@ fn read_contract_inputs(
         salts = struct_ty
             .fields
             .iter()
-            .map(|_| read_advice_generic(compiler, &Type::PrimitiveType(PrimitiveType::UInt32)))
+            .map(|_| read_advice_generic(compiler, &Type::Hash))
             .collect::<Result<Vec<_>>>()?;
 
         read_struct_from_advice_tape(compiler, this, struct_ty, lazy)?;
@@ -3662,7 +4167,7 @@ fn read_contract_inputs(
     Ok((this.map(|t| (salts, t)), args_symbols))
 }
 
-fn prepare_scope(program: &ast::Program) -> Scope {
+fn prepare_scope(program: &ast::Program) -> Result<Scope> {
     let mut scope = Scope::new();
 
     for (name, type_, func) in USABLE_BUILTINS.iter() {
@@ -3672,12 +4177,20 @@ fn prepare_scope(program: &ast::Program) -> Scope {
         }
     }
 
+    for node in &program.nodes {
+        if let ast::RootNode::Const(c) = node {
+            let constant = constant::new(c, &scope.constants)?;
+            scope.constants.push((c.name.clone(), constant));
+        }
+    }
+
     for node in &program.nodes {
         match node {
             ast::RootNode::Contract(c) => {
                 let mut contract = Contract {
                     name: c.name.clone(),
                     functions: vec![],
+                    constants: vec![],
                     fields: vec![],
                     call_directive: match c.decorators.iter().find(|d| {
                         d.name == "call"
@@ -3712,6 +4225,8 @@ fn prepare_scope(program: &ast::Program) -> Scope {
                         // collections are public by default
                         None => true,
                     },
+                    signed: c.decorators.iter().any(|d| d.name == "signed"),
+                    events: vec![],
                 };
 
                 for item in &c.items {
@@ -3722,12 +4237,35 @@ fn prepare_scope(program: &ast::Program) -> Scope {
                                 type_: ast_type_to_type(f.required, &f.type_),
                                 delegate: f.decorators.iter().any(|d| d.name == "delegate"),
                                 read: f.decorators.iter().any(|d| d.name == "read"),
+                                private: f.decorators.iter().any(|d| d.name == "private"),
                             });
                         }
                         ast::ContractItem::Function(f) => {
                             contract.functions.push((f.name.clone(), f));
                         }
                         ast::ContractItem::Index(_) => {}
+                        ast::ContractItem::Const(c) => {
+                            let visible_constants = scope
+                                .constants
+                                .iter()
+                                .chain(contract.constants.iter())
+                                .cloned()
+                                .collect::<Vec<_>>();
+                            let constant = constant::new(c, &visible_constants)?;
+                            contract.constants.push((c.name.clone(), constant));
+                        }
+                        ast::ContractItem::Event(e) => {
+                            contract.events.push(Struct {
+                                name: e.name.clone(),
+                                fields: e
+                                    .fields
+                                    .iter()
+                                    .map(|f| {
+                                        (f.name.clone(), ast_type_to_type(f.required, &f.type_))
+                                    })
+                                    .collect(),
+                            });
+                        }
                     }
                 }
 
@@ -3736,10 +4274,96 @@ fn prepare_scope(program: &ast::Program) -> Scope {
             ast::RootNode::Function(function) => scope
                 .functions
                 .push((function.name.clone(), Function::Ast(function))),
+            ast::RootNode::Const(_) => {}
+        }
+    }
+
+    Ok(scope)
+}
+
+/// The standard library modules used by compiled code.
+#[derive(Debug, Default, Clone, Copy)]
+struct StdImports {
+    sha256: bool,
+    blake3: bool,
+    rpo_falcon512: bool,
+    smt: bool,
+    mmr: bool,
+}
+
+impl StdImports {
+    fn of(instructions: &[encoder::Instruction]) -> Self {
+        let mut imports = Self::default();
+        encoder::walk(instructions, &mut |inst| match inst {
+            encoder::Instruction::Exec(name) if name.starts_with("sha256::") => {
+                imports.sha256 = true;
+            }
+            encoder::Instruction::Exec(name) if name.starts_with("blake3::") => {
+                imports.blake3 = true;
+            }
+            encoder::Instruction::Exec(name) if name.starts_with("rpo_falcon512::") => {
+                imports.rpo_falcon512 = true;
+            }
+            encoder::Instruction::Exec(name) if name.starts_with("smt::") => {
+                imports.smt = true;
+            }
+            encoder::Instruction::Exec(name) if name.starts_with("mmr::") => {
+                imports.mmr = true;
+            }
+            _ => {}
+        });
+
+        imports
+    }
+
+    fn merge(self, other: Self) -> Self {
+        Self {
+            sha256: self.sha256 || other.sha256,
+            blake3: self.blake3 || other.blake3,
+            rpo_falcon512: self.rpo_falcon512 || other.rpo_falcon512,
+            smt: self.smt || other.smt,
+            mmr: self.mmr || other.mmr,
         }
     }
 
-    scope
+    fn write(&self, miden_code: &mut String) {
+        miden_code.push_str("use.std::math::u64\n");
+        if self.sha256 {
+            miden_code.push_str("use.std::crypto::hashes::sha256\n");
+        }
+        if self.blake3 {
+            miden_code.push_str("use.std::crypto::hashes::blake3\n");
+        }
+        if self.rpo_falcon512 {
+            miden_code.push_str("use.std::crypto::dsa::rpo_falcon512\n");
+        }
+        if self.smt {
+            miden_code.push_str("use.std::collections::smt\n");
+        }
+        if self.mmr {
+            miden_code.push_str("use.std::collections::mmr\n");
+        }
+    }
+}
+
+/// A compiled function, its code can be the body of a program or of a procedure.
+struct EntryPoint {
+    code: String,
+    imports: StdImports,
+    abi: Abi,
+}
+
+impl EntryPoint {
+    fn program(&self) -> String {
+        let mut miden_code = String::new();
+        miden_code
+            .push_str(format!("# ABI: {}\n", serde_json::to_string(&self.abi).unwrap()).as_str());
+        self.imports.write(&mut miden_code);
+        miden_code.push_str("begin\n");
+        miden_code.push_str(&self.code);
+        miden_code.push_str("end\n");
+        miden_code
+    }
 }
 
 pub fn compile(
@@ -3747,7 +4371,232 @@ pub fn compile(
     contract_name: Option<&str>,
     function_name: &str,
 ) -> Result<(String, Abi)> {
-    let mut scope = prepare_scope(&program);
+    compile_for_version(program, contract_name, function_name, StdVersion::default())
+}
+
+/// Like [`compile`], but for a specific Miden VM version, which is recorded in the ABI.
+///
+/// Every version is encoded the same way, and only 0.7.0 is supported,
+/// older versions are rejected.
+pub fn compile_for_version(
+    program: ast::Program,
+    contract_name: Option<&str>,
+    function_name: &str,
+    std_version: StdVersion,
+) -> Result<(String, Abi)> {
+    if matches!(std_version, StdVersion::V0_5_0 | StdVersion::V0_6_1) {
+        return Err(Error::simple(format!(
+            "compiling for std version {std_version} is no longer supported"
+        )));
+    }
+
+    let entry_point =
+        compile_entry_point(&program, contract_name, function_name, std_version, false)?;
+    Ok((entry_point.program(), entry_point.abi))
+}
+
+/// Like [`compile`], but every statement stores its start when it runs,
+/// and the ABI maps the starts to source spans, so that the prover can point at the failing statement.
+///
+/// The stores change the program hash, release builds should use [`compile`].
+pub fn compile_debug(
+    program: ast::Program,
+    contract_name: Option<&str>,
+    function_name: &str,
+) -> Result<(String, Abi)> {
+    let entry_point = compile_entry_point(
+        &program,
+        contract_name,
+        function_name,
+        StdVersion::default(),
+        true,
+    )?;
+    Ok((entry_point.program(), entry_point.abi))
+}
+
+/// Compiles every function of a contract, and its read authorization,
+/// into exported procedures of a single module.
+///
+/// The `dispatch` procedure takes a selector from the top of the stack
+/// and executes the entry point with that selector. The rest of the stack,
+/// and the advice tape, are the same as for the program compiled by [`compile`].
+pub fn compile_library(
+    program: ast::Program,
+    contract_name: &str,
+) -> Result<(String, abi::LibraryAbi)> {
+    let scope = prepare_scope(&program)?;
+    let contract = scope
+        .find_contract(contract_name)
+        .not_found("contract", contract_name)?;
+    let function_names = contract
+        .functions
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .chain([".readAuth".to_owned()])
+        .collect::<Vec<_>>();
+
+    let mut imports = StdImports::default();
+    let mut procedures = String::new();
+    let mut entry_points = Vec::new();
+    for (selector, function_name) in function_names.into_iter().enumerate() {
+        let entry_point = compile_entry_point(
+            &program,
+            Some(contract_name),
+            &function_name,
+            StdVersion::default(),
+            false,
+        )?;
+        let procedure = match function_name.as_str() {
+            ".readAuth" => "read_auth".to_owned(),
+            name => format!("fn_{name}"),
+        };
+
+        procedures.push_str(&format!("export.{procedure}\n"));
+        procedures.push_str(&entry_point.code);
+        procedures.push_str("end\n\n");
+
+        imports = imports.merge(entry_point.imports);
+        entry_points.push(abi::EntryPoint {
+            selector: selector as u32,
+            function: function_name,
+            procedure,
+            // The procedure has the same body as the program, so they have the same MAST root.
+            mast_root: mast_root(&entry_point.program())?,
+            abi: entry_point.abi,
+        });
+    }
+
+    let library_abi = abi::LibraryAbi {
+        std_version: Some(StdVersion::default()),
+        contract: contract_name.to_owned(),
+        dispatcher: "dispatch".to_owned(),
+        entry_points,
+    };
+
+    let mut dispatch = vec![encoder::Instruction::Push(0), encoder::Instruction::Assert];
+    for entry_point in library_abi.entry_points.iter().rev() {
+        dispatch = vec![encoder::Instruction::If {
+            condition: vec![
+                // [selector]
+                encoder::Instruction::Dup(None),
+                encoder::Instruction::Push(entry_point.selector),
+                encoder::Instruction::Eq,
+                // [selector == entry_point.selector, selector]
+            ],
+            then: vec![
+                encoder::Instruction::Drop,
+                encoder::Instruction::Exec(&entry_point.procedure),
+            ],
+            else_: dispatch,
+        }];
+    }
+
+    let mut miden_code = String::new();
+    miden_code
+        .push_str(format!("# ABI: {}\n", serde_json::to_string(&library_abi).unwrap()).as_str());
+    imports.write(&mut miden_code);
+    miden_code.push('\n');
+    miden_code.push_str(&procedures);
+    miden_code.push_str(&format!("export.{}\n", library_abi.dispatcher));
+    for instruction in dispatch {
+        instruction
+            .encode(unsafe { miden_code.as_mut_vec() }, 1)
+            .context(IoSnafu)?;
+        miden_code.push('\n');
+    }
+    miden_code.push_str("end\n");
+
+    Ok((miden_code, library_abi))
+}
+
+/// Compiles every function of a contract, or every standalone function if there is no contract,
+/// into a manifest of their program hashes, so that proofs can be checked without the source.
+pub fn compile_manifest(
+    program: ast::Program,
+    source: &str,
+    contract_name: Option<&str>,
+    std_version: StdVersion,
+) -> Result<abi::Manifest> {
+    if std_version != StdVersion::default() {
+        return Err(Error::simple(format!(
+            "program hashes can only be computed for std version {}",
+            StdVersion::default()
+        )));
+    }
+
+    let scope = prepare_scope(&program)?;
+    let mut function_names = match contract_name {
+        Some(contract_name) => scope
+            .find_contract(contract_name)
+            .not_found("contract", contract_name)?
+            .functions
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .chain([".readAuth".to_owned()])
+            .collect::<Vec<_>>(),
+        None => program
+            .nodes
+            .iter()
+            .filter_map(|node| match node {
+                ast::RootNode::Function(f) => Some(f.name.clone()),
+                _ => None,
+            })
+            .collect(),
+    };
+    function_names.sort();
+
+    let functions = function_names
+        .into_iter()
+        .map(|function| {
+            let entry_point =
+                compile_entry_point(&program, contract_name, &function, std_version, false)?;
+            Ok(abi::ManifestFunction {
+                program_hash: mast_root(&entry_point.program())?,
+                function,
+                abi: entry_point.abi,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(abi::Manifest {
+        source_hash: blake3::hash(source.as_bytes()).to_hex().to_string(),
+        compiler_version: env!("CARGO_PKG_VERSION").to_owned(),
+        std_version,
+        contract: contract_name.map(|name| name.to_owned()),
+        functions,
+    })
+}
+
+/// Returns the hex encoded MAST root of a program.
+fn mast_root(miden_code: &str) -> Result<String> {
+    let assembler = miden::Assembler::default()
+        .with_library(&miden_stdlib::StdLibrary::default())
+        .map_err(|e| Error::simple(format!("failed to load the standard library: {e}")))?;
+    let program = assembler
+        .compile(miden_code)
+        .map_err(|e| Error::simple(format!("failed to assemble the program: {e}")))?;
+
+    Ok(program_hash(&program))
+}
+
+/// Returns the hex encoded hash of an assembled program, the same as its MAST root.
+pub fn program_hash(program: &miden::Program) -> String {
+    program
+        .hash()
+        .to_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn compile_entry_point(
+    program: &ast::Program,
+    contract_name: Option<&str>,
+    function_name: &str,
+    std_version: StdVersion,
+    debug: bool,
+) -> Result<EntryPoint> {
+    let mut scope = prepare_scope(program)?;
     let contract = contract_name.map(|name| scope.find_contract(name).cloned().unwrap());
     let contract = contract.as_ref();
     let contract_struct = contract.map(|c| Struct::from(c.clone()));
@@ -3779,10 +4628,13 @@ pub fn compile(
         }
     };
 
+    let signed = function.is_some() && contract.is_some_and(|c| c.signed);
+
     let mut instructions = vec![];
     let mut memory = Memory::new();
     let this_addr;
     let result;
+    let mut private_read_auth_addr = None;
     // A vector of hashmaps for each field, mapping the address of one of the field elements to the count of times it was used
     let mut used_fields_count: Vec<HashMap<u32, usize>>;
     let mut dependent_fields = Vec::<(String, Type)>::new();
@@ -3791,12 +4643,24 @@ pub fn compile(
 
     let ctx_struct = Struct {
         name: "Context".to_string(),
-        fields: vec![(
-            "publicKey".to_owned(),
-            Type::Nullable(Box::new(Type::PublicKey)),
-        )],
+        fields: vec![
+            (
+                "publicKey".to_owned(),
+                Type::Nullable(Box::new(Type::PublicKey)),
+            ),
+            // Contracts can store the last nonce to reject replayed calls
+            (
+                "nonce".to_owned(),
+                Type::PrimitiveType(PrimitiveType::UInt64),
+            ),
+        ],
+    };
+    // Nothing was allocated before, so the memory doesn't need to be zeroed
+    let ctx_type = Type::Struct(ctx_struct.clone());
+    let ctx = Symbol {
+        memory_addr: memory.allocate(ctx_type.miden_width()),
+        type_: ctx_type,
     };
-    let ctx = memory.allocate_symbol(Type::Struct(ctx_struct.clone()));
 
     scope.add_symbol("ctx".to_string(), ctx.clone());
 
@@ -3808,24 +4672,23 @@ pub fn compile(
                 abi::RecordHashes {
                     contract: c.0.clone(),
                 },
-                memory.allocate_symbol(Type::Array(Box::new(Type::Hash))),
+                Symbol {
+                    memory_addr: memory.allocate(array::WIDTH),
+                    type_: Type::Array(Box::new(Type::Hash)),
+                },
             )
         })
         .collect::<Vec<_>>();
 
     {
         let mut compiler = Compiler::new(&mut instructions, &mut memory, &scope);
-        compiler.record_depenencies = all_possible_record_dependencies.clone();
+        compiler.memory.record_depenencies = all_possible_record_dependencies.clone();
 
         let fields_in_use = contract_struct
             .as_ref()
             .iter()
             .flat_map(|s| &s.fields)
-            .map(|_| {
-                compiler
-                    .memory
-                    .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean))
-            })
+            .map(|_| compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean)))
             .collect::<Vec<_>>();
 
         let expected_hashes = contract_struct
@@ -3834,7 +4697,7 @@ pub fn compile(
             .flat_map(|s| &s.fields)
             .enumerate()
             .map(|(i, _)| {
-                let hash = compiler.memory.allocate_symbol(Type::Hash);
+                let hash = compiler.allocate_symbol(Type::Hash);
                 compiler.instructions.extend([encoder::Instruction::If {
                     condition: vec![encoder::Instruction::MemLoad(Some(
                         fields_in_use[i].memory_addr,
@@ -3852,12 +4715,8 @@ pub fn compile(
             .collect::<Vec<_>>();
 
         for (_, symbol) in &all_possible_record_dependencies {
-            let array_length = compiler
-                .memory
-                .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
-            let full_width = compiler
-                .memory
-                .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+            let array_length = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+            let full_width = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
 
             compiler.instructions.extend([
                 // array_len is provided by the host on the stack
@@ -3952,6 +4811,7 @@ pub fn compile(
                 &mut compiler,
                 &ctx_pk,
                 &salts_this_symbol.as_ref().unwrap().1,
+                &arg_symbols,
                 contract_name.unwrap(),
                 function_name,
             )?;
@@ -3964,6 +4824,20 @@ pub fn compile(
             compile_function_call(&mut compiler, assert_fn, &[auth_result, error_str], None)?;
         }
 
+        let call_message = if signed {
+            let nonce = struct_field(&mut compiler, &ctx, "nonce")?;
+            Some(compile_call_message(
+                &mut compiler,
+                &ctx_pk,
+                &nonce,
+                function_name,
+                &arg_symbols,
+                &expected_hashes,
+            )?)
+        } else {
+            None
+        };
+
         this_addr = salts_this_symbol.as_ref().map(|(_, ts)| ts.memory_addr);
 
         if let Some((salts, this_symbol)) = &salts_this_symbol {
@@ -4028,16 +4902,100 @@ pub fn compile(
             )?,
         };
 
-        if let Some(result) = &result {
-            let result_hash = hash(&mut compiler, result.clone())?;
+        if let (Some(contract), Some((_, this_symbol)), Some(_)) =
+            (contract, &salts_this_symbol, function)
+        {
+            if contract.fields.iter().any(|f| f.private) {
+                comment!(compiler, "Checking read access to private fields");
+                let ctx_pk = struct_field(&mut compiler, &ctx, "publicKey")?;
+                let private_read_auth = compile_read_fields_authorization(
+                    &mut compiler,
+                    this_symbol,
+                    contract,
+                    &ctx_pk,
+                )?;
+                private_read_auth_addr = Some(private_read_auth.memory_addr);
+            }
+        }
+
+        if let Some(events) = compiler.memory.events.clone() {
+            comment!(compiler, "Reading the hash of the events");
+            let events = compiler.memory.events(events.types);
+            let events_hash = hash(&mut compiler, events)?;
             compiler.memory.read(
                 compiler.instructions,
-                result_hash.memory_addr,
-                result_hash.type_.miden_width(),
+                events_hash.memory_addr,
+                events_hash.type_.miden_width(),
             );
         }
 
-        if let Some((salts, this_symbol)) = &salts_this_symbol {
+        // The first contract in the ABI ends up on top
+        for (contract, addr) in compiler.memory.created_records.clone().into_iter().rev() {
+            comment!(compiler, "Reading the hash of the new {contract} records");
+            let record = Struct::from(scope.find_contract(&contract).unwrap().clone());
+            let created = Symbol {
+                type_: Type::Array(Box::new(Type::Struct(abi::created_record_struct(record)))),
+                memory_addr: addr,
+            };
+            let created_hash = hash(&mut compiler, created)?;
+            compiler.memory.read(
+                compiler.instructions,
+                created_hash.memory_addr,
+                created_hash.type_.miden_width(),
+            );
+        }
+
+        for (contract, addr) in compiler.memory.record_updates.clone().into_iter().rev() {
+            comment!(compiler, "Reading the hash of the {contract} updates");
+            let record = Struct::from(scope.find_contract(&contract).unwrap().clone());
+            let updates = Symbol {
+                type_: Type::Array(Box::new(Type::Struct(abi::record_update_struct(record)))),
+                memory_addr: addr,
+            };
+            let updates_hash = hash(&mut compiler, updates)?;
+            compiler.memory.read(
+                compiler.instructions,
+                updates_hash.memory_addr,
+                updates_hash.type_.miden_width(),
+            );
+        }
+
+        if compiler.memory.signature_claims.is_some() {
+            comment!(compiler, "Reading the hash of the signature claims");
+            let claims = compiler.memory.signature_claims();
+            let claims = struct_field(&mut compiler, &claims, "claims")?;
+            let claims_hash = hash(&mut compiler, claims)?;
+            compiler.memory.read(
+                compiler.instructions,
+                claims_hash.memory_addr,
+                claims_hash.type_.miden_width(),
+            );
+        }
+
+        if let Some((message, public_key_hash)) = &call_message {
+            comment!(compiler, "Reading the call message and public key hash");
+            compiler.memory.read(
+                compiler.instructions,
+                public_key_hash.memory_addr,
+                public_key_hash.type_.miden_width(),
+            );
+            compiler.memory.read(
+                compiler.instructions,
+                message.memory_addr,
+                message.type_.miden_width(),
+            );
+        }
+
+        if let Some(result) = &result {
+            let result_hash = hash(&mut compiler, result.clone())?;
+            compiler.memory.read(
+                compiler.instructions,
+                result_hash.memory_addr,
+                result_hash.type_.miden_width(),
+            );
+        }
+
+        if let Some((salts, this_symbol)) = &salts_this_symbol {
             for (i, (field_name, _)) in contract_struct.as_ref().unwrap().fields.iter().enumerate()
             {
                 let if_in_use_then_insts = {
@@ -4072,7 +5030,7 @@ pub fn compile(
         compiler.memory.read(compiler.instructions, 6, 1);
 
         assert_eq!(
-            compiler.record_depenencies.len(),
+            compiler.memory.record_depenencies.len(),
             all_possible_record_dependencies.len()
         );
 
@@ -4155,19 +5113,60 @@ pub fn compile(
         }
 
         assert_eq!(
-            compiler.record_depenencies.len(),
+            compiler.memory.record_depenencies.len(),
             all_possible_record_dependencies.len()
         );
     }
 
+    let mut source_map = vec![];
+    if !debug {
+        encoder::remove_statements(&mut instructions);
+    }
+    encoder::walk(&instructions, &mut |inst| {
+        if let encoder::Instruction::Abstract(encoder::AbstractInstruction::Statement(span)) = inst
+        {
+            source_map.push(*span);
+        }
+    });
+    // Functions that are called more than once are inlined more than once
+    source_map.sort();
+    source_map.dedup();
+
     let instructions = encoder::unabstract(
         instructions,
-        &mut |size| memory.allocate(size),
+        &mut |size| memory.allocate_fresh(size),
         &mut None,
         &mut None,
         &mut false,
         false,
     );
+    let instructions = [
+        encoder::Instruction::Push(memory.peak),
+        encoder::Instruction::Dup(None),
+        // dynamic allocation pointer
+        encoder::Instruction::MemStore(Some(3)),
+        // start of the heap
+        encoder::Instruction::MemStore(Some(8)),
+    ]
+    .into_iter()
+    .chain(peephole::optimize(instructions))
+    .collect::<Vec<_>>();
+
+    let private_fields = |contract: &str| {
+        scope
+            .find_contract(contract)
+            .iter()
+            .flat_map(|c| &c.fields)
+            .filter(|f| f.private)
+            .map(|f| f.name.clone())
+            .collect::<Vec<_>>()
+    };
+    let read_auth_addr = |read_auths: &[(String, u32)], contract: &str| {
+        read_auths
+            .iter()
+            .find(|(c, _)| c == contract)
+            .map(|(_, addr)| *addr)
+    };
 
     let abi = Abi {
         dependent_fields,
@@ -4185,43 +5184,54 @@ pub fn compile(
             .into_iter()
             .map(|x| x.0)
             .collect(),
-        std_version: Some(StdVersion::V0_7_0),
+        std_version: Some(std_version),
+        signed,
+        constructor: contract.is_some() && function_name == "constructor",
+        signature_claims_addr: memory.signature_claims,
+        record_updates: memory
+            .record_updates
+            .iter()
+            .map(|(contract, addr)| abi::RecordUpdates {
+                contract: contract.clone(),
+                addr: *addr,
+                private_fields: private_fields(contract),
+                private_read_auth_addr: read_auth_addr(&memory.record_updates_read_auth, contract),
+            })
+            .collect(),
+        created_records: memory
+            .created_records
+            .iter()
+            .map(|(contract, addr)| abi::CreatedRecords {
+                contract: contract.clone(),
+                addr: *addr,
+                private_fields: private_fields(contract),
+                private_read_auth_addr: read_auth_addr(&memory.created_records_read_auth, contract),
+            })
+            .collect(),
+        events: memory.events.clone(),
+        private_fields: contract
+            .iter()
+            .flat_map(|c| &c.fields)
+            .filter(|f| f.private)
+            .map(|f| f.name.clone())
+            .collect(),
+        private_read_auth_addr,
+        peak_static_memory: memory.peak,
+        source_map,
+        cycles: cost::estimate(&instructions),
     };
 
-    let mut uses_sha256 = false;
-    let mut uses_blake3 = false;
-    encoder::walk(&instructions, &mut |inst| match inst {
-        encoder::Instruction::Exec(name) if name.starts_with("sha256::") => {
-            uses_sha256 = true;
-        }
-        encoder::Instruction::Exec(name) if name.starts_with("blake3::") => {
-            uses_blake3 = true;
-        }
-        _ => {}
-    });
+    let imports = StdImports::of(&instructions);
 
-    let mut miden_code = String::new();
-    miden_code.push_str(format!("# ABI: {}\n", serde_json::to_string(&abi).unwrap()).as_str());
-    miden_code.push_str("use.std::math::u64\n");
-    if uses_sha256 {
-        miden_code.push_str("use.std::crypto::hashes::sha256\n");
-    }
-    if uses_blake3 {
-        miden_code.push_str("use.std::crypto::hashes::blake3\n");
-    }
-    miden_code.push_str("begin\n");
-    miden_code.push_str("  push.");
-    miden_code.push_str(&memory.static_alloc_ptr.to_string());
-    miden_code.push_str("\n  mem_store.3\n"); // dynamic allocation pointer
+    let mut code = String::new();
     for instruction in instructions {
         instruction
-            .encode(unsafe { miden_code.as_mut_vec() }, 1)
+            .encode(unsafe { code.as_mut_vec() }, 1)
             .context(IoSnafu)?;
-        miden_code.push('\n');
+        code.push('\n');
     }
-    miden_code.push_str("end\n");
 
-    Ok((miden_code, abi))
+    Ok(EntryPoint { code, imports, abi })
 }
 
 fn compile_read_authorization_proof(
@@ -4230,9 +5240,7 @@ fn compile_read_authorization_proof(
     contract: &Contract,
     auth_pk: &Symbol,
 ) -> Result<Symbol> {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
 
     if contract.read_directive {
         compiler.instructions.push(encoder::Instruction::Push(1));
@@ -4242,6 +5250,19 @@ fn compile_read_authorization_proof(
         return Ok(result);
     }
 
+    compile_read_fields_authorization(compiler, struct_symbol, contract, auth_pk)
+}
+
+/// Checks `auth_pk` against the `@read` fields of the record.
+/// `@private` fields can only be read this way, even if the contract is public.
+fn compile_read_fields_authorization(
+    compiler: &mut Compiler,
+    struct_symbol: &Symbol,
+    contract: &Contract,
+    auth_pk: &Symbol,
+) -> Result<Symbol> {
+    let result = boolean::new(compiler, false);
+
     for field in contract.fields.iter().filter(|f| f.read) {
         let field_symbol = struct_field(compiler, struct_symbol, &field.name)?;
         compiler.memory.read(
@@ -4264,17 +5285,88 @@ fn compile_read_authorization_proof(
     Ok(result)
 }
 
+/// Computes the message that the caller signs, and the hash of `ctx.publicKey` that signs it.
+///
+/// The message is the hash of the function name, the hash of the arguments,
+/// the hash of the input field hashes and the hash of `ctx.nonce`.
+/// Miden VM 0.7 can't verify secp256k1 signatures, so both are outputs of the program,
+/// and the signature is verified against them outside the VM, see `verify_call_signature`.
+fn compile_call_message(
+    compiler: &mut Compiler,
+    ctx_pk: &Symbol,
+    nonce: &Symbol,
+    function_name: &str,
+    args: &[Symbol],
+    field_hashes: &[Symbol],
+) -> Result<(Symbol, Symbol)> {
+    let assert_fn = compiler.root_scope.find_function("assert").unwrap();
+    let (error_str, _) = string::new(compiler, "A signature is required to call this function");
+    compile_function_call(
+        compiler,
+        assert_fn,
+        &[nullable::is_not_null(ctx_pk), error_str],
+        None,
+    )?;
+    let public_key_hash = hash(compiler, nullable::value(ctx_pk.clone()))?;
+
+    let (function_name, _) = string::new(compiler, function_name);
+    let function_name_hash = hash(compiler, function_name)?;
+    let arg_hashes = args
+        .iter()
+        .map(|arg| hash(compiler, arg.clone()))
+        .collect::<Result<Vec<_>>>()?;
+    let args_hash = merge_hashes(compiler, &arg_hashes);
+    let this_hash = merge_hashes(compiler, field_hashes);
+    let nonce_hash = hash(compiler, nonce.clone())?;
+
+    let message = merge_hashes(
+        compiler,
+        &[function_name_hash, args_hash, this_hash, nonce_hash],
+    );
+
+    Ok((message, public_key_hash))
+}
+
+/// Merges the hashes in order, starting from a zero hash, the same way structs are hashed.
+fn merge_hashes(compiler: &mut Compiler, hashes: &[Symbol]) -> Symbol {
+    let result = compiler.allocate_symbol(Type::Hash);
+    compiler.memory.write(
+        compiler.instructions,
+        result.memory_addr,
+        &vec![ValueSource::Immediate(0); 4],
+    );
+    for hash in hashes {
+        compiler.memory.read(
+            compiler.instructions,
+            result.memory_addr,
+            result.type_.miden_width(),
+        );
+        compiler.memory.read(
+            compiler.instructions,
+            hash.memory_addr,
+            hash.type_.miden_width(),
+        );
+        compiler.instructions.push(encoder::Instruction::HMerge);
+        compiler.memory.write(
+            compiler.instructions,
+            result.memory_addr,
+            &vec![ValueSource::Stack; 4],
+        );
+    }
+
+    result
+}
+
 fn compile_call_authorization_proof(
     compiler: &mut Compiler,
     // Symbol of type Type::Nullable(Type::PublicKey)
     auth_pk: &Symbol,
     contract_symbol: &Symbol,
+    args: &[Symbol],
     contract_name: &str,
     function_name: &str,
 ) -> Result<Symbol> {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+    let result = boolean::new(compiler, false);
 
     if function_name == "constructor" {
         compiler.instructions.push(encoder::Instruction::Push(1));
@@ -4340,25 +5432,46 @@ fn compile_call_authorization_proof(
     for (decorator_span, call_arg) in call_args {
         maybe_start!(decorator_span);
 
-        let arg_value = match call_arg {
-            ast::DecoratorArgument::Identifier(id) => {
-                let mut current_field = contract_symbol.clone();
-                {
-                    let field = &id;
-                    current_field = struct_field(compiler, &current_field, field)?;
+        let passed = match call_arg {
+            ast::DecoratorArgument::Identifier(path) => {
+                let path = path.split('.').collect::<Vec<_>>();
+                compile_check_field_path(compiler, contract_symbol.clone(), &path, auth_pk)?
+            }
+            ast::DecoratorArgument::Literal(l) => {
+                let key = match l {
+                    ast::Literal::Eth(pk) => abi::publickey::Key::from_secp256k1_bytes(pk),
+                    ast::Literal::Ed25519(pk) => abi::publickey::Key::from_ed25519_bytes(pk),
+                    ast::Literal::P256(pk) => abi::publickey::Key::from_p256_bytes(pk),
                 }
-
-                current_field
+                .wrap_err()?;
+                let key = publickey::new(compiler, key);
+                compile_check_eq_or_ownership(compiler, key, auth_pk)?
             }
-            ast::DecoratorArgument::Literal(l) => match l {
-                ast::Literal::Eth(pk) => {
-                    let key = abi::publickey::Key::from_secp256k1_bytes(pk).wrap_err()?;
-                    publickey::new(compiler, key)
+            ast::DecoratorArgument::When { condition, .. } => {
+                check_when_condition(condition)?;
+
+                // The condition sees the same `this`, `ctx` and parameters as the function
+                let when_scope = &mut Scope::new();
+                when_scope.parent = Some(compiler.root_scope);
+                when_scope
+                    .constants
+                    .extend(contract.constants.iter().cloned());
+                when_scope.add_symbol("this".to_string(), contract_symbol.clone());
+                for (arg, param) in args.iter().zip(function.parameters.iter()) {
+                    when_scope.add_symbol(param.name.clone(), arg.clone());
                 }
-            },
+
+                let passed = compile_expression(condition, compiler, when_scope)?;
+                if passed.type_ != Type::PrimitiveType(PrimitiveType::Boolean) {
+                    return Err(Error::simple(format!(
+                        "The condition of @call(when: ...) must be a boolean, found {:?}",
+                        passed.type_
+                    )));
+                }
+                passed
+            }
         };
 
-        let passed = compile_check_eq_or_ownership(compiler, arg_value, auth_pk)?;
         compiler.instructions.push(encoder::Instruction::If {
             condition: vec![encoder::Instruction::MemLoad(Some(passed.memory_addr))],
             then: vec![
@@ -4372,116 +5485,162 @@ fn compile_call_authorization_proof(
     Ok(result)
 }
 
-fn compile_check_eq_or_ownership(
+/// The methods that a `@call(when: ...)` condition can call, they don't change their receiver.
+const WHEN_CONDITION_METHODS: &[&str] = &["includes", "indexOf", "slice", "toHex"];
+
+/// Rejects `@call(when: ...)` conditions that could change state. The condition runs
+/// before the function, so it can't assign, create records or call functions,
+/// other than the [`WHEN_CONDITION_METHODS`].
+fn check_when_condition(expr: &Expression) -> Result<()> {
+    use ast::ExpressionKind;
+
+    let children: Vec<&Expression> = match &**expr {
+        ExpressionKind::Primitive(_) | ExpressionKind::Ident(_) | ExpressionKind::Boolean(_) => {
+            vec![]
+        }
+        ExpressionKind::Assign(_, _)
+        | ExpressionKind::AssignAdd(_, _)
+        | ExpressionKind::AssignSub(_, _)
+        | ExpressionKind::Increment(_) => {
+            return Err(Error::simple(
+                "The condition of @call(when: ...) can't assign to variables or fields",
+            ))
+        }
+        ExpressionKind::New(contract, _) => {
+            return Err(Error::simple(format!(
+                "The condition of @call(when: ...) can't create a {contract} record"
+            )))
+        }
+        ExpressionKind::Asm(_) => {
+            return Err(Error::simple(
+                "The condition of @call(when: ...) can't use inline assembly",
+            ))
+        }
+        ExpressionKind::Call(func, args) => match &***func {
+            ExpressionKind::Dot(obj, method)
+                if WHEN_CONDITION_METHODS.contains(&method.as_str()) =>
+            {
+                std::iter::once(&**obj).chain(args).collect()
+            }
+            ExpressionKind::Dot(_, name) | ExpressionKind::Ident(name) => {
+                return Err(Error::simple(format!(
+                    "The condition of @call(when: ...) can't call {name}, only the methods {}",
+                    WHEN_CONDITION_METHODS.join(", ")
+                )))
+            }
+            _ => {
+                return Err(Error::simple(
+                    "The condition of @call(when: ...) can't call functions",
+                ))
+            }
+        },
+        ExpressionKind::Object(object) => object.fields.iter().map(|(_, e)| e).collect(),
+        ExpressionKind::Array(exprs) => exprs.iter().collect(),
+        ExpressionKind::Not(a)
+        | ExpressionKind::BitNot(a)
+        | ExpressionKind::Negate(a)
+        | ExpressionKind::Dot(a, _)
+        | ExpressionKind::OptionalDot(a, _) => vec![a],
+        ExpressionKind::Or(a, b)
+        | ExpressionKind::NullishCoalesce(a, b)
+        | ExpressionKind::And(a, b)
+        | ExpressionKind::Equal(a, b)
+        | ExpressionKind::NotEqual(a, b)
+        | ExpressionKind::LessThan(a, b)
+        | ExpressionKind::LessThanOrEqual(a, b)
+        | ExpressionKind::GreaterThan(a, b)
+        | ExpressionKind::GreaterThanOrEqual(a, b)
+        | ExpressionKind::BitOr(a, b)
+        | ExpressionKind::BitXor(a, b)
+        | ExpressionKind::BitAnd(a, b)
+        | ExpressionKind::ShiftLeft(a, b)
+        | ExpressionKind::ShiftRight(a, b)
+        | ExpressionKind::Add(a, b)
+        | ExpressionKind::Subtract(a, b)
+        | ExpressionKind::Multiply(a, b)
+        | ExpressionKind::Divide(a, b)
+        | ExpressionKind::Modulo(a, b)
+        | ExpressionKind::Exponent(a, b)
+        | ExpressionKind::Index(a, b) => vec![a, b],
+    };
+
+    children.into_iter().try_for_each(check_when_condition)
+}
+
+/// Checks `auth_pk` against the field at `path`, such as `owner.admin`, of `symbol`.
+/// Paths can go through nested objects, optional fields and records.
+fn compile_check_field_path(
     compiler: &mut Compiler,
-    field: Symbol,
+    symbol: Symbol,
+    path: &[&str],
     auth_pk: &Symbol,
 ) -> Result<Symbol> {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+    let Some((field_name, rest)) = path.split_first() else {
+        return compile_check_eq_or_ownership(compiler, symbol, auth_pk);
+    };
 
-    let is_eq = match &field.type_ {
-        Type::PublicKey => compile_eq(compiler, &field, auth_pk)?,
-        Type::Nullable(t) if **t == Type::PublicKey => compile_eq(compiler, &field, auth_pk)?,
+    match &symbol.type_ {
         Type::ContractReference { contract } => {
             let contract_type = compiler.root_scope.find_contract(contract).unwrap();
-            let contract_record_hashes = compiler.get_record_dependency(contract_type).unwrap();
-            let id = struct_field(compiler, &field, "id").unwrap();
-
-            let hash_id = hash(compiler, id.clone())?;
-            compiler.memory.read(
-                compiler.instructions,
-                hash_id.memory_addr,
-                hash_id.type_.miden_width(),
-            );
-            // [...id_hash]
-            compiler
-                .instructions
-                .push(encoder::Instruction::AdvPushMapval);
-            // advice = [Nullable(public_record_hash_position), ...record_data]
-            compiler.instructions.push(encoder::Instruction::Dropw);
-            // []
-
-            let public_hash_position = read_advice_generic(
-                compiler,
-                &Type::Nullable(Box::new(Type::PrimitiveType(PrimitiveType::UInt32))),
-            )?;
-
-            let (not_null_instructions, result) = {
+            compile_with_record(compiler, &symbol, contract_type, |compiler, record| {
+                let field = struct_field(compiler, record, field_name)?;
+                compile_check_field_path(compiler, field, rest, auth_pk)
+            })
+        }
+        Type::Nullable(_) => {
+            let (check_insts, passed) = {
                 let mut insts = vec![];
                 std::mem::swap(compiler.instructions, &mut insts);
 
-                let public_hash_position = nullable::value(public_hash_position.clone());
-
-                let record_public_hash =
-                    array::get(compiler, &contract_record_hashes, &public_hash_position);
-
-                let record = compiler
-                    .memory
-                    .allocate_symbol(Type::Struct(Struct::from(contract_type.clone())));
-                compiler.instructions.push(encoder::Instruction::AdvPush(
-                    contract_type.fields.len() as u32,
-                ));
-                let salts = contract_type
-                    .fields
-                    .iter()
-                    .map(|_| {
-                        let salt = compiler
-                            .memory
-                            .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
-                        compiler.memory.write(
-                            compiler.instructions,
-                            salt.memory_addr,
-                            &[ValueSource::Stack],
-                        );
-                        salt
-                    })
-                    .collect::<Vec<_>>();
-                read_struct_from_advice_tape(
+                let result = compile_check_field_path(
                     compiler,
-                    &record,
-                    &Struct::from(contract_type.clone()),
-                    None,
+                    nullable::value(symbol.clone()),
+                    path,
+                    auth_pk,
                 )?;
-                let actual_record_hash = hash_record_with_salts(compiler, &record, &salts)?;
-                compiler.memory.read(
-                    compiler.instructions,
-                    actual_record_hash.memory_addr,
-                    actual_record_hash.type_.miden_width(),
-                );
-
-                let is_hash_eq = compile_eq(compiler, &record_public_hash, &actual_record_hash)?;
-                let assert = compiler.root_scope.find_function("assert").unwrap();
-                let (error_str, _) =
-                    string::new(compiler, "Record hash does not match the expected hash");
-                compile_function_call(compiler, assert, &[is_hash_eq, error_str], None)?;
-
-                let record_id = struct_field(compiler, &record, "id")?;
-                let is_id_eq = compile_eq(compiler, &record_id, &id)?;
-                let (error_str, _) = string::new(compiler, "Record id does not match");
-                compile_function_call(compiler, assert, &[is_id_eq, error_str], None)?;
-
-                let result = compile_check_ownership(compiler, &record, contract_type, auth_pk)?;
 
                 std::mem::swap(compiler.instructions, &mut insts);
                 (insts, result)
             };
 
+            // The check is skipped if the field is null
+            compiler.zero(&passed);
             compiler.instructions.push(encoder::Instruction::If {
                 condition: vec![encoder::Instruction::MemLoad(Some(
-                    nullable::is_not_null(&public_hash_position).memory_addr,
+                    nullable::is_not_null(&symbol).memory_addr,
                 ))],
-                then: not_null_instructions,
+                then: check_insts,
                 else_: vec![],
             });
 
-            result
+            Ok(passed)
+        }
+        _ => {
+            let field = struct_field(compiler, &symbol, field_name)?;
+            compile_check_field_path(compiler, field, rest, auth_pk)
+        }
+    }
+}
+
+fn compile_check_eq_or_ownership(
+    compiler: &mut Compiler,
+    field: Symbol,
+    auth_pk: &Symbol,
+) -> Result<Symbol> {
+    let result = boolean::new(compiler, false);
+
+    let is_eq = match &field.type_ {
+        Type::PublicKey => compile_eq(compiler, &field, auth_pk)?,
+        Type::Nullable(t) if **t == Type::PublicKey => compile_eq(compiler, &field, auth_pk)?,
+        Type::ContractReference { contract } => {
+            let contract_type = compiler.root_scope.find_contract(contract).unwrap();
+            compile_with_record(compiler, &field, contract_type, |compiler, record| {
+                compile_check_ownership(compiler, record, contract_type, auth_pk)
+            })?
         }
         Type::Array(_) => {
             // We need to iterate over the array and check if any of the elements match
             let index = compiler
-                .memory
                 .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
 
             let (current_array_element, current_array_element_insts) = {
@@ -4508,6 +5667,8 @@ fn compile_check_eq_or_ownership(
                 (result, insts)
             };
 
+            // The loop stops when an element passed, so it must start as not passed
+            compiler.zero(&passed);
             compiler.instructions.extend([
                 encoder::Instruction::MemLoad(Some(array::length(&field).memory_addr)),
                 // [array_len]
@@ -4544,7 +5705,40 @@ fn compile_check_eq_or_ownership(
 
             passed
         }
-        _ => todo!(),
+        Type::Map(_, _) => {
+            // Any of the values can match, the same as an array
+            let values = map::values_arr(&field)?;
+            compile_check_eq_or_ownership(compiler, values, auth_pk)?
+        }
+        Type::Nullable(_) => {
+            let (check_insts, passed) = {
+                let mut insts = vec![];
+                std::mem::swap(compiler.instructions, &mut insts);
+
+                let result =
+                    compile_check_eq_or_ownership(compiler, nullable::value(field.clone()), auth_pk)?;
+
+                std::mem::swap(compiler.instructions, &mut insts);
+                (insts, result)
+            };
+
+            // The check is skipped if the field is null
+            compiler.zero(&passed);
+            compiler.instructions.push(encoder::Instruction::If {
+                condition: vec![encoder::Instruction::MemLoad(Some(
+                    nullable::is_not_null(&field).memory_addr,
+                ))],
+                then: check_insts,
+                else_: vec![],
+            });
+
+            passed
+        }
+        t => {
+            return Err(Error::simple(format!(
+                "Cannot authorize against a value of type {t:?}, expected a public key, a record, or an array or map of them"
+            )))
+        }
     };
 
     compiler.instructions.push(encoder::Instruction::If {
@@ -4559,15 +5753,573 @@ fn compile_check_eq_or_ownership(
     Ok(result)
 }
 
+/// Loads the record that `field` references from the advice provider and runs `check` on it.
+/// Records that are not public fail the check.
+fn compile_with_record(
+    compiler: &mut Compiler,
+    field: &Symbol,
+    contract_type: &Contract,
+    check: impl FnOnce(&mut Compiler, &Symbol) -> Result<Symbol>,
+) -> Result<Symbol> {
+    let public_hash_position = read_record_position(compiler, field)?;
+
+    let (not_null_instructions, result) = {
+        let mut insts = vec![];
+        std::mem::swap(compiler.instructions, &mut insts);
+
+        let public_hash_position = nullable::value(public_hash_position.clone());
+        let (record, _) = read_record(compiler, field, contract_type, &public_hash_position)?;
+        let result = check(compiler, &record)?;
+
+        std::mem::swap(compiler.instructions, &mut insts);
+        (insts, result)
+    };
+
+    // Records that are not found fail the check
+    compiler.zero(&result);
+    compiler.instructions.push(encoder::Instruction::If {
+        condition: vec![encoder::Instruction::MemLoad(Some(
+            nullable::is_not_null(&public_hash_position).memory_addr,
+        ))],
+        then: not_null_instructions,
+        else_: vec![],
+    });
+
+    Ok(result)
+}
+
+/// Looks up the record that `field` references in the advice map, returns the position
+/// of its hash in the record hashes of its contract, or null if the record is unknown.
+/// The salts and data of the record are left on the advice stack, see `read_record`.
+fn read_record_position(compiler: &mut Compiler, field: &Symbol) -> Result<Symbol> {
+    let id = struct_field(compiler, field, "id")?;
+
+    let hash_id = hash(compiler, id)?;
+    compiler.memory.read(
+        compiler.instructions,
+        hash_id.memory_addr,
+        hash_id.type_.miden_width(),
+    );
+    // [...id_hash]
+    compiler
+        .instructions
+        .push(encoder::Instruction::AdvPushMapval);
+    // advice = [Nullable(public_record_hash_position), ...record_data]
+    compiler.instructions.push(encoder::Instruction::Dropw);
+    // []
+
+    read_advice_generic(
+        compiler,
+        &Type::Nullable(Box::new(Type::PrimitiveType(PrimitiveType::UInt32))),
+    )
+}
+
+/// The records of a contract that foreign calls updated, and the positions of their hashes.
+fn record_cache_struct(record: Struct) -> Struct {
+    Struct {
+        name: format!("{}Cache", record.name),
+        fields: vec![
+            (
+                "positions".to_owned(),
+                Type::Array(Box::new(Type::PrimitiveType(PrimitiveType::UInt32))),
+            ),
+            (
+                "records".to_owned(),
+                Type::Array(Box::new(Type::Struct(record))),
+            ),
+        ],
+    }
+}
+
+/// Looks up the record at `position` in the record cache,
+/// returns whether it is cached and its index in the cache.
+fn find_cached_record(
+    compiler: &mut Compiler,
+    cache: &Symbol,
+    position: &Symbol,
+) -> Result<(Symbol, Symbol)> {
+    let positions = struct_field(compiler, cache, "positions")?;
+    let index = array::find_index(compiler, &positions, position)?;
+    let zero = int32::new(compiler, 0);
+    let is_cached = int32::gte(compiler, &index, &zero)?;
+
+    // The index is -1 if the record is not cached, otherwise it fits in a u32
+    let index = Symbol {
+        type_: Type::PrimitiveType(PrimitiveType::UInt32),
+        memory_addr: index.memory_addr,
+    };
+
+    Ok((is_cached, index))
+}
+
+/// Reads the record at `position` from the advice stack and checks it against its hash,
+/// returns the record and the salts of its fields.
+///
+/// The advice map holds the record before the program ran, so a record that a foreign call
+/// already updated is taken from the record cache, and checked against its new hash.
+fn read_record(
+    compiler: &mut Compiler,
+    field: &Symbol,
+    contract_type: &Contract,
+    position: &Symbol,
+) -> Result<(Symbol, Vec<Symbol>)> {
+    let contract_record_hashes = compiler.get_record_dependency(contract_type).unwrap();
+    let id = struct_field(compiler, field, "id")?;
+
+    let record_public_hash = array::get(compiler, &contract_record_hashes, position);
+
+    let contract_struct = Struct::from(contract_type.clone());
+    let record = compiler.allocate_symbol(Type::Struct(contract_struct.clone()));
+    let salts = contract_type
+        .fields
+        .iter()
+        .map(|_| read_advice_generic(compiler, &Type::Hash))
+        .collect::<Result<Vec<_>>>()?;
+    read_struct_from_advice_tape(compiler, &record, &contract_struct, None)?;
+
+    let cache = compiler.memory.record_cache(&contract_struct);
+    let (is_cached, index) = find_cached_record(compiler, &cache, position)?;
+    let copy_cached_insts = {
+        let mut insts = vec![];
+        std::mem::swap(compiler.instructions, &mut insts);
+
+        let records = struct_field(compiler, &cache, "records")?;
+        let cached = array::get(compiler, &records, &index);
+        compiler.memory.read(
+            compiler.instructions,
+            cached.memory_addr,
+            cached.type_.miden_width(),
+        );
+        compiler.memory.write(
+            compiler.instructions,
+            record.memory_addr,
+            &vec![ValueSource::Stack; record.type_.miden_width() as usize],
+        );
+
+        std::mem::swap(compiler.instructions, &mut insts);
+        insts
+    };
+    compiler.instructions.push(encoder::Instruction::If {
+        condition: vec![encoder::Instruction::MemLoad(Some(is_cached.memory_addr))],
+        then: copy_cached_insts,
+        else_: vec![],
+    });
+
+    let actual_record_hash = hash_record_with_salts(compiler, &record, &salts)?;
+    compiler.memory.read(
+        compiler.instructions,
+        actual_record_hash.memory_addr,
+        actual_record_hash.type_.miden_width(),
+    );
+
+    let is_hash_eq = compile_eq(compiler, &record_public_hash, &actual_record_hash)?;
+    let assert = compiler.root_scope.find_function("assert").unwrap();
+    let (error_str, _) = string::new(compiler, "Record hash does not match the expected hash");
+    compile_function_call(compiler, assert, &[is_hash_eq, error_str], None)?;
+
+    let record_id = struct_field(compiler, &record, "id")?;
+    let is_id_eq = compile_eq(compiler, &record_id, &id)?;
+    let (error_str, _) = string::new(compiler, "Record id does not match");
+    compile_function_call(compiler, assert, &[is_id_eq, error_str], None)?;
+
+    Ok((record, salts))
+}
+
+/// Calls a function of the record that `reference` points to, such as `this.account.deposit(5)`.
+///
+/// The record is read from the advice map like in `compile_with_record`, the caller must pass
+/// the `@call` checks of the function, and the function is inlined with the record as `this`.
+/// The updated record and its new hash are pushed to the record updates of its contract,
+/// which the program outputs the hash of. The new hash replaces the old one in the record hashes,
+/// and the record is cached for the next calls, see `read_record`. A record called more than once
+/// has one update with its last state.
+fn compile_foreign_call(
+    compiler: &mut Compiler,
+    scope: &Scope,
+    reference: &Symbol,
+    contract_name: &str,
+    function_name: &str,
+    args: &[Symbol],
+) -> Result<Option<Symbol>> {
+    let contract = compiler
+        .root_scope
+        .find_contract(contract_name)
+        .not_found("contract", contract_name)?;
+    let function = contract
+        .functions
+        .iter()
+        .find(|(name, _)| name == function_name)
+        .map(|(_, f)| *f)
+        .not_found("function", &format!("{contract_name}.{function_name}"))?;
+    if contract.signed {
+        // The signature is of the call to the outer function
+        return Err(Error::simple(format!(
+            "Cannot call {contract_name}.{function_name} from another contract, it is @signed"
+        )));
+    }
+
+    let contract_struct = Struct::from(contract.clone());
+    check_arguments(function, &contract_struct, args)?;
+
+    comment!(compiler, "Calling {contract_name}.{function_name}");
+    let assert = compiler.root_scope.find_function("assert").unwrap();
+
+    let position = read_record_position(compiler, reference)?;
+    let (error_str, _) = string::new(compiler, &format!("{contract_name} record not found"));
+    compile_function_call(
+        compiler,
+        assert,
+        &[nullable::is_not_null(&position), error_str],
+        None,
+    )?;
+    let position = nullable::value(position);
+    let (record, salts) = read_record(compiler, reference, contract, &position)?;
+
+    let ctx = compiler
+        .root_scope
+        .find_symbol("ctx")
+        .not_found("symbol", "ctx")?;
+    let ctx_pk = struct_field(compiler, &ctx, "publicKey")?;
+    let authorized = compile_call_authorization_proof(
+        compiler,
+        &ctx_pk,
+        &record,
+        args,
+        contract_name,
+        function_name,
+    )?;
+    let (error_str, _) = string::new(
+        compiler,
+        &format!("You are not authorized to call {contract_name}.{function_name}"),
+    );
+    compile_function_call(compiler, assert, &[authorized, error_str], None)?;
+
+    let result = compile_ast_function_call(function, compiler, args, Some(record.clone()))?;
+
+    let new_hash = hash_record_with_salts(compiler, &record, &salts)?;
+    let contract_record_hashes = compiler.get_record_dependency(contract).unwrap();
+    array::set(compiler, &contract_record_hashes, &position, &new_hash);
+
+    let updates = compiler.memory.record_updates(&contract_struct);
+    let update = compiler.allocate_symbol(array::element_type(&updates.type_).clone());
+    for (field, value) in [("record", &record), ("hash", &new_hash)] {
+        let field = struct_field(compiler, &update, field)?;
+        compiler.memory.read(
+            compiler.instructions,
+            value.memory_addr,
+            value.type_.miden_width(),
+        );
+        compiler.memory.write(
+            compiler.instructions,
+            field.memory_addr,
+            &vec![ValueSource::Stack; field.type_.miden_width() as usize],
+        );
+    }
+
+    // Like the private fields of `this`, the caller only gets the private fields it can read
+    let read_auth = if contract.fields.iter().any(|f| f.private) {
+        comment!(compiler, "Checking read access to private fields");
+        let read_auth = compile_read_fields_authorization(compiler, &record, contract, &ctx_pk)?;
+        Some((
+            compiler.memory.record_updates_read_auth(&contract_struct),
+            read_auth,
+        ))
+    } else {
+        None
+    };
+
+    // The updates and the cache are pushed together, so a cached record has the same index in both
+    let cache = compiler.memory.record_cache(&contract_struct);
+    let positions = struct_field(compiler, &cache, "positions")?;
+    let records = struct_field(compiler, &cache, "records")?;
+    let (is_cached, index) = find_cached_record(compiler, &cache, &position)?;
+    let (replace_insts, push_insts) = {
+        let mut insts = vec![];
+        std::mem::swap(compiler.instructions, &mut insts);
+
+        array::set(compiler, &updates, &index, &update);
+        array::set(compiler, &records, &index, &record);
+        if let Some((read_auths, read_auth)) = &read_auth {
+            array::set(compiler, read_auths, &index, read_auth);
+        }
+
+        std::mem::swap(compiler.instructions, &mut insts);
+        let replace_insts = insts;
+
+        let mut insts = vec![];
+        std::mem::swap(compiler.instructions, &mut insts);
+
+        array::push(compiler, scope, &[updates, update])?;
+        array::push(compiler, scope, &[positions, position])?;
+        array::push(compiler, scope, &[records, record])?;
+        if let Some((read_auths, read_auth)) = read_auth {
+            array::push(compiler, scope, &[read_auths, read_auth])?;
+        }
+
+        std::mem::swap(compiler.instructions, &mut insts);
+        (replace_insts, insts)
+    };
+    compiler.instructions.push(encoder::Instruction::If {
+        condition: vec![encoder::Instruction::MemLoad(Some(is_cached.memory_addr))],
+        then: replace_insts,
+        else_: push_insts,
+    });
+
+    Ok(result)
+}
+
+/// Creates a record of another contract by calling its constructor, such as `new Listing(id, 5)`.
+///
+/// The constructor is inlined with a zeroed record as `this`. The salts of the fields are derived
+/// from a seed in the advice map, so that the prover doesn't need to know how many records are created.
+/// The record, its salts and its hash are pushed to the created records of its contract,
+/// which the program outputs the hash of. Returns a reference to the new record.
+fn compile_new(
+    compiler: &mut Compiler,
+    scope: &Scope,
+    contract_name: &str,
+    args: &[Symbol],
+) -> Result<Symbol> {
+    let contract = compiler
+        .root_scope
+        .find_contract(contract_name)
+        .not_found("contract", contract_name)?;
+    let constructor = contract
+        .functions
+        .iter()
+        .find(|(name, _)| *name == "constructor")
+        .map(|(_, f)| *f)
+        .not_found("function", &format!("{contract_name}.constructor"))?;
+    if contract.signed {
+        return Err(Error::simple(format!(
+            "Cannot create {contract_name} records from another contract, it is @signed"
+        )));
+    }
+
+    let contract_struct = Struct::from(contract.clone());
+    if !contract_struct
+        .fields
+        .iter()
+        .any(|(name, type_)| name == "id" && *type_ == Type::String)
+    {
+        return Err(Error::simple(format!(
+            "Cannot create {contract_name} records, their id must be a string"
+        )));
+    }
+    check_arguments(constructor, &contract_struct, args)?;
+
+    comment!(compiler, "Creating a {contract_name} record");
+    let record = compiler.allocate_symbol(Type::Struct(contract_struct.clone()));
+    compile_ast_function_call(constructor, compiler, args, Some(record.clone()))?;
+
+    let created_records = compiler.memory.created_records(&contract_struct);
+    let contract_index = compiler
+        .memory
+        .created_records
+        .iter()
+        .position(|(contract, _)| contract == contract_name)
+        .unwrap();
+    let created = compiler.allocate_symbol(array::element_type(&created_records.type_).clone());
+
+    compiler.instructions.extend([
+        encoder::Instruction::Push(0),
+        encoder::Instruction::Push(0),
+        encoder::Instruction::Push(0),
+        encoder::Instruction::Push(2),
+        // [0, 0, 0, 2]
+        encoder::Instruction::AdvPushMapval,
+        // advice = [...seed]
+        encoder::Instruction::Dropw,
+    ]);
+    let seed = read_advice_generic(compiler, &Type::Hash)?;
+
+    // Every field of every created record gets a different salt
+    let salts_symbol = struct_field(compiler, &created, "salts")?;
+    let mut salts = vec![];
+    for (i, (field_name, _)) in contract_struct.fields.iter().enumerate() {
+        let salt = struct_field(compiler, &salts_symbol, field_name)?;
+        compiler.memory.read(
+            compiler.instructions,
+            seed.memory_addr,
+            seed.type_.miden_width(),
+        );
+        compiler.instructions.extend([
+            encoder::Instruction::Push(0),
+            encoder::Instruction::Push(contract_index as u32),
+            encoder::Instruction::MemLoad(Some(array::length(&created_records).memory_addr)),
+            encoder::Instruction::Push(i as u32),
+            // [i, created_records.length, contract_index, 0, ...seed]
+            encoder::Instruction::HMerge,
+        ]);
+        compiler.memory.write(
+            compiler.instructions,
+            salt.memory_addr,
+            &vec![ValueSource::Stack; salt.type_.miden_width() as usize],
+        );
+        salts.push(salt);
+    }
+
+    let hash = hash_record_with_salts(compiler, &record, &salts)?;
+    for (field, value) in [("record", &record), ("hash", &hash)] {
+        let field = struct_field(compiler, &created, field)?;
+        compiler.memory.read(
+            compiler.instructions,
+            value.memory_addr,
+            value.type_.miden_width(),
+        );
+        compiler.memory.write(
+            compiler.instructions,
+            field.memory_addr,
+            &vec![ValueSource::Stack; field.type_.miden_width() as usize],
+        );
+    }
+    array::push(compiler, scope, &[created_records, created])?;
+
+    if contract.fields.iter().any(|f| f.private) {
+        comment!(compiler, "Checking read access to private fields");
+        let ctx = compiler
+            .root_scope
+            .find_symbol("ctx")
+            .not_found("symbol", "ctx")?;
+        let ctx_pk = struct_field(compiler, &ctx, "publicKey")?;
+        let read_auth = compile_read_fields_authorization(compiler, &record, contract, &ctx_pk)?;
+        let read_auths = compiler.memory.created_records_read_auth(&contract_struct);
+        array::push(compiler, scope, &[read_auths, read_auth])?;
+    }
+
+    let id = struct_field(compiler, &record, "id")?;
+    Ok(Symbol {
+        type_: Type::ContractReference {
+            contract: contract_name.to_owned(),
+        },
+        memory_addr: id.memory_addr,
+    })
+}
+
+/// Appends an event of the contract of `this` to the events of the program.
+fn compile_emit(emit: &ast::Emit, compiler: &mut Compiler, scope: &Scope) -> Result<()> {
+    let contract_name = match scope.find_symbol("this").map(|this| this.type_) {
+        Some(Type::Struct(this)) => this.name,
+        _ => return Err(Error::simple("emit can only be used in contract functions")),
+    };
+    let event_name = format!("{contract_name}.{}", emit.event);
+    let event = compiler
+        .root_scope
+        .find_contract(&contract_name)
+        .and_then(|c| c.events.iter().find(|e| e.name == emit.event))
+        .not_found("event", &event_name)?
+        .clone();
+
+    if let Some((field, _)) = emit
+        .fields
+        .iter()
+        .find(|(field, _)| !event.fields.iter().any(|(name, _)| name == field))
+    {
+        return Err(Error::simple(format!(
+            "Event {event_name} has no field {field}"
+        )));
+    }
+    if let Some((field, _)) = event.fields.iter().find(|(name, type_)| {
+        !matches!(type_, Type::Nullable(_)) && !emit.fields.iter().any(|(field, _)| field == name)
+    }) {
+        return Err(Error::simple(format!(
+            "Missing field {field} of event {event_name}"
+        )));
+    }
+
+    comment!(compiler, "Emitting {event_name}");
+    let types = compiler
+        .root_scope
+        .contracts
+        .iter()
+        .flat_map(|(_, c)| {
+            c.events.iter().map(|e| abi::EventType {
+                contract: c.name.clone(),
+                event: e.clone(),
+            })
+        })
+        .collect();
+    let events = compiler.memory.events(types);
+    let element = compiler.allocate_symbol(array::element_type(&events.type_).clone());
+    // Only the emitted event is not null
+    compiler.memory.write(
+        compiler.instructions,
+        element.memory_addr,
+        &vec![ValueSource::Immediate(0); element.type_.miden_width() as usize],
+    );
+    let emitted = struct_field(compiler, &element, &event_name)?;
+    compiler.memory.write(
+        compiler.instructions,
+        nullable::is_not_null(&emitted).memory_addr,
+        &[ValueSource::Immediate(1)],
+    );
+    let emitted = nullable::value(emitted);
+
+    for (field, expr) in &emit.fields {
+        let value = compile_expression(expr, compiler, scope)?;
+        let field = struct_field(compiler, &emitted, field)?;
+        let target = match (&field.type_, &value.type_) {
+            (Type::Nullable(inner), value_type) if !matches!(value_type, Type::Nullable(_)) => {
+                ensure_eq_type!(@inner.as_ref(), @value_type);
+                compiler.memory.write(
+                    compiler.instructions,
+                    nullable::is_not_null(&field).memory_addr,
+                    &[ValueSource::Immediate(1)],
+                );
+                nullable::value(field)
+            }
+            (field_type, value_type) => {
+                ensure_eq_type!(@field_type, @value_type);
+                field
+            }
+        };
+
+        compiler.memory.read(
+            compiler.instructions,
+            value.memory_addr,
+            value.type_.miden_width(),
+        );
+        compiler.memory.write(
+            compiler.instructions,
+            target.memory_addr,
+            &vec![ValueSource::Stack; value.type_.miden_width() as usize],
+        );
+    }
+
+    array::push(compiler, scope, &[events, element])?;
+
+    Ok(())
+}
+
+/// Checks that `args` match the parameters of a function of a contract.
+fn check_arguments(
+    function: &ast::Function,
+    contract_struct: &Struct,
+    args: &[Symbol],
+) -> Result<()> {
+    ensure!(
+        args.len() == function.parameters.len(),
+        ArgumentsCountSnafu {
+            found: args.len(),
+            expected: function.parameters.len()
+        }
+    );
+    for (arg, param) in args.iter().zip(&function.parameters) {
+        let param_type =
+            ast_param_type_to_type(param.required, &param.type_, Some(contract_struct))?;
+        ensure_eq_type!(@arg.type_.clone(), @param_type);
+    }
+
+    Ok(())
+}
+
 fn compile_check_ownership(
     compiler: &mut Compiler,
     struct_symbol: &Symbol,
     contract: &Contract,
     auth_pk: &Symbol,
 ) -> Result<Symbol> {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+    let result = boolean::new(compiler, false);
 
     for delegate_field in contract.fields.iter().filter(|f| f.delegate) {
         let delegate_symbol = struct_field(compiler, struct_symbol, &delegate_field.name)?;
@@ -4675,39 +6427,41 @@ fn ast_type_to_type(required: bool, type_: &ast::Type) -> Type {
 }
 
 /// A function that takes in a struct type and generates a program that hashes a value of that type and returns the hash on the stack.
-pub fn compile_hasher(t: Type, salts: Option<&[u32]>) -> Result<String> {
+///
+/// If `salted`, the program reads a salt word for each field of a struct, or one salt for other types,
+/// from the advice tape before the value.
+pub fn compile_hasher(t: Type, salted: bool) -> Result<String> {
     let mut instructions = vec![];
     let mut memory = Memory::new();
     let empty_program = ast::Program { nodes: vec![] };
-    let scope = prepare_scope(&empty_program);
+    let scope = prepare_scope(&empty_program)?;
 
     {
         let mut compiler = Compiler::new(&mut instructions, &mut memory, &scope);
 
-        let salts = salts.map(|s| {
-            s.iter()
-                .map(|s| {
-                    let salt = compiler
-                        .memory
-                        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
-                    compiler.memory.write(
-                        compiler.instructions,
-                        salt.memory_addr,
-                        &[ValueSource::Immediate(*s)],
-                    );
-                    salt
-                })
-                .collect::<Vec<_>>()
-        });
+        let salts = if salted {
+            let count = match &t {
+                Type::Struct(struct_) => struct_.fields.len(),
+                _ => 1,
+            };
+            Some(
+                (0..count)
+                    .map(|_| read_advice_generic(&mut compiler, &Type::Hash))
+                    .collect::<Result<Vec<_>>>()?,
+            )
+        } else {
+            None
+        };
 
         let hash = match t {
             Type::Struct(struct_) => {
-                let value = compiler
-                    .memory
-                    .allocate_symbol(Type::Struct(struct_.clone()));
+                let value = compiler.allocate_symbol(Type::Struct(struct_.clone()));
                 read_struct_from_advice_tape(&mut compiler, &value, &struct_, None)?;
 
-                hash_record_with_salts(&mut compiler, &value, salts.as_ref().unwrap())?
+                let Some(salts) = &salts else {
+                    return Err(Error::simple("Structs can only be hashed with salts"));
+                };
+                hash_record_with_salts(&mut compiler, &value, salts)?
             }
             t => {
                 let value = read_advice_generic(&mut compiler, &t)?;
@@ -4731,19 +6485,21 @@ pub fn compile_hasher(t: Type, salts: Option<&[u32]>) -> Result<String> {
 
     let instructions = encoder::unabstract(
         instructions,
-        &mut |size| memory.allocate(size),
+        &mut |size| memory.allocate_fresh(size),
         &mut None,
         &mut None,
         &mut false,
         false,
     );
+    let instructions = peephole::optimize(instructions);
 
     let mut miden_code = String::new();
     miden_code.push_str("use.std::math::u64\n");
     miden_code.push_str("begin\n");
     miden_code.push_str("  push.");
-    miden_code.push_str(&memory.static_alloc_ptr.to_string());
-    miden_code.push_str("\n  mem_store.3\n"); // dynamic allocation pointer
+    miden_code.push_str(&memory.peak.to_string());
+    miden_code.push_str("\n  dup\n  mem_store.3\n"); // dynamic allocation pointer
+    miden_code.push_str("  mem_store.8\n"); // start of the heap
     for instruction in instructions {
         instruction
             .encode(unsafe { miden_code.as_mut_vec() }, 1)
@@ -24,9 +24,7 @@ pub(crate) fn value(value: Symbol) -> Symbol {
 }
 
 pub(crate) fn eq(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
 
     let (inner_type_eq_result, inner_type_eq_insts) = {
         let mut insts = vec![];
@@ -66,3 +64,115 @@ pub(crate) fn eq(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
 
     result
 }
+
+/// Compiles `a?.field`. The result is null if `a` is null,
+/// otherwise it's the field wrapped in a nullable, unless it already is one.
+pub(crate) fn optional_field(compiler: &mut Compiler, a: &Symbol, field: &str) -> Result<Symbol> {
+    let field = struct_field(compiler, &value(a.clone()), field)?;
+    let (field_is_not_null, field_value) = match &field.type_ {
+        Type::Nullable(_) => (
+            ValueSource::Memory(is_not_null(&field).memory_addr),
+            value(field),
+        ),
+        _ => (ValueSource::Immediate(1), field),
+    };
+
+    let result = compiler.allocate_symbol(Type::Nullable(Box::new(field_value.type_.clone())));
+
+    let mut then = vec![];
+    compiler.memory.write(
+        &mut then,
+        is_not_null(&result).memory_addr,
+        &[field_is_not_null],
+    );
+    compiler.memory.read(
+        &mut then,
+        field_value.memory_addr,
+        field_value.type_.miden_width(),
+    );
+    compiler.memory.write(
+        &mut then,
+        value(result.clone()).memory_addr,
+        &vec![ValueSource::Stack; field_value.type_.miden_width() as usize],
+    );
+
+    let mut else_ = vec![];
+    compiler.memory.write(
+        &mut else_,
+        is_not_null(&result).memory_addr,
+        &[ValueSource::Immediate(0)],
+    );
+
+    compiler.instructions.push(Instruction::If {
+        condition: vec![Instruction::MemLoad(Some(is_not_null(a).memory_addr))],
+        then,
+        else_,
+    });
+
+    Ok(result)
+}
+
+/// Compiles `a ?? b`. `b` is only evaluated if `a` is null.
+pub(crate) fn coalesce(
+    compiler: &mut Compiler,
+    a: &Symbol,
+    b: impl FnOnce(&mut Compiler) -> Result<Symbol>,
+) -> Result<Symbol> {
+    let (b, b_insts) = {
+        let mut insts = vec![];
+        std::mem::swap(compiler.instructions, &mut insts);
+
+        let res = b(compiler);
+
+        std::mem::swap(compiler.instructions, &mut insts);
+        (res?, insts)
+    };
+
+    let a_value = value(a.clone());
+    let b_inner_type = match &b.type_ {
+        Type::Nullable(t) => &**t,
+        t => t,
+    };
+    ensure_eq_type!(a_value, @b_inner_type);
+
+    let result = compiler.allocate_symbol(b.type_.clone());
+    let result_value = match &result.type_ {
+        Type::Nullable(_) => value(result.clone()),
+        _ => result.clone(),
+    };
+
+    let mut then = vec![];
+    if let Type::Nullable(_) = &result.type_ {
+        compiler.memory.write(
+            &mut then,
+            is_not_null(&result).memory_addr,
+            &[ValueSource::Immediate(1)],
+        );
+    }
+    compiler
+        .memory
+        .read(&mut then, a_value.memory_addr, a_value.type_.miden_width());
+    compiler.memory.write(
+        &mut then,
+        result_value.memory_addr,
+        &vec![ValueSource::Stack; a_value.type_.miden_width() as usize],
+    );
+
+    let mut else_ = b_insts;
+    compiler
+        .memory
+        .read(&mut else_, b.memory_addr, b.type_.miden_width());
+    compiler.memory.write(
+        &mut else_,
+        result.memory_addr,
+        &vec![ValueSource::Stack; b.type_.miden_width() as usize],
+    );
+
+    compiler.instructions.push(Instruction::If {
+        condition: vec![Instruction::MemLoad(Some(is_not_null(a).memory_addr))],
+        then,
+        else_,
+    });
+
+    Ok(result)
+}
@@ -0,0 +1,417 @@
+//! Peephole optimizations over unabstracted instructions.
+//!
+//! The compiler keeps every value in memory, so the generated code is full of
+//! stores that are immediately loaded back, constants that are stored only to be
+//! loaded again and stack operations that cancel out. These passes rewrite such
+//! sequences into cheaper stack operations. Stores are only removed if the address
+//! already holds the stored value, because memory is read after the program finishes.
+
+use std::collections::HashMap;
+
+use super::encoder::Instruction;
+
+pub(crate) fn optimize(instructions: Vec<Instruction>) -> Vec<Instruction> {
+    let mut instructions = instructions
+        .into_iter()
+        .map(optimize_nested)
+        .collect::<Vec<_>>();
+
+    loop {
+        let optimized = rewrite(remove_redundant_copies(propagate_constants(
+            instructions.clone(),
+        )));
+        if optimized == instructions {
+            break optimized;
+        }
+        instructions = optimized;
+    }
+}
+
+fn optimize_nested(instruction: Instruction) -> Instruction {
+    match instruction {
+        Instruction::While { condition, body } => Instruction::While {
+            condition: optimize(condition),
+            body: optimize(body),
+        },
+        Instruction::WhileTrueRaw { instructions } => Instruction::WhileTrueRaw {
+            instructions: optimize(instructions),
+        },
+        Instruction::Repeat {
+            count,
+            instructions,
+        } => Instruction::Repeat {
+            count,
+            instructions: optimize(instructions),
+        },
+        Instruction::If {
+            condition,
+            then,
+            else_,
+        } => Instruction::If {
+            condition: optimize(condition),
+            then: optimize(then),
+            else_: optimize(else_),
+        },
+        other => other,
+    }
+}
+
+/// Replaces loads of addresses that a constant was just stored to with a push of the constant.
+///
+/// Only straight-line code is tracked, anything that could write to memory
+/// in a way we can't see forgets all known values.
+fn propagate_constants(instructions: Vec<Instruction>) -> Vec<Instruction> {
+    let mut known = HashMap::new();
+    let mut result = Vec::with_capacity(instructions.len());
+
+    for instruction in instructions {
+        match instruction {
+            Instruction::MemStore(Some(addr)) => {
+                match result.last() {
+                    Some(Instruction::Push(value)) => known.insert(addr, *value),
+                    _ => known.remove(&addr),
+                };
+            }
+            Instruction::MemLoad(Some(addr)) => {
+                if let Some(value) = known.get(&addr) {
+                    result.push(Instruction::Push(*value));
+                    continue;
+                }
+            }
+            Instruction::MemStore(None)
+            | Instruction::Exec(_)
+            | Instruction::While { .. }
+            | Instruction::WhileTrueRaw { .. }
+            | Instruction::Repeat { .. }
+            | Instruction::If { .. }
+            | Instruction::IfTrue
+            | Instruction::IfElse
+            | Instruction::IfEnd
+            | Instruction::Abstract(_) => known.clear(),
+            _ => {}
+        }
+
+        result.push(instruction);
+    }
+
+    result
+}
+
+/// Removes copies, a `Memory::read` of one address followed by a `Memory::write` to another,
+/// of values that are already in the target address, such as copying a value back
+/// to the address it was copied from.
+///
+/// Like `propagate_constants`, only straight-line code is tracked.
+fn remove_redundant_copies(instructions: Vec<Instruction>) -> Vec<Instruction> {
+    // The address that each copied address was copied from
+    let mut copied_from = HashMap::<u32, u32>::new();
+    let mut result = Vec::with_capacity(instructions.len());
+
+    for instruction in instructions {
+        match instruction {
+            Instruction::MemStore(Some(addr)) => {
+                let source = |a: u32| copied_from.get(&a).copied().unwrap_or(a);
+                match result.last() {
+                    Some(Instruction::MemLoad(Some(loaded))) if source(*loaded) == source(addr) => {
+                        result.pop();
+                        continue;
+                    }
+                    Some(Instruction::MemLoad(Some(loaded))) => {
+                        let loaded = source(*loaded);
+                        copied_from.retain(|to, from| *to != addr && *from != addr);
+                        copied_from.insert(addr, loaded);
+                    }
+                    _ => copied_from.retain(|to, from| *to != addr && *from != addr),
+                }
+            }
+            Instruction::MemStore(None)
+            | Instruction::Exec(_)
+            | Instruction::While { .. }
+            | Instruction::WhileTrueRaw { .. }
+            | Instruction::Repeat { .. }
+            | Instruction::If { .. }
+            | Instruction::IfTrue
+            | Instruction::IfElse
+            | Instruction::IfEnd
+            | Instruction::Abstract(_) => copied_from.clear(),
+            _ => {}
+        }
+
+        result.push(instruction);
+    }
+
+    result
+}
+
+fn rewrite(instructions: Vec<Instruction>) -> Vec<Instruction> {
+    let mut result: Vec<Instruction> = Vec::with_capacity(instructions.len());
+    let mut i = 0;
+
+    while i < instructions.len() {
+        if let Some(n) = store_load_run(&instructions[i..]) {
+            // [v0, v1, ...] stored to a, b, ... and loaded back from ..., b, a
+            // is the same as duplicating the values and storing them.
+            let dup = if n == 1 { None } else { Some(n as u32 - 1) };
+            result.extend(std::iter::repeat(Instruction::Dup(dup)).take(n));
+            result.extend(instructions[i..i + n].iter().cloned());
+            i += 2 * n;
+            continue;
+        }
+
+        let replacement = match (&instructions[i], instructions.get(i + 1)) {
+            (
+                Instruction::Push(_) | Instruction::Dup(_) | Instruction::MemLoad(Some(_)),
+                Some(Instruction::Drop),
+            )
+            | (Instruction::Swap, Some(Instruction::Swap)) => Some(vec![]),
+            (Instruction::MovUp(a), Some(Instruction::MovDown(b)))
+            | (Instruction::MovDown(a), Some(Instruction::MovUp(b)))
+                if a == b =>
+            {
+                Some(vec![])
+            }
+            (Instruction::MemLoad(None), Some(Instruction::Drop)) => Some(vec![Instruction::Drop]),
+            (Instruction::MemLoad(Some(a)), Some(Instruction::MemStore(Some(b)))) if a == b => {
+                Some(vec![])
+            }
+            (Instruction::Push(n), Some(Instruction::U32CheckedSHL(None))) if *n < 32 => {
+                Some(vec![Instruction::U32CheckedSHL(Some(*n))])
+            }
+            (Instruction::Push(n), Some(Instruction::U32CheckedSHR(None))) if *n < 32 => {
+                Some(vec![Instruction::U32CheckedSHR(Some(*n))])
+            }
+            // Dividing by zero has to fail at runtime, not when assembling
+            (Instruction::Push(n), Some(Instruction::U32CheckedDiv(None))) if *n != 0 => {
+                Some(vec![Instruction::U32CheckedDiv(Some(*n))])
+            }
+            (Instruction::Push(n), Some(Instruction::U32CheckedMod(None))) if *n != 0 => {
+                Some(vec![Instruction::U32CheckedMod(Some(*n))])
+            }
+            _ => None,
+        };
+
+        match replacement {
+            Some(replacement) => {
+                result.extend(replacement);
+                i += 2;
+            }
+            None => {
+                result.push(instructions[i].clone());
+                i += 1;
+            }
+        }
+    }
+
+    result
+}
+
+/// Returns the length of the longest run of stores to distinct addresses
+/// that is immediately followed by loads of the same addresses, in reverse order.
+fn store_load_run(instructions: &[Instruction]) -> Option<usize> {
+    let stores = instructions
+        .iter()
+        .take_while(|i| matches!(i, Instruction::MemStore(Some(_))))
+        .count()
+        // dup can reach at most 16 elements deep
+        .min(16);
+
+    (1..=stores).rev().find(|&n| {
+        let (stored, rest) = instructions.split_at(n);
+        let addrs = stored
+            .iter()
+            .map(|i| match i {
+                Instruction::MemStore(Some(addr)) => *addr,
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+
+        let distinct = addrs
+            .iter()
+            .enumerate()
+            .all(|(i, a)| !addrs[..i].contains(a));
+
+        distinct
+            && rest.len() >= n
+            && rest[..n]
+                .iter()
+                .zip(addrs.iter().rev())
+                .all(|(i, addr)| *i == Instruction::MemLoad(Some(*addr)))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_store_load() {
+        let instructions = vec![
+            Instruction::MemLoad(None),
+            Instruction::MemStore(Some(10)),
+            Instruction::MemLoad(Some(10)),
+            Instruction::U32CheckedAdd,
+        ];
+
+        assert_eq!(
+            optimize(instructions),
+            vec![
+                Instruction::MemLoad(None),
+                Instruction::Dup(None),
+                Instruction::MemStore(Some(10)),
+                Instruction::U32CheckedAdd,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_store_load_run() {
+        let instructions = vec![
+            Instruction::MemStore(Some(10)),
+            Instruction::MemStore(Some(11)),
+            Instruction::MemLoad(Some(11)),
+            Instruction::MemLoad(Some(10)),
+        ];
+
+        assert_eq!(
+            optimize(instructions),
+            vec![
+                Instruction::Dup(Some(1)),
+                Instruction::Dup(Some(1)),
+                Instruction::MemStore(Some(10)),
+                Instruction::MemStore(Some(11)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_store_load_same_address() {
+        // Both loads read the second stored value
+        let instructions = vec![
+            Instruction::MemStore(Some(10)),
+            Instruction::MemStore(Some(10)),
+            Instruction::MemLoad(Some(10)),
+            Instruction::MemLoad(Some(10)),
+        ];
+
+        assert_eq!(
+            optimize(instructions),
+            vec![
+                Instruction::MemStore(Some(10)),
+                Instruction::Dup(None),
+                Instruction::Dup(None),
+                Instruction::MemStore(Some(10)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_constant_shift() {
+        let instructions = vec![
+            Instruction::MemLoad(Some(20)),
+            Instruction::Push(4),
+            Instruction::MemStore(Some(10)),
+            Instruction::MemLoad(Some(10)),
+            Instruction::U32CheckedSHL(None),
+        ];
+
+        assert_eq!(
+            optimize(instructions),
+            vec![
+                Instruction::MemLoad(Some(20)),
+                Instruction::Push(4),
+                Instruction::MemStore(Some(10)),
+                Instruction::U32CheckedSHL(Some(4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_constants_are_forgotten_in_loops() {
+        let instructions = vec![
+            Instruction::Push(4),
+            Instruction::MemStore(Some(10)),
+            Instruction::While {
+                condition: vec![Instruction::MemLoad(Some(10))],
+                body: vec![Instruction::Push(0), Instruction::MemStore(Some(10))],
+            },
+            Instruction::MemLoad(Some(10)),
+        ];
+
+        assert_eq!(optimize(instructions.clone()), instructions);
+    }
+
+    #[test]
+    fn test_redundant_copies() {
+        let instructions = vec![
+            // 11 = 10, 12 = 11, 10 = 12
+            Instruction::MemLoad(Some(10)),
+            Instruction::MemStore(Some(11)),
+            Instruction::Swap,
+            Instruction::MemLoad(Some(11)),
+            Instruction::MemStore(Some(12)),
+            Instruction::Swap,
+            Instruction::MemLoad(Some(12)),
+            Instruction::MemStore(Some(10)),
+        ];
+
+        assert_eq!(
+            optimize(instructions),
+            vec![
+                Instruction::MemLoad(Some(10)),
+                Instruction::MemStore(Some(11)),
+                Instruction::Swap,
+                Instruction::MemLoad(Some(11)),
+                Instruction::MemStore(Some(12)),
+                Instruction::Swap,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_copies_are_forgotten_when_overwritten() {
+        let instructions = vec![
+            Instruction::MemLoad(Some(10)),
+            Instruction::MemStore(Some(11)),
+            Instruction::Push(1),
+            Instruction::MemStore(Some(10)),
+            Instruction::MemLoad(Some(11)),
+            Instruction::MemStore(Some(10)),
+            Instruction::MemLoad(Some(20)),
+            Instruction::MemStore(Some(21)),
+            Instruction::MemLoad(Some(22)),
+            Instruction::MemStore(None),
+            Instruction::MemLoad(Some(21)),
+            Instruction::MemStore(Some(20)),
+        ];
+
+        assert_eq!(optimize(instructions.clone()), instructions);
+    }
+
+    #[test]
+    fn test_no_division_by_zero_immediate() {
+        let instructions = vec![
+            Instruction::MemLoad(Some(20)),
+            Instruction::Push(0),
+            Instruction::U32CheckedDiv(None),
+        ];
+
+        assert_eq!(optimize(instructions.clone()), instructions);
+    }
+
+    #[test]
+    fn test_cancelling_instructions() {
+        let instructions = vec![
+            Instruction::Push(1),
+            Instruction::Swap,
+            Instruction::Swap,
+            Instruction::Drop,
+            Instruction::MovUp(3),
+            Instruction::MovDown(3),
+            Instruction::MemLoad(Some(5)),
+            Instruction::MemStore(Some(5)),
+        ];
+
+        assert_eq!(optimize(instructions), vec![]);
+    }
+}
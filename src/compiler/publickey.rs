@@ -1,15 +1,16 @@
-use abi::publickey::Key;
+use abi::publickey::{Crv, Key};
 use error::prelude::*;
 
 use super::{encoder::Instruction, *};
 
 // Layout: [key, crv, alg, use, extra_ptr]
-// `extra_ptr` in secp256k1 is pointer to 64 bytes of data,
-// the x and y coordinates of the public key.
+// `extra_ptr` is a pointer to 64 bytes of data, the x and y coordinates of the public key.
+// Ed25519 keys only have an x coordinate, their y bytes are zero.
 #[allow(unused)]
 pub(crate) fn new(compiler: &mut Compiler, key: Key) -> Symbol {
-    let symbol = compiler.memory.allocate_symbol(Type::PublicKey);
-    let ptr_xy = compiler.memory.allocate(64);
+    let symbol = compiler.allocate_symbol(Type::PublicKey);
+    let ptr_xy = compiler.allocate(64);
+    compiler.memory.pin();
 
     compiler.memory.write(
         compiler.instructions,
@@ -72,9 +73,7 @@ pub(crate) fn extra_ptr(symbol: &Symbol) -> Symbol {
 }
 
 pub(crate) fn eq(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let symbol = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+    let symbol = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
 
     compiler.instructions.push(Instruction::Push(1));
     for i in 0..4 {
@@ -203,6 +202,25 @@ pub(crate) fn to_hex(compiler: &mut Compiler, args: &[Symbol]) -> Symbol {
             Instruction::Drop,
         ]);
     }
+    // [extra_ptr]
+    compiler.instructions.push(Instruction::Drop);
+
+    // Ed25519 keys are only the x coordinate
+    let mut x_only = vec![];
+    compiler.memory.write(
+        &mut x_only,
+        string::length(&result).memory_addr,
+        &[ValueSource::Immediate(2 + 32 * 2)],
+    );
+    compiler.instructions.push(Instruction::If {
+        condition: vec![
+            Instruction::MemLoad(Some(crv(pk).memory_addr)),
+            Instruction::Push(u8::from(Crv::Ed25519) as u32),
+            Instruction::Eq,
+        ],
+        then: x_only,
+        else_: vec![],
+    });
 
     result
 }
@@ -218,7 +236,7 @@ pub(crate) fn hash(compiler: &mut Compiler, args: &[Symbol]) -> Result<Symbol> {
     let public_key = &args[0];
     ensure_eq_type!(public_key, Type::PublicKey);
 
-    let result = compiler.memory.allocate_symbol(Type::Hash);
+    let result = compiler.allocate_symbol(Type::Hash);
 
     compiler.instructions.extend([
         encoder::Instruction::Push(0),
@@ -299,3 +317,92 @@ pub(crate) fn hash(compiler: &mut Compiler, args: &[Symbol]) -> Result<Symbol> {
 
     Ok(result)
 }
+
+/// `verifySignature(publicKey, message, signature)`, returns whether `signature`
+/// is a valid signature of `message` by `publicKey`.
+///
+/// Miden VM can't verify ECDSA or Ed25519 signatures, so the claim is left pending while
+/// the result is read from the advice provider, and the prover verifies the pending claim
+/// when the program reads it. Every claim is kept and the program outputs their hash,
+/// so that the results can be checked with `verify_signature_claims`.
+/// Until they are, the result is only as trustworthy as the prover.
+pub(crate) fn verify_signature(
+    compiler: &mut Compiler,
+    scope: &mut Scope,
+    args: &[Symbol],
+) -> Result<Symbol> {
+    ensure!(
+        args.len() == 3,
+        ArgumentsCountSnafu {
+            found: args.len(),
+            expected: 3usize
+        }
+    );
+    let [public_key, message, signature] = args else {
+        unreachable!()
+    };
+    ensure_eq_type!(public_key, Type::PublicKey);
+    ensure_eq_type!(message, Type::Bytes);
+    ensure_eq_type!(signature, Type::Bytes);
+
+    let claims = compiler.memory.signature_claims();
+    let pending = struct_field(compiler, &claims, "pending")?;
+    for (field, value) in [
+        ("publicKey", public_key),
+        ("message", message),
+        ("signature", signature),
+    ] {
+        let field = struct_field(compiler, &pending, field)?;
+        compiler.memory.read(
+            compiler.instructions,
+            value.memory_addr,
+            value.type_.miden_width(),
+        );
+        compiler.memory.write(
+            compiler.instructions,
+            field.memory_addr,
+            &vec![ValueSource::Stack; field.type_.miden_width() as usize],
+        );
+    }
+
+    let waiting = struct_field(compiler, &claims, "waiting")?;
+    compiler.memory.write(
+        compiler.instructions,
+        waiting.memory_addr,
+        &[ValueSource::Immediate(1)],
+    );
+
+    let valid = struct_field(compiler, &pending, "valid")?;
+    compiler.instructions.extend([
+        Instruction::AdvPush(1),
+        // [valid]
+        Instruction::Dup(None),
+        Instruction::Push(1),
+        Instruction::U32CheckedLTE,
+        // [valid <= 1, valid]
+        Instruction::Assert,
+        // [valid]
+    ]);
+    compiler.memory.write(
+        compiler.instructions,
+        valid.memory_addr,
+        &[ValueSource::Stack],
+    );
+    compiler.memory.write(
+        compiler.instructions,
+        waiting.memory_addr,
+        &[ValueSource::Immediate(0)],
+    );
+
+    let claims_array = struct_field(compiler, &claims, "claims")?;
+    array::push(compiler, scope, &[claims_array, pending])?;
+
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+    compiler.memory.write(
+        compiler.instructions,
+        result.memory_addr,
+        &[ValueSource::Memory(valid.memory_addr)],
+    );
+
+    Ok(result)
+}
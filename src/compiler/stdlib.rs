@@ -0,0 +1,261 @@
+//! Builtins that execute procedures of the Miden standard library.
+//!
+//! Most bindings push their arguments in order, so the last one is on top of the stack,
+//! execute the procedure and keep one of its outputs. The `std::math::u64` bindings are
+//! methods of `u64`, the receiver is the first argument. Words, such as Merkle roots,
+//! keys and Falcon public keys, are `Hash` values.
+
+use super::*;
+
+const BOOLEAN: Type = Type::PrimitiveType(PrimitiveType::Boolean);
+const UINT32: Type = Type::PrimitiveType(PrimitiveType::UInt32);
+const UINT64: Type = Type::PrimitiveType(PrimitiveType::UInt64);
+const WORD: Type = Type::Hash;
+
+/// The number of words that `mmr::unpack` can write, the number of leaves and up to 63 peaks,
+/// padded to an even number.
+const MMR_WORDS: u32 = 1 + 64;
+
+struct Binding<'a> {
+    procedure: &'static str,
+    inputs: &'a [Type],
+    /// The outputs of the procedure, starting from the top of the stack.
+    outputs: &'a [Type],
+    /// The output that is returned, the others are dropped.
+    result: Option<usize>,
+}
+
+macro_rules! bindings {
+    ($($name:literal $(on $method_of:expr)? => $procedure:literal ($($input:expr),*) -> [$($output:expr),*] $(returns $result:literal)?;)*) => {
+        vec![$((
+            $name,
+            bindings!(@method_of $($method_of)?),
+            Function::Builtin(|compiler, _scope, args| {
+                call(
+                    compiler,
+                    args,
+                    &Binding {
+                        procedure: $procedure,
+                        inputs: &[$($input),*],
+                        outputs: &[$($output),*],
+                        result: bindings!(@result [$($output),*] $($result)?),
+                    },
+                )
+            }),
+        )),*]
+    };
+    (@method_of) => { None };
+    (@method_of $method_of:expr) => { Some(TypeConstraint::Exact($method_of)) };
+    (@result [] ) => { None };
+    (@result [$($output:expr),+] ) => { Some(0) };
+    (@result [$($output:expr),*] $result:literal) => { Some($result) };
+}
+
+pub(super) fn builtins() -> impl Iterator<Item = (String, Option<TypeConstraint>, Function<'static>)>
+{
+    let mut builtins: Vec<(&str, Option<TypeConstraint>, Function<'static>)> = bindings! {
+        // std::math::u64, the checked arithmetic, division and comparisons are the u64 operators
+        "wrappingAdd" on UINT64 => "u64::wrapping_add"(UINT64, UINT64) -> [UINT64];
+        "wrappingSub" on UINT64 => "u64::wrapping_sub"(UINT64, UINT64) -> [UINT64];
+        "wrappingMul" on UINT64 => "u64::wrapping_mul"(UINT64, UINT64) -> [UINT64];
+        "addOverflows" on UINT64 => "u64::overflowing_add"(UINT64, UINT64) -> [BOOLEAN, UINT64];
+        "subOverflows" on UINT64 => "u64::overflowing_sub"(UINT64, UINT64) -> [BOOLEAN, UINT64];
+        // The 128-bit product is returned as two u64s, the high one on top
+        "mulHigh" on UINT64 => "u64::overflowing_mul"(UINT64, UINT64) -> [UINT64, UINT64];
+        "min" on UINT64 => "u64::checked_min"(UINT64, UINT64) -> [UINT64];
+        "max" on UINT64 => "u64::checked_max"(UINT64, UINT64) -> [UINT64];
+        "and" on UINT64 => "u64::checked_and"(UINT64, UINT64) -> [UINT64];
+        "or" on UINT64 => "u64::checked_or"(UINT64, UINT64) -> [UINT64];
+        "xor" on UINT64 => "u64::checked_xor"(UINT64, UINT64) -> [UINT64];
+        "shl" on UINT64 => "u64::checked_shl"(UINT64, UINT32) -> [UINT64];
+        "shr" on UINT64 => "u64::checked_shr"(UINT64, UINT32) -> [UINT64];
+        "rotl" on UINT64 => "u64::checked_rotl"(UINT64, UINT32) -> [UINT64];
+        "rotr" on UINT64 => "u64::checked_rotr"(UINT64, UINT32) -> [UINT64];
+        "isZero" on UINT64 => "u64::checked_eqz"(UINT64) -> [BOOLEAN];
+        "leadingZeros" on UINT64 => "u64::unchecked_clz"(UINT64) -> [UINT32];
+        "trailingZeros" on UINT64 => "u64::unchecked_ctz"(UINT64) -> [UINT32];
+        "leadingOnes" on UINT64 => "u64::unchecked_clo"(UINT64) -> [UINT32];
+        "trailingOnes" on UINT64 => "u64::unchecked_cto"(UINT64) -> [UINT32];
+        // std::crypto::dsa, the signature is requested from the advice provider
+        "falconVerify" => "rpo_falcon512::verify"(WORD, WORD) -> [];
+        // std::collections::smt, the nodes are requested from the advice provider
+        "smtGet" => "smt::get"(WORD, WORD) -> [WORD, WORD];
+        "smtInsert" => "smt::insert"(WORD, WORD, WORD) -> [WORD, WORD] returns 1;
+    };
+
+    builtins.push((
+        "mmrGet",
+        None,
+        Function::Builtin(|compiler, _scope, args| {
+            ensure!(
+                args.len() == 2,
+                ArgumentsCountSnafu {
+                    found: args.len(),
+                    expected: 2usize
+                }
+            );
+            mmr_get(compiler, &args[0], &args[1])
+        }),
+    ));
+
+    builtins.push((
+        "mmrAdd",
+        None,
+        Function::Builtin(|compiler, _scope, args| {
+            ensure!(
+                args.len() == 2,
+                ArgumentsCountSnafu {
+                    found: args.len(),
+                    expected: 2usize
+                }
+            );
+            mmr_add(compiler, &args[0], &args[1])
+        }),
+    ));
+
+    builtins
+        .into_iter()
+        .map(|(name, method_of, func)| (name.to_string(), method_of, func))
+}
+
+fn call(compiler: &mut Compiler, args: &[Symbol], binding: &Binding) -> Result<Symbol> {
+    ensure!(
+        args.len() == binding.inputs.len(),
+        ArgumentsCountSnafu {
+            found: args.len(),
+            expected: binding.inputs.len()
+        }
+    );
+    for (arg, input) in args.iter().zip(binding.inputs) {
+        ensure_eq_type!(arg, @input);
+    }
+
+    for arg in args {
+        compiler.memory.read(
+            compiler.instructions,
+            arg.memory_addr,
+            arg.type_.miden_width(),
+        );
+    }
+    compiler
+        .instructions
+        .push(encoder::Instruction::Exec(binding.procedure));
+
+    let mut result = Symbol {
+        type_: Type::Nullable(Box::new(BOOLEAN)),
+        memory_addr: 0,
+    };
+    for (i, output) in binding.outputs.iter().enumerate() {
+        if binding.result == Some(i) {
+            result = compiler.allocate_symbol(output.clone());
+            compiler.memory.write(
+                compiler.instructions,
+                result.memory_addr,
+                &vec![ValueSource::Stack; output.miden_width() as usize],
+            );
+        } else {
+            drop_value(compiler, output);
+        }
+    }
+
+    Ok(result)
+}
+
+fn drop_value(compiler: &mut Compiler, type_: &Type) {
+    let width = type_.miden_width();
+    for _ in 0..width / 4 {
+        compiler.instructions.push(encoder::Instruction::Dropw);
+    }
+    for _ in 0..width % 4 {
+        compiler.instructions.push(encoder::Instruction::Drop);
+    }
+}
+
+/// Unpacks the MMR with the hash of its peaks into memory, the peaks are requested
+/// from the advice provider. Returns the address of the MMR.
+fn mmr_unpack(compiler: &mut Compiler, peaks: &Symbol) -> Result<u32> {
+    ensure_eq_type!(peaks, Type::Hash);
+
+    // `mmr::unpack` addresses words, and so does the compiler.
+    // The MMR builtins read the words it writes through the pointer, so they are pinned
+    let mmr_ptr = compiler.allocate(MMR_WORDS);
+    compiler.memory.pin();
+
+    // [HASH, mmr_ptr]
+    compiler
+        .instructions
+        .push(encoder::Instruction::Push(mmr_ptr));
+    compiler.memory.read(
+        compiler.instructions,
+        peaks.memory_addr,
+        peaks.type_.miden_width(),
+    );
+    compiler
+        .instructions
+        .push(encoder::Instruction::Exec("mmr::unpack"));
+
+    Ok(mmr_ptr)
+}
+
+fn mmr_get(compiler: &mut Compiler, peaks: &Symbol, pos: &Symbol) -> Result<Symbol> {
+    ensure_eq_type!(pos, Type::PrimitiveType(PrimitiveType::UInt32));
+    let mmr_ptr = mmr_unpack(compiler, peaks)?;
+
+    // [pos, mmr_ptr]
+    compiler
+        .instructions
+        .push(encoder::Instruction::Push(mmr_ptr));
+    compiler.memory.read(
+        compiler.instructions,
+        pos.memory_addr,
+        pos.type_.miden_width(),
+    );
+    compiler
+        .instructions
+        .push(encoder::Instruction::Exec("mmr::get"));
+
+    let result = compiler.allocate_symbol(Type::Hash);
+    compiler.memory.write(
+        compiler.instructions,
+        result.memory_addr,
+        &vec![ValueSource::Stack; 4],
+    );
+
+    Ok(result)
+}
+
+/// Adds a leaf to the MMR, returns the hash of the new peaks.
+fn mmr_add(compiler: &mut Compiler, peaks: &Symbol, leaf: &Symbol) -> Result<Symbol> {
+    ensure_eq_type!(leaf, Type::Hash);
+    let mmr_ptr = mmr_unpack(compiler, peaks)?;
+
+    // [mmr_ptr, N]
+    compiler.memory.read(
+        compiler.instructions,
+        leaf.memory_addr,
+        leaf.type_.miden_width(),
+    );
+    compiler
+        .instructions
+        .push(encoder::Instruction::Push(mmr_ptr));
+    compiler
+        .instructions
+        .push(encoder::Instruction::Exec("mmr::add"));
+
+    // [HASH]
+    compiler
+        .instructions
+        .push(encoder::Instruction::Push(mmr_ptr));
+    compiler
+        .instructions
+        .push(encoder::Instruction::Exec("mmr::pack"));
+
+    let result = compiler.allocate_symbol(Type::Hash);
+    compiler.memory.write(
+        compiler.instructions,
+        result.memory_addr,
+        &vec![ValueSource::Stack; 4],
+    );
+
+    Ok(result)
+}
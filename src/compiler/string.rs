@@ -2,11 +2,13 @@ use super::{encoder::Instruction, *};
 
 // A string is represented as [length, pointer]
 pub(crate) fn new(compiler: &mut Compiler, value: &str) -> (Symbol, Option<u32>) {
-    let symbol = compiler.memory.allocate_symbol(Type::String);
+    let symbol = compiler.allocate_symbol(Type::String);
 
     let mut string_addr = None;
     if !value.is_empty() {
-        string_addr = Some(compiler.memory.allocate(value.len() as u32));
+        string_addr = Some(compiler.allocate(value.len() as u32));
+        // the data is referenced by the pointer, which outlives the symbol if it's copied
+        compiler.memory.pin();
         let string_addr = string_addr.unwrap();
 
         compiler.memory.write(
@@ -687,9 +689,7 @@ pub(crate) fn concat(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Result<
 }
 
 pub(crate) fn eq(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
 
     compiler.memory.read(
         compiler.instructions,
@@ -792,7 +792,7 @@ pub(crate) fn hash(compiler: &mut Compiler, _scope: &Scope, args: &[Symbol]) ->
         Type::String | Type::Bytes | Type::ContractReference { .. }
     );
 
-    let result = compiler.memory.allocate_symbol(Type::Hash);
+    let result = compiler.allocate_symbol(Type::Hash);
 
     compiler.instructions.extend([
         Instruction::Push(0),
@@ -1,9 +1,7 @@
 use super::*;
 
 pub(crate) fn new(compiler: &mut Compiler, value: u32) -> Symbol {
-    let symbol = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+    let symbol = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
 
     // memory is zero-initialized, so we don't need to write for 0
     if value > 0 {
@@ -18,9 +16,7 @@ pub(crate) fn new(compiler: &mut Compiler, value: u32) -> Symbol {
 }
 
 pub(crate) fn add(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
     compiler
         .memory
         .read(compiler.instructions, a.memory_addr, a.type_.miden_width());
@@ -40,9 +36,7 @@ pub(crate) fn add(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
 }
 
 pub(crate) fn sub(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
     compiler
         .memory
         .read(compiler.instructions, a.memory_addr, a.type_.miden_width());
@@ -62,9 +56,7 @@ pub(crate) fn sub(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
 }
 
 pub(crate) fn eq(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
     compiler
         .memory
         .read(compiler.instructions, a.memory_addr, a.type_.miden_width());
@@ -84,9 +76,7 @@ pub(crate) fn eq(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
 }
 
 pub(crate) fn gte(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
     compiler
         .memory
         .read(compiler.instructions, a.memory_addr, a.type_.miden_width());
@@ -106,9 +96,7 @@ pub(crate) fn gte(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
 }
 
 pub(crate) fn gt(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
     compiler
         .memory
         .read(compiler.instructions, a.memory_addr, a.type_.miden_width());
@@ -128,9 +116,7 @@ pub(crate) fn gt(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
 }
 
 pub(crate) fn lte(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
     compiler
         .memory
         .read(compiler.instructions, a.memory_addr, a.type_.miden_width());
@@ -150,9 +136,7 @@ pub(crate) fn lte(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
 }
 
 pub(crate) fn lt(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
     compiler
         .memory
         .read(compiler.instructions, a.memory_addr, a.type_.miden_width());
@@ -172,9 +156,7 @@ pub(crate) fn lt(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
 }
 
 pub(crate) fn modulo(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
     compiler
         .memory
         .read(compiler.instructions, a.memory_addr, a.type_.miden_width());
@@ -194,9 +176,7 @@ pub(crate) fn modulo(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol
 }
 
 pub(crate) fn div(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
     compiler
         .memory
         .read(compiler.instructions, a.memory_addr, a.type_.miden_width());
@@ -216,9 +196,7 @@ pub(crate) fn div(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
 }
 
 pub(crate) fn mul(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
     compiler
         .memory
         .read(compiler.instructions, a.memory_addr, a.type_.miden_width());
@@ -238,16 +216,15 @@ pub(crate) fn mul(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
 }
 
 pub(crate) fn shift_left(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
     compiler
         .memory
         .read(compiler.instructions, a.memory_addr, a.type_.miden_width());
     compiler
         .memory
         .read(compiler.instructions, b.memory_addr, b.type_.miden_width());
-    // TODO: SHL with Some is an order of magnitude faster, optimize this for constants
+    // SHL with Some is an order of magnitude faster,
+    // the peephole optimizer uses it when b is a constant
     compiler
         .instructions
         .push(encoder::Instruction::U32CheckedSHL(None));
@@ -261,16 +238,15 @@ pub(crate) fn shift_left(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Sym
 }
 
 pub(crate) fn shift_right(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt32));
     compiler
         .memory
         .read(compiler.instructions, a.memory_addr, a.type_.miden_width());
     compiler
         .memory
         .read(compiler.instructions, b.memory_addr, b.type_.miden_width());
-    // TODO: SHR with Some is an order of magnitude faster, optimize this for constants
+    // SHR with Some is an order of magnitude faster,
+    // the peephole optimizer uses it when b is a constant
     compiler
         .instructions
         .push(encoder::Instruction::U32CheckedSHR(None));
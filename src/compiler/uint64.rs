@@ -1,6 +1,21 @@
 use super::*;
 
 // Layout: [high, low]
+pub(crate) fn new(compiler: &mut Compiler, value: u64) -> Symbol {
+    let symbol = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
+
+    compiler.memory.write(
+        compiler.instructions,
+        symbol.memory_addr,
+        &[
+            ValueSource::Immediate((value >> 32) as u32),
+            ValueSource::Immediate(value as u32),
+        ],
+    );
+
+    symbol
+}
+
 pub(crate) fn cast_from_uint32(compiler: &mut Compiler, from: &Symbol, dest: &Symbol) {
     assert_eq!(from.type_, Type::PrimitiveType(PrimitiveType::UInt32));
     assert_eq!(dest.type_, Type::PrimitiveType(PrimitiveType::UInt64));
@@ -18,9 +33,7 @@ pub(crate) fn cast_from_uint32(compiler: &mut Compiler, from: &Symbol, dest: &Sy
 }
 
 pub(crate) fn add(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
 
     compiler
         .memory
@@ -41,9 +54,7 @@ pub(crate) fn add(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
 }
 
 pub(crate) fn sub(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
 
     compiler
         .memory
@@ -64,9 +75,7 @@ pub(crate) fn sub(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
 }
 
 pub(crate) fn eq(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
 
     compiler
         .memory
@@ -87,9 +96,7 @@ pub(crate) fn eq(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
 }
 
 pub(crate) fn gte(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
 
     compiler
         .memory
@@ -110,9 +117,7 @@ pub(crate) fn gte(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
 }
 
 pub(crate) fn gt(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
 
     compiler
         .memory
@@ -133,9 +138,7 @@ pub(crate) fn gt(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
 }
 
 pub(crate) fn lte(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
 
     compiler
         .memory
@@ -156,9 +159,7 @@ pub(crate) fn lte(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
 }
 
 pub(crate) fn lt(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::Boolean));
 
     compiler
         .memory
@@ -179,9 +180,7 @@ pub(crate) fn lt(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
 }
 
 pub(crate) fn modulo(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
 
     compiler
         .memory
@@ -202,9 +201,7 @@ pub(crate) fn modulo(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol
 }
 
 pub(crate) fn div(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
 
     compiler
         .memory
@@ -225,9 +222,7 @@ pub(crate) fn div(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
 }
 
 pub(crate) fn mul(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
 
     compiler
         .memory
@@ -248,9 +243,7 @@ pub(crate) fn mul(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
 }
 
 pub(crate) fn shift_left(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
 
     compiler
         .memory
@@ -271,9 +264,7 @@ pub(crate) fn shift_left(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Sym
 }
 
 pub(crate) fn shift_right(compiler: &mut Compiler, a: &Symbol, b: &Symbol) -> Symbol {
-    let result = compiler
-        .memory
-        .allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
+    let result = compiler.allocate_symbol(Type::PrimitiveType(PrimitiveType::UInt64));
 
     compiler
         .memory
@@ -6,30 +6,65 @@ pub struct JSContract {
     pub code: String,
 }
 
-pub fn generate_js_contract(contract_ast: &stableast::Contract) -> JSContract {
+/// Generates the JS of the methods of `contract_ast`, with its constants
+/// and the root-level constants of `root` that it doesn't redeclare.
+pub fn generate_js_contract(
+    contract_ast: &stableast::Contract,
+    root: &stableast::Root,
+) -> JSContract {
     let fns = contract_ast
         .attributes
         .iter()
         .filter_map(|item| {
             if let stableast::ContractAttribute::Method(m) = item {
-                let JSFunc { name, code } = generate_js_function(m);
-                Some(format!("instance.{} = {}", &name, &code))
+                Some(
+                    std::iter::once(generate_js_function(m))
+                        .chain(generate_js_condition(m))
+                        .map(|JSFunc { name, code }| format!("instance.{} = {}", &name, &code)),
+                )
             } else {
                 None
             }
         })
+        .flatten()
         .collect::<Vec<String>>()
         .join(";");
 
+    let contract_constants = contract_ast
+        .attributes
+        .iter()
+        .filter_map(|item| {
+            if let stableast::ContractAttribute::Constant(c) = item {
+                Some(c)
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let root_constants = root.0.iter().filter_map(|node| match node {
+        stableast::RootNode::Constant(c)
+            if !contract_constants.iter().any(|cc| cc.name == c.name) =>
+        {
+            Some(c)
+        }
+        _ => None,
+    });
+
+    let constants = root_constants
+        .chain(contract_constants.iter().copied())
+        .map(|c| format!("const {} = {};\n", c.name, c.value))
+        .collect::<String>();
+
     JSContract {
         code: format!(
             "function error(str) {{
                 return new Error(str);
             }}
             
-            const instance = $$__instance;
+            {}const instance = $$__instance;
             {};",
-            fns,
+            constants, fns,
         ),
     }
 }
@@ -41,7 +76,52 @@ struct JSFunc {
 }
 
 fn generate_js_function(func_ast: &stableast::Method) -> JSFunc {
-    let parameters = func_ast
+    let parameters = js_parameters(func_ast);
+
+    JSFunc {
+        name: func_ast.name.to_string(),
+        code: format!(
+            "function {} ({}) {{\n{}\n}}",
+            func_ast.name, parameters, &func_ast.code,
+        ),
+    }
+}
+
+/// Generates `$$__when_<method>`, which returns whether any of the `@call(when: ...)`
+/// conditions of the method are true.
+fn generate_js_condition(func_ast: &stableast::Method) -> Option<JSFunc> {
+    let conditions = func_ast
+        .attributes
+        .iter()
+        .filter_map(|item| match item {
+            stableast::MethodAttribute::Directive(d) if d.name == "call" => Some(&d.arguments),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|argument| match argument {
+            stableast::DirectiveArgument::Condition(c) => Some(format!("({})", c.code)),
+            _ => None,
+        })
+        .collect::<Vec<String>>();
+
+    if conditions.is_empty() {
+        return None;
+    }
+
+    let name = format!("$$__when_{}", func_ast.name);
+    Some(JSFunc {
+        code: format!(
+            "function {} ({}) {{\nreturn {}\n}}",
+            name,
+            js_parameters(func_ast),
+            conditions.join(" || "),
+        ),
+        name,
+    })
+}
+
+fn js_parameters(func_ast: &stableast::Method) -> String {
+    func_ast
         .attributes
         .iter()
         .filter_map(|item| {
@@ -53,15 +133,7 @@ fn generate_js_function(func_ast: &stableast::Method) -> JSFunc {
         })
         .map(|p| format!("{}", p.name))
         .collect::<Vec<String>>()
-        .join(", ");
-
-    JSFunc {
-        name: func_ast.name.to_string(),
-        code: format!(
-            "function {} ({}) {{\n{}\n}}",
-            func_ast.name, parameters, &func_ast.code,
-        ),
-    }
+        .join(", ")
 }
 
 #[cfg(test)]
@@ -106,6 +178,42 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_generate_js_condition() {
+        let func_ast = stableast::Method {
+            name: "spend".into(),
+            attributes: vec![
+                stableast::MethodAttribute::Parameter(stableast::Parameter {
+                    name: "amount".into(),
+                    type_: stableast::Type::Primitive(stableast::Primitive {
+                        value: stableast::PrimitiveType::Number,
+                    }),
+                    required: true,
+                }),
+                stableast::MethodAttribute::Directive(stableast::Directive {
+                    name: "call".into(),
+                    arguments: vec![
+                        stableast::DirectiveArgument::Condition(stableast::Condition {
+                            code: "amount <= this.limit".into(),
+                        }),
+                        stableast::DirectiveArgument::Condition(stableast::Condition {
+                            code: "ctx.publicKey == this.owner".into(),
+                        }),
+                    ],
+                }),
+            ],
+            code: "this.limit -= amount".into(),
+        };
+
+        assert_eq!(
+            generate_js_condition(&func_ast),
+            Some(JSFunc {
+                name: "$$__when_spend".to_string(),
+                code: "function $$__when_spend (amount) {\nreturn (amount <= this.limit) || (ctx.publicKey == this.owner)\n}".to_string(),
+            })
+        );
+    }
+
     #[test]
     fn test_generate_contract_function() {
         let contract_ast = stableast::Contract {
@@ -176,7 +284,7 @@ mod tests {
         };
 
         assert_eq!(
-            generate_js_contract(&contract_ast),
+            generate_js_contract(&contract_ast, &stableast::Root(vec![])),
             JSContract{
                 code: "function error(str) {
                 return new Error(str);
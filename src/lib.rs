@@ -154,17 +154,20 @@ fn validate_set_out_json(contract_ast_json: &str, data_json: &str) -> String {
     serde_json::to_string(&validate_set(contract_ast_json, data_json)).unwrap()
 }
 
-fn generate_contract_function(contract_ast: &str) -> Result<js::JSContract, Error> {
+fn generate_contract_function(contract_ast: &str, root_ast: &str) -> Result<js::JSContract, Error> {
     let contract_ast: stableast::Contract =
         serde_json::from_str(contract_ast).map_err(|e| Error {
             message: e.to_string(),
         })?;
+    let root_ast: stableast::Root = serde_json::from_str(root_ast).map_err(|e| Error {
+        message: e.to_string(),
+    })?;
 
-    Ok(js::generate_js_contract(&contract_ast))
+    Ok(js::generate_js_contract(&contract_ast, &root_ast))
 }
 
-fn generate_js_contract_out_json(contract_ast: &str) -> String {
-    serde_json::to_string(&generate_contract_function(contract_ast)).unwrap()
+fn generate_js_contract_out_json(contract_ast: &str, root_ast: &str) -> String {
+    serde_json::to_string(&generate_contract_function(contract_ast, root_ast)).unwrap()
 }
 
 #[cfg(test)]
@@ -209,6 +212,83 @@ mod tests {
         expected_output.assert_eq(&output);
     }
 
+    #[test]
+    fn test_parse_constants() {
+        let input = "const DAY: u32 = 60 * 60 * 24; contract Test { const WEEK = DAY * 7; }";
+        let expected_output = expect![[
+            r#"[{"kind":"constant","name":"DAY","type":{"kind":"primitive","value":"u32"},"value":86400},{"kind":"contract","namespace":{"kind":"namespace","value":""},"name":"Test","attributes":[{"kind":"constant","name":"WEEK","type":{"kind":"primitive","value":"number"},"value":604800}]}]"#
+        ]];
+
+        let mut program = None::<ast::Program>;
+        let output = parse(input, "", &mut program).unwrap().1;
+        let output = serde_json::to_string(&output).unwrap();
+
+        expected_output.assert_eq(&output);
+    }
+
+    #[test]
+    fn test_generate_js_contract_constants() {
+        let input = "const DAY: u32 = 60 * 60 * 24; const WEEK = 7; contract Test { const WEEK = DAY * 7; days(): number { return WEEK / DAY; } }";
+        let expected_output = expect![[r#"
+            function error(str) {
+                            return new Error(str);
+                        }
+                        
+                        const DAY = 86400;
+            const WEEK = 604800;
+            const instance = $$__instance;
+                        instance.days = function days () {
+            return WEEK / DAY;
+            };"#]];
+
+        let mut program = None::<ast::Program>;
+        let root = parse(input, "", &mut program).unwrap().1;
+        let stableast::RootNode::Contract(contract) = &root.0[2] else {
+            panic!("expected a contract");
+        };
+
+        let output = generate_contract_function(
+            &serde_json::to_string(contract).unwrap(),
+            &serde_json::to_string(&root).unwrap(),
+        )
+        .unwrap();
+
+        expected_output.assert_eq(&output.code);
+    }
+
+    #[test]
+    fn test_parse_constant_overflow() {
+        let mut program = None::<ast::Program>;
+        let err = parse("const X: u32 = 2 ** 32;", "", &mut program).unwrap_err();
+
+        assert!(err.message.contains("does not fit"), "{}", err.message);
+    }
+
+    #[test]
+    fn test_parse_constant_u64() {
+        let input = "const MAX: u64 = 18446744073709551615; const ODD: u64 = 2 ** 53 + 1;";
+        let expected_output = expect![[
+            r#"[{"kind":"constant","name":"MAX","type":{"kind":"primitive","value":"u64"},"value":18446744073709551615},{"kind":"constant","name":"ODD","type":{"kind":"primitive","value":"u64"},"value":9007199254740993}]"#
+        ]];
+
+        let mut program = None::<ast::Program>;
+        let output = parse(input, "", &mut program).unwrap().1;
+        let output = serde_json::to_string(&output).unwrap();
+
+        expected_output.assert_eq(&output);
+    }
+
+    #[test_case::test_case("const X: u64 = 2 ** 64;", "does not fit"; "u64 overflow")]
+    #[test_case::test_case("const X: u64 = 18446744073709551616;", "is too large"; "literal overflow")]
+    #[test_case::test_case("const X: u64 = 2 ** 53 + 0.5;", "does not fit"; "inexact")]
+    #[test_case::test_case("const X = 9007199254740993;", "does not fit"; "number")]
+    fn test_parse_constant_u64_errors(input: &str, error: &str) {
+        let mut program = None::<ast::Program>;
+        let err = parse(input, "", &mut program).unwrap_err();
+
+        assert!(err.message.contains(error), "{}", err.message);
+    }
+
     #[test]
     fn test_contract() {
         let mut program = None::<ast::Program>;
@@ -469,6 +549,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_optional_dot_nullish_coalesce() {
+        let expr = polylang_parser::parse_expression("a?.b ?? c").unwrap();
+
+        assert_eq!(
+            &*expr,
+            &ast::ExpressionKind::NullishCoalesce(
+                Box::new(
+                    ast::ExpressionKind::OptionalDot(
+                        Box::new(ast::ExpressionKind::Ident("a".to_owned()).into()),
+                        "b".to_string()
+                    )
+                    .into()
+                ),
+                Box::new(ast::ExpressionKind::Ident("c".to_owned()).into())
+            )
+        );
+    }
+
     #[test]
     fn test_assign_sub() {
         let dot = polylang_parser::parse_expression("a -= b").unwrap();
@@ -980,6 +1079,36 @@ function x() {
         );
     }
 
+    #[test]
+    fn test_decorator_field_path() {
+        let code = "
+            contract Account {
+                owner: { admin: PublicKey; };
+
+                @call(owner.admin)
+                function noop() {}
+            }
+        ";
+
+        let mut program = None::<ast::Program>;
+        let (program, _) = parse(code, "", &mut program).unwrap();
+
+        let contract = match &program.nodes[0] {
+            ast::RootNode::Contract(c) => c,
+            _ => panic!("expected contract"),
+        };
+
+        let function = match &contract.items[1] {
+            ast::ContractItem::Function(f) => f,
+            _ => panic!("expected function"),
+        };
+
+        assert_eq!(
+            function.decorators[0].arguments,
+            vec![ast::DecoratorArgument::Identifier("owner.admin".to_owned())],
+        );
+    }
+
     #[test]
     fn test_foreign_record_array() {
         let code = "
@@ -1078,6 +1207,22 @@ function x() {
         );
     }
 
+    #[test]
+    fn test_expr_new() {
+        let code = "new Listing(1, [])";
+        let expr = polylang_parser::parse_expression(code).unwrap();
+        assert_eq!(
+            *expr,
+            ast::ExpressionKind::New(
+                "Listing".to_owned(),
+                vec![
+                    ast::ExpressionKind::Primitive(ast::Primitive::Number(1.0, false)).into(),
+                    ast::ExpressionKind::Array(vec![]).into(),
+                ]
+            )
+        );
+    }
+
     #[test]
     fn test_object() {
         let code = r#"
@@ -1139,6 +1284,73 @@ function x() {
         );
     }
 
+    #[test]
+    fn test_event() {
+        let code = r#"
+            contract Token {
+                event Transfer {
+                    to: string;
+                    memo?: string;
+                }
+
+                transfer(to: string) {
+                    emit Transfer { to: to };
+                }
+            }
+        "#;
+
+        let mut program = None;
+        let (program, _) = parse(code, "", &mut program).unwrap();
+        let contract = match &program.nodes[0] {
+            ast::RootNode::Contract(c) => c,
+            _ => panic!("expected contract"),
+        };
+
+        assert_eq!(contract.items.len(), 2);
+
+        let event = match &contract.items[0] {
+            ast::ContractItem::Event(e) => e,
+            _ => panic!("expected event"),
+        };
+
+        assert_eq!(event.name, "Transfer");
+        assert_eq!(
+            event.fields,
+            vec![
+                ast::Field {
+                    name: "to".to_string(),
+                    type_: ast::Type::String,
+                    required: true,
+                    decorators: vec![],
+                },
+                ast::Field {
+                    name: "memo".to_string(),
+                    type_: ast::Type::String,
+                    required: false,
+                    decorators: vec![],
+                },
+            ]
+        );
+
+        let function = match &contract.items[1] {
+            ast::ContractItem::Function(f) => f,
+            _ => panic!("expected function"),
+        };
+
+        let emit = match &*function.statements[0] {
+            ast::StatementKind::Emit(e) => e,
+            _ => panic!("expected emit"),
+        };
+
+        assert_eq!(emit.event, "Transfer");
+        assert_eq!(emit.fields.len(), 1);
+        assert_eq!(emit.fields[0].0, "to");
+        assert_eq!(
+            *emit.fields[0].1,
+            ast::ExpressionKind::Ident("to".to_string())
+        );
+    }
+
     #[test]
     fn test_public_key_array_decl() {
         let code = r#"
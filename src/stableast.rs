@@ -7,6 +7,8 @@ use std::{borrow::Cow, fmt::Display};
 use polylang_parser::ast;
 use serde::{Deserialize, Serialize};
 
+use crate::compiler::constant;
+
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct Root<'a>(#[serde(borrow)] pub Vec<RootNode<'a>>);
 
@@ -15,6 +17,8 @@ pub struct Root<'a>(#[serde(borrow)] pub Vec<RootNode<'a>>);
 pub enum RootNode<'a> {
     #[serde(borrow, rename = "contract")]
     Contract(Contract<'a>),
+    #[serde(borrow, rename = "constant")]
+    Constant(Constant<'a>),
     #[serde(other)]
     Unknown,
 }
@@ -44,10 +48,23 @@ pub enum ContractAttribute<'a> {
     Index(Index<'a>),
     #[serde(borrow, rename = "directive")]
     Directive(Directive<'a>),
+    #[serde(borrow, rename = "constant")]
+    Constant(Constant<'a>),
+    #[serde(borrow, rename = "event")]
+    Event(Event<'a>),
     #[serde(other)]
     Unknown,
 }
 
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Constant<'a> {
+    pub name: Cow<'a, str>,
+    #[serde(rename = "type", borrow)]
+    pub type_: Type<'a>,
+    /// The value folded at compile time.
+    pub value: serde_json::Value,
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct Property<'a> {
     pub name: Cow<'a, str>,
@@ -57,6 +74,14 @@ pub struct Property<'a> {
     pub required: bool,
 }
 
+/// An event that the methods of the contract can emit.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Event<'a> {
+    pub name: Cow<'a, str>,
+    #[serde(borrow)]
+    pub properties: Vec<Property<'a>>,
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct Method<'a> {
     pub name: Cow<'a, str>,
@@ -110,6 +135,8 @@ pub struct Directive<'a> {
 pub enum DirectiveArgument<'a> {
     #[serde(rename = "fieldreference")]
     FieldReference(FieldReference<'a>),
+    #[serde(rename = "condition")]
+    Condition(Condition<'a>),
     #[serde(other)]
     Unknown,
 }
@@ -119,6 +146,12 @@ pub struct FieldReference<'a> {
     pub path: Vec<Cow<'a, str>>,
 }
 
+/// The `when:` argument of `@call`, the call is allowed if the condition is true.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Condition<'a> {
+    pub code: Cow<'a, str>,
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct Parameter<'a> {
     pub name: Cow<'a, str>,
@@ -273,7 +306,28 @@ pub struct ForeignRecord<'a> {
 impl<'a> Root<'a> {
     pub fn from_ast(namespace: &'a str, program: &'a ast::Program) -> Result<Self, String> {
         let mut root = Root(vec![]);
+        let root_constants = fold_constants(
+            program.nodes.iter().filter_map(|node| match node {
+                ast::RootNode::Const(c) => Some(c),
+                _ => None,
+            }),
+            vec![],
+        )?;
+        let mut root_constants_iter = root_constants.iter();
+
         for node in &program.nodes {
+            let contract_constants = match node {
+                ast::RootNode::Contract(c) => fold_constants(
+                    c.items.iter().filter_map(|item| match item {
+                        ast::ContractItem::Const(c) => Some(c),
+                        _ => None,
+                    }),
+                    root_constants.clone(),
+                )?,
+                _ => vec![],
+            };
+            let mut contract_constants_iter = contract_constants.iter().skip(root_constants.len());
+
             root.0.push(match node {
                 ast::RootNode::Contract(c) => RootNode::Contract(Contract {
                     namespace: Namespace {
@@ -284,16 +338,9 @@ impl<'a> Root<'a> {
                         .items
                         .iter()
                         .map(|item| match item {
-                            ast::ContractItem::Field(f) => ContractAttribute::Property(Property {
-                                name: Cow::Borrowed(&f.name),
-                                type_: Type::from_ast_type(&f.type_),
-                                directives: f
-                                    .decorators
-                                    .iter()
-                                    .map(Directive::from_decorator_ast)
-                                    .collect(),
-                                required: f.required,
-                            }),
+                            ast::ContractItem::Field(f) => {
+                                ContractAttribute::Property(Property::from_ast(f))
+                            }
                             ast::ContractItem::Function(f) => ContractAttribute::Method(Method {
                                 name: Cow::Borrowed(&f.name),
                                 code: Cow::Borrowed(&f.statements_code),
@@ -343,6 +390,13 @@ impl<'a> Root<'a> {
                                     })
                                     .collect(),
                             }),
+                            ast::ContractItem::Const(k) => ContractAttribute::Constant(
+                                Constant::from_ast(k, &contract_constants_iter.next().unwrap().1),
+                            ),
+                            ast::ContractItem::Event(e) => ContractAttribute::Event(Event {
+                                name: Cow::Borrowed(&e.name),
+                                properties: e.fields.iter().map(Property::from_ast).collect(),
+                            }),
                         })
                         .chain(c.decorators.iter().map(|d| {
                             ContractAttribute::Directive(Directive::from_decorator_ast(d))
@@ -350,6 +404,10 @@ impl<'a> Root<'a> {
                         .collect(),
                 }),
                 ast::RootNode::Function(_) => Err("Functions are not supported at the root level")?,
+                ast::RootNode::Const(k) => RootNode::Constant(Constant::from_ast(
+                    k,
+                    &root_constants_iter.next().unwrap().1,
+                )),
             });
         }
 
@@ -357,6 +415,55 @@ impl<'a> Root<'a> {
     }
 }
 
+/// Folds `consts` in declaration order, each one can refer to `visible` and the ones before it.
+fn fold_constants<'a>(
+    consts: impl Iterator<Item = &'a ast::Const>,
+    mut visible: Vec<(String, constant::Constant)>,
+) -> Result<Vec<(String, constant::Constant)>, String> {
+    for c in consts {
+        let folded = constant::new(c, &visible).map_err(|e| e.to_string())?;
+        visible.push((c.name.clone(), folded));
+    }
+
+    Ok(visible)
+}
+
+impl<'a> Property<'a> {
+    fn from_ast(f: &'a ast::Field) -> Self {
+        Property {
+            name: Cow::Borrowed(&f.name),
+            type_: Type::from_ast_type(&f.type_),
+            directives: f
+                .decorators
+                .iter()
+                .map(Directive::from_decorator_ast)
+                .collect(),
+            required: f.required,
+        }
+    }
+}
+
+impl<'a> Constant<'a> {
+    fn from_ast(ast: &'a ast::Const, folded: &constant::Constant) -> Self {
+        Constant {
+            name: Cow::Borrowed(&ast.name),
+            type_: match &ast.type_ {
+                Some(t) => Type::from_ast_type(t),
+                None => Type::Primitive(Primitive {
+                    value: match folded.value {
+                        constant::Value::Integer(_) | constant::Value::Number(_) => {
+                            PrimitiveType::Number
+                        }
+                        constant::Value::String(_) => PrimitiveType::String,
+                        constant::Value::Boolean(_) => PrimitiveType::Boolean,
+                    },
+                }),
+            },
+            value: (&folded.value).into(),
+        }
+    }
+}
+
 impl<'a> Type<'a> {
     fn from_ast_type(type_: &'a ast::Type) -> Self {
         match type_ {
@@ -494,6 +601,9 @@ impl<'a> DirectiveArgument<'a> {
                 })
             }
             ast::DecoratorArgument::Literal(_) => todo!(),
+            ast::DecoratorArgument::When { code, .. } => DirectiveArgument::Condition(Condition {
+                code: Cow::Borrowed(code),
+            }),
         }
     }
 }
@@ -668,6 +778,38 @@ mod tests {
             }"#]]
     );
 
+    test_serialize_json!(
+        test_serialize_json_directive_condition,
+        Directive {
+            name: "call".into(),
+            arguments: vec![
+                DirectiveArgument::FieldReference(FieldReference {
+                    path: vec!["owner".into(), "admin".into()],
+                }),
+                DirectiveArgument::Condition(Condition {
+                    code: "ctx.publicKey == this.owner".into(),
+                }),
+            ],
+        },
+        expect![[r#"
+            {
+              "name": "call",
+              "arguments": [
+                {
+                  "kind": "fieldreference",
+                  "path": [
+                    "owner",
+                    "admin"
+                  ]
+                },
+                {
+                  "kind": "condition",
+                  "code": "ctx.publicKey == this.owner"
+                }
+              ]
+            }"#]]
+    );
+
     test_serialize_json!(
         test_serialize_json_with_unknown_root_node,
         Root(vec![RootNode::Contract(Contract {
@@ -356,7 +356,7 @@ pub(crate) fn validate_value<'a>(
                     map.get("x"),
                     map.get("y"),
                 ) {
-                    (Some(kty), Some(crv), Some(alg), Some(use_), Some(x), Some(y)) => {
+                    (Some(kty), Some(crv), Some(alg), Some(use_), Some(x), y) => {
                         if let Some(extra_field) = map.iter().find(|(k, _)| {
                             !matches!(k.as_str(), "kty" | "crv" | "alg" | "use" | "x" | "y")
                         }) {
@@ -365,38 +365,50 @@ pub(crate) fn validate_value<'a>(
                             return Err(ValidationError::ExtraField { path });
                         }
 
-                        match kty {
-                            Value::String(s) if s == "EC" => {}
-                            _ => {
-                                let mut path = path.clone();
-                                path.0.push(PathPart::Field("kty"));
-                                return Err(ValidationError::Other {
-                                    path,
-                                    message: "Invalid kty, should be EC".to_string(),
-                                });
-                            }
-                        }
+                        let kty = match kty {
+                            Value::String(s) => s.parse::<abi::publickey::Kty>().ok(),
+                            _ => None,
+                        };
+                        let Some(kty) = kty else {
+                            let mut path = path.clone();
+                            path.0.push(PathPart::Field("kty"));
+                            return Err(ValidationError::Other {
+                                path,
+                                message: "Invalid kty, should be EC or OKP".to_string(),
+                            });
+                        };
 
-                        match crv {
-                            Value::String(s) if s == "secp256k1" => {}
-                            _ => {
-                                let mut path = path.clone();
-                                path.0.push(PathPart::Field("crv"));
-                                return Err(ValidationError::Other {
-                                    path,
-                                    message: "Invalid crv, should be secp256k1".to_string(),
-                                });
-                            }
-                        }
+                        let crv = match crv {
+                            Value::String(s) => s
+                                .parse::<abi::publickey::Crv>()
+                                .ok()
+                                .filter(|crv| crv.kty() == kty),
+                            _ => None,
+                        };
+                        let Some(crv) = crv else {
+                            let mut path = path.clone();
+                            path.0.push(PathPart::Field("crv"));
+                            return Err(ValidationError::Other {
+                                path,
+                                message: match kty {
+                                    abi::publickey::Kty::EC => {
+                                        "Invalid crv, should be secp256k1 or P-256".to_string()
+                                    }
+                                    abi::publickey::Kty::OKP => {
+                                        "Invalid crv, should be Ed25519".to_string()
+                                    }
+                                },
+                            });
+                        };
 
                         match alg {
-                            Value::String(s) if s == "ES256K" => {}
+                            Value::String(s) if s.parse().ok() == Some(crv.alg()) => {}
                             _ => {
                                 let mut path = path.clone();
                                 path.0.push(PathPart::Field("alg"));
                                 return Err(ValidationError::Other {
                                     path,
-                                    message: "Invalid alg, should be ES256K".to_string(),
+                                    message: format!("Invalid alg, should be {:?}", crv.alg()),
                                 });
                             }
                         }
@@ -413,6 +425,22 @@ pub(crate) fn validate_value<'a>(
                             }
                         }
 
+                        // Ed25519 keys only have an x coordinate
+                        let y = match (y, crv.has_y()) {
+                            (Some(y), true) => Some(y),
+                            (None, false) => None,
+                            (None, true) => {
+                                let mut path = path.clone();
+                                path.0.push(PathPart::Field("y"));
+                                return Err(ValidationError::MissingField { path });
+                            }
+                            (Some(_), false) => {
+                                let mut path = path.clone();
+                                path.0.push(PathPart::Field("y"));
+                                return Err(ValidationError::ExtraField { path });
+                            }
+                        };
+
                         let x = match x {
                             Value::String(s) => base64::engine::general_purpose::URL_SAFE
                                 .decode(s.as_bytes())
@@ -433,25 +461,29 @@ pub(crate) fn validate_value<'a>(
                             }
                         };
 
-                        let y = match y {
-                            Value::String(s) => base64::engine::general_purpose::URL_SAFE
-                                .decode(s.as_bytes())
-                                .map_err(|err| {
+                        let y = y
+                            .map(|y| match y {
+                                Value::String(s) => base64::engine::general_purpose::URL_SAFE
+                                    .decode(s.as_bytes())
+                                    .map_err(|err| {
+                                        let mut path = path.clone();
+                                        path.0.push(PathPart::Field("y"));
+                                        ValidationError::Base64DecodeError { path, error: err }
+                                    }),
+                                _ => {
                                     let mut path = path.clone();
                                     path.0.push(PathPart::Field("y"));
-                                    ValidationError::Base64DecodeError { path, error: err }
-                                })?,
-                            _ => {
-                                let mut path = path.clone();
-                                path.0.push(PathPart::Field("y"));
-                                return Err(ValidationError::InvalidType {
-                                    path,
-                                    expected: stableast::Type::Primitive(stableast::Primitive {
-                                        value: stableast::PrimitiveType::String,
-                                    }),
-                                });
-                            }
-                        };
+                                    Err(ValidationError::InvalidType {
+                                        path,
+                                        expected: stableast::Type::Primitive(
+                                            stableast::Primitive {
+                                                value: stableast::PrimitiveType::String,
+                                            },
+                                        ),
+                                    })
+                                }
+                            })
+                            .transpose()?;
 
                         if x.len() != 32 {
                             let mut path = path.clone();
@@ -462,7 +494,7 @@ pub(crate) fn validate_value<'a>(
                             });
                         }
 
-                        if y.len() != 32 {
+                        if y.is_some_and(|y| y.len() != 32) {
                             let mut path = path.clone();
                             path.0.push(PathPart::Field("y"));
                             return Err(ValidationError::Other {
@@ -501,12 +533,6 @@ pub(crate) fn validate_value<'a>(
                         let mut path = path.clone();
                         path.0.push(PathPart::Field("x"));
 
-                        Err(ValidationError::MissingField { path })
-                    }
-                    (_, _, _, _, _, None) => {
-                        let mut path = path.clone();
-                        path.0.push(PathPart::Field("y"));
-
                         Err(ValidationError::MissingField { path })
                     }
                 }
@@ -1304,6 +1330,88 @@ mod tests {
         Ok(())
     );
 
+    test_validate_public_key!(
+        test_validate_public_key_ed25519,
+        HashMap::from([(
+            "public_key".to_string(),
+            Value::Map(HashMap::from([
+                ("kty".to_string(), Value::String("OKP".to_string())),
+                ("crv".to_string(), Value::String("Ed25519".to_string())),
+                ("alg".to_string(), Value::String("EdDSA".to_string())),
+                ("use".to_string(), Value::String("sig".to_string())),
+                (
+                    "x".to_string(),
+                    Value::String(
+                        base64::engine::general_purpose::URL_SAFE
+                            .encode(rand::random::<[u8; 32]>())
+                    )
+                ),
+            ])),
+        )]),
+        Ok(())
+    );
+
+    test_validate_public_key!(
+        test_validate_public_key_ed25519_with_y,
+        HashMap::from([(
+            "public_key".to_string(),
+            Value::Map(HashMap::from([
+                ("kty".to_string(), Value::String("OKP".to_string())),
+                ("crv".to_string(), Value::String("Ed25519".to_string())),
+                ("alg".to_string(), Value::String("EdDSA".to_string())),
+                ("use".to_string(), Value::String("sig".to_string())),
+                (
+                    "x".to_string(),
+                    Value::String(
+                        base64::engine::general_purpose::URL_SAFE
+                            .encode(rand::random::<[u8; 32]>())
+                    )
+                ),
+                (
+                    "y".to_string(),
+                    Value::String(
+                        base64::engine::general_purpose::URL_SAFE
+                            .encode(rand::random::<[u8; 32]>())
+                    )
+                ),
+            ])),
+        )]),
+        Err(ValidationError::ExtraField {
+            path: PathParts(vec![PathPart::Field("public_key"), PathPart::Field("y")]),
+        })
+    );
+
+    test_validate_public_key!(
+        test_validate_public_key_p256_wrong_alg,
+        HashMap::from([(
+            "public_key".to_string(),
+            Value::Map(HashMap::from([
+                ("kty".to_string(), Value::String("EC".to_string())),
+                ("crv".to_string(), Value::String("P-256".to_string())),
+                ("alg".to_string(), Value::String("ES256K".to_string())),
+                ("use".to_string(), Value::String("sig".to_string())),
+                (
+                    "x".to_string(),
+                    Value::String(
+                        base64::engine::general_purpose::URL_SAFE
+                            .encode(rand::random::<[u8; 32]>())
+                    )
+                ),
+                (
+                    "y".to_string(),
+                    Value::String(
+                        base64::engine::general_purpose::URL_SAFE
+                            .encode(rand::random::<[u8; 32]>())
+                    )
+                ),
+            ])),
+        )]),
+        Err(ValidationError::Other {
+            path: PathParts(vec![PathPart::Field("public_key"), PathPart::Field("alg")]),
+            message: "Invalid alg, should be ES256".to_string(),
+        })
+    );
+
     test_validate_public_key!(
         test_validate_public_key_invalid_x,
         HashMap::from([(
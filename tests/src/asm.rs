@@ -0,0 +1,70 @@
+use super::*;
+
+const CODE: &str = r#"
+    contract Account {
+        balance: u32;
+        total: u64;
+
+        deposit(amt: u32) {
+            this.balance = asm(this.balance, amt): u32 { u32checked_add };
+            this.total = asm(this.total, this.total): u64 {
+                exec.u64::checked_add # doubles the total
+            };
+        }
+
+        check(amt: u32) {
+            asm(amt) { push.10 u32checked_lt assert };
+        }
+    }
+"#;
+
+fn run_fn(function: &str, amt: u32) -> Result<abi::Value, error::Error> {
+    let (abi, output) = run(
+        CODE,
+        "Account",
+        function,
+        serde_json::json!({ "balance": 3, "total": 5 }),
+        vec![serde_json::json!(amt)],
+        None,
+        HashMap::new(),
+    )?;
+
+    output.this(&abi)
+}
+
+fn compile_asm(asm: &str) -> Result<(String, abi::Abi), error::Error> {
+    let code = format!("function main(a: u32, b: u32) {{ let c = {asm}; }}");
+    let program = polylang::parse_program(&code).unwrap();
+    polylang::compiler::compile(program, None, "main")
+}
+
+#[test]
+fn test_asm_inputs_and_outputs() {
+    assert_eq!(
+        run_fn("deposit", 4).unwrap(),
+        abi::Value::StructValue(vec![
+            ("balance".to_owned(), abi::Value::UInt32(7)),
+            ("total".to_owned(), abi::Value::UInt64(10)),
+        ])
+    );
+}
+
+#[test]
+fn test_asm_without_output() {
+    assert!(run_fn("check", 9).is_ok());
+    assert!(run_fn("check", 10).is_err());
+}
+
+#[test]
+fn test_asm_is_validated() {
+    let (miden_code, _) = compile_asm("asm(a, b): u32 { u32checked_add }").unwrap();
+    assert!(miden_code.contains("u32checked_add"), "{miden_code}");
+
+    let err = |asm| compile_asm(asm).unwrap_err().to_string();
+    assert!(err("asm(a): u32 { drop drop }")
+        .contains("asm block takes more stack elements than its inputs"));
+    assert!(err("asm(a, b): u32 { swap }")
+        .contains("asm block leaves 2 stack elements, expected 1 for the output"));
+    assert!(err("asm(a, b): u32 { u32checked_add mem_stream }")
+        .contains("unsupported instruction `mem_stream` in asm block"));
+}
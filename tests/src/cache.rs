@@ -0,0 +1,76 @@
+use super::*;
+
+use polylang_prover::cache::{DiskStore, ProgramCache, ProgramKey};
+
+fn program(code: &str) -> (ProgramKey, String, abi::Abi) {
+    let program = polylang::parse_program(code).unwrap();
+    let (miden_code, abi) = polylang::compiler::compile(program, None, "main").unwrap();
+
+    (
+        ProgramKey::new(abi.std_version.unwrap(), &miden_code),
+        miden_code,
+        abi,
+    )
+}
+
+#[test]
+fn test_key_depends_on_code_and_version() {
+    let (key, miden_code, _) = program("function main() { }");
+
+    assert_eq!(key, ProgramKey::new(abi::StdVersion::V0_7_0, &miden_code));
+    assert_ne!(key, ProgramKey::new(abi::StdVersion::V0_6_1, &miden_code));
+    assert_ne!(key, program("function main() { let x = 1; }").0);
+}
+
+#[test]
+fn test_least_recently_used_is_evicted() {
+    let mut cache = ProgramCache::new(2);
+    let programs = ["1", "2", "3"].map(|n| {
+        let (key, miden_code, abi) = program(&format!("function main() {{ let x = {n}; }}"));
+        (
+            key,
+            polylang_prover::compile_program(&abi, &miden_code).unwrap(),
+        )
+    });
+
+    let [(a, program_a), (b, program_b), (c, program_c)] = programs;
+    cache.insert(a.clone(), program_a.clone());
+    cache.insert(b.clone(), program_b);
+    assert_eq!(cache.get(&a).unwrap().hash(), program_a.hash());
+
+    cache.insert(c.clone(), program_c);
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get(&a).is_some());
+    assert!(cache.get(&b).is_none());
+    assert!(cache.get(&c).is_some());
+}
+
+#[test]
+fn test_compile_program_is_cached() {
+    let (key, miden_code, abi) = program("function main() { let x = 42; }");
+    let program = polylang_prover::compile_program(&abi, &miden_code).unwrap();
+
+    let cached = polylang_prover::cache::PROGRAM_CACHE
+        .lock()
+        .unwrap()
+        .get(&key)
+        .unwrap();
+    assert_eq!(cached.hash(), program.hash());
+}
+
+#[test]
+fn test_disk_store() {
+    let dir = std::env::temp_dir().join(format!("polylang-program-cache-{}", std::process::id()));
+    let (key, miden_code, _) = program("function main() { let x = 7; }");
+    let ast = polylang_prover::cache::parse_program(&miden_code).unwrap();
+
+    let store = DiskStore::new(&dir).unwrap();
+    assert!(store.load(&key).is_none());
+    store.save(&key, &ast).unwrap();
+
+    // Another store in the same directory, like after a restart
+    let store = DiskStore::new(&dir).unwrap();
+    assert_eq!(store.load(&key), Some(ast));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,138 @@
+use super::*;
+
+const CODE: &str = r#"
+    contract Admin {
+        id: string;
+        @delegate
+        pk: PublicKey;
+    }
+
+    contract Vault {
+        id: string;
+        name: string;
+        owner: { admin: PublicKey; };
+        members: PublicKey[];
+        admins: Admin[];
+        roles: map<string, PublicKey>;
+        manager: Admin;
+
+        @call(owner.admin)
+        renameByOwner(name: string) {
+            this.name = name;
+        }
+
+        @call(members)
+        renameByMember(name: string) {
+            this.name = name;
+        }
+
+        @call(admins)
+        renameByAdmin(name: string) {
+            this.name = name;
+        }
+
+        @call(roles)
+        renameByRole(name: string) {
+            this.name = name;
+        }
+
+        @call(manager.pk)
+        renameByManager(name: string) {
+            this.name = name;
+        }
+    }
+"#;
+
+fn rename(
+    function: &str,
+    ctx_public_key: abi::publickey::Key,
+) -> Result<(abi::Abi, polylang_prover::RunOutput), error::Error> {
+    run(
+        CODE,
+        "Vault",
+        function,
+        serde_json::json!({
+            "id": "vault",
+            "name": "old",
+            "owner": { "admin": fixtures::pk1() },
+            "members": [fixtures::pk2(), fixtures::pk1()],
+            "admins": [{ "id": "admin1" }],
+            "roles": { "auditor": fixtures::pk1() },
+            "manager": { "id": "admin1" },
+        }),
+        vec![serde_json::json!("new")],
+        Some(ctx_public_key),
+        {
+            let mut hm = HashMap::new();
+            hm.insert(
+                "Admin".to_owned(),
+                vec![serde_json::json!({
+                    "id": "admin1",
+                    "pk": fixtures::pk1(),
+                })],
+            );
+            hm
+        },
+    )
+}
+
+fn name(abi: &abi::Abi, output: &polylang_prover::RunOutput) -> abi::Value {
+    match output.this(abi).unwrap() {
+        abi::Value::StructValue(fields) => {
+            fields
+                .into_iter()
+                .find(|(name, _)| name == "name")
+                .unwrap()
+                .1
+        }
+        this => panic!("unexpected this: {this:?}"),
+    }
+}
+
+#[test_case::test_case("renameByOwner"; "nested field")]
+#[test_case::test_case("renameByMember"; "array of public keys")]
+#[test_case::test_case("renameByAdmin"; "array of records")]
+#[test_case::test_case("renameByRole"; "map of public keys")]
+#[test_case::test_case("renameByManager"; "field of a record")]
+fn test_call_path_allows_key(function: &str) {
+    let (abi, output) = rename(function, fixtures::pk1_key()).unwrap();
+    assert_eq!(name(&abi, &output), abi::Value::String("new".to_owned()));
+}
+
+#[test_case::test_case("renameByOwner"; "nested field")]
+#[test_case::test_case("renameByAdmin"; "array of records")]
+#[test_case::test_case("renameByRole"; "map of public keys")]
+#[test_case::test_case("renameByManager"; "field of a record")]
+fn test_call_path_rejects_other_key(function: &str) {
+    let err = rename(function, fixtures::pk2_key()).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("You are not authorized to call this function"));
+}
+
+#[test]
+fn test_call_path_any_array_element() {
+    // pk2 is the first of the members, pk1 the last
+    let (abi, output) = rename("renameByMember", fixtures::pk2_key()).unwrap();
+    assert_eq!(name(&abi, &output), abi::Value::String("new".to_owned()));
+}
+
+#[test]
+fn test_call_path_must_exist() {
+    let program = polylang::parse_program(
+        r#"
+        contract Vault {
+            id: string;
+            owner: { admin: PublicKey; };
+
+            @call(owner.root)
+            noop() {}
+        }
+        "#,
+    )
+    .unwrap();
+    let err = polylang::compiler::compile(program, Some("Vault"), "noop")
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("root"), "{err}");
+}
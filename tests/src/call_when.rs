@@ -0,0 +1,178 @@
+use super::*;
+
+const CODE: &str = r#"
+    contract Team {
+        id: string;
+        name: string;
+        owner: PublicKey;
+        admins: PublicKey[];
+        limit: u32;
+
+        @call(when: this.admins.includes(ctx.publicKey) || ctx.publicKey == this.owner)
+        rename(name: string) {
+            this.name = name;
+        }
+
+        @call(when: amount <= this.limit)
+        spend(amount: u32) {
+            this.limit = this.limit - amount;
+        }
+    }
+"#;
+
+fn team() -> serde_json::Value {
+    serde_json::json!({
+        "id": "team",
+        "name": "old",
+        "owner": fixtures::pk1(),
+        "admins": [fixtures::pk2()],
+        "limit": 10,
+    })
+}
+
+fn call(
+    function: &str,
+    this: serde_json::Value,
+    args: Vec<serde_json::Value>,
+    ctx_public_key: Option<abi::publickey::Key>,
+) -> Result<(abi::Abi, polylang_prover::RunOutput), error::Error> {
+    run(
+        CODE,
+        "Team",
+        function,
+        this,
+        args,
+        ctx_public_key,
+        HashMap::new(),
+    )
+}
+
+fn field(abi: &abi::Abi, output: &polylang_prover::RunOutput, name: &str) -> abi::Value {
+    match output.this(abi).unwrap() {
+        abi::Value::StructValue(fields) => {
+            fields
+                .into_iter()
+                .find(|(field, _)| field == name)
+                .unwrap()
+                .1
+        }
+        this => panic!("unexpected this: {this:?}"),
+    }
+}
+
+#[test_case::test_case(fixtures::pk1_key(); "owner")]
+#[test_case::test_case(fixtures::pk2_key(); "admin")]
+fn test_call_when_allowed(ctx_public_key: abi::publickey::Key) {
+    let (abi, output) = call(
+        "rename",
+        team(),
+        vec![serde_json::json!("new")],
+        Some(ctx_public_key),
+    )
+    .unwrap();
+
+    assert_eq!(
+        field(&abi, &output, "name"),
+        abi::Value::String("new".to_owned())
+    );
+}
+
+#[test]
+fn test_call_when_rejected() {
+    let mut this = team();
+    this["admins"] = serde_json::json!([]);
+    let err = call(
+        "rename",
+        this,
+        vec![serde_json::json!("new")],
+        Some(fixtures::pk2_key()),
+    )
+    .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("You are not authorized to call this function"));
+
+    // Null is not included in the admins and not equal to the owner
+    let err = call("rename", team(), vec![serde_json::json!("new")], None).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("You are not authorized to call this function"));
+}
+
+#[test]
+fn test_call_when_parameters() {
+    let (abi, output) = call("spend", team(), vec![serde_json::json!(4)], None).unwrap();
+    assert_eq!(field(&abi, &output, "limit"), abi::Value::UInt32(6));
+
+    let err = call("spend", team(), vec![serde_json::json!(11)], None).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("You are not authorized to call this function"));
+}
+
+#[test]
+fn test_call_when_must_be_boolean() {
+    let program = polylang::parse_program(
+        r#"
+        contract Team {
+            id: string;
+            limit: u32;
+
+            @call(when: this.limit)
+            noop() {}
+        }
+        "#,
+    )
+    .unwrap();
+    let err = polylang::compiler::compile(program, Some("Team"), "noop")
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.contains("The condition of @call(when: ...) must be a boolean"),
+        "{err}"
+    );
+}
+
+#[test_case::test_case("this.limit++ > 0", "can't assign to variables or fields"; "increment")]
+#[test_case::test_case("this.admins.push(ctx.publicKey) > 0", "can't call push"; "mutating method")]
+#[test_case::test_case("isAdmin(this.admins)", "can't call isAdmin"; "function")]
+fn test_call_when_cant_change_state(condition: &str, expected: &str) {
+    let program = polylang::parse_program(&format!(
+        r#"
+        function isAdmin(admins: PublicKey[]): boolean {{
+            return admins.includes(ctx.publicKey);
+        }}
+
+        contract Team {{
+            id: string;
+            limit: u32;
+            admins: PublicKey[];
+
+            @call(when: {condition})
+            noop() {{}}
+        }}
+        "#
+    ))
+    .unwrap();
+    let err = polylang::compiler::compile(program, Some("Team"), "noop")
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains(expected), "{err}");
+}
+
+#[test]
+fn test_call_when_unknown_argument() {
+    let err = polylang::parse_program(
+        r#"
+        contract Team {
+            id: string;
+
+            @call(unless: true)
+            noop() {}
+        }
+        "#,
+    )
+    .unwrap_err()
+    .to_string();
+    assert!(err.contains("Unknown decorator argument: unless"), "{err}");
+}
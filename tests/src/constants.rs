@@ -0,0 +1,91 @@
+use super::*;
+
+const CODE: &str = r#"
+    const DAY: u32 = 60 * 60 * 24;
+    const PREFIX = 'user:';
+
+    contract Account {
+        const MAX_SUPPLY: u64 = 1000000;
+        const WEEK: u32 = DAY * 7;
+        const GREETING = PREFIX + 'hello';
+
+        supply: u64;
+        week: u32;
+        greeting: string;
+
+        setAll() {
+            this.supply = MAX_SUPPLY;
+            this.week = WEEK;
+            this.greeting = GREETING;
+        }
+
+        assignConstant() {
+            WEEK = 1;
+        }
+
+        addAssignConstant() {
+            WEEK += 1;
+        }
+
+        subAssignConstant() {
+            WEEK -= 1;
+        }
+
+        incrementConstant() {
+            WEEK++;
+        }
+    }
+"#;
+
+#[test]
+fn test_constants() {
+    let (abi, output) = run(
+        CODE,
+        "Account",
+        "setAll",
+        serde_json::json!({
+            "supply": 0,
+            "week": 0,
+            "greeting": "",
+        }),
+        vec![],
+        None,
+        HashMap::new(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        output.this(&abi).unwrap(),
+        abi::Value::StructValue(vec![
+            ("supply".to_owned(), abi::Value::UInt64(1000000)),
+            ("week".to_owned(), abi::Value::UInt32(604800)),
+            (
+                "greeting".to_owned(),
+                abi::Value::String("user:hello".to_owned())
+            ),
+        ])
+    );
+}
+
+#[test_case::test_case("assignConstant"; "assign")]
+#[test_case::test_case("addAssignConstant"; "add assign")]
+#[test_case::test_case("subAssignConstant"; "sub assign")]
+#[test_case::test_case("incrementConstant"; "increment")]
+fn test_assign_constant(function: &str) {
+    let err = run(
+        CODE,
+        "Account",
+        function,
+        serde_json::json!({
+            "supply": 0,
+            "week": 0,
+            "greeting": "",
+        }),
+        vec![],
+        None,
+        HashMap::new(),
+    )
+    .unwrap_err();
+
+    assert!(err.to_string().contains("cannot assign to constant WEEK"));
+}
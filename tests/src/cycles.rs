@@ -0,0 +1,130 @@
+use super::*;
+
+const CODE: &str = r#"
+    contract Account {
+        balance: u32;
+
+        deposit(amt: u32) {
+            this.balance = this.balance + amt;
+        }
+
+        depositEach(amt: u32) {
+            let one: u32 = 1;
+            for (let i: u32 = 0; i < amt; i++) {
+                this.balance = this.balance + one;
+            }
+        }
+    }
+"#;
+
+fn estimate(function: &str) -> abi::CycleEstimate {
+    let program = polylang::parse_program(CODE).unwrap();
+    let (_, abi) = polylang::compiler::compile(program, Some("Account"), function).unwrap();
+
+    abi.cycles.unwrap()
+}
+
+#[test]
+fn test_straight_line_has_constant_estimate() {
+    let estimate = estimate("deposit");
+
+    assert_eq!(estimate.worst.len(), 1);
+    assert!(estimate.best <= estimate.worst[0], "{estimate:?}");
+}
+
+#[test]
+fn test_loop_grows_with_iterations() {
+    let estimate = estimate("depositEach");
+
+    assert_eq!(estimate.worst.len(), 2);
+    assert!(
+        estimate.worst_case(10) < estimate.worst_case(100),
+        "{estimate:?}"
+    );
+}
+
+#[test]
+fn test_budget() {
+    let estimate = estimate("depositEach");
+    let budget = |max_cycles| abi::CycleBudget {
+        max_cycles,
+        loop_iterations: 10,
+    };
+
+    assert!(budget(estimate.worst_case(10)).check(&estimate).is_ok());
+    assert_eq!(
+        budget(estimate.best)
+            .check(&estimate)
+            .unwrap_err()
+            .to_string(),
+        format!(
+            "the program can take up to {} cycles, over the budget of {} cycles",
+            estimate.worst_case(10),
+            estimate.best
+        )
+    );
+}
+
+#[test]
+fn test_budget_rejects_programs_without_estimate() {
+    let mut abi = abi::Abi::default();
+    let budget = abi::CycleBudget {
+        max_cycles: u64::MAX,
+        loop_iterations: 10,
+    };
+    assert!(budget.check_abi(&abi).is_err());
+
+    abi.cycles = Some(estimate("deposit"));
+    assert!(budget.check_abi(&abi).is_ok());
+}
+
+#[test]
+fn test_budget_is_enforced_with_a_falsified_estimate() {
+    let program = polylang::parse_program(CODE).unwrap();
+    let (miden_code, mut abi) =
+        polylang::compiler::compile(program, Some("Account"), "depositEach").unwrap();
+    let budget = abi::CycleBudget {
+        max_cycles: estimate("depositEach").worst_case(10),
+        loop_iterations: 10,
+    };
+
+    // The loop runs 1000 times, but the request claims that the program takes one cycle
+    abi.cycles = Some(abi::CycleEstimate {
+        best: 1,
+        worst: vec![1],
+    });
+    let err = prove_request(
+        &miden_code,
+        &abi,
+        serde_json::json!({
+            "this": { "balance": 0 },
+            "args": [1000],
+        }),
+        Some(budget),
+    )
+    .unwrap_err();
+
+    assert!(
+        err.to_string().contains(&format!(
+            "the program took more than {} cycles, over the budget",
+            budget.max_cycles
+        )),
+        "{err}"
+    );
+}
+
+#[test]
+fn test_best_case_is_a_lower_bound() {
+    let (abi, output) = run(
+        CODE,
+        "Account",
+        "depositEach",
+        serde_json::json!({ "balance": 0 }),
+        vec![serde_json::json!(0)],
+        None,
+        HashMap::new(),
+    )
+    .unwrap();
+
+    assert!(abi.cycles.unwrap().best <= output.cycle_count as u64);
+}
@@ -0,0 +1,202 @@
+use super::*;
+
+const CODE: &str = r#"
+    contract Account {
+        id: string;
+        balance: u32;
+
+        event Deposited {
+            amount: u32;
+        }
+
+        @call
+        deposit(amount: u32) {
+            this.balance = this.balance + amount;
+            emit Deposited { amount: amount };
+        }
+    }
+
+    contract Token {
+        id: string;
+        balance: u32;
+        account: Account;
+
+        event Transfer {
+            to: string;
+            amount: u32;
+            memo?: string;
+        }
+
+        event Burn {
+            amount: u32;
+        }
+
+        @call
+        transfer(to: string, amount: u32, memo: string) {
+            this.balance = this.balance - amount;
+            emit Transfer { to: to, amount: amount };
+            emit Burn { amount: amount };
+            emit Transfer { to: to, amount: amount, memo: memo };
+        }
+
+        @call
+        withdraw(amount: u32) {
+            this.balance = this.balance - amount;
+            this.account.deposit(amount);
+        }
+    }
+"#;
+
+fn token() -> serde_json::Value {
+    serde_json::json!({
+        "id": "token",
+        "balance": 10,
+        "account": { "id": "account1" },
+    })
+}
+
+fn transfer(to: &str, amount: u32, memo: Option<&str>) -> abi::Value {
+    abi::Value::StructValue(vec![
+        ("to".to_owned(), abi::Value::String(to.to_owned())),
+        ("amount".to_owned(), abi::Value::UInt32(amount)),
+        (
+            "memo".to_owned(),
+            abi::Value::Nullable(memo.map(|m| Box::new(abi::Value::String(m.to_owned())))),
+        ),
+    ])
+}
+
+#[test]
+fn test_emit_events() {
+    let (abi, output) = run(
+        CODE,
+        "Token",
+        "transfer",
+        token(),
+        vec![
+            serde_json::json!("alice"),
+            serde_json::json!(3),
+            serde_json::json!("rent"),
+        ],
+        None,
+        HashMap::new(),
+    )
+    .unwrap();
+
+    let events = output.events(&abi).unwrap();
+    assert_eq!(
+        events,
+        vec![
+            polylang_prover::Event {
+                contract: "Token".to_owned(),
+                name: "Transfer".to_owned(),
+                value: transfer("alice", 3, None),
+            },
+            polylang_prover::Event {
+                contract: "Token".to_owned(),
+                name: "Burn".to_owned(),
+                value: abi::Value::StructValue(vec![("amount".to_owned(), abi::Value::UInt32(3))]),
+            },
+            polylang_prover::Event {
+                contract: "Token".to_owned(),
+                name: "Transfer".to_owned(),
+                value: transfer("alice", 3, Some("rent")),
+            },
+        ]
+    );
+
+    polylang_prover::verify_events(&abi, &output.stack, &events).unwrap();
+
+    let mut tampered = events.clone();
+    tampered[0].value = transfer("bob", 3, None);
+    assert!(polylang_prover::verify_events(&abi, &output.stack, &tampered).is_err());
+
+    let mut tampered = events;
+    tampered.pop();
+    assert!(polylang_prover::verify_events(&abi, &output.stack, &tampered).is_err());
+}
+
+#[test]
+fn test_emit_in_foreign_call() {
+    let (abi, output) = run(
+        CODE,
+        "Token",
+        "withdraw",
+        token(),
+        vec![serde_json::json!(4)],
+        None,
+        HashMap::from([(
+            "Account".to_owned(),
+            vec![serde_json::json!({ "id": "account1", "balance": 1 })],
+        )]),
+    )
+    .unwrap();
+
+    let events = output.events(&abi).unwrap();
+    assert_eq!(
+        events,
+        vec![polylang_prover::Event {
+            contract: "Account".to_owned(),
+            name: "Deposited".to_owned(),
+            value: abi::Value::StructValue(vec![("amount".to_owned(), abi::Value::UInt32(4))]),
+        }]
+    );
+
+    polylang_prover::verify_events(&abi, &output.stack, &events).unwrap();
+    polylang_prover::verify_record_updates(
+        &abi,
+        &output.stack,
+        &output.record_updates(&abi).unwrap(),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_no_events() {
+    let program = polylang::parse_program(
+        r#"
+        contract Token {
+            id: string;
+            balance: u32;
+
+            event Burn {
+                amount: u32;
+            }
+
+            burn(amount: u32) {
+                this.balance = this.balance - amount;
+            }
+        }
+        "#,
+    )
+    .unwrap();
+    let (_, abi) = polylang::compiler::compile(program, Some("Token"), "burn").unwrap();
+    assert!(abi.events.is_none());
+}
+
+#[test_case::test_case("emit Mint { amount: amount }", "event Token.Mint not found"; "unknown event")]
+#[test_case::test_case("emit Burn { }", "Missing field amount of event Token.Burn"; "missing field")]
+#[test_case::test_case("emit Burn { amount: amount, to: to }", "Event Token.Burn has no field to"; "unknown field")]
+#[test_case::test_case("emit Burn { amount: to }", "type mismatch"; "wrong type")]
+fn test_emit_errors(statement: &str, error: &str) {
+    let code = format!(
+        r#"
+        contract Token {{
+            id: string;
+
+            event Burn {{
+                amount: u32;
+            }}
+
+            burn(to: string, amount: u32) {{
+                {statement};
+            }}
+        }}
+        "#
+    );
+    let program = polylang::parse_program(&code).unwrap();
+    let err = polylang::compiler::compile(program, Some("Token"), "burn")
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains(error), "{err}");
+}
@@ -0,0 +1,123 @@
+use super::*;
+
+const CODE: &str = r#"
+    const DEBUG = false;
+
+    contract Account {
+        x: number;
+        b: boolean;
+        s: string;
+
+        fold() {
+            this.x = 1 + 2 * 4 - 2;
+            this.b = 3 > 2 && !DEBUG;
+            this.s = 'a' + 'b';
+        }
+
+        empty() {}
+
+        rounding() {
+            this.x = 16777216 + 1 - 16777216;
+        }
+
+        roundingAtRuntime(x: number) {
+            this.x = x + 1 - x;
+        }
+
+        ifFalse() {
+            if (DEBUG) {
+                let i = 0;
+                while (i < 100) {
+                    i = i + 1;
+                }
+                this.s = 'debug';
+            }
+            while (false) {
+                this.x = 2;
+            }
+        }
+
+        ifTrue() {
+            if (!DEBUG) {
+                this.b = true;
+            } else {
+                this.s = 'debug';
+            }
+        }
+
+        afterThrow() {
+            throw error('stop');
+            this.x = 5;
+        }
+    }
+"#;
+
+fn run_fn(function: &str) -> Result<(abi::Value, u32), error::Error> {
+    run_fn_with_args(function, vec![])
+}
+
+fn run_fn_with_args(
+    function: &str,
+    args: Vec<serde_json::Value>,
+) -> Result<(abi::Value, u32), error::Error> {
+    let (abi, output) = run(
+        CODE,
+        "Account",
+        function,
+        serde_json::json!({
+            "x": 0,
+            "b": false,
+            "s": "",
+        }),
+        args,
+        None,
+        HashMap::new(),
+    )?;
+
+    Ok((output.this(&abi)?, output.cycle_count))
+}
+
+fn this(x: f32, b: bool, s: &str) -> abi::Value {
+    abi::Value::StructValue(vec![
+        ("x".to_owned(), abi::Value::Float32(x)),
+        ("b".to_owned(), abi::Value::Boolean(b)),
+        ("s".to_owned(), abi::Value::String(s.to_owned())),
+    ])
+}
+
+#[test]
+fn test_fold() {
+    let (this_value, _) = run_fn("fold").unwrap();
+    assert_eq!(this_value, this(7.0, true, "ab"));
+}
+
+#[test]
+fn test_fold_rounds_like_runtime() {
+    let (folded, _) = run_fn("rounding").unwrap();
+    let (runtime, _) =
+        run_fn_with_args("roundingAtRuntime", vec![serde_json::json!(16777216)]).unwrap();
+
+    assert_eq!(folded, this(0.0, false, ""));
+    assert_eq!(runtime, folded);
+}
+
+#[test]
+fn test_dead_branches_are_not_compiled() {
+    let (this_value, cycles) = run_fn("ifFalse").unwrap();
+    assert_eq!(this_value, this(0.0, false, ""));
+
+    let (_, empty_cycles) = run_fn("empty").unwrap();
+    assert_eq!(cycles, empty_cycles);
+}
+
+#[test]
+fn test_if_true() {
+    let (this_value, _) = run_fn("ifTrue").unwrap();
+    assert_eq!(this_value, this(0.0, true, ""));
+}
+
+#[test]
+fn test_unreachable_after_throw() {
+    let err = run_fn("afterThrow").unwrap_err();
+    assert!(err.to_string().contains("stop"));
+}
@@ -0,0 +1,179 @@
+use super::*;
+
+const CODE: &str = r#"
+    contract Account {
+        id: string;
+        owner: PublicKey;
+        balance: u32;
+
+        @call(owner)
+        deposit(amount: u32): u32 {
+            this.balance = this.balance + amount;
+            return this.balance;
+        }
+    }
+
+    contract Bank {
+        id: string;
+        account: Account;
+        total: u32;
+
+        @call
+        deposit(amount: u32): u32 {
+            this.total = this.total + amount;
+            return this.account.deposit(amount);
+        }
+
+        @call
+        depositTwice(amount: u32): u32 {
+            this.account.deposit(amount);
+            return this.account.deposit(amount);
+        }
+
+        @call
+        depositEach(amount: u32): u32 {
+            let balance: u32 = 0;
+            for (let i: u32 = 0; i < amount; i++) {
+                balance = this.account.deposit(amount);
+            }
+            return balance;
+        }
+    }
+"#;
+
+fn account(balance: u32) -> serde_json::Value {
+    serde_json::json!({
+        "id": "account1",
+        "owner": fixtures::pk1(),
+        "balance": balance,
+    })
+}
+
+fn deposit(
+    ctx_public_key: abi::publickey::Key,
+    accounts: Vec<serde_json::Value>,
+) -> Result<(abi::Abi, polylang_prover::RunOutput), error::Error> {
+    call_bank("deposit", ctx_public_key, accounts)
+}
+
+fn call_bank(
+    function: &str,
+    ctx_public_key: abi::publickey::Key,
+    accounts: Vec<serde_json::Value>,
+) -> Result<(abi::Abi, polylang_prover::RunOutput), error::Error> {
+    run(
+        CODE,
+        "Bank",
+        function,
+        serde_json::json!({
+            "id": "bank",
+            "account": { "id": "account1" },
+            "total": 10,
+        }),
+        vec![serde_json::json!(5)],
+        Some(ctx_public_key),
+        HashMap::from([("Account".to_owned(), accounts)]),
+    )
+}
+
+#[test]
+fn test_foreign_call_updates_record() {
+    let (abi, output) = deposit(fixtures::pk1_key(), vec![account(7)]).unwrap();
+
+    assert_eq!(output.result(&abi).unwrap(), abi::Value::UInt32(12));
+
+    let updates = output.record_updates(&abi).unwrap();
+    assert_eq!(updates.len(), 1);
+    assert_eq!(updates[0].contract, "Account");
+    match &updates[0].record {
+        abi::Value::StructValue(fields) => {
+            assert!(fields.contains(&("balance".to_owned(), abi::Value::UInt32(12))))
+        }
+        record => panic!("unexpected record: {record:?}"),
+    }
+    assert_eq!(
+        updates[0].hash,
+        polylang_prover::hash_this(
+            abi.other_contract_types[0].clone(),
+            &updates[0].record,
+            Some(&[abi::salt::Salt::ZERO; 3])
+        )
+        .unwrap()
+    );
+
+    polylang_prover::verify_record_updates(&abi, &output.stack, &updates).unwrap();
+
+    let mut tampered = updates;
+    tampered[0].hash[0] += 1;
+    assert!(polylang_prover::verify_record_updates(&abi, &output.stack, &tampered).is_err());
+}
+
+// The second call reads the record updated by the first one, instead of the one in the advice map
+#[test_case::test_case("depositTwice", 17; "twice")]
+#[test_case::test_case("depositEach", 32; "in a loop")]
+fn test_foreign_calls_update_record_once(function: &str, balance: u32) {
+    let (abi, output) = call_bank(function, fixtures::pk1_key(), vec![account(7)]).unwrap();
+
+    assert_eq!(output.result(&abi).unwrap(), abi::Value::UInt32(balance));
+
+    let updates = output.record_updates(&abi).unwrap();
+    assert_eq!(updates.len(), 1);
+    match &updates[0].record {
+        abi::Value::StructValue(fields) => {
+            assert!(fields.contains(&("balance".to_owned(), abi::Value::UInt32(balance))))
+        }
+        record => panic!("unexpected record: {record:?}"),
+    }
+
+    polylang_prover::verify_record_updates(&abi, &output.stack, &updates).unwrap();
+}
+
+#[test]
+fn test_foreign_call_checks_callee_authorization() {
+    let err = deposit(fixtures::pk2_key(), vec![account(7)]).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("You are not authorized to call Account.deposit"),
+        "{err}"
+    );
+}
+
+#[test]
+fn test_foreign_call_requires_record() {
+    let err = deposit(fixtures::pk1_key(), vec![]).unwrap_err();
+    assert!(
+        err.to_string().contains("Account record not found"),
+        "{err}"
+    );
+}
+
+#[test]
+fn test_foreign_call_signed_contract() {
+    let program = polylang::parse_program(
+        r#"
+        @signed
+        contract Account {
+            id: string;
+            balance: u32;
+
+            deposit(amount: u32) {
+                this.balance = this.balance + amount;
+            }
+        }
+
+        contract Bank {
+            id: string;
+            account: Account;
+
+            deposit(amount: u32) {
+                this.account.deposit(amount);
+            }
+        }
+        "#,
+    )
+    .unwrap();
+    let err = polylang::compiler::compile(program, Some("Bank"), "deposit")
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("it is @signed"), "{err}");
+}
@@ -0,0 +1,151 @@
+use super::*;
+
+const CODE: &str = r#"
+    contract Account {
+        arr: number[];
+        strs: string[];
+
+        pushLoop(n: number) {
+            for (let i = 0; i < n; i++) {
+                this.arr.push(i);
+            }
+        }
+
+        pushBoth(n: number) {
+            for (let i = 0; i < n; i++) {
+                this.arr.push(i);
+                this.strs.push('s');
+            }
+        }
+    }
+"#;
+
+fn run_fn(
+    function: &str,
+    this: serde_json::Value,
+    n: f32,
+) -> Result<(abi::Value, u64), error::Error> {
+    let (abi, output) = run(
+        CODE,
+        "Account",
+        function,
+        this,
+        vec![serde_json::json!(n)],
+        None,
+        HashMap::new(),
+    )?;
+
+    Ok((output.this(&abi)?, output.heap_size()))
+}
+
+fn numbers(n: usize) -> abi::Value {
+    abi::Value::Array((0..n).map(|i| abi::Value::Float32(i as f32)).collect())
+}
+
+#[test]
+fn test_push_loop_grows_in_place() {
+    let (this, heap_size) = run_fn(
+        "pushLoop",
+        serde_json::json!({ "arr": [], "strs": ["a", "b"] }),
+        100.,
+    )
+    .unwrap();
+
+    assert_eq!(
+        this,
+        abi::Value::StructValue(vec![
+            ("arr".to_owned(), numbers(100)),
+            (
+                "strs".to_owned(),
+                abi::Value::Array(vec![
+                    abi::Value::String("a".to_owned()),
+                    abi::Value::String("b".to_owned()),
+                ]),
+            ),
+        ])
+    );
+
+    // Reallocating on every push would allocate over 10000 words
+    assert!(heap_size < 1000, "heap size: {heap_size}");
+}
+
+#[test]
+fn test_push_reallocates() {
+    let (this, heap_size) = run_fn(
+        "pushBoth",
+        serde_json::json!({ "arr": [], "strs": ["a", "b"] }),
+        40.,
+    )
+    .unwrap();
+
+    let mut strs = vec![
+        abi::Value::String("a".to_owned()),
+        abi::Value::String("b".to_owned()),
+    ];
+    strs.extend((0..40).map(|_| abi::Value::String("s".to_owned())));
+
+    assert_eq!(
+        this,
+        abi::Value::StructValue(vec![
+            ("arr".to_owned(), numbers(40)),
+            ("strs".to_owned(), abi::Value::Array(strs)),
+        ])
+    );
+
+    assert!(heap_size < 1000, "heap size: {heap_size}");
+}
+
+#[test]
+fn test_grow_copy_of_array() {
+    // `b` outgrows the data it shares with `this.arr`, which must not be reused
+    // when `this.other` grows
+    let (abi, output) = run(
+        r#"
+        contract Account {
+            id: string;
+            arr: number[];
+            other: number[];
+            copy: number[];
+
+            pushCopy(x: number) {
+                let b = this.arr;
+                for (let i = 0; i < 25; i++) {
+                    b.push(i);
+                }
+                this.other.push(x);
+                this.copy = b;
+            }
+        }
+        "#,
+        "Account",
+        "pushCopy",
+        serde_json::json!({
+            "id": "account1",
+            "arr": (0..20).collect::<Vec<_>>(),
+            "other": [7],
+            "copy": [],
+        }),
+        vec![serde_json::json!(8)],
+        None,
+        HashMap::new(),
+    )
+    .unwrap();
+
+    let mut copy = (0..20)
+        .map(|i| abi::Value::Float32(i as f32))
+        .collect::<Vec<_>>();
+    copy.extend((0..25).map(|i| abi::Value::Float32(i as f32)));
+
+    assert_eq!(
+        output.this(&abi).unwrap(),
+        abi::Value::StructValue(vec![
+            ("id".to_owned(), abi::Value::String("account1".to_owned())),
+            ("arr".to_owned(), numbers(20)),
+            (
+                "other".to_owned(),
+                abi::Value::Array(vec![abi::Value::Float32(7.0), abi::Value::Float32(8.0)]),
+            ),
+            ("copy".to_owned(), abi::Value::Array(copy)),
+        ])
+    );
+}
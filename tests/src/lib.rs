@@ -1,11 +1,34 @@
 #![cfg(test)]
 
+mod asm;
+mod cache;
+mod call_paths;
+mod call_when;
 mod col_refs;
+mod constants;
+mod cycles;
+mod events;
+mod folding;
+mod foreign_calls;
+mod heap;
+mod library;
+mod manifest;
+mod memory;
+mod new_records;
+mod nullable;
+mod peephole;
+mod private_fields;
 mod push;
+mod salts;
+mod signed;
 mod slice;
+mod source_map;
 mod splice;
+mod std_version;
+mod stdlib;
 mod string;
 mod unshift;
+mod verify_signature;
 
 use expect_test::expect;
 use serde::de::Deserialize;
@@ -74,7 +97,7 @@ fn run(
         abi.clone(),
         ctx_public_key,
         match &abi.this_type {
-            Some(abi::Type::Struct(s)) => s.fields.iter().map(|_| 0).collect(),
+            Some(abi::Type::Struct(s)) => s.fields.iter().map(|_| abi::salt::Salt::ZERO).collect(),
             _ => unreachable!(),
         },
         this,
@@ -98,7 +121,10 @@ fn run(
                         .map(|record| {
                             (
                                 record.clone(),
-                                col.fields.iter().map(|_| 0).collect::<Vec<_>>(),
+                                col.fields
+                                    .iter()
+                                    .map(|_| abi::salt::Salt::ZERO)
+                                    .collect::<Vec<_>>(),
                             )
                         })
                         .collect(),
@@ -108,11 +134,29 @@ fn run(
         },
     )?;
 
-    let (output, _) = polylang_prover::run(&program, &inputs)?;
+    let (output, _) =
+        polylang_prover::run(&program, &inputs).map_err(|e| e.add_source(polylang_code))?;
 
     Ok((abi, output))
 }
 
+/// Proves a call with the route of the prover server, `request` has the fields of
+/// the request other than the program and its ABI.
+fn prove_request(
+    miden_code: &str,
+    abi: &abi::Abi,
+    mut request: serde_json::Value,
+    cycle_budget: Option<abi::CycleBudget>,
+) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    request["midenCode"] = miden_code.into();
+    request["abi"] = serde_json::to_value(abi)?;
+    let request = serde_json::from_value(request)?;
+
+    tokio::runtime::Builder::new_current_thread()
+        .build()?
+        .block_on(server_routes::prove::prove(request, cycle_budget))
+}
+
 #[test]
 fn call_public_collection() {
     let code = r#"
@@ -945,6 +989,225 @@ fn call_auth_literal_compressed() {
     );
 }
 
+#[test]
+fn call_auth_literal_ed25519() {
+    let key = "0xd75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+    let code = format!(
+        r#"
+        contract Account {{
+            id: string;
+            name: string;
+
+            @call(ed#{key})
+            changeName(name: string) {{
+                this.name = name;
+            }}
+        }}
+    "#
+    );
+
+    let ed_key = abi::publickey::Key::from_ed25519_bytes(
+        &hex::decode(key.trim_start_matches("0x")).unwrap(),
+    )
+    .unwrap();
+    let (abi, output) = run(
+        &code,
+        "Account",
+        "changeName",
+        serde_json::json!({
+            "id": "test",
+            "name": "test",
+        }),
+        vec![serde_json::json!("test2")],
+        Some(ed_key),
+        HashMap::new(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        output.this(&abi).unwrap(),
+        abi::Value::StructValue(vec![
+            ("id".to_owned(), abi::Value::String("".to_owned())),
+            ("name".to_owned(), abi::Value::String("test2".to_owned())),
+        ]),
+    );
+
+    // An Ed25519 key is not equal to a secp256k1 key
+    let err = run(
+        &code,
+        "Account",
+        "changeName",
+        serde_json::json!({
+            "id": "test",
+            "name": "test",
+        }),
+        vec![serde_json::json!("test2")],
+        Some(fixtures::pk1_key()),
+        HashMap::new(),
+    )
+    .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("You are not authorized to call this function"));
+}
+
+#[test]
+fn public_key_to_hex_ed25519() {
+    let code = r#"
+        contract Account {
+            id: string;
+            key: string;
+
+            setKey(pk: PublicKey) {
+                this.key = pk.toHex();
+            }
+        }
+    "#;
+
+    let bytes =
+        hex::decode("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a").unwrap();
+    let key = abi::publickey::Key::from_ed25519_bytes(&bytes).unwrap();
+    let (abi, output) = run(
+        code,
+        "Account",
+        "setKey",
+        serde_json::json!({
+            "id": "test",
+            "key": "",
+        }),
+        vec![serde_json::to_value(&key).unwrap()],
+        None,
+        HashMap::new(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        output.this(&abi).unwrap(),
+        abi::Value::StructValue(vec![
+            ("id".to_owned(), abi::Value::String("".to_owned())),
+            (
+                "key".to_owned(),
+                abi::Value::String(format!("0x{}", hex::encode(bytes)))
+            ),
+        ]),
+    );
+    assert_eq!(key.to_hex(), format!("0x{}", hex::encode(&key.x)));
+}
+
+fn p256_key(seed: u8) -> (p256::ecdsa::SigningKey, abi::publickey::Key) {
+    let signing_key = p256::ecdsa::SigningKey::from_slice(&[seed; 32]).unwrap();
+    let key = abi::publickey::Key::from_p256_bytes(
+        signing_key
+            .verifying_key()
+            .to_encoded_point(true)
+            .as_bytes(),
+    )
+    .unwrap();
+
+    (signing_key, key)
+}
+
+#[test]
+fn call_auth_literal_p256() {
+    let (signing_key, p256_key) = p256_key(1);
+    let key = hex::encode(
+        signing_key
+            .verifying_key()
+            .to_encoded_point(true)
+            .as_bytes(),
+    );
+    let code = format!(
+        r#"
+        contract Account {{
+            id: string;
+            name: string;
+
+            @call(p256#0x{key})
+            changeName(name: string) {{
+                this.name = name;
+            }}
+        }}
+    "#
+    );
+
+    let (abi, output) = run(
+        &code,
+        "Account",
+        "changeName",
+        serde_json::json!({
+            "id": "test",
+            "name": "test",
+        }),
+        vec![serde_json::json!("test2")],
+        Some(p256_key),
+        HashMap::new(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        output.this(&abi).unwrap(),
+        abi::Value::StructValue(vec![
+            ("id".to_owned(), abi::Value::String("".to_owned())),
+            ("name".to_owned(), abi::Value::String("test2".to_owned())),
+        ]),
+    );
+
+    // A different P-256 key is not authorized
+    let err = run(
+        &code,
+        "Account",
+        "changeName",
+        serde_json::json!({
+            "id": "test",
+            "name": "test",
+        }),
+        vec![serde_json::json!("test2")],
+        Some(p256_key(2).1),
+        HashMap::new(),
+    )
+    .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("You are not authorized to call this function"));
+}
+
+#[test]
+fn p256_key_jwk() {
+    let (signing_key, key) = p256_key(1);
+
+    let jwk = serde_json::to_value(&key).unwrap();
+    assert_eq!(jwk["kty"], "EC");
+    assert_eq!(jwk["crv"], "P-256");
+    assert_eq!(jwk["alg"], "ES256");
+    assert_eq!(abi::publickey::Key::deserialize(jwk).unwrap(), key);
+
+    // The compressed, uncompressed and x || y encodings are the same key
+    let uncompressed = signing_key.verifying_key().to_encoded_point(false);
+    assert_eq!(
+        abi::publickey::Key::from_p256_bytes(uncompressed.as_bytes()).unwrap(),
+        key
+    );
+    assert_eq!(
+        abi::publickey::Key::from_p256_bytes(&uncompressed.as_bytes()[1..]).unwrap(),
+        key
+    );
+    assert_eq!(
+        key.to_hex(),
+        format!("0x{}", hex::encode(&uncompressed.as_bytes()[1..]))
+    );
+
+    // The key parsed from the JWK verifies signatures of the SHA-256 hash of the message
+    let signature: p256::ecdsa::Signature =
+        p256::ecdsa::signature::Signer::sign(&signing_key, b"message");
+    let key = abi::publickey::Key::deserialize(serde_json::to_value(&key).unwrap()).unwrap();
+    assert!(key
+        .verify_message(b"message", &signature.to_bytes())
+        .unwrap());
+    assert!(!key
+        .verify_message(b"other message", &signature.to_bytes())
+        .unwrap());
+}
+
 #[test]
 fn read_auth_field_correct_ctx() {
     let code = r#"
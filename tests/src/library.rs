@@ -0,0 +1,104 @@
+use super::*;
+
+const CODE: &str = r#"
+    contract Account {
+        id: string;
+        balance: u32;
+
+        constructor(id: string) {
+            this.id = id;
+        }
+
+        deposit(amount: u32) {
+            this.balance = this.balance + amount;
+        }
+    }
+"#;
+
+/// Turns the library into a program that calls the dispatcher with `selector`.
+fn dispatcher_program(library: &str, selector: u32) -> String {
+    format!(
+        "{}\nbegin\n  push.{selector}\n  exec.dispatch\nend\n",
+        library.replace("export.", "proc.")
+    )
+}
+
+/// Assembles the library and returns the MAST root of `procedure`.
+/// A program that only executes the procedure has the same root as the procedure.
+fn procedure_root(library: &str, procedure: &str) -> String {
+    let program = format!(
+        "{}\nbegin\n  exec.{procedure}\nend\n",
+        library.replace("export.", "proc.")
+    );
+    let program = polylang_prover::compile_program(&abi::Abi::default(), &program).unwrap();
+    polylang::compiler::program_hash(&program)
+}
+
+#[test]
+fn test_library_entry_points() {
+    let program = polylang::parse_program(CODE).unwrap();
+    let (library, library_abi) = polylang::compiler::compile_library(program, "Account").unwrap();
+
+    assert_eq!(library_abi.dispatcher, "dispatch");
+    assert_eq!(
+        library_abi
+            .entry_points
+            .iter()
+            .map(|e| (e.selector, e.function.as_str(), e.procedure.as_str()))
+            .collect::<Vec<_>>(),
+        vec![
+            (0, "constructor", "fn_constructor"),
+            (1, "deposit", "fn_deposit"),
+            (2, ".readAuth", "read_auth"),
+        ]
+    );
+
+    for entry_point in &library_abi.entry_points {
+        assert_eq!(entry_point.mast_root.len(), 64);
+        assert_eq!(
+            entry_point.mast_root,
+            procedure_root(&library, &entry_point.procedure),
+            "{}",
+            entry_point.function
+        );
+    }
+    assert_ne!(
+        library_abi.entry_points[0].mast_root,
+        library_abi.entry_points[1].mast_root
+    );
+}
+
+#[test]
+fn test_dispatch_runs_selected_function() {
+    let program = polylang::parse_program(CODE).unwrap();
+    let (library, library_abi) = polylang::compiler::compile_library(program, "Account").unwrap();
+    let entry_point = library_abi
+        .entry_points
+        .iter()
+        .find(|e| e.function == "deposit")
+        .unwrap();
+
+    let program = polylang_prover::compile_program(
+        &entry_point.abi,
+        &dispatcher_program(&library, entry_point.selector),
+    )
+    .unwrap();
+    let inputs = polylang_prover::Inputs::new(
+        entry_point.abi.clone(),
+        None,
+        vec![abi::salt::Salt::ZERO; 2],
+        serde_json::json!({ "id": "a", "balance": 1 }),
+        vec![serde_json::json!(2)],
+        HashMap::new(),
+    )
+    .unwrap();
+    let (output, _) = polylang_prover::run(&program, &inputs).unwrap();
+
+    assert_eq!(
+        output.this(&entry_point.abi).unwrap(),
+        abi::Value::StructValue(vec![
+            ("id".to_owned(), abi::Value::String("a".to_owned())),
+            ("balance".to_owned(), abi::Value::UInt32(3)),
+        ])
+    );
+}
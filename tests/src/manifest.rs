@@ -0,0 +1,86 @@
+use super::*;
+
+use polylang_prover::ProgramExt;
+
+const CODE: &str = r#"
+    contract Account {
+        id: string;
+        balance: u32;
+
+        constructor(id: string) {
+            this.id = id;
+        }
+
+        deposit(amount: u32) {
+            this.balance = this.balance + amount;
+        }
+    }
+"#;
+
+fn manifest(code: &str) -> abi::Manifest {
+    let program = polylang::parse_program(code).unwrap();
+    polylang::compiler::compile_manifest(program, code, Some("Account"), abi::StdVersion::default())
+        .unwrap()
+}
+
+fn compile(code: &str, function: &str) -> (String, abi::Abi) {
+    let program = polylang::parse_program(code).unwrap();
+    polylang::compiler::compile(program, Some("Account"), function).unwrap()
+}
+
+#[test]
+fn test_manifest_is_deterministic() {
+    let manifest = manifest(CODE);
+
+    assert_eq!(
+        manifest
+            .functions
+            .iter()
+            .map(|f| f.function.as_str())
+            .collect::<Vec<_>>(),
+        [".readAuth", "constructor", "deposit"]
+    );
+    assert_eq!(manifest.contract.as_deref(), Some("Account"));
+    assert_eq!(
+        serde_json::to_string(&manifest).unwrap(),
+        serde_json::to_string(&self::manifest(CODE)).unwrap()
+    );
+}
+
+#[test]
+fn test_manifest_program_hashes() {
+    let manifest = manifest(CODE);
+
+    for function in ["constructor", "deposit"] {
+        let (miden_code, abi) = compile(CODE, function);
+        let program = polylang_prover::compile_program(&abi, &miden_code).unwrap();
+
+        assert_eq!(
+            manifest.function(function).unwrap().program_hash,
+            program.program_hash()
+        );
+        assert_eq!(
+            polylang_prover::check_manifest(&program, &manifest)
+                .unwrap()
+                .function,
+            function
+        );
+        assert_eq!(
+            *polylang_prover::manifest_program_info(&manifest, function)
+                .unwrap()
+                .program_hash(),
+            program.hash()
+        );
+    }
+}
+
+#[test]
+fn test_changed_source_is_not_in_manifest() {
+    let manifest = manifest(CODE);
+    let changed = CODE.replace("this.balance + amount", "this.balance + amount + 1");
+
+    assert_ne!(manifest.source_hash, self::manifest(&changed).source_hash);
+    let (miden_code, abi) = compile(&changed, "deposit");
+    let program = polylang_prover::compile_program(&abi, &miden_code).unwrap();
+    assert!(polylang_prover::check_manifest(&program, &manifest).is_err());
+}
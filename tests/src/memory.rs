@@ -0,0 +1,196 @@
+use super::*;
+
+const CODE: &str = r#"
+    function distance(a: number, b: number): number {
+        if (a > b) {
+            return a - b;
+        }
+        return b - a;
+    }
+
+    contract Account {
+        x: number;
+        b: boolean;
+        s: string;
+
+        once(a: number) {
+            this.x = this.x + a * 2;
+        }
+
+        many(a: number) {
+            this.x = this.x + a * 2;
+            this.x = this.x + a * 2;
+            this.x = this.x + a * 2;
+            this.x = this.x + a * 2;
+            this.x = this.x + a * 2;
+        }
+
+        branches(a: number) {
+            if (a > 1) {
+                this.x = this.x + a * 2;
+            } else {
+                this.x = this.x - a * 2;
+            }
+        }
+
+        reuse(a: number) {
+            this.x = this.x + a * 2;
+            let b = false;
+            let s = '';
+            this.b = b;
+            this.s = s;
+        }
+
+        returnInLoop(a: number) {
+            for (let i = 0; i < 5; i++) {
+                this.x = this.x + distance(i, a);
+            }
+        }
+
+        breakInLoop(a: number) {
+            for (let i = 0; i < a; i++) {
+                let j = 0;
+                while (j < 10) {
+                    if (j > 1) {
+                        break;
+                    }
+                    j = j + 1;
+                }
+                this.x = this.x + j;
+            }
+        }
+
+        literalInLoop(a: number) {
+            for (let i = 0; i < a; i++) {
+                let b = false;
+                if (i == 1) {
+                    b = true;
+                }
+                if (b) {
+                    this.x = this.x + 1;
+                }
+            }
+        }
+    }
+"#;
+
+fn run_fn(function: &str, a: f32) -> Result<(abi::Abi, abi::Value), error::Error> {
+    let (abi, output) = run(
+        CODE,
+        "Account",
+        function,
+        serde_json::json!({
+            "x": 1,
+            "b": true,
+            "s": "hello",
+        }),
+        vec![serde_json::json!(a)],
+        None,
+        HashMap::new(),
+    )?;
+
+    let this = output.this(&abi)?;
+    Ok((abi, this))
+}
+
+fn this(x: f32, b: bool, s: &str) -> abi::Value {
+    abi::Value::StructValue(vec![
+        ("x".to_owned(), abi::Value::Float32(x)),
+        ("b".to_owned(), abi::Value::Boolean(b)),
+        ("s".to_owned(), abi::Value::String(s.to_owned())),
+    ])
+}
+
+#[test]
+fn test_statements_reuse_memory() {
+    let (once_abi, once_this) = run_fn("once", 3.0).unwrap();
+    assert_eq!(once_this, this(7.0, true, "hello"));
+
+    let (many_abi, many_this) = run_fn("many", 3.0).unwrap();
+    assert_eq!(many_this, this(31.0, true, "hello"));
+
+    assert!(once_abi.peak_static_memory > 0);
+    assert_eq!(once_abi.peak_static_memory, many_abi.peak_static_memory);
+}
+
+#[test_case::test_case(3.0, 7.0; "then")]
+#[test_case::test_case(1.0, -1.0; "else")]
+fn test_branches_reuse_memory(a: f32, expected_x: f32) {
+    let (_, this_value) = run_fn("branches", a).unwrap();
+    assert_eq!(this_value, this(expected_x, true, "hello"));
+}
+
+#[test]
+fn test_reused_memory_is_initialized() {
+    let (_, this_value) = run_fn("reuse", 3.0).unwrap();
+    assert_eq!(this_value, this(7.0, false, ""));
+}
+
+#[test]
+fn test_function_returns_in_every_iteration() {
+    // 1 + |0 - 2| + |1 - 2| + |2 - 2| + |3 - 2| + |4 - 2|
+    let (_, this_value) = run_fn("returnInLoop", 2.0).unwrap();
+    assert_eq!(this_value, this(7.0, true, "hello"));
+}
+
+#[test]
+fn test_loop_breaks_in_every_iteration() {
+    let (_, this_value) = run_fn("breakInLoop", 3.0).unwrap();
+    assert_eq!(this_value, this(7.0, true, "hello"));
+}
+
+#[test]
+fn test_memory_allocated_in_loop_is_zeroed_every_iteration() {
+    // `b` is only set to true in the second iteration
+    let (_, this_value) = run_fn("literalInLoop", 3.0).unwrap();
+    assert_eq!(this_value, this(2.0, true, "hello"));
+}
+
+#[test]
+fn test_reused_memory_holding_true_is_zeroed() {
+    // The second `includes` reuses the memory of the first, which set its `finished` flag
+    let (abi, output) = run(
+        r#"
+        contract Account {
+            id: string;
+            items: u32[];
+            first: boolean;
+            second: boolean;
+
+            check(item: u32) {
+                this.first = this.items.includes(item);
+                this.second = this.items.includes(item);
+            }
+        }
+        "#,
+        "Account",
+        "check",
+        serde_json::json!({
+            "id": "account1",
+            "items": [1, 2, 3],
+            "first": false,
+            "second": false,
+        }),
+        vec![serde_json::json!(2)],
+        None,
+        HashMap::new(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        output.this(&abi).unwrap(),
+        abi::Value::StructValue(vec![
+            ("id".to_owned(), abi::Value::String("account1".to_owned())),
+            (
+                "items".to_owned(),
+                abi::Value::Array(vec![
+                    abi::Value::UInt32(1),
+                    abi::Value::UInt32(2),
+                    abi::Value::UInt32(3),
+                ])
+            ),
+            ("first".to_owned(), abi::Value::Boolean(true)),
+            ("second".to_owned(), abi::Value::Boolean(true)),
+        ])
+    );
+}
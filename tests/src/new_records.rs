@@ -0,0 +1,186 @@
+use super::*;
+
+const CODE: &str = r#"
+    contract Listing {
+        id: string;
+        seller: PublicKey;
+        price: u32;
+
+        constructor(id: string, price: u32) {
+            this.id = id;
+            if (ctx.publicKey) this.seller = ctx.publicKey;
+            this.price = price;
+        }
+    }
+
+    contract Marketplace {
+        id: string;
+        volume: u32;
+        last: Listing;
+
+        @call
+        list(id: string, price: u32): Listing {
+            this.volume = this.volume + price;
+            this.last = new Listing(id, price);
+            return this.last;
+        }
+
+        @call
+        listPair(id: string, otherId: string, price: u32) {
+            new Listing(id, price);
+            new Listing(otherId, price);
+        }
+    }
+"#;
+
+fn marketplace() -> serde_json::Value {
+    serde_json::json!({
+        "id": "marketplace",
+        "volume": 0,
+        "last": { "id": "" },
+    })
+}
+
+fn field(record: &abi::Value, name: &str) -> abi::Value {
+    match record {
+        abi::Value::StructValue(fields) => fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .unwrap()
+            .1
+            .clone(),
+        record => panic!("unexpected record: {record:?}"),
+    }
+}
+
+#[test]
+fn test_new_creates_record() {
+    let (abi, output) = run(
+        CODE,
+        "Marketplace",
+        "list",
+        marketplace(),
+        vec![serde_json::json!("listing1"), serde_json::json!(5)],
+        Some(fixtures::pk1_key()),
+        HashMap::new(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        output.result(&abi).unwrap(),
+        abi::Value::ContractReference(b"listing1".to_vec())
+    );
+    assert_eq!(
+        field(&output.this(&abi).unwrap(), "last"),
+        abi::Value::ContractReference(b"listing1".to_vec())
+    );
+
+    let created = output.created_records(&abi).unwrap();
+    assert_eq!(created.len(), 1);
+    assert_eq!(created[0].contract, "Listing");
+    assert_eq!(
+        created[0].record,
+        abi::Value::StructValue(vec![
+            ("id".to_owned(), abi::Value::String("listing1".to_owned())),
+            (
+                "seller".to_owned(),
+                abi::Value::PublicKey(fixtures::pk1_key())
+            ),
+            ("price".to_owned(), abi::Value::UInt32(5)),
+        ])
+    );
+    assert_eq!(created[0].salts.len(), 3);
+    assert!(!created[0].salts.contains(&abi::salt::Salt::ZERO));
+    assert_eq!(
+        created[0].hash,
+        polylang_prover::hash_this(
+            abi.other_contract_types[0].clone(),
+            &created[0].record,
+            Some(&created[0].salts)
+        )
+        .unwrap()
+    );
+
+    polylang_prover::verify_created_records(&abi, &output.stack, &created).unwrap();
+
+    let mut tampered = created.clone();
+    tampered[0].hash[0] += 1;
+    assert!(polylang_prover::verify_created_records(&abi, &output.stack, &tampered).is_err());
+
+    let mut tampered = created;
+    tampered[0].salts[0] = abi::salt::Salt::ZERO;
+    assert!(polylang_prover::verify_created_records(&abi, &output.stack, &tampered).is_err());
+}
+
+#[test]
+fn test_new_records_have_different_salts() {
+    let (abi, output) = run(
+        CODE,
+        "Marketplace",
+        "listPair",
+        marketplace(),
+        vec![
+            serde_json::json!("listing1"),
+            serde_json::json!("listing2"),
+            serde_json::json!(5),
+        ],
+        Some(fixtures::pk1_key()),
+        HashMap::new(),
+    )
+    .unwrap();
+
+    let created = output.created_records(&abi).unwrap();
+    assert_eq!(created.len(), 2);
+    assert_eq!(
+        field(&created[1].record, "id"),
+        abi::Value::String("listing2".to_owned())
+    );
+    for salt in &created[0].salts {
+        assert!(!created[1].salts.contains(salt));
+    }
+    assert_ne!(created[0].salts[1], created[0].salts[2]);
+
+    polylang_prover::verify_created_records(&abi, &output.stack, &created).unwrap();
+}
+
+#[test_case::test_case("new Listing(id)", "incorrect number of arguments"; "wrong arguments")]
+#[test_case::test_case("new Auction(id, 5)", "contract Auction not found"; "unknown contract")]
+#[test_case::test_case("new Shop(id, 5)", "function Shop.constructor not found"; "no constructor")]
+#[test_case::test_case("new Account(id)", "it is @signed"; "signed contract")]
+fn test_new_errors(expression: &str, error: &str) {
+    let code = format!(
+        r#"
+        contract Listing {{
+            id: string;
+            price: u32;
+
+            constructor(id: string, price: u32) {{
+                this.id = id;
+                this.price = price;
+            }}
+        }}
+
+        @signed
+        contract Account {{
+            id: string;
+
+            constructor(id: string) {{
+                this.id = id;
+            }}
+        }}
+
+        contract Shop {{
+            id: string;
+
+            create(id: string) {{
+                {expression};
+            }}
+        }}
+        "#
+    );
+    let program = polylang::parse_program(&code).unwrap();
+    let err = polylang::compiler::compile(program, Some("Shop"), "create")
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains(error), "{err}");
+}
@@ -0,0 +1,117 @@
+use super::*;
+
+fn run_fn(
+    f: &str,
+    name: Option<&str>,
+    profile_name: Option<&str>,
+) -> Result<abi::Value, error::Error> {
+    let code = r#"
+        contract Account {
+            name?: string;
+            profile?: { name: string; };
+            result: string;
+
+            coalesce() {
+                this.result = this.name ?? 'anonymous';
+            }
+
+            optionalChain() {
+                this.result = this.profile?.name ?? 'no profile';
+            }
+
+            narrowIf() {
+                if (this.name != null) {
+                    this.result = this.name;
+                } else {
+                    this.result = 'else';
+                }
+            }
+
+            narrowElse() {
+                if (null == this.name) {
+                    this.result = 'then';
+                } else {
+                    this.result = this.name;
+                }
+            }
+
+            narrowAfterThrow() {
+                if (this.name == null) {
+                    throw error('name is required');
+                }
+
+                this.result = this.name;
+            }
+        }
+    "#;
+
+    let (abi, output) = run(
+        code,
+        "Account",
+        f,
+        serde_json::json!({
+            "name": name,
+            "profile": profile_name.map(|name| serde_json::json!({ "name": name })),
+            "result": "",
+        }),
+        vec![],
+        None,
+        HashMap::new(),
+    )?;
+
+    let this = output.this(&abi)?;
+    match this {
+        abi::Value::StructValue(fields) => {
+            let result = fields
+                .iter()
+                .find(|(k, _)| k == "result")
+                .unwrap()
+                .1
+                .clone();
+            Ok(result)
+        }
+        _ => panic!("unexpected value"),
+    }
+}
+
+fn string(s: &str) -> abi::Value {
+    abi::Value::String(s.to_owned())
+}
+
+#[test_case::test_case(None, "anonymous"; "null")]
+#[test_case::test_case(Some("bob"), "bob"; "not null")]
+fn test_coalesce(name: Option<&str>, expected: &str) {
+    assert_eq!(run_fn("coalesce", name, None).unwrap(), string(expected));
+}
+
+#[test_case::test_case(None, "no profile"; "null")]
+#[test_case::test_case(Some("bob"), "bob"; "not null")]
+fn test_optional_chain(profile_name: Option<&str>, expected: &str) {
+    assert_eq!(
+        run_fn("optionalChain", None, profile_name).unwrap(),
+        string(expected)
+    );
+}
+
+#[test_case::test_case(None, "else"; "null")]
+#[test_case::test_case(Some("bob"), "bob"; "not null")]
+fn test_narrow_if(name: Option<&str>, expected: &str) {
+    assert_eq!(run_fn("narrowIf", name, None).unwrap(), string(expected));
+}
+
+#[test_case::test_case(None, "then"; "null")]
+#[test_case::test_case(Some("bob"), "bob"; "not null")]
+fn test_narrow_else(name: Option<&str>, expected: &str) {
+    assert_eq!(run_fn("narrowElse", name, None).unwrap(), string(expected));
+}
+
+#[test]
+fn test_narrow_after_throw() {
+    assert_eq!(
+        run_fn("narrowAfterThrow", Some("bob"), None).unwrap(),
+        string("bob")
+    );
+
+    let err = run_fn("narrowAfterThrow", None, None).unwrap_err();
+    assert!(err.to_string().contains("name is required"), "{err}");
+}
@@ -0,0 +1,86 @@
+use super::*;
+
+const CODE: &str = r#"
+    const SHIFT: u32 = 4;
+
+    contract Account {
+        x: u32;
+
+        shiftLeftConstant(a: u32) {
+            this.x = a << SHIFT;
+        }
+
+        shiftLeft(a: u32, b: u32) {
+            this.x = a << b;
+        }
+
+        shiftRightConstant(a: u32) {
+            this.x = a >> SHIFT;
+        }
+
+        shiftRight(a: u32, b: u32) {
+            this.x = a >> b;
+        }
+
+        copyBack(a: u32) {
+            let t = this.x;
+            this.x = t;
+        }
+
+        copyArgument(a: u32) {
+            let t = this.x;
+            this.x = a;
+        }
+    }
+"#;
+
+fn run_fn(function: &str, args: Vec<serde_json::Value>) -> Result<(abi::Value, u32), error::Error> {
+    let (abi, output) = run(
+        CODE,
+        "Account",
+        function,
+        serde_json::json!({ "x": 0 }),
+        args,
+        None,
+        HashMap::new(),
+    )?;
+
+    Ok((output.this(&abi)?, output.cycle_count))
+}
+
+fn this(x: u32) -> abi::Value {
+    abi::Value::StructValue(vec![("x".to_owned(), abi::Value::UInt32(x))])
+}
+
+#[test_case::test_case("shiftLeftConstant", "shiftLeft", 1600; "shift left")]
+#[test_case::test_case("shiftRightConstant", "shiftRight", 6; "shift right")]
+fn test_constant_shift_uses_immediate(constant_fn: &str, fn_: &str, expected_x: u32) {
+    let (constant_this, constant_cycles) =
+        run_fn(constant_fn, vec![serde_json::json!(100)]).unwrap();
+    assert_eq!(constant_this, this(expected_x));
+
+    let (this_value, cycles) =
+        run_fn(fn_, vec![serde_json::json!(100), serde_json::json!(4)]).unwrap();
+    assert_eq!(this_value, this(expected_x));
+
+    assert!(
+        constant_cycles < cycles,
+        "constant: {constant_cycles}, argument: {cycles}"
+    );
+}
+
+#[test]
+fn test_copy_back_is_removed() {
+    let (copy_back_this, copy_back_cycles) =
+        run_fn("copyBack", vec![serde_json::json!(100)]).unwrap();
+    assert_eq!(copy_back_this, this(0));
+
+    let (copy_argument_this, copy_argument_cycles) =
+        run_fn("copyArgument", vec![serde_json::json!(100)]).unwrap();
+    assert_eq!(copy_argument_this, this(100));
+
+    assert!(
+        copy_back_cycles < copy_argument_cycles,
+        "copy back: {copy_back_cycles}, copy argument: {copy_argument_cycles}"
+    );
+}
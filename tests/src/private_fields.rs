@@ -0,0 +1,340 @@
+use super::*;
+
+const CODE: &str = r#"
+    contract Patient {
+        id: string;
+        @read
+        doctor: PublicKey;
+        @private
+        diagnosis: string;
+
+        constructor(id: string, doctor: PublicKey, diagnosis: string) {
+            this.id = id;
+            this.doctor = doctor;
+            this.diagnosis = diagnosis;
+        }
+
+        diagnose(diagnosis: string) {
+            this.diagnosis = diagnosis;
+        }
+
+        diagnoseUrgent(level: u32, diagnosis: string) {
+            let zero: u32 = 0;
+            let urgent = level > zero;
+            if (urgent) {
+                this.diagnosis = diagnosis;
+            }
+        }
+
+        diagnoseLevel(level: u32, diagnosis: string) {
+            this.diagnosis = diagnosis;
+        }
+    }
+"#;
+
+const SALTS: [abi::salt::Salt; 3] = [abi::salt::Salt::ZERO; 3];
+
+fn diagnose(ctx_public_key: Option<abi::publickey::Key>) -> (abi::Abi, polylang_prover::RunOutput) {
+    run(
+        CODE,
+        "Patient",
+        "diagnose",
+        serde_json::json!({
+            "id": "patient",
+            "doctor": fixtures::pk1(),
+            "diagnosis": "",
+        }),
+        vec![serde_json::json!("flu")],
+        ctx_public_key,
+        HashMap::new(),
+    )
+    .unwrap()
+}
+
+fn diagnosis(this: abi::Value) -> abi::Value {
+    match this {
+        abi::Value::StructValue(fields) => {
+            fields
+                .into_iter()
+                .find(|(name, _)| name == "diagnosis")
+                .unwrap()
+                .1
+        }
+        this => panic!("unexpected this: {this:?}"),
+    }
+}
+
+#[test]
+fn test_private_field_abi() {
+    let (abi, _) = diagnose(None);
+    assert_eq!(abi.private_fields, vec!["diagnosis".to_owned()]);
+    assert!(abi.private_read_auth_addr.is_some());
+}
+
+#[test]
+fn test_private_field_revealed_to_reader() {
+    let (abi, output) = diagnose(Some(fixtures::pk1_key()));
+
+    assert!(output.private_read_auth(&abi));
+    assert_eq!(
+        diagnosis(output.redacted_this(&abi, &SALTS).unwrap()),
+        abi::Value::String("flu".to_owned())
+    );
+    assert_eq!(
+        output.redacted_salts(&abi, &SALTS),
+        vec![Some(abi::salt::Salt::ZERO); 3]
+    );
+}
+
+#[test_case::test_case(Some(fixtures::pk2_key()); "other key")]
+#[test_case::test_case(None; "no key")]
+fn test_private_field_redacted(ctx_public_key: Option<abi::publickey::Key>) {
+    let (abi, output) = diagnose(ctx_public_key);
+
+    assert!(!output.private_read_auth(&abi));
+    let expected_hash = polylang_prover::hash_this(
+        abi::Type::String,
+        &abi::Value::String("flu".to_owned()),
+        Some(&[abi::salt::Salt::ZERO]),
+    )
+    .unwrap();
+    assert_eq!(
+        diagnosis(output.redacted_this(&abi, &SALTS).unwrap()),
+        abi::Value::Hash(expected_hash)
+    );
+
+    // The other fields are not redacted, and neither is `this`
+    match output.redacted_this(&abi, &SALTS).unwrap() {
+        abi::Value::StructValue(fields) => assert!(fields.contains(&(
+            "doctor".to_owned(),
+            abi::Value::PublicKey(fixtures::pk1_key())
+        ))),
+        this => panic!("unexpected this: {this:?}"),
+    }
+    assert_eq!(
+        diagnosis(output.this(&abi).unwrap()),
+        abi::Value::String("flu".to_owned())
+    );
+
+    // Without its salt, the hash of the private field can't be checked against guesses
+    assert_eq!(
+        output.redacted_salts(&abi, &SALTS),
+        vec![
+            Some(abi::salt::Salt::ZERO),
+            Some(abi::salt::Salt::ZERO),
+            None
+        ]
+    );
+}
+
+// The result of the read check is zeroed first, the memory it is allocated in
+// could still hold a true boolean or a u32 1 from the function body
+#[test_case::test_case("diagnoseUrgent"; "boolean")]
+#[test_case::test_case("diagnoseLevel"; "u32")]
+fn test_private_field_redacted_after_true_value(function: &str) {
+    let (abi, output) = run(
+        CODE,
+        "Patient",
+        function,
+        serde_json::json!({
+            "id": "patient",
+            "doctor": fixtures::pk1(),
+            "diagnosis": "",
+        }),
+        vec![serde_json::json!(1), serde_json::json!("flu")],
+        Some(fixtures::pk2_key()),
+        HashMap::new(),
+    )
+    .unwrap();
+
+    assert!(!output.private_read_auth(&abi));
+    assert!(matches!(
+        diagnosis(output.redacted_this(&abi, &SALTS).unwrap()),
+        abi::Value::Hash(_)
+    ));
+}
+
+fn prove_patient(function: &str, request: serde_json::Value) -> serde_json::Value {
+    let program = polylang::parse_program(CODE).unwrap();
+    let (miden_code, abi) =
+        polylang::compiler::compile(program, Some("Patient"), function).unwrap();
+
+    prove_request(&miden_code, &abi, request, None).unwrap()
+}
+
+fn hashes(hashes: &serde_json::Value) -> Vec<String> {
+    hashes
+        .as_array()
+        .unwrap()
+        .iter()
+        .flat_map(|hash| hash.as_array().unwrap())
+        .map(|x| {
+            x.as_str()
+                .map(str::to_owned)
+                .unwrap_or_else(|| x.to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn test_private_field_created_and_called() {
+    // The creator can't read the private field, but gets the salts generated for it
+    let created = prove_patient(
+        "constructor",
+        serde_json::json!({
+            "args": ["patient", fixtures::pk1(), "flu"],
+        }),
+    );
+    let salts = created["new"]["salts"].as_array().unwrap();
+    assert_eq!(salts.len(), 3);
+    assert!(salts.iter().all(|salt| salt.is_string()), "{salts:?}");
+    assert!(created["new"]["this"]["diagnosis"] != "flu");
+
+    // The next call with the stored salts has the hashes of the created record
+    let called = prove_patient(
+        "diagnose",
+        serde_json::json!({
+            "this": { "id": "patient", "doctor": fixtures::pk1(), "diagnosis": "flu" },
+            "thisSalts": salts,
+            "args": ["cold"],
+        }),
+    );
+    assert_eq!(
+        hashes(&called["old"]["hashes"]),
+        hashes(&created["new"]["hashes"])
+    );
+}
+
+const CLINIC_CODE: &str = r#"
+    contract Patient {
+        id: string;
+        @read
+        doctor: PublicKey;
+        @private
+        diagnosis: string;
+
+        constructor(id: string, doctor: PublicKey, diagnosis: string) {
+            this.id = id;
+            this.doctor = doctor;
+            this.diagnosis = diagnosis;
+        }
+
+        @call
+        rediagnose(diagnosis: string) {
+            this.diagnosis = diagnosis;
+        }
+    }
+
+    contract Clinic {
+        id: string;
+        patient: Patient;
+
+        @call
+        rediagnose(diagnosis: string) {
+            this.patient.rediagnose(diagnosis);
+        }
+
+        @call
+        admit(id: string, doctor: PublicKey, diagnosis: string) {
+            new Patient(id, doctor, diagnosis);
+        }
+    }
+"#;
+
+fn clinic() -> serde_json::Value {
+    serde_json::json!({ "id": "clinic", "patient": { "id": "patient" } })
+}
+
+fn patient() -> serde_json::Value {
+    serde_json::json!({ "id": "patient", "doctor": fixtures::pk1(), "diagnosis": "flu" })
+}
+
+#[test_case::test_case(fixtures::pk1_key(), true; "doctor")]
+#[test_case::test_case(fixtures::pk2_key(), false; "other key")]
+fn test_updated_record_private_read_auth(ctx_public_key: abi::publickey::Key, readable: bool) {
+    let (abi, output) = run(
+        CLINIC_CODE,
+        "Clinic",
+        "rediagnose",
+        clinic(),
+        vec![serde_json::json!("cold")],
+        Some(ctx_public_key),
+        HashMap::from([("Patient".to_owned(), vec![patient()])]),
+    )
+    .unwrap();
+
+    assert_eq!(abi.record_updates[0].private_fields, vec!["diagnosis"]);
+    let updates = output.record_updates(&abi).unwrap();
+    assert_eq!(updates.len(), 1);
+    assert_eq!(updates[0].private_read_auth, readable);
+}
+
+#[test]
+fn test_updated_record_private_field_redacted() {
+    let program = polylang::parse_program(CLINIC_CODE).unwrap();
+    let (miden_code, abi) =
+        polylang::compiler::compile(program, Some("Clinic"), "rediagnose").unwrap();
+
+    let output = prove_request(
+        &miden_code,
+        &abi,
+        serde_json::json!({
+            "ctxPublicKey": fixtures::pk2(),
+            "this": clinic(),
+            "thisSalts": SALTS[..2],
+            "args": ["cold"],
+            "otherRecords": { "Patient": [[patient(), SALTS]] },
+        }),
+        None,
+    )
+    .unwrap();
+
+    let record = &output["recordUpdates"][0]["record"];
+    assert_eq!(record["id"], "patient");
+    assert!(!record["diagnosis"].is_null());
+    assert!(record["diagnosis"] != "cold", "{record}");
+}
+
+#[test_case::test_case(fixtures::pk1_key(), true; "doctor")]
+#[test_case::test_case(fixtures::pk2_key(), false; "other key")]
+fn test_created_record_private_field_redacted(ctx_public_key: abi::publickey::Key, readable: bool) {
+    let (abi, output) = run(
+        CLINIC_CODE,
+        "Clinic",
+        "admit",
+        clinic(),
+        vec![
+            serde_json::json!("patient"),
+            fixtures::pk1(),
+            serde_json::json!("flu"),
+        ],
+        Some(ctx_public_key),
+        HashMap::new(),
+    )
+    .unwrap();
+
+    assert_eq!(abi.created_records[0].private_fields, vec!["diagnosis"]);
+    let created = output.created_records(&abi).unwrap();
+    assert_eq!(created.len(), 1);
+    assert_eq!(created[0].private_read_auth, readable);
+
+    let salts = created[0].redacted_salts(&abi).unwrap();
+    assert_eq!(
+        salts[..2],
+        [Some(created[0].salts[0]), Some(created[0].salts[1])]
+    );
+    let record = created[0].redacted_record(&abi).unwrap();
+    if readable {
+        assert_eq!(salts[2], Some(created[0].salts[2]));
+        assert_eq!(diagnosis(record), abi::Value::String("flu".to_owned()));
+    } else {
+        assert_eq!(salts[2], None);
+        let expected_hash = polylang_prover::hash_this(
+            abi::Type::String,
+            &abi::Value::String("flu".to_owned()),
+            Some(&[created[0].salts[2]]),
+        )
+        .unwrap();
+        assert_eq!(diagnosis(record), abi::Value::Hash(expected_hash));
+    }
+}
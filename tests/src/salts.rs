@@ -0,0 +1,241 @@
+use abi::salt::Salt;
+
+use super::*;
+
+const CODE: &str = r#"
+    contract Admin {
+        id: string;
+        @delegate
+        pk: PublicKey;
+    }
+
+    contract Account {
+        id: string;
+        name: string;
+        manager: Admin;
+
+        @call(manager.pk)
+        setName(name: string) {
+            this.name = name;
+        }
+    }
+"#;
+
+fn set_name(
+    this_salts: Vec<Salt>,
+    admin_salts: Vec<Salt>,
+) -> Result<(polylang_prover::Inputs, polylang_prover::RunOutput), error::Error> {
+    let program = polylang::parse_program(CODE).unwrap();
+    let (miden_code, abi) = polylang::compiler::compile(program, Some("Account"), "setName")?;
+    let program = polylang_prover::compile_program(&abi, &miden_code)?;
+
+    let inputs = polylang_prover::Inputs::new(
+        abi,
+        Some(fixtures::pk1_key()),
+        this_salts,
+        serde_json::json!({
+            "id": "account",
+            "name": "old",
+            "manager": { "id": "admin" },
+        }),
+        vec![serde_json::json!("new")],
+        HashMap::from([(
+            "Admin".to_owned(),
+            vec![(
+                serde_json::json!({ "id": "admin", "pk": fixtures::pk1() }),
+                admin_salts,
+            )],
+        )]),
+    )?;
+
+    let (output, _) = polylang_prover::run(&program, &inputs)?;
+    Ok((inputs, output))
+}
+
+#[test]
+fn test_generate_salt() {
+    let salts = (0..8)
+        .map(|_| polylang_prover::generate_salt())
+        .collect::<Vec<_>>();
+
+    for (i, salt) in salts.iter().enumerate() {
+        assert_ne!(*salt, Salt::ZERO);
+        assert!(!salts[..i].contains(salt));
+        // Only salts in the field can be parsed
+        assert_eq!(salt.to_string().parse::<Salt>().unwrap(), *salt);
+    }
+}
+
+#[test]
+fn test_salt_json() {
+    let salt = polylang_prover::generate_salt();
+    let json = serde_json::to_value(salt).unwrap();
+    assert_eq!(json, serde_json::Value::String(salt.to_string()));
+    assert_eq!(serde_json::from_value::<Salt>(json).unwrap(), salt);
+
+    assert!("ff".repeat(32).parse::<Salt>().is_err());
+    assert!("00".repeat(31).parse::<Salt>().is_err());
+}
+
+#[test]
+fn test_salted_hash_is_stable() {
+    let value = abi::Value::String("secret".to_owned());
+    let salt = polylang_prover::generate_salt();
+
+    let hash = polylang_prover::hash_this(abi::Type::String, &value, Some(&[salt])).unwrap();
+    assert_eq!(
+        polylang_prover::hash_this(abi::Type::String, &value, Some(&[salt])).unwrap(),
+        hash
+    );
+    assert_ne!(
+        polylang_prover::hash_this(
+            abi::Type::String,
+            &value,
+            Some(&[polylang_prover::generate_salt()])
+        )
+        .unwrap(),
+        hash
+    );
+}
+
+#[test]
+fn test_generated_salts() {
+    let this_salts = (0..3)
+        .map(|_| polylang_prover::generate_salt())
+        .collect::<Vec<_>>();
+    let admin_salts = (0..2)
+        .map(|_| polylang_prover::generate_salt())
+        .collect::<Vec<_>>();
+
+    // The program checks the salted hashes of `this` and of the admin record
+    let (inputs, output) = set_name(this_salts.clone(), admin_salts.clone()).unwrap();
+    assert_eq!(
+        output.this(&inputs.abi).unwrap(),
+        abi::Value::StructValue(vec![
+            ("id".to_owned(), abi::Value::String("account".to_owned())),
+            ("name".to_owned(), abi::Value::String("new".to_owned())),
+            (
+                "manager".to_owned(),
+                abi::Value::ContractReference(b"admin".to_vec())
+            ),
+        ])
+    );
+    assert_eq!(
+        inputs.this_field_hashes[1],
+        polylang_prover::hash_this(
+            abi::Type::String,
+            &abi::Value::String("old".to_owned()),
+            Some(&[this_salts[1]])
+        )
+        .unwrap()
+    );
+
+    // The hashes stay the same across calls with the same salts
+    let (next_inputs, _) = set_name(this_salts, admin_salts.clone()).unwrap();
+    assert_eq!(next_inputs.this_field_hashes, inputs.this_field_hashes);
+
+    let (other_inputs, _) = set_name(
+        polylang_prover::generate_salts(&inputs.abi).unwrap(),
+        admin_salts,
+    )
+    .unwrap();
+    for (hash, other_hash) in inputs
+        .this_field_hashes
+        .iter()
+        .zip(&other_inputs.this_field_hashes)
+    {
+        assert_ne!(hash, other_hash);
+    }
+}
+
+#[test]
+fn test_wrong_salts() {
+    let err = set_name(vec![Salt::ZERO; 2], vec![Salt::ZERO; 2])
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("expected 3 salts"), "{err}");
+}
+
+#[test]
+fn test_constructor_abi() {
+    let code = r#"
+        contract Account {
+            id: string;
+            name: string;
+
+            constructor(id: string) {
+                this.id = id;
+            }
+
+            setName(name: string) {
+                this.name = name;
+            }
+        }
+    "#;
+
+    let compile = |function_name| {
+        let program = polylang::parse_program(code).unwrap();
+        polylang::compiler::compile(program, Some("Account"), function_name)
+            .unwrap()
+            .1
+    };
+
+    // New salts are only generated for the constructor, which creates the record
+    assert!(compile("constructor").constructor);
+    assert!(!compile("setName").constructor);
+}
+
+#[test]
+fn test_zero_salts() {
+    let program = polylang::parse_program(CODE).unwrap();
+    let (_, abi) = polylang::compiler::compile(program, Some("Account"), "setName").unwrap();
+    let this_salts = polylang_prover::zero_salts(&abi).unwrap();
+    assert_eq!(this_salts, vec![Salt::ZERO; 3]);
+
+    // Records without salts keep their hashes
+    let (inputs, _) = set_name(this_salts, vec![Salt::ZERO; 2]).unwrap();
+    assert_eq!(
+        inputs.this_field_hashes[1],
+        polylang_prover::hash_this(
+            abi::Type::String,
+            &abi::Value::String("old".to_owned()),
+            Some(&[Salt::ZERO])
+        )
+        .unwrap()
+    );
+}
+
+#[test]
+fn test_private_fields_require_salts() {
+    let code = r#"
+        contract Patient {
+            id: string;
+            @private
+            diagnosis: string;
+
+            diagnose(diagnosis: string) {
+                this.diagnosis = diagnosis;
+            }
+        }
+    "#;
+
+    let program = polylang::parse_program(code).unwrap();
+    let (miden_code, abi) =
+        polylang::compiler::compile(program, Some("Patient"), "diagnose").unwrap();
+
+    let err = prove_request(
+        &miden_code,
+        &abi,
+        serde_json::json!({
+            "this": { "id": "patient", "diagnosis": "" },
+            "args": ["flu"],
+        }),
+        None,
+    )
+    .unwrap_err()
+    .to_string();
+    assert!(
+        err.contains("thisSalts is required for records with private fields"),
+        "{err}"
+    );
+}
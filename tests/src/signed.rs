@@ -0,0 +1,174 @@
+use super::*;
+
+const CODE: &str = r#"
+    @signed
+    contract Account {
+        id: string;
+        balance: u32;
+
+        deposit(amount: u32) {
+            this.balance = this.balance + amount;
+        }
+    }
+"#;
+
+fn secret_key() -> libsecp256k1::SecretKey {
+    libsecp256k1::SecretKey::parse(&[7; 32]).unwrap()
+}
+
+fn public_key(secret_key: &libsecp256k1::SecretKey) -> abi::publickey::Key {
+    abi::publickey::Key::from_secp256k1_bytes(
+        &libsecp256k1::PublicKey::from_secret_key(secret_key).serialize(),
+    )
+    .unwrap()
+}
+
+fn sign(message: &[u64; 4]) -> Vec<u8> {
+    let message = libsecp256k1::Message::parse(&polylang_prover::call_message_bytes(message));
+    libsecp256k1::sign(&message, &secret_key())
+        .0
+        .serialize()
+        .to_vec()
+}
+
+fn run_deposit(
+    ctx_public_key: Option<abi::publickey::Key>,
+    nonce: u64,
+) -> Result<(abi::Abi, polylang_prover::RunOutput), error::Error> {
+    let program = polylang::parse_program(CODE).unwrap();
+    let (miden_code, abi) = polylang::compiler::compile(program, Some("Account"), "deposit")?;
+
+    let program = polylang_prover::compile_program(&abi, &miden_code)?;
+    let inputs = polylang_prover::Inputs::new(
+        abi.clone(),
+        ctx_public_key,
+        vec![abi::salt::Salt::ZERO; 2],
+        serde_json::json!({ "id": "a", "balance": 1 }),
+        vec![serde_json::json!(2)],
+        HashMap::new(),
+    )?
+    .with_call_signature(nonce, None);
+    let (output, _) = polylang_prover::run(&program, &inputs)?;
+
+    Ok((abi, output))
+}
+
+#[test]
+fn test_signed_call_outputs_call_message() {
+    let (abi, output) = run_deposit(Some(public_key(&secret_key())), 1).unwrap();
+    assert!(abi.signed);
+
+    let message = output.call_message().unwrap();
+    assert!(polylang_prover::verify_call_signature(
+        &abi,
+        &output.stack,
+        &public_key(&secret_key()),
+        &sign(&message),
+    )
+    .is_ok());
+
+    // The nonce is part of the message
+    let (_, other_output) = run_deposit(Some(public_key(&secret_key())), 2).unwrap();
+    assert_ne!(other_output.call_message().unwrap(), message);
+    assert!(polylang_prover::verify_call_signature(
+        &abi,
+        &other_output.stack,
+        &public_key(&secret_key()),
+        &sign(&message),
+    )
+    .unwrap_err()
+    .to_string()
+    .contains("invalid call signature"));
+}
+
+#[test]
+fn test_signed_call_must_be_made_with_the_signing_key() {
+    let (abi, output) = run_deposit(Some(public_key(&secret_key())), 1).unwrap();
+    let other_key = public_key(&libsecp256k1::SecretKey::parse(&[8; 32]).unwrap());
+
+    assert!(polylang_prover::verify_call_signature(
+        &abi,
+        &output.stack,
+        &other_key,
+        &sign(&output.call_message().unwrap()),
+    )
+    .unwrap_err()
+    .to_string()
+    .contains("the call was made with a different public key"));
+}
+
+#[test]
+fn test_signed_call_requires_public_key() {
+    let err = run_deposit(None, 1).unwrap_err().to_string();
+    assert!(
+        err.contains("A signature is required to call this function"),
+        "{err}"
+    );
+}
+
+#[test]
+fn test_unsigned_call_has_no_call_message() {
+    let (abi, output) = run(
+        &CODE.replace("@signed", ""),
+        "Account",
+        "deposit",
+        serde_json::json!({ "id": "a", "balance": 1 }),
+        vec![serde_json::json!(2)],
+        None,
+        HashMap::new(),
+    )
+    .unwrap();
+
+    assert!(!abi.signed);
+    assert_eq!(output.call_message(), None);
+}
+
+#[test]
+fn test_replayed_call_is_rejected() {
+    let code = r#"
+        @signed
+        contract Account {
+            balance: u32;
+            nonce: u64;
+
+            deposit(amount: u32) {
+                if (ctx.nonce <= this.nonce) throw error('nonce already used');
+                this.nonce = ctx.nonce;
+                this.balance = this.balance + amount;
+            }
+        }
+    "#;
+
+    let deposit = |this: serde_json::Value, nonce: u64| {
+        let program = polylang::parse_program(code).unwrap();
+        let (miden_code, abi) = polylang::compiler::compile(program, Some("Account"), "deposit")?;
+        let program = polylang_prover::compile_program(&abi, &miden_code)?;
+        let inputs = polylang_prover::Inputs::new(
+            abi.clone(),
+            Some(public_key(&secret_key())),
+            vec![abi::salt::Salt::ZERO; 2],
+            this,
+            vec![serde_json::json!(2)],
+            HashMap::new(),
+        )?
+        .with_call_signature(nonce, None);
+        let (output, _) = polylang_prover::run(&program, &inputs)?;
+
+        output.this(&abi)
+    };
+
+    let this = deposit(serde_json::json!({ "balance": 1, "nonce": 0 }), 1).unwrap();
+    assert_eq!(
+        this,
+        abi::Value::StructValue(vec![
+            ("balance".to_owned(), abi::Value::UInt32(3)),
+            ("nonce".to_owned(), abi::Value::UInt64(1)),
+        ])
+    );
+
+    // Replaying the call with the same nonce on the new record fails
+    let err = deposit(serde_json::json!({ "balance": 3, "nonce": 1 }), 1)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("nonce already used"), "{err}");
+}
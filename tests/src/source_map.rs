@@ -0,0 +1,105 @@
+use super::*;
+
+const CODE: &str = r#"
+    contract Account {
+        balance: u32;
+
+        withdraw(amt: u32) {
+            assert(this.balance >= amt, 'insufficient balance');
+            this.balance = this.balance - amt;
+        }
+
+        withdrawUnchecked(amt: u32) {
+            if (amt != this.balance) {
+                this.balance = this.balance - amt;
+            }
+        }
+
+        withdrawAll(amt: u32) {
+            while (this.balance != amt) {
+                this.balance = this.balance - amt;
+            }
+        }
+    }
+"#;
+
+fn compile_debug(function: &str) -> (String, abi::Abi) {
+    let program = polylang::parse_program(CODE).unwrap();
+    polylang::compiler::compile_debug(program, Some("Account"), function).unwrap()
+}
+
+fn run_fn(function: &str, amt: u32) -> Result<abi::Value, error::Error> {
+    let (miden_code, abi) = compile_debug(function);
+    let program = polylang_prover::compile_program(&abi, &miden_code).unwrap();
+    let inputs = polylang_prover::Inputs::new(
+        abi.clone(),
+        None,
+        vec![abi::salt::Salt::ZERO],
+        serde_json::json!({ "balance": 3 }),
+        vec![serde_json::json!(amt)],
+        HashMap::new(),
+    )?;
+
+    let (output, _) = polylang_prover::run(&program, &inputs).map_err(|e| e.add_source(CODE))?;
+    output.this(&abi)
+}
+
+#[test]
+fn test_source_map_covers_statements() {
+    let (_, abi) = compile_debug("withdraw");
+
+    let statements = abi
+        .source_map
+        .iter()
+        .map(|span| &CODE[span.start..=span.end])
+        .collect::<Vec<_>>();
+    assert_eq!(
+        statements,
+        [
+            "assert(this.balance >= amt, 'insufficient balance');",
+            "this.balance = this.balance - amt;",
+        ]
+    );
+}
+
+#[test]
+fn test_no_markers_without_debug() {
+    let program = polylang::parse_program(CODE).unwrap();
+    let (miden_code, abi) =
+        polylang::compiler::compile(program, Some("Account"), "withdraw").unwrap();
+
+    assert!(abi.source_map.is_empty());
+    assert!(!miden_code.contains("mem_store.9\n"), "{miden_code}");
+}
+
+#[test]
+fn test_failed_assertion_points_at_statement() {
+    let err = run_fn("withdraw", 5).unwrap_err().to_string();
+
+    assert!(err.starts_with("insufficient balance: "), "{err}");
+    assert!(
+        err.contains("source `assert(this.balance >= amt, 'insufficient balance');` at line 6:"),
+        "{err}"
+    );
+}
+
+#[test_case::test_case("withdrawUnchecked", 5, 12; "in if")]
+#[test_case::test_case("withdrawAll", 2, 18; "in loop")]
+fn test_trap_points_at_statement(function: &str, amt: u32, line: usize) {
+    let err = run_fn(function, amt).unwrap_err().to_string();
+
+    assert!(
+        err.contains(&format!(
+            "source `this.balance = this.balance - amt;` at line {line}:"
+        )),
+        "{err}"
+    );
+}
+
+#[test]
+fn test_no_error_without_failure() {
+    assert_eq!(
+        run_fn("withdraw", 1).unwrap(),
+        abi::Value::StructValue(vec![("balance".to_owned(), abi::Value::UInt32(2))])
+    );
+}
@@ -0,0 +1,51 @@
+use super::*;
+
+const CODE: &str = r#"
+    contract Account {
+        balance: u32;
+
+        deposit(amt: u32) {
+            this.balance = this.balance + amt;
+        }
+    }
+"#;
+
+fn compile(std_version: abi::StdVersion) -> Result<(String, abi::Abi), error::Error> {
+    let program = polylang::parse_program(CODE).unwrap();
+    polylang::compiler::compile_for_version(program, Some("Account"), "deposit", std_version)
+}
+
+#[test]
+fn test_unknown_version_is_rejected() {
+    let err = "0.8.0".parse::<abi::StdVersion>().unwrap_err();
+    assert!(
+        err.to_string().contains("invalid std version: 0.8.0"),
+        "{err}"
+    );
+}
+
+#[test]
+fn test_default_version_is_proven() {
+    let (miden_code, abi) = compile(abi::StdVersion::default()).unwrap();
+
+    assert_eq!(abi.std_version, Some(abi::StdVersion::V0_7_0));
+    assert!(polylang_prover::compile_program(&abi, &miden_code).is_ok());
+}
+
+#[test]
+fn test_prover_rejects_unsupported_version() {
+    let (miden_code, mut abi) = compile(abi::StdVersion::default()).unwrap();
+    abi.std_version = Some(abi::StdVersion::V0_6_1);
+
+    let err = polylang_prover::compile_program(&abi, &miden_code).unwrap_err();
+    assert!(
+        err.to_string().contains("unsupported std version 0.6.1"),
+        "{err}"
+    );
+}
+
+#[test]
+fn test_old_versions_are_not_compiled() {
+    let err = compile(abi::StdVersion::V0_5_0).unwrap_err();
+    assert!(err.to_string().contains("0.5.0"), "{err}");
+}
@@ -0,0 +1,237 @@
+use super::*;
+
+use polylang_prover::stdlib::{KeyPair, StdlibAdvice};
+
+const CODE: &str = r#"
+    contract Counter {
+        value: u64;
+        flags: u64;
+
+        wrap(amt: u64) {
+            this.value = this.value.wrappingAdd(amt);
+        }
+
+        mix(amt: u64, shift: u32) {
+            this.value = this.value.xor(amt).rotl(shift);
+            this.flags = this.flags.max(amt);
+        }
+
+        mulHigh() {
+            this.value = this.value.mulHigh(this.value);
+            this.flags = this.flags.shl(this.flags.leadingZeros());
+        }
+    }
+"#;
+
+const ADVICE_CODE: &str = r#"
+    contract Stdlib {
+        smtGet() {
+            let root = readAdviceHash();
+            let key = readAdviceHash();
+            let value = readAdviceHash();
+            assert(smtGet(root, key) == value, 'wrong SMT value');
+        }
+
+        smtInsert() {
+            let root = readAdviceHash();
+            let key = readAdviceHash();
+            let value = readAdviceHash();
+            let newRoot = readAdviceHash();
+            assert(smtInsert(root, key, value) == newRoot, 'wrong SMT root');
+        }
+
+        mmrGet(pos: u32) {
+            let peaks = readAdviceHash();
+            let leaf = readAdviceHash();
+            assert(mmrGet(peaks, pos) == leaf, 'wrong MMR leaf');
+        }
+
+        mmrAdd() {
+            let peaks = readAdviceHash();
+            let leaf = readAdviceHash();
+            let newPeaks = readAdviceHash();
+            assert(mmrAdd(peaks, leaf) == newPeaks, 'wrong MMR peaks');
+        }
+
+        falconVerify() {
+            let message = readAdviceHash();
+            let publicKey = readAdviceHash();
+            falconVerify(message, publicKey);
+        }
+    }
+"#;
+
+fn run_fn(function: &str, args: Vec<serde_json::Value>) -> Result<abi::Value, error::Error> {
+    let (abi, output) = run(
+        CODE,
+        "Counter",
+        function,
+        serde_json::json!({ "value": u64::MAX, "flags": 5 }),
+        args,
+        None,
+        HashMap::new(),
+    )?;
+
+    output.this(&abi)
+}
+
+fn run_with_advice(
+    function: &str,
+    args: Vec<serde_json::Value>,
+    stdlib_advice: StdlibAdvice,
+) -> Result<(), error::Error> {
+    let program = polylang::parse_program(ADVICE_CODE).unwrap();
+    let (miden_code, abi) = polylang::compiler::compile(program, Some("Stdlib"), function)?;
+    let program = polylang_prover::compile_program(&abi, &miden_code).unwrap();
+    let inputs = polylang_prover::Inputs::new(
+        abi,
+        None,
+        vec![],
+        serde_json::json!({}),
+        args,
+        HashMap::new(),
+    )?
+    .with_stdlib_advice(stdlib_advice);
+
+    polylang_prover::run(&program, &inputs).map_err(|e| e.add_source(ADVICE_CODE))?;
+    Ok(())
+}
+
+fn compile_main(body: &str) -> Result<(String, abi::Abi), error::Error> {
+    let code = format!("function main(a: u64, b: u32) {{ {body} }}");
+    let program = polylang::parse_program(&code).unwrap();
+    polylang::compiler::compile(program, None, "main")
+}
+
+fn word(x: u64) -> [u64; 4] {
+    [x, x + 1, x + 2, x + 3]
+}
+
+#[test]
+fn test_uint64_methods() {
+    assert_eq!(
+        run_fn("wrap", vec![serde_json::json!(3)]).unwrap(),
+        abi::Value::StructValue(vec![
+            ("value".to_owned(), abi::Value::UInt64(2)),
+            ("flags".to_owned(), abi::Value::UInt64(5)),
+        ])
+    );
+
+    assert_eq!(
+        run_fn(
+            "mix",
+            vec![serde_json::json!(u64::MAX), serde_json::json!(4)]
+        )
+        .unwrap(),
+        abi::Value::StructValue(vec![
+            ("value".to_owned(), abi::Value::UInt64(0)),
+            ("flags".to_owned(), abi::Value::UInt64(u64::MAX)),
+        ])
+    );
+
+    // (2^64 - 1)^2 = (2^64 - 2) * 2^64 + 1, and 5 has 61 leading zeros
+    assert_eq!(
+        run_fn("mulHigh", vec![]).unwrap(),
+        abi::Value::StructValue(vec![
+            ("value".to_owned(), abi::Value::UInt64(u64::MAX - 1)),
+            ("flags".to_owned(), abi::Value::UInt64(5 << 61)),
+        ])
+    );
+}
+
+#[test]
+fn test_smt() {
+    let entries = [(word(1), word(10)), (word(2), word(20))];
+
+    let mut advice = StdlibAdvice::default();
+    let root = advice.add_smt(&entries).unwrap();
+    for w in [root, word(2), word(20)] {
+        advice.push_word(w);
+    }
+    run_with_advice("smtGet", vec![], advice).unwrap();
+
+    let mut advice = StdlibAdvice::default();
+    let root = advice.add_smt(&entries).unwrap();
+    for w in [root, word(2), word(10)] {
+        advice.push_word(w);
+    }
+    let err = run_with_advice("smtGet", vec![], advice).unwrap_err();
+    assert!(err.to_string().contains("wrong SMT value"), "{err}");
+
+    let mut advice = StdlibAdvice::default();
+    let root = advice.add_smt(&entries).unwrap();
+    let new_root = StdlibAdvice::default()
+        .add_smt(&[entries[0], entries[1], (word(3), word(30))])
+        .unwrap();
+    for w in [root, word(3), word(30), new_root] {
+        advice.push_word(w);
+    }
+    run_with_advice("smtInsert", vec![], advice).unwrap();
+}
+
+#[test]
+fn test_mmr() {
+    let leaves = [word(1), word(2), word(3)];
+
+    for (pos, leaf) in leaves.iter().enumerate() {
+        let mut advice = StdlibAdvice::default();
+        let peaks = advice.add_mmr(&leaves);
+        advice.push_word(peaks);
+        advice.push_word(*leaf);
+        run_with_advice("mmrGet", vec![serde_json::json!(pos)], advice).unwrap();
+    }
+
+    let mut advice = StdlibAdvice::default();
+    let peaks = advice.add_mmr(&leaves);
+    let new_peaks = StdlibAdvice::default().add_mmr(&[leaves[0], leaves[1], leaves[2], word(4)]);
+    for w in [peaks, word(4), new_peaks] {
+        advice.push_word(w);
+    }
+    run_with_advice("mmrAdd", vec![], advice).unwrap();
+}
+
+#[test]
+fn test_falcon_verify() {
+    let key_pair = KeyPair::new().unwrap();
+    let other_key_pair = KeyPair::new().unwrap();
+
+    let mut advice = StdlibAdvice::default();
+    let public_key = advice.add_falcon_key_pair(&key_pair);
+    advice.push_word(word(1));
+    advice.push_word(public_key);
+    run_with_advice("falconVerify", vec![], advice).unwrap();
+
+    // There is no key pair to sign the message with for the other public key
+    let mut advice = StdlibAdvice::default();
+    advice.add_falcon_key_pair(&key_pair);
+    let other_public_key = StdlibAdvice::default().add_falcon_key_pair(&other_key_pair);
+    advice.push_word(word(1));
+    advice.push_word(other_public_key);
+    assert!(run_with_advice("falconVerify", vec![], advice).is_err());
+}
+
+#[test]
+fn test_stdlib_modules_are_imported() {
+    let (miden_code, _) = compile_main("let x = a.isZero();").unwrap();
+    assert!(!miden_code.contains("use.std::collections"), "{miden_code}");
+
+    let (miden_code, _) = compile_main(
+        "let root = readAdviceHash(); let key = readAdviceHash(); let value = smtGet(root, key); falconVerify(key, mmrAdd(root, value));",
+    )
+    .unwrap();
+    for module in [
+        "use.std::crypto::dsa::rpo_falcon512",
+        "use.std::collections::smt",
+        "use.std::collections::mmr",
+    ] {
+        assert!(miden_code.contains(module), "{miden_code}");
+    }
+}
+
+#[test]
+fn test_stdlib_bindings_are_type_checked() {
+    let err = |body| compile_main(body).unwrap_err().to_string();
+    assert!(err("let x = a.shl(a);").contains("expected to be PrimitiveType(UInt32)"));
+    assert!(err("let x = smtGet(readAdviceHash(), b);").contains("expected to be Hash"));
+    assert!(err("let x = a.min();").contains("expected 2"));
+}
@@ -0,0 +1,247 @@
+use sha2::{Digest, Sha256};
+
+use super::*;
+
+const CODE: &str = r#"
+    contract Approval {
+        id: string;
+        approved: boolean;
+
+        approve(owner: PublicKey, message: bytes, signature: bytes) {
+            this.approved = verifySignature(owner, message, signature);
+        }
+    }
+"#;
+
+const MESSAGE: &[u8] = b"transfer 100 to bob";
+
+fn secret_key(seed: u8) -> libsecp256k1::SecretKey {
+    libsecp256k1::SecretKey::parse(&[seed; 32]).unwrap()
+}
+
+fn public_key(secret_key: &libsecp256k1::SecretKey) -> abi::publickey::Key {
+    abi::publickey::Key::from_secp256k1_bytes(
+        &libsecp256k1::PublicKey::from_secret_key(secret_key).serialize(),
+    )
+    .unwrap()
+}
+
+fn sign(secret_key: &libsecp256k1::SecretKey, message: &[u8]) -> Vec<u8> {
+    let message = libsecp256k1::Message::parse(&Sha256::digest(message).into());
+    libsecp256k1::sign(&message, secret_key)
+        .0
+        .serialize()
+        .to_vec()
+}
+
+fn approve(
+    owner: &abi::publickey::Key,
+    signature: &[u8],
+) -> Result<(abi::Abi, polylang_prover::RunOutput), error::Error> {
+    run(
+        CODE,
+        "Approval",
+        "approve",
+        serde_json::json!({ "id": "a", "approved": false }),
+        vec![
+            serde_json::to_value(owner).unwrap(),
+            serde_json::json!(hex::encode(MESSAGE)),
+            serde_json::json!(hex::encode(signature)),
+        ],
+        None,
+        HashMap::new(),
+    )
+}
+
+fn approved(abi: &abi::Abi, output: &polylang_prover::RunOutput) -> bool {
+    match output.this(abi).unwrap() {
+        abi::Value::StructValue(fields) => {
+            fields.contains(&("approved".to_owned(), abi::Value::Boolean(true)))
+        }
+        this => panic!("unexpected this: {this:?}"),
+    }
+}
+
+#[test]
+fn test_verify_signature_valid() {
+    let owner = public_key(&secret_key(1));
+    let (abi, output) = approve(&owner, &sign(&secret_key(1), MESSAGE)).unwrap();
+
+    assert!(approved(&abi, &output));
+    assert_eq!(output.signature_claims.len(), 1);
+    assert!(output.signature_claims[0].valid);
+    assert_eq!(output.signature_claims[0].public_key, owner);
+    assert_eq!(output.signature_claims[0].message, MESSAGE);
+    assert!(polylang_prover::verify_signature_claims(
+        &abi,
+        &output.stack,
+        &output.signature_claims
+    )
+    .is_ok());
+}
+
+#[test]
+fn test_verify_signature_invalid() {
+    // Signed by a different key
+    let (abi, output) =
+        approve(&public_key(&secret_key(1)), &sign(&secret_key(2), MESSAGE)).unwrap();
+
+    assert!(!approved(&abi, &output));
+    assert!(!output.signature_claims[0].valid);
+    assert!(polylang_prover::verify_signature_claims(
+        &abi,
+        &output.stack,
+        &output.signature_claims
+    )
+    .is_ok());
+
+    // Malformed signatures are invalid
+    let (abi, output) = approve(&public_key(&secret_key(1)), &[1, 2, 3]).unwrap();
+    assert!(!approved(&abi, &output));
+}
+
+#[test]
+fn test_verify_signatures_in_a_loop() {
+    let code = r#"
+        contract Approvals {
+            id: string;
+            approvals: u32;
+
+            approveAll(owners: PublicKey[], message: bytes, signatures: bytes[]) {
+                let one: u32 = 1;
+                for (let i: u32 = 0; i < owners.length; i++) {
+                    if (verifySignature(owners[i], message, signatures[i])) {
+                        this.approvals = this.approvals + one;
+                    }
+                }
+            }
+        }
+    "#;
+
+    let owners = [1, 2, 3].map(|seed| public_key(&secret_key(seed)));
+    // The second signature is by the wrong key
+    let signatures = [1, 1, 3].map(|seed| hex::encode(sign(&secret_key(seed), MESSAGE)));
+    let (abi, output) = run(
+        code,
+        "Approvals",
+        "approveAll",
+        serde_json::json!({ "id": "a", "approvals": 0 }),
+        vec![
+            serde_json::to_value(&owners).unwrap(),
+            serde_json::json!(hex::encode(MESSAGE)),
+            serde_json::json!(signatures),
+        ],
+        None,
+        HashMap::new(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        output.this(&abi).unwrap(),
+        abi::Value::StructValue(vec![
+            ("id".to_owned(), abi::Value::String("a".to_owned())),
+            ("approvals".to_owned(), abi::Value::UInt32(2)),
+        ])
+    );
+    assert_eq!(
+        output
+            .signature_claims
+            .iter()
+            .map(|claim| (claim.public_key.clone(), claim.valid))
+            .collect::<Vec<_>>(),
+        vec![
+            (owners[0].clone(), true),
+            (owners[1].clone(), false),
+            (owners[2].clone(), true),
+        ]
+    );
+    assert!(polylang_prover::verify_signature_claims(
+        &abi,
+        &output.stack,
+        &output.signature_claims
+    )
+    .is_ok());
+}
+
+#[test]
+fn test_verify_signature_claims_must_match_outputs() {
+    let (abi, output) =
+        approve(&public_key(&secret_key(1)), &sign(&secret_key(2), MESSAGE)).unwrap();
+
+    let mut claims = output.signature_claims.clone();
+    claims[0].valid = true;
+    assert!(
+        polylang_prover::verify_signature_claims(&abi, &output.stack, &claims)
+            .unwrap_err()
+            .to_string()
+            .contains("the signature claims don't match the program outputs")
+    );
+
+    assert!(
+        polylang_prover::verify_signature_claims(&abi, &output.stack, &[])
+            .unwrap_err()
+            .to_string()
+            .contains("the signature claims don't match the program outputs")
+    );
+}
+
+#[test]
+fn test_verify_signature_ed25519() {
+    use ed25519_dalek::Signer;
+
+    let signing_key = ed25519_dalek::SigningKey::from_bytes(&[1; 32]);
+    let owner =
+        abi::publickey::Key::from_ed25519_bytes(signing_key.verifying_key().as_bytes()).unwrap();
+
+    let signature = signing_key.sign(MESSAGE).to_bytes();
+    let (abi, output) = approve(&owner, &signature).unwrap();
+    assert!(approved(&abi, &output));
+    assert!(output.signature_claims[0].valid);
+
+    let signature = ed25519_dalek::SigningKey::from_bytes(&[2; 32])
+        .sign(MESSAGE)
+        .to_bytes();
+    let (abi, output) = approve(&owner, &signature).unwrap();
+    assert!(!approved(&abi, &output));
+}
+
+#[test]
+fn test_verify_signature_p256() {
+    use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+
+    let signing_key = SigningKey::from_slice(&[1; 32]).unwrap();
+    let owner = abi::publickey::Key::from_p256_bytes(
+        signing_key
+            .verifying_key()
+            .to_encoded_point(true)
+            .as_bytes(),
+    )
+    .unwrap();
+
+    let signature: Signature = signing_key.sign(MESSAGE);
+    let (abi, output) = approve(&owner, &signature.to_bytes()).unwrap();
+    assert!(approved(&abi, &output));
+    assert!(output.signature_claims[0].valid);
+    assert!(polylang_prover::verify_signature_claims(
+        &abi,
+        &output.stack,
+        &output.signature_claims
+    )
+    .is_ok());
+
+    let signature: Signature = SigningKey::from_slice(&[2; 32]).unwrap().sign(MESSAGE);
+    let (abi, output) = approve(&owner, &signature.to_bytes()).unwrap();
+    assert!(!approved(&abi, &output));
+}
+
+#[test]
+fn test_verify_signature_is_type_checked() {
+    let program = polylang::parse_program(
+        "function main(message: bytes) { let valid = verifySignature(message, message, message); }",
+    )
+    .unwrap();
+    let err = polylang::compiler::compile(program, None, "main")
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("expected to be \"Type::PublicKey\""), "{err}");
+}
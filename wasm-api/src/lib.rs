@@ -36,6 +36,21 @@ pub fn compile(
     Ok(Program { miden_code, abi })
 }
 
+/// Compiles every function of a contract, or every standalone function,
+/// and returns the JSON manifest of their program hashes.
+#[wasm_bindgen]
+pub fn manifest(code: String, contract_name: Option<String>) -> Result<String, JsError> {
+    let program = polylang::parse_program(&code)?;
+    let manifest = polylang::compiler::compile_manifest(
+        program,
+        &code,
+        contract_name.as_deref(),
+        abi::StdVersion::default(),
+    )?;
+
+    Ok(serde_json::to_string(&manifest)?)
+}
+
 #[wasm_bindgen]
 impl Program {
     pub fn miden_code(&self) -> String {
@@ -58,15 +73,7 @@ impl Program {
         let inputs = Inputs::new(
             self.abi.clone(),
             None,
-            self.abi
-                .this_type
-                .as_ref()
-                .map(|ty| match ty {
-                    abi::Type::Struct(st) => Ok(st.fields.iter().map(|_| 0).collect()),
-                    _ => Err(Error::simple("this type must be a struct")),
-                })
-                .transpose()?
-                .unwrap_or(vec![]),
+            polylang_prover::zero_salts(&self.abi)?,
             this,
             args,
             HashMap::new(),
@@ -210,6 +217,44 @@ pub fn verify(
     )
     .map_err(|e| JsError::new(&e.to_string()))?;
 
+    verify_program(
+        proof,
+        program_info,
+        stack_inputs,
+        output_stack,
+        overflow_addrs,
+    )
+}
+
+/// Verifies a proof of a function in a manifest returned by [`manifest`].
+#[wasm_bindgen]
+pub fn verify_manifest(
+    proof: Option<Vec<u8>>,
+    manifest: String,
+    function: &str,
+    stack_inputs: Vec<JsValue>,
+    output_stack: Vec<JsValue>,
+    overflow_addrs: Vec<JsValue>,
+) -> Result<bool, JsError> {
+    let manifest = serde_json::from_str::<abi::Manifest>(&manifest)?;
+    let program_info = polylang_prover::manifest_program_info(&manifest, function)?;
+
+    verify_program(
+        proof,
+        program_info,
+        stack_inputs,
+        output_stack,
+        overflow_addrs,
+    )
+}
+
+fn verify_program(
+    proof: Option<Vec<u8>>,
+    program_info: ProgramInfo,
+    stack_inputs: Vec<JsValue>,
+    output_stack: Vec<JsValue>,
+    overflow_addrs: Vec<JsValue>,
+) -> Result<bool, JsError> {
     let mut stack_inputs = stack_inputs
         .into_iter()
         .map(|s| s.as_string().unwrap().parse::<u64>().unwrap())